@@ -0,0 +1,136 @@
+use std::fmt;
+
+use clap::parser::MatchesError;
+use clap::ArgMatches;
+
+/// The command line arguments don't match what `dispatch` expects, because
+/// the definition of the commands changed without `dispatch` following suit.
+#[derive(Debug)]
+pub struct Mismatch(String);
+
+impl std::error::Error for Mismatch {}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Internal error: the command line arguments aren't what I expected ({}). \
+             Please report this as a bug.",
+            self.0
+        )
+    }
+}
+
+type Result<T> = std::result::Result<T, Mismatch>;
+
+/// Returns the value of an argument which is always there, because it's
+/// required or has a default value.
+pub fn required<'a>(matches: &'a ArgMatches, name: &str) -> Result<&'a str> {
+    optional(matches, name)?.ok_or_else(|| Mismatch(format!("{name} is missing")))
+}
+
+/// Returns the value of an argument, if it was given.
+pub fn optional<'a>(matches: &'a ArgMatches, name: &str) -> Result<Option<&'a str>> {
+    matches
+        .try_get_one::<String>(name)
+        .map(|value| value.map(String::as_str))
+        .map_err(|error| mismatch(name, error))
+}
+
+/// Returns all values of an argument which can be given several times.
+pub fn many<'a>(matches: &'a ArgMatches, name: &str) -> Result<Vec<&'a str>> {
+    matches
+        .try_get_many::<String>(name)
+        .map(|values| values.into_iter().flatten().map(String::as_str).collect())
+        .map_err(|error| mismatch(name, error))
+}
+
+/// Returns whether a flag was given.
+pub fn flag(matches: &ArgMatches, name: &str) -> Result<bool> {
+    matches
+        .try_get_one::<bool>(name)
+        .map(|value| value.copied().unwrap_or(false))
+        .map_err(|error| mismatch(name, error))
+}
+
+/// Returns the subcommand which is always there, because it's required.
+pub fn subcommand(matches: &ArgMatches) -> Result<(&str, &ArgMatches)> {
+    matches
+        .subcommand()
+        .ok_or_else(|| Mismatch("the subcommand is missing".to_owned()))
+}
+
+/// Returns the error for a value `dispatch` doesn't know, although the
+/// definition of the commands allows it.
+pub fn unexpected(kind: &str, value: &str) -> Mismatch {
+    Mismatch(format!("unexpected {kind} {value:?}"))
+}
+
+fn mismatch(name: &str, error: MatchesError) -> Mismatch {
+    match error {
+        MatchesError::UnknownArgument { .. } => Mismatch(format!("{name} isn't defined")),
+        error => Mismatch(format!("{name} has another type: {error}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{Arg, ArgAction, Command};
+    use eva::configuration::{
+        default_break_length, default_granularity, Configuration, EstimateBuffers,
+        SchedulingStrategy, TaskDefaults,
+    };
+    use eva::database::memory::MemoryDatabase;
+
+    use super::*;
+
+    #[test]
+    fn reports_missing_arguments_as_errors() {
+        let matches = Command::new("eva")
+            .arg(Arg::new("content"))
+            .arg(Arg::new("urgent").long("urgent").action(ArgAction::SetTrue))
+            .arg(Arg::new("busy").long("busy").action(ArgAction::Append))
+            .try_get_matches_from(["eva", "water plants"])
+            .unwrap();
+
+        assert_eq!(required(&matches, "content").unwrap(), "water plants");
+        assert_eq!(optional(&matches, "content").unwrap(), Some("water plants"));
+        assert!(!flag(&matches, "urgent").unwrap());
+        assert!(many(&matches, "busy").unwrap().is_empty());
+        // Not defined at all
+        assert!(required(&matches, "deadline").is_err());
+        assert!(optional(&matches, "deadline").is_err());
+        assert!(flag(&matches, "dry-run").is_err());
+        assert!(many(&matches, "deadline").is_err());
+        // Defined, but of another type
+        assert!(flag(&matches, "content").is_err());
+        assert!(subcommand(&matches).is_err());
+    }
+
+    #[test]
+    fn dispatch_reports_mismatched_definitions() {
+        let configuration = Configuration {
+            database: Box::new(MemoryDatabase::new()),
+            scheduling_strategy: SchedulingStrategy::Importance,
+            estimate_buffers: EstimateBuffers::default(),
+            granularity: default_granularity(),
+            deadline_format: None,
+            max_continuous: None,
+            break_length: default_break_length(),
+            task_defaults: TaskDefaults::default(),
+        };
+        // A definition of `eva done` which lost its task id
+        let inputs = Command::new("eva")
+            .subcommand(Command::new("done"))
+            .try_get_matches_from(["eva", "done"])
+            .unwrap();
+
+        let error = crate::dispatch(&inputs, &configuration).unwrap_err();
+        assert!(error.is::<Mismatch>());
+        assert_eq!(
+            error.to_string(),
+            "Internal error: the command line arguments aren't what I expected (task-id isn't \
+             defined). Please report this as a bug."
+        );
+    }
+}
@@ -0,0 +1,76 @@
+use std::ops::Range;
+
+use chrono::{DateTime, Utc};
+use eva::time_segment::TimeSegment;
+use itertools::Itertools;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Availability {
+    pub range: Range<DateTime<Utc>>,
+    /// Whether tasks in the segment can be scheduled in this range
+    pub available: bool,
+}
+
+/// Splits the time between `start` and `end` into the ranges the time segment
+/// covers, where its tasks can be scheduled, and the ranges of its inverse,
+/// where they can't.
+pub fn availability(
+    time_segment: &impl TimeSegment,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Vec<Availability> {
+    let available = time_segment
+        .generate_ranges(start, end)
+        .into_iter()
+        .map(|range| (range, true));
+    let blocked = time_segment
+        .inverse()
+        .generate_ranges(start, end)
+        .into_iter()
+        .map(|range| (range, false));
+    available
+        .chain(blocked)
+        .map(|(range, available)| Availability {
+            range: range.start.max(start)..range.end.min(end),
+            available,
+        })
+        .filter(|availability| availability.range.start < availability.range.end)
+        .sorted_by_key(|availability| availability.range.start)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+    use eva::time_segment::UnnamedTimeSegment;
+
+    use super::*;
+
+    #[test]
+    fn alternates_between_segment_and_its_inverse() {
+        let monday = Utc.with_ymd_and_hms(2019, 1, 7, 0, 0, 0).unwrap();
+        let hours = |hours| monday + Duration::hours(hours);
+        // Daily from 9 to 5
+        let time_segment = UnnamedTimeSegment {
+            ranges: vec![hours(9)..hours(17)],
+            start: monday,
+            period: Duration::days(1),
+        };
+
+        let availability = availability(&time_segment, hours(12), hours(24 + 12));
+
+        let expected = [
+            (hours(12)..hours(17), true),
+            (hours(17)..hours(24 + 9), false),
+            (hours(24 + 9)..hours(24 + 12), true),
+        ];
+        assert_eq!(
+            availability,
+            expected
+                .iter()
+                .cloned()
+                .map(|(range, available)| Availability { range, available })
+                .collect_vec()
+        );
+    }
+}
@@ -0,0 +1,266 @@
+//! A portable backup of all tasks and time segments, to move them to another
+//! database or another machine.
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Utc};
+use eva::configuration::Configuration;
+use eva::time_segment::{NamedTimeSegment, NewNamedTimeSegment};
+use futures_executor::block_on;
+use serde::{Deserialize, Serialize};
+
+/// The version of the format backups are written in. Bump it when the format
+/// changes, and keep reading the older versions.
+pub const VERSION: u32 = 1;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Backup {
+    pub version: u32,
+    pub time_segments: Vec<NamedTimeSegment>,
+    pub tasks: Vec<eva::Task>,
+}
+
+/// Returns a backup of all time segments and the tasks added or changed since
+/// the given moment, or all of them.
+pub fn export(configuration: &Configuration, since: Option<DateTime<Utc>>) -> Result<Backup> {
+    let tasks = match since {
+        Some(since) => block_on(eva::tasks_updated_since(configuration, since))?,
+        None => block_on(eva::tasks(configuration))?,
+    };
+    Ok(Backup {
+        version: VERSION,
+        time_segments: block_on(eva::time_segments(configuration))?,
+        tasks,
+    })
+}
+
+/// Reads a backup written by this or an earlier version of Eva.
+pub fn parse(json: &str) -> Result<Backup> {
+    let document: serde_json::Value =
+        serde_json::from_str(json).context("The backup isn't valid JSON.")?;
+    let version = document
+        .get("version")
+        .and_then(serde_json::Value::as_u64)
+        .ok_or_else(|| anyhow!("The backup doesn't say which version of the format it has."))?;
+    match version {
+        1 => serde_json::from_value(document).context("The backup is malformed."),
+        _ => bail!(
+            "The backup has version {version} of the format, but I only know up to version \
+             {VERSION}. Please update Eva to import it."
+        ),
+    }
+}
+
+/// Adds the time segments and tasks of the backup to the database. Time
+/// segments which already exist, like the default one, are reused rather than
+/// added a second time, taking the hue and strategy from the backup.
+/// The items get new ids, so the references between them are updated.
+///
+/// Unless forced, this refuses to import into a database which already has
+/// tasks, to avoid doubling them by importing a backup twice.
+pub fn import(configuration: &Configuration, backup: Backup, force: bool) -> Result<()> {
+    if !force && block_on(eva::task_count(configuration))? > 0 {
+        bail!(
+            "There are already tasks in the database. Pass --force if you want to import the \
+             backup on top of them."
+        );
+    }
+
+    let existing_segments = block_on(eva::time_segments(configuration))?;
+    let mut segment_ids = HashMap::new();
+    for time_segment in backup.time_segments {
+        let old_id = time_segment.id;
+        let new_time_segment = NewNamedTimeSegment {
+            name: time_segment.name,
+            ranges: time_segment.ranges,
+            start: time_segment.start,
+            period: time_segment.period,
+            hue: time_segment.hue,
+            strategy: time_segment.strategy,
+        };
+        let existing = existing_segments
+            .iter()
+            .find(|existing| **existing == new_time_segment);
+        let new_id = match existing {
+            Some(existing) => {
+                if existing.hue != new_time_segment.hue
+                    || existing.strategy != new_time_segment.strategy
+                {
+                    let mut existing = existing.clone();
+                    existing.hue = new_time_segment.hue;
+                    existing.strategy = new_time_segment.strategy;
+                    block_on(eva::update_time_segment(configuration, existing))?;
+                }
+                existing.id
+            }
+            None => block_on(eva::add_time_segment(configuration, new_time_segment))?.id,
+        };
+        segment_ids.insert(old_id, new_id);
+    }
+
+    // Dependencies have to exist before the tasks depending on them can be
+    // added. The ones outside of the backup, like in a partial export, are
+    // dropped.
+    let in_backup = backup.tasks.iter().map(|task| task.id).collect::<Vec<_>>();
+    let mut task_ids = HashMap::new();
+    let mut pending = backup.tasks;
+    while !pending.is_empty() {
+        let (ready, blocked): (Vec<_>, Vec<_>) = pending.into_iter().partition(|task| {
+            task.depends_on
+                .iter()
+                .all(|id| task_ids.contains_key(id) || !in_backup.contains(id))
+        });
+        if ready.is_empty() {
+            bail!("The tasks in the backup depend on each other in a cycle.");
+        }
+        for task in ready {
+            let time_segment_id = *segment_ids.get(&task.time_segment_id).ok_or_else(|| {
+                anyhow!(
+                    "Task {} is in time segment {}, which isn't in the backup.",
+                    task.id,
+                    task.time_segment_id
+                )
+            })?;
+            let new_task = eva::NewTask::from(&task)
+                // Backups of tasks stored before importances were validated
+                // may be off the scale
+                .with_importance(
+                    task.importance
+                        .clamp(*eva::IMPORTANCE.start(), *eva::IMPORTANCE.end()),
+                )
+                .with_time_segment_id(time_segment_id)
+                .with_depends_on(
+                    task.depends_on
+                        .iter()
+                        .filter_map(|id| task_ids.get(id).copied())
+                        .collect(),
+                );
+            let mut added = block_on(eva::add_task(configuration, new_task))?;
+            if task.color.is_some() || task.pinned {
+                added.color = task.color;
+                added.pinned = task.pinned;
+                block_on(eva::update_task(configuration, added.clone()))?;
+            }
+            task_ids.insert(task.id, added.id);
+        }
+        pending = blocked;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+    use eva::configuration::{
+        default_break_length, default_granularity, EstimateBuffers, SchedulingStrategy,
+        TaskDefaults,
+    };
+    use eva::database::memory::MemoryDatabase;
+
+    use super::*;
+
+    fn configuration() -> Configuration {
+        Configuration {
+            database: Box::new(MemoryDatabase::new()),
+            scheduling_strategy: SchedulingStrategy::Importance,
+            estimate_buffers: EstimateBuffers::default(),
+            granularity: default_granularity(),
+            deadline_format: None,
+            max_continuous: None,
+            break_length: default_break_length(),
+            task_defaults: TaskDefaults::default(),
+        }
+    }
+
+    fn fill(configuration: &Configuration) {
+        let mut default = block_on(eva::get_time_segment(configuration, 0)).unwrap();
+        default.hue = 300;
+        block_on(eva::update_time_segment(configuration, default)).unwrap();
+        let start = Utc.with_ymd_and_hms(2019, 8, 5, 18, 0, 0).unwrap();
+        let evenings = block_on(eva::add_time_segment(
+            configuration,
+            NewNamedTimeSegment {
+                name: "Evenings".to_owned(),
+                ranges: vec![start..start + Duration::hours(3)],
+                start,
+                period: Duration::days(1),
+                hue: 240,
+                strategy: Some(SchedulingStrategy::Urgency),
+            },
+        ))
+        .unwrap();
+        let new_task = |content: &str, time_segment_id, depends_on| eva::NewTask {
+            content: content.to_owned(),
+            deadline: start + Duration::days(7),
+            duration: Duration::hours(1),
+            importance: 5,
+            confidence: eva::Confidence::Medium,
+            time_segment_id,
+            depends_on,
+        };
+        let mut plan = block_on(eva::add_task(configuration, new_task("Plan", 0, vec![]))).unwrap();
+        plan.pinned = true;
+        block_on(eva::update_task(configuration, plan.clone())).unwrap();
+        let mut build = block_on(eva::add_task(
+            configuration,
+            new_task("Build", evenings.id, vec![plan.id]),
+        ))
+        .unwrap();
+        build.color = Some(120);
+        block_on(eva::update_task(configuration, build)).unwrap();
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let original = configuration();
+        fill(&original);
+        let json = serde_json::to_string(&export(&original, None).unwrap()).unwrap();
+
+        let restored = configuration();
+        import(&restored, parse(&json).unwrap(), false).unwrap();
+        let backup = export(&restored, None).unwrap();
+        assert_eq!(backup, export(&original, None).unwrap());
+        assert_eq!(backup.version, VERSION);
+        // The default time segment isn't doubled, but it does get its hue
+        assert_eq!(backup.time_segments.len(), 2);
+        assert_eq!(backup.time_segments[0].hue, 300);
+        assert_eq!(
+            backup.time_segments[1].strategy,
+            Some(SchedulingStrategy::Urgency)
+        );
+        assert_eq!(backup.tasks[1].depends_on, [backup.tasks[0].id]);
+        assert_eq!(backup.tasks[1].color, Some(120));
+        assert!(backup.tasks[0].pinned);
+        assert!(!backup.tasks[1].pinned);
+    }
+
+    #[test]
+    fn only_imports_into_a_non_empty_database_when_forced() {
+        let configuration = configuration();
+        fill(&configuration);
+        let json = serde_json::to_string(&export(&configuration, None).unwrap()).unwrap();
+
+        let error = import(&configuration, parse(&json).unwrap(), false).unwrap_err();
+        assert!(error.to_string().contains("--force"));
+        assert_eq!(block_on(eva::task_count(&configuration)).unwrap(), 2);
+
+        import(&configuration, parse(&json).unwrap(), true).unwrap();
+        let backup = export(&configuration, None).unwrap();
+        assert_eq!(backup.tasks.len(), 4);
+        assert_eq!(backup.time_segments.len(), 2);
+        assert_eq!(backup.tasks[3].depends_on, [backup.tasks[2].id]);
+    }
+
+    #[test]
+    fn refuses_unknown_versions() {
+        let error = parse(r#"{"version": 2, "time_segments": [], "tasks": []}"#).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The backup has version 2 of the format, but I only know up to version 1. Please \
+             update Eva to import it."
+        );
+        assert!(parse(r#"{"time_segments": [], "tasks": []}"#).is_err());
+        assert!(parse("[]").is_err());
+    }
+}
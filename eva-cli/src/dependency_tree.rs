@@ -0,0 +1,151 @@
+use std::collections::{HashMap, HashSet};
+
+use eva::Task;
+
+/// Renders the tasks as a tree of their dependencies: the tasks which don't
+/// depend on anything come first, with the tasks which depend on them indented
+/// beneath. A task with several dependencies appears under each of them, but
+/// only the first time with its own dependents. Tasks which depend on each
+/// other in a cycle can't be put in a tree, so they're listed separately.
+pub fn render(tasks: &[Task]) -> String {
+    let ids = tasks.iter().map(|task| task.id).collect::<HashSet<_>>();
+    let mut dependents = HashMap::<u32, Vec<&Task>>::new();
+    for task in tasks {
+        for dependency in &task.depends_on {
+            dependents.entry(*dependency).or_default().push(task);
+        }
+    }
+    let mut tree = Tree {
+        dependents,
+        lines: vec![],
+        shown: HashSet::new(),
+    };
+    let roots = tasks.iter().filter(|task| {
+        // Dependencies which aren't listed don't hold the task back
+        !task
+            .depends_on
+            .iter()
+            .any(|dependency| ids.contains(dependency))
+    });
+    for root in roots {
+        tree.render(root, 0, &mut vec![]);
+    }
+
+    let in_cycles = tasks
+        .iter()
+        .filter(|task| !tree.shown.contains(&task.id))
+        .collect::<Vec<_>>();
+    if !in_cycles.is_empty() {
+        tree.lines.push("In a dependency cycle:".to_owned());
+        for task in in_cycles {
+            tree.lines.push(format!("  {}. {}", task.id, task.content));
+        }
+    }
+    tree.lines.join("\n")
+}
+
+struct Tree<'a> {
+    dependents: HashMap<u32, Vec<&'a Task>>,
+    lines: Vec<String>,
+    shown: HashSet<u32>,
+}
+
+impl<'a> Tree<'a> {
+    /// Adds the task and, unless they were shown before, its dependents.
+    /// `path` holds the tasks on the way to this one, to guard against cycles.
+    fn render(&mut self, task: &'a Task, depth: usize, path: &mut Vec<u32>) {
+        let indentation = "  ".repeat(depth);
+        if path.contains(&task.id) {
+            self.lines.push(format!(
+                "{}{}. {} (dependency cycle)",
+                indentation, task.id, task.content
+            ));
+            return;
+        }
+        if !self.shown.insert(task.id) {
+            self.lines.push(format!(
+                "{}{}. {} (shared, see above)",
+                indentation, task.id, task.content
+            ));
+            return;
+        }
+        self.lines
+            .push(format!("{}{}. {}", indentation, task.id, task.content));
+        path.push(task.id);
+        let dependents = self.dependents.get(&task.id).cloned().unwrap_or_default();
+        for dependent in dependents {
+            self.render(dependent, depth + 1, path);
+        }
+        path.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+
+    use super::*;
+
+    fn task(id: u32, depends_on: Vec<u32>) -> Task {
+        Task {
+            id,
+            content: format!("task {}", id),
+            deadline: Utc::now() + Duration::days(1),
+            duration: Duration::hours(1),
+            importance: 5,
+            confidence: eva::Confidence::High,
+            time_segment_id: 0,
+            depends_on,
+            color: None,
+            scheduled_for: None,
+            done_at: None,
+            pinned: false,
+        }
+    }
+
+    #[test]
+    fn renders_a_chain() {
+        let tasks = [
+            task(1, vec![]),
+            task(2, vec![1]),
+            task(3, vec![2]),
+            task(4, vec![]),
+        ];
+        assert_eq!(
+            render(&tasks),
+            "1. task 1\n  2. task 2\n    3. task 3\n4. task 4"
+        );
+    }
+
+    #[test]
+    fn renders_a_diamond() {
+        let tasks = [
+            task(1, vec![]),
+            task(2, vec![1]),
+            task(3, vec![1]),
+            task(4, vec![2, 3]),
+            task(5, vec![4]),
+        ];
+        assert_eq!(
+            render(&tasks),
+            "1. task 1\n  2. task 2\n    4. task 4\n      5. task 5\n  3. task 3\n    4. task 4 \
+             (shared, see above)"
+        );
+    }
+
+    #[test]
+    fn reports_cycles() {
+        let tasks = [
+            task(1, vec![]),
+            task(2, vec![1, 3]),
+            task(3, vec![2]),
+            task(4, vec![5]),
+            task(5, vec![4]),
+        ];
+        assert_eq!(
+            render(&tasks),
+            "1. task 1\n  2. task 2\n    3. task 3\n      2. task 2 (dependency cycle)\nIn a \
+             dependency cycle:\n  4. task 4\n  5. task 5"
+        );
+    }
+}
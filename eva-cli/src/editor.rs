@@ -0,0 +1,227 @@
+//! Lets the user change a task in their own text editor, as a document with a
+//! field on each line followed by the content, which can span several lines.
+
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+use chrono::{Duration, Local};
+use itertools::Itertools;
+
+use crate::parse;
+
+/// The editor to fall back on when neither $VISUAL nor $EDITOR is set.
+const DEFAULT_EDITOR: &str = "vi";
+
+const HEADER: &str = "\
+# Change the fields and the content below the empty line, then save and quit.
+# Leave the file empty to keep the task as it is.
+";
+
+/// Opens `text` in the editor in $VISUAL or $EDITOR, or else vi, and returns
+/// the text as the user saved it.
+pub fn edit(text: &str) -> Result<String> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .ok()
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_EDITOR.to_owned());
+    // The editor may come with arguments, like "code --wait"
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or(DEFAULT_EDITOR);
+
+    let path = env::temp_dir().join(format!("eva-edit-{}.txt", std::process::id()));
+    create_new(&path, text)?;
+    let status = Command::new(program).args(words).arg(&path).status();
+    let edited = fs::read_to_string(&path);
+    let _ = fs::remove_file(&path);
+
+    let status = status.with_context(|| {
+        format!(
+            "I couldn't start your editor ({editor}). Set $EDITOR to the one you'd like to use."
+        )
+    })?;
+    if !status.success() {
+        bail!("Your editor exited with {status}, so I left the task as it was.");
+    }
+    edited.with_context(|| format!("I couldn't read back {}.", path.display()))
+}
+
+/// Writes `text` to a file at `path` which mustn't exist yet, so nobody else
+/// can have put it there for the editor to open.
+fn create_new(path: &Path, text: &str) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .with_context(|| format!("I couldn't create {}.", path.display()))?;
+    file.write_all(text.as_bytes())
+        .with_context(|| format!("I couldn't write to {}.", path.display()))
+}
+
+/// Writes the task as a document to edit, with the deadline in the given
+/// format or else the default one.
+pub fn to_document(task: &eva::Task, deadline_format: Option<&str>) -> String {
+    let fields = fields(task, deadline_format)
+        .into_iter()
+        .map(|(key, value)| format!("{key}: {value}"))
+        .join("\n");
+    format!("{HEADER}{fields}\n\n{}\n", task.content)
+}
+
+/// Reads an edited document back into the task. Fields which are written the
+/// same as in `to_document` are left as they were, so they don't lose
+/// precision, like the seconds of a deadline.
+///
+/// Returns `None` when the document was emptied, to leave the task as it is.
+pub fn from_document(
+    document: &str,
+    task: &eva::Task,
+    deadline_format: Option<&str>,
+) -> Result<Option<eva::Task>> {
+    let lines = document
+        .lines()
+        .skip_while(|line| line.starts_with('#'))
+        .collect_vec();
+    if lines.iter().all(|line| line.trim().is_empty()) {
+        return Ok(None);
+    }
+    let separator = lines
+        .iter()
+        .position(|line| line.trim().is_empty())
+        .unwrap_or(lines.len());
+    let content = lines[separator..].join("\n").trim().to_owned();
+    if content.is_empty() {
+        bail!("The content of a task can't be empty. Leave the whole file empty to keep it.");
+    }
+
+    let original = fields(task, deadline_format);
+    let mut task = eva::Task {
+        content,
+        ..task.clone()
+    };
+    for line in &lines[..separator] {
+        let (key, value) = line
+            .split_once(':')
+            .with_context(|| format!("I expected a field like \"key: value\", not {line:?}."))?;
+        let (key, value) = (key.trim(), value.trim());
+        let unchanged = original
+            .iter()
+            .any(|(other_key, other_value)| *other_key == key && *other_value == value);
+        if unchanged {
+            continue;
+        }
+        match key {
+            "deadline" => task.deadline = parse::deadline(value, deadline_format)?,
+            "duration" => task.duration = parse::duration(value)?,
+            "importance" => task.importance = parse::importance(value)?,
+            "confidence" => task.confidence = parse::confidence(value)?,
+            "time_segment_id" => task.time_segment_id = parse::time_segment_id(value)?,
+            "depends_on" => task.depends_on = parse::task_ids(value)?,
+            _ => bail!("There is no field called {key:?}."),
+        }
+    }
+    Ok(Some(task))
+}
+
+/// The fields of a task other than its content, written like the user would
+/// give them to `eva set`.
+fn fields(task: &eva::Task, deadline_format: Option<&str>) -> Vec<(&'static str, String)> {
+    vec![
+        (
+            "deadline",
+            task.deadline
+                .with_timezone(&Local)
+                .format(deadline_format.unwrap_or(parse::DEFAULT_DEADLINE_FORMAT))
+                .to_string(),
+        ),
+        ("duration", duration(task.duration)),
+        ("importance", task.importance.to_string()),
+        ("confidence", task.confidence.as_str().to_owned()),
+        ("time_segment_id", task.time_segment_id.to_string()),
+        ("depends_on", task.depends_on.iter().join(", ")),
+    ]
+}
+
+/// Writes a duration with units, like "1h30m".
+fn duration(duration: Duration) -> String {
+    let (hours, minutes) = (duration.num_hours(), duration.num_minutes() % 60);
+    match (hours, minutes) {
+        (0, minutes) => format!("{minutes}m"),
+        (hours, 0) => format!("{hours}h"),
+        (hours, minutes) => format!("{hours}h{minutes}m"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+
+    fn task() -> eva::Task {
+        eva::Task {
+            id: 1,
+            content: "Write the report".to_string(),
+            deadline: Utc.with_ymd_and_hms(2019, 8, 4, 12, 0, 30).unwrap(),
+            duration: Duration::minutes(90),
+            importance: 5,
+            confidence: eva::Confidence::Medium,
+            time_segment_id: 0,
+            depends_on: vec![2, 3],
+            color: None,
+            scheduled_for: None,
+            done_at: None,
+            pinned: false,
+        }
+    }
+
+    #[test]
+    fn read_back_a_document_as_it_was_written() {
+        let task = task();
+        let document = to_document(&task, None);
+        assert!(document.contains("\nduration: 1h30m\n"));
+        assert!(document.contains("\ndepends_on: 2, 3\n"));
+        assert_eq!(from_document(&document, &task, None).unwrap(), Some(task));
+    }
+
+    #[test]
+    fn read_back_changes_to_the_document() {
+        let task = task();
+        let document = to_document(&task, None)
+            .replace("importance: 5", "importance: 8")
+            .replace("depends_on: 2, 3", "depends_on:")
+            .replace("Write the report", "Write the report\n\n- intro\n- results");
+        let edited = from_document(&document, &task, None).unwrap().unwrap();
+        assert_eq!(edited.importance, 8);
+        assert_eq!(edited.depends_on, Vec::<u32>::new());
+        assert_eq!(edited.content, "Write the report\n\n- intro\n- results");
+        assert_eq!(edited.deadline, task.deadline);
+    }
+
+    #[test]
+    fn leave_the_task_alone_when_the_document_is_emptied() {
+        assert_eq!(from_document("", &task(), None).unwrap(), None);
+        assert_eq!(from_document(HEADER, &task(), None).unwrap(), None);
+    }
+
+    #[test]
+    fn refuse_to_edit_a_file_which_already_exists() {
+        let path = env::temp_dir().join(format!("eva-edit-test-{}.txt", std::process::id()));
+        create_new(&path, "mine").unwrap();
+        assert!(create_new(&path, "theirs").is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "mine");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn refuse_unknown_fields_and_empty_content() {
+        let document = to_document(&task(), None).replace("importance", "urgency");
+        assert!(from_document(&document, &task(), None).is_err());
+        let document = to_document(&task(), None).replace("Write the report", "");
+        assert!(from_document(&document, &task(), None).is_err());
+    }
+}
@@ -0,0 +1,153 @@
+//! The exit codes Eva uses to tell scripts what went wrong. These are part of
+//! Eva's interface, so they should never change. Invalid command line usage
+//! exits with code 2, like any other program using clap.
+
+use std::fmt;
+
+use anyhow::Error;
+
+use crate::parse;
+
+/// Something went wrong which doesn't fall in any of the categories below.
+pub const GENERIC: i32 = 1;
+/// An argument, like a deadline or a duration, couldn't be understood.
+pub const PARSE: i32 = 3;
+/// A task or time segment doesn't exist.
+pub const NOT_FOUND: i32 = 4;
+/// The tasks can't be scheduled, e.g. because there isn't enough time for all
+/// of them.
+pub const INFEASIBLE: i32 = 5;
+/// The database couldn't be read or changed.
+pub const DATABASE: i32 = 6;
+/// The configuration couldn't be read.
+pub const CONFIGURATION: i32 = 7;
+/// The tasks can't be scheduled because one of them can't be finished before
+/// its deadline, even on its own.
+pub const DEADLINE_MISSED: i32 = 8;
+
+/// Lists the exit codes at the end of `eva --help`.
+pub const HELP: &str = "\
+EXIT CODES:
+    0    Everything went fine
+    1    Something else went wrong
+    2    The command line arguments were invalid
+    3    An argument, like a deadline or a duration, couldn't be understood
+    4    A task or time segment doesn't exist
+    5    The tasks can't be scheduled, e.g. because there isn't enough time
+    6    The database couldn't be read or changed
+    7    The configuration couldn't be read
+    8    The tasks can't be scheduled because a deadline was or will be missed";
+
+/// Marks an error which occurred while reading the configuration.
+#[derive(Debug)]
+pub struct ConfigurationError(pub Error);
+
+impl fmt::Display for ConfigurationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for ConfigurationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.0.as_ref())
+    }
+}
+
+/// Returns the exit code for the given error. The most specific cause wins,
+/// e.g. a database error while reading the configuration is a database error.
+pub fn of(error: &Error) -> i32 {
+    match error.chain().find_map(category) {
+        Some(code) => code,
+        None if error.is::<ConfigurationError>() => CONFIGURATION,
+        None => GENERIC,
+    }
+}
+
+fn category(cause: &(dyn std::error::Error + 'static)) -> Option<i32> {
+    if cause.is::<parse::Error>() {
+        return Some(PARSE);
+    }
+    if cause.is::<eva::database::NotFound>() {
+        return Some(NOT_FOUND);
+    }
+    if let Some(error) = cause.downcast_ref::<eva::Error>() {
+        return Some(match error {
+            _ if error.is_not_found() => NOT_FOUND,
+            _ if error.is_deadline_missed() => DEADLINE_MISSED,
+            eva::Error::Database(_) => DATABASE,
+            eva::Error::Schedule(_) => INFEASIBLE,
+            eva::Error::TimeSegment(_) => GENERIC,
+            eva::Error::Validation(_) | eva::Error::Strategy(_) => PARSE,
+        });
+    }
+    cause.downcast_ref::<eva::database::Error>().map(|error| {
+        if error.is_not_found() {
+            NOT_FOUND
+        } else {
+            DATABASE
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use eva::configuration::{
+        default_break_length, default_granularity, Configuration, EstimateBuffers,
+        SchedulingStrategy, TaskDefaults,
+    };
+    use eva::database::memory::MemoryDatabase;
+
+    use super::*;
+
+    fn exit_code_of_running(arguments: &[&str], configuration: &Configuration) -> i32 {
+        let inputs = crate::cli()
+            .try_get_matches_from([&["eva", "--dry-run"], arguments].concat())
+            .unwrap();
+        match crate::dispatch(&inputs, configuration) {
+            Ok(()) => 0,
+            Err(error) => of(&error),
+        }
+    }
+
+    #[test]
+    fn maps_error_categories_to_exit_codes() {
+        let configuration = Configuration {
+            database: Box::new(MemoryDatabase::new()),
+            scheduling_strategy: SchedulingStrategy::Importance,
+            estimate_buffers: EstimateBuffers::default(),
+            granularity: default_granularity(),
+            deadline_format: None,
+            max_continuous: None,
+            break_length: default_break_length(),
+            task_defaults: TaskDefaults::default(),
+        };
+        let run = |arguments: &[&str]| exit_code_of_running(arguments, &configuration);
+
+        assert_eq!(run(&["add", "water plants", "someday", "1", "5"]), PARSE);
+        assert_eq!(run(&["rm", "42"]), NOT_FOUND);
+        assert_eq!(run(&["set", "content", "42", "water plants"]), NOT_FOUND);
+        assert_eq!(run(&["segment", "free", "42"]), NOT_FOUND);
+        assert_eq!(run(&["postpone", "42", "3d"]), NOT_FOUND);
+        assert_eq!(run(&["postpone", "42", "a while"]), PARSE);
+        assert_eq!(
+            run(&["add", "water plants", "1 Jan 2000 0:00", "1", "5"]),
+            0
+        );
+        assert_eq!(run(&["schedule"]), DEADLINE_MISSED);
+        assert_eq!(run(&["done", "1"]), 0);
+        for _ in 0..2 {
+            assert_eq!(run(&["add", "paint", "in 3 days", "40", "5"]), 0);
+        }
+        assert_eq!(run(&["schedule"]), INFEASIBLE);
+
+        let database_error = eva::database::Error("while testing", "disk full".into());
+        assert_eq!(of(&database_error.into()), DATABASE);
+        let configuration_error = ConfigurationError(anyhow::anyhow!("unknown strategy"));
+        assert_eq!(of(&configuration_error.into()), CONFIGURATION);
+        let wrapped_database_error =
+            ConfigurationError(eva::database::Error("while migrating", "disk full".into()).into());
+        assert_eq!(of(&wrapped_database_error.into()), DATABASE);
+        assert_eq!(of(&anyhow::anyhow!("something else")), GENERIC);
+    }
+}
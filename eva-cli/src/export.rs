@@ -0,0 +1,170 @@
+use chrono::prelude::*;
+use itertools::Itertools;
+
+use crate::pretty_print::{Palette, PrettyPrint};
+
+/// Renders the schedule as a self-contained HTML page with an agenda per day,
+/// showing each task in its hue. It doesn't refer to any external assets, so
+/// the page can be shared as a single file.
+pub fn to_html(schedule: &eva::Schedule<eva::Task>, palette: &Palette) -> String {
+    let days = schedule
+        .0
+        .iter()
+        .group_by(|scheduled| scheduled.when.with_timezone(&Local).date_naive());
+    let agenda = if schedule.is_empty() {
+        "<p>No tasks left.</p>".to_owned()
+    } else {
+        days.into_iter()
+            .map(|(day, scheduled)| {
+                format!(
+                    "<section>\n<h2>{}</h2>\n<ul>\n{}\n</ul>\n</section>",
+                    day.format("%A %-d %B %Y"),
+                    scheduled
+                        .map(|scheduled| to_html_item(scheduled, palette))
+                        .join("\n")
+                )
+            })
+            .join("\n")
+    };
+    let (busy, idle) = schedule.utilisation(DateTime::<Utc>::MAX_UTC);
+    format!(
+        "<!DOCTYPE html>\n\
+         <html lang=\"en\">\n\
+         <head>\n\
+         <meta charset=\"utf-8\">\n\
+         <title>Schedule</title>\n\
+         <style>\n{STYLE}\n</style>\n\
+         </head>\n\
+         <body>\n\
+         <h1>Schedule</h1>\n\
+         {agenda}\n\
+         <footer>Total: {} busy, {} free</footer>\n\
+         </body>\n\
+         </html>\n",
+        busy.pretty_print(),
+        idle.pretty_print()
+    )
+}
+
+const STYLE: &str = "\
+body { font-family: sans-serif; max-width: 40em; margin: 2em auto; color: #222; }
+h2 { font-size: 1.1em; margin-top: 2em; border-bottom: 1px solid #ccc; }
+ul { list-style: none; padding: 0; }
+li { margin: 0.5em 0; padding: 0.3em 0.6em; border-left: 0.4em solid #999; }
+time { font-variant-numeric: tabular-nums; font-weight: bold; margin-right: 0.5em; }
+small { display: block; color: #666; }
+footer { margin-top: 2em; color: #666; }";
+
+fn to_html_item(scheduled: &eva::Scheduled<eva::Task>, palette: &Palette) -> String {
+    let task = &scheduled.task;
+    let style = match palette.hue_of(task) {
+        Some(hue) => format!(" style=\"border-left-color: hsl({hue}, 70%, 55%)\""),
+        None => String::new(),
+    };
+    let start = scheduled.when.with_timezone(&Local);
+    let end = scheduled.end().with_timezone(&Local);
+    format!(
+        "<li{style}><time datetime=\"{}\">{} - {}</time>{}<small>deadline: {}</small></li>",
+        start.to_rfc3339_opts(SecondsFormat::Secs, false),
+        start.format("%-H:%M"),
+        end.format("%-H:%M"),
+        escape(&task.content),
+        escape(&task.deadline.pretty_print())
+    )
+}
+
+/// Escapes the characters with a special meaning in HTML, both in text and in
+/// attribute values.
+pub(crate) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            character => escaped.push(character),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    fn scheduled(id: u32, content: &str, when: DateTime<Utc>) -> eva::Scheduled<eva::Task> {
+        eva::Scheduled {
+            task: eva::Task {
+                id,
+                content: content.to_owned(),
+                deadline: when + Duration::days(2),
+                duration: Duration::hours(1),
+                importance: 5,
+                confidence: eva::Confidence::High,
+                time_segment_id: 0,
+                depends_on: vec![],
+                color: if id == 2 { Some(120) } else { None },
+                scheduled_for: None,
+                done_at: None,
+                pinned: false,
+            },
+            when,
+            duration: Duration::hours(1),
+            part: None,
+        }
+    }
+
+    #[test]
+    fn renders_every_task_on_a_well_formed_page() {
+        let monday = Utc.with_ymd_and_hms(2019, 8, 5, 9, 0, 0).unwrap();
+        let schedule = eva::Schedule(vec![
+            scheduled(1, "Water the plants", monday),
+            scheduled(
+                2,
+                "Fix <b>bold</b> & \"quotes\"",
+                monday + Duration::minutes(30),
+            ),
+            scheduled(3, "Call mom", monday + Duration::days(1)),
+        ]);
+        let html = to_html(&schedule, &Palette::new(&[]));
+
+        assert!(html.starts_with("<!DOCTYPE html>\n<html lang=\"en\">"));
+        assert!(html.ends_with("</html>\n"));
+        for tag in [
+            "html", "head", "title", "style", "body", "section", "ul", "li",
+        ] {
+            assert_eq!(
+                html.matches(&format!("<{}", tag)).count(),
+                html.matches(&format!("</{}>", tag)).count(),
+                "<{}> isn't closed",
+                tag
+            );
+        }
+        assert_eq!(html.matches("<section>").count(), 2);
+        assert_eq!(html.matches("<li").count(), 3);
+        assert!(html.contains("Water the plants"));
+        assert!(html.contains("Fix &lt;b&gt;bold&lt;/b&gt; &amp; &quot;quotes&quot;"));
+        assert!(!html.contains("<b>"));
+        assert!(html.contains("Call mom"));
+        assert!(html.contains("<li style=\"border-left-color: hsl(120, 70%, 55%)\">"));
+    }
+
+    #[test]
+    fn renders_an_empty_schedule() {
+        let html = to_html(&eva::Schedule(vec![]), &Palette::new(&[]));
+        assert!(html.contains("<p>No tasks left.</p>"));
+        assert!(!html.contains("<section>"));
+    }
+
+    #[test]
+    fn escapes_html() {
+        assert_eq!(
+            escape("<a href='x'>Tom & \"Jerry\"</a>"),
+            "&lt;a href=&#39;x&#39;&gt;Tom &amp; &quot;Jerry&quot;&lt;/a&gt;"
+        );
+    }
+}
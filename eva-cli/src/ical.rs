@@ -0,0 +1,326 @@
+use chrono::{DateTime, Duration, FixedOffset, Local, Offset, TimeZone, Utc};
+
+/// Lines may be at most this many octets long, excluding the line break.
+const MAX_LINE_LENGTH: usize = 75;
+
+/// A time zone as calendar apps know it, alongside the UTC offset it has at
+/// any moment.
+pub struct Timezone {
+    /// The identifier of the time zone, like "Europe/Brussels"
+    pub id: String,
+    pub offset_at: Box<dyn Fn(DateTime<Utc>) -> FixedOffset>,
+}
+
+impl Timezone {
+    /// Returns the time zone of this system, or `None` when it can't be
+    /// determined.
+    pub fn local() -> Option<Timezone> {
+        // Like the offsets, prefer the time zone from the environment over
+        // the system's
+        let id = match std::env::var("TZ") {
+            Ok(id) if !id.trim_start_matches(':').is_empty() => {
+                id.trim_start_matches(':').to_owned()
+            }
+            _ => iana_time_zone::get_timezone().ok()?,
+        };
+        Some(Timezone {
+            id,
+            offset_at: Box::new(|datetime| {
+                Local.offset_from_utc_datetime(&datetime.naive_utc()).fix()
+            }),
+        })
+    }
+}
+
+/// Renders the schedule as an iCalendar (RFC 5545) calendar with one event per
+/// scheduled task. Times are given in the given time zone, or in UTC if there
+/// is none.
+pub fn schedule_to_ical(
+    schedule: &eva::Schedule<eva::Task>,
+    now: DateTime<Utc>,
+    calendar_name: Option<&str>,
+    timezone: Option<&Timezone>,
+) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_owned(),
+        "VERSION:2.0".to_owned(),
+        "PRODID:-//Procrat//Eva//EN".to_owned(),
+    ];
+    if let Some(calendar_name) = calendar_name {
+        let calendar_name = escape_text(calendar_name);
+        lines.push(format!("NAME:{calendar_name}"));
+        lines.push(format!("X-WR-CALNAME:{calendar_name}"));
+    }
+    let first_start = schedule.iter().map(|scheduled| scheduled.when).min();
+    let last_end = schedule.iter().map(eva::Scheduled::end).max();
+    if let (Some(timezone), Some(first_start), Some(last_end)) = (timezone, first_start, last_end) {
+        lines.extend(vtimezone(timezone, first_start, last_end));
+    }
+    for scheduled in schedule {
+        let task = &scheduled.task;
+        // Calendars keep only one event per UID, so every part needs its own
+        let uid = match scheduled.part {
+            Some(part) => format!("task-{}-part-{}@eva", task.id, part.number),
+            None => format!("task-{}@eva", task.id),
+        };
+        lines.extend([
+            "BEGIN:VEVENT".to_owned(),
+            format!("UID:{uid}"),
+            format!("DTSTAMP:{}", format_utc_datetime(now)),
+            format!("DTSTART{}", format_datetime(scheduled.when, timezone)),
+            format!("DTEND{}", format_datetime(scheduled.end(), timezone)),
+            format!("SUMMARY:{}", escape_text(&task.content)),
+            "END:VEVENT".to_owned(),
+        ]);
+    }
+    lines.push("END:VCALENDAR".to_owned());
+    lines.iter().map(|line| fold(line) + "\r\n").collect()
+}
+
+/// Describes the time zone between `start` and `end`, with an observance for
+/// every change of its UTC offset, e.g. because of daylight saving time.
+fn vtimezone(timezone: &Timezone, start: DateTime<Utc>, end: DateTime<Utc>) -> Vec<String> {
+    let changes = offset_changes(timezone, start, end);
+    let standard_offset = changes
+        .iter()
+        .map(|(_, offset)| offset.local_minus_utc())
+        .min()
+        .expect("there is always an initial offset");
+    let mut lines = vec![
+        "BEGIN:VTIMEZONE".to_owned(),
+        format!("TZID:{}", timezone.id),
+    ];
+    let mut previous_offset = changes[0].1;
+    for (since, offset) in changes {
+        let kind = if offset.local_minus_utc() == standard_offset {
+            "STANDARD"
+        } else {
+            "DAYLIGHT"
+        };
+        lines.extend([
+            format!("BEGIN:{kind}"),
+            // The onset is expressed in the local time before the change
+            format!(
+                "DTSTART:{}",
+                since
+                    .with_timezone(&previous_offset)
+                    .format("%Y%m%dT%H%M%S")
+            ),
+            format!("TZOFFSETFROM:{}", format_offset(previous_offset)),
+            format!("TZOFFSETTO:{}", format_offset(offset)),
+            format!("END:{kind}"),
+        ]);
+        previous_offset = offset;
+    }
+    lines.push("END:VTIMEZONE".to_owned());
+    lines
+}
+
+/// Returns the offset at `start` and every moment after that until `end` when
+/// the offset changes, to the second.
+fn offset_changes(
+    timezone: &Timezone,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Vec<(DateTime<Utc>, FixedOffset)> {
+    let mut changes = vec![(start, (timezone.offset_at)(start))];
+    let mut current = start;
+    while current < end {
+        let offset = changes[changes.len() - 1].1;
+        // Offsets don't change more than once a day
+        let next = std::cmp::min(current + Duration::days(1), end);
+        if (timezone.offset_at)(next) == offset {
+            current = next;
+            continue;
+        }
+        let (mut before, mut after) = (current, next);
+        while after - before > Duration::seconds(1) {
+            let middle = before + (after - before) / 2;
+            if (timezone.offset_at)(middle) == offset {
+                before = middle;
+            } else {
+                after = middle;
+            }
+        }
+        changes.push((after, (timezone.offset_at)(after)));
+        current = after;
+    }
+    changes
+}
+
+fn format_datetime(datetime: DateTime<Utc>, timezone: Option<&Timezone>) -> String {
+    match timezone {
+        Some(timezone) => format!(
+            ";TZID={}:{}",
+            timezone.id,
+            datetime
+                .with_timezone(&(timezone.offset_at)(datetime))
+                .format("%Y%m%dT%H%M%S")
+        ),
+        None => format!(":{}", format_utc_datetime(datetime)),
+    }
+}
+
+fn format_utc_datetime(datetime: DateTime<Utc>) -> String {
+    datetime.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn format_offset(offset: FixedOffset) -> String {
+    let seconds = offset.local_minus_utc();
+    let sign = if seconds < 0 { '-' } else { '+' };
+    let minutes = seconds.abs() / 60;
+    format!("{}{:02}{:02}", sign, minutes / 60, minutes % 60)
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Splits lines which are too long into multiple lines, each continuation line
+/// starting with a space. Multi-byte characters are never split.
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len());
+    let mut line_length = 0;
+    for character in line.chars() {
+        if line_length + character.len_utf8() > MAX_LINE_LENGTH {
+            folded.push_str("\r\n ");
+            // The leading space counts towards the line length
+            line_length = 1;
+        }
+        folded.push(character);
+        line_length += character.len_utf8();
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_schedule() -> eva::Schedule<eva::Task> {
+        let when = Utc.with_ymd_and_hms(2019, 8, 1, 14, 23, 0).unwrap();
+        eva::Schedule(vec![eva::Scheduled {
+            task: eva::Task {
+                id: 13,
+                content: "Prepare epic-sounding one-liners, again; or not".to_string(),
+                deadline: Utc.with_ymd_and_hms(2019, 8, 2, 19, 0, 0).unwrap(),
+                duration: Duration::minutes(90),
+                importance: 10,
+                confidence: eva::Confidence::High,
+                time_segment_id: 0,
+                depends_on: vec![],
+                color: None,
+                scheduled_for: None,
+                done_at: None,
+                pinned: false,
+            },
+            when,
+            duration: Duration::minutes(90),
+            part: None,
+        }])
+    }
+
+    #[test]
+    fn exports_scheduled_tasks_as_events() {
+        let now = Utc.with_ymd_and_hms(2019, 8, 1, 14, 22, 0).unwrap();
+
+        assert_eq!(
+            schedule_to_ical(&test_schedule(), now, None, None),
+            "BEGIN:VCALENDAR\r\n\
+             VERSION:2.0\r\n\
+             PRODID:-//Procrat//Eva//EN\r\n\
+             BEGIN:VEVENT\r\n\
+             UID:task-13@eva\r\n\
+             DTSTAMP:20190801T142200Z\r\n\
+             DTSTART:20190801T142300Z\r\n\
+             DTEND:20190801T155300Z\r\n\
+             SUMMARY:Prepare epic-sounding one-liners\\, again\\; or not\r\n\
+             END:VEVENT\r\n\
+             END:VCALENDAR\r\n"
+        );
+    }
+
+    #[test]
+    fn gives_every_part_of_a_split_task_its_own_uid() {
+        let now = Utc.with_ymd_and_hms(2019, 8, 1, 14, 22, 0).unwrap();
+        let whole = test_schedule().0.pop().unwrap();
+        let part = |number, day| eva::Scheduled {
+            when: Utc.with_ymd_and_hms(2019, 8, day, 9, 0, 0).unwrap(),
+            duration: Duration::minutes(45),
+            part: Some(eva::Part { number, of: 2 }),
+            ..whole.clone()
+        };
+        let schedule = eva::Schedule(vec![part(1, 1), part(2, 2)]);
+
+        let uids = schedule_to_ical(&schedule, now, None, None)
+            .lines()
+            .filter(|line| line.starts_with("UID:"))
+            .map(str::to_owned)
+            .collect::<Vec<_>>();
+        assert_eq!(uids, ["UID:task-13-part-1@eva", "UID:task-13-part-2@eva"]);
+    }
+
+    #[test]
+    fn exports_events_in_local_time() {
+        let now = Utc.with_ymd_and_hms(2019, 8, 1, 14, 22, 0).unwrap();
+        // Summer time ends during the task
+        let end_of_summer_time = Utc.with_ymd_and_hms(2019, 8, 1, 15, 0, 0).unwrap();
+        let timezone = Timezone {
+            id: "Europe/Eva".to_string(),
+            offset_at: Box::new(move |datetime| {
+                if datetime < end_of_summer_time {
+                    FixedOffset::east_opt(2 * 3600).unwrap()
+                } else {
+                    FixedOffset::east_opt(3600).unwrap()
+                }
+            }),
+        };
+
+        assert_eq!(
+            schedule_to_ical(&test_schedule(), now, Some("Eva"), Some(&timezone)),
+            "BEGIN:VCALENDAR\r\n\
+             VERSION:2.0\r\n\
+             PRODID:-//Procrat//Eva//EN\r\n\
+             NAME:Eva\r\n\
+             X-WR-CALNAME:Eva\r\n\
+             BEGIN:VTIMEZONE\r\n\
+             TZID:Europe/Eva\r\n\
+             BEGIN:DAYLIGHT\r\n\
+             DTSTART:20190801T162300\r\n\
+             TZOFFSETFROM:+0200\r\n\
+             TZOFFSETTO:+0200\r\n\
+             END:DAYLIGHT\r\n\
+             BEGIN:STANDARD\r\n\
+             DTSTART:20190801T170000\r\n\
+             TZOFFSETFROM:+0200\r\n\
+             TZOFFSETTO:+0100\r\n\
+             END:STANDARD\r\n\
+             END:VTIMEZONE\r\n\
+             BEGIN:VEVENT\r\n\
+             UID:task-13@eva\r\n\
+             DTSTAMP:20190801T142200Z\r\n\
+             DTSTART;TZID=Europe/Eva:20190801T162300\r\n\
+             DTEND;TZID=Europe/Eva:20190801T165300\r\n\
+             SUMMARY:Prepare epic-sounding one-liners\\, again\\; or not\r\n\
+             END:VEVENT\r\n\
+             END:VCALENDAR\r\n"
+        );
+    }
+
+    #[test]
+    fn folds_long_lines() {
+        let line = format!("SUMMARY:{}", "é".repeat(40));
+        let folded = fold(&line);
+        let lines = folded.split("\r\n").collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|line| line.len() <= MAX_LINE_LENGTH));
+        assert!(lines[1].starts_with(' '));
+        assert_eq!(folded.replace("\r\n ", ""), line);
+
+        assert_eq!(fold("SUMMARY:short"), "SUMMARY:short");
+    }
+}
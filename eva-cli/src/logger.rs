@@ -0,0 +1,29 @@
+//! Shows what Eva is doing under the hood, e.g. why the scheduler put a task
+//! where it did, when asked for with --verbose.
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+/// Writes Eva's own debug messages to stderr, so they don't mix with the
+/// output.
+struct StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.target().starts_with("eva")
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("{} {}: {}", record.level(), record.target(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Logs everything down to the debug messages from here on.
+pub fn init() {
+    if log::set_logger(&StderrLogger).is_ok() {
+        log::set_max_level(LevelFilter::Debug);
+    }
+}
@@ -1,17 +1,35 @@
+use std::cmp::Reverse;
 use std::env;
+use std::ops::Range;
 use std::process;
 
-use anyhow::{Error, Result};
-use clap::{builder::PossibleValuesParser, Arg, ArgMatches, Command};
-use eva::configuration::Configuration;
+use anyhow::{Context, Error, Result};
+use chrono::{DateTime, Local, Utc};
+use clap::{builder::PossibleValuesParser, Arg, ArgAction, ArgMatches, Command};
+use eva::configuration::{Configuration, SchedulingStrategy};
+use eva::database::memory::MemoryDatabase;
+use eva::database::TaskOrder;
+use eva::time_segment::{TimeSegment, UnnamedTimeSegment};
 use futures_executor::block_on;
 use itertools::Itertools;
 
-use crate::pretty_print::PrettyPrint;
+use crate::pretty_print::{Palette, PrettyPrint, PrettyPrintColored};
 
+mod arguments;
+mod availability;
+mod backup;
 mod configuration;
+mod dependency_tree;
+mod editor;
+mod exit_code;
+mod export;
+mod ical;
+mod logger;
 mod parse;
 mod pretty_print;
+mod schedule_cache;
+mod svg;
+mod triage;
 
 fn main() {
     if let Err(error) = run() {
@@ -20,12 +38,22 @@ fn main() {
 }
 
 fn run() -> Result<()> {
-    let configuration = configuration::read()?;
-    let arguments = cli(&configuration).get_matches();
-    dispatch(&arguments, &configuration)
+    let inputs = cli().get_matches();
+    if arguments::flag(&inputs, "verbose")? {
+        logger::init();
+    }
+    let run_migrations = !arguments::flag(&inputs, "no-migrations")?
+        || matches!(inputs.subcommand(), Some(("migrate", _)));
+    let mut configuration =
+        configuration::read(run_migrations).map_err(exit_code::ConfigurationError)?;
+    if arguments::flag(&inputs, "dry-run")? {
+        let database = block_on(MemoryDatabase::copy_of(&*configuration.database))?;
+        configuration.database = Box::new(database);
+    }
+    dispatch(&inputs, &configuration)
 }
 
-fn cli(configuration: &Configuration) -> Command {
+fn cli() -> Command<'static> {
     let add = Command::new("add")
         .about("Adds a task")
         .arg(
@@ -35,22 +63,69 @@ fn cli(configuration: &Configuration) -> Command {
         )
         .arg(Arg::new("deadline").required(true).help(
             "When should it be finished? \
-                   Give it in the format of '2 Aug 2017 14:03'.",
+                   Give it in the format of '2 Aug 2017 14:03', or relative to now like \
+                   'tomorrow', 'in 3 days' or 'next monday'.",
         ))
-        .arg(Arg::new("duration").required(true).help(
+        .arg(Arg::new("duration").help(
             "How long do you estimate it will take? \
-                   Give it in a (whole or decimal) number of hours.",
+                   Give it in a (whole or decimal) number of hours, with units like '30m', \
+                   '1h30m' or '2d', or in ISO 8601 like 'PT1H30M'. Defaults to the \
+                   default_duration setting in the configuration.",
+        ))
+        .arg(Arg::new("importance").help(
+            "How important is this task to you on a scale from 1 to 10? Defaults to the \
+             default_importance setting in the configuration.",
         ))
         .arg(
-            Arg::new("importance")
-                .required(true)
-                .help("How important is this task to you on a scale from 1 to 10?"),
+            Arg::new("confidence")
+                .long("confidence")
+                .takes_value(true)
+                .value_parser(PossibleValuesParser::new(["low", "medium", "high"]))
+                .default_value("high")
+                .help(
+                    "How sure are you about the duration? Eva plans some extra time for \
+                     less certain estimates.",
+                ),
+        )
+        .arg(
+            Arg::new("depends-on")
+                .long("depends-on")
+                .takes_value(true)
+                .value_name("IDS")
+                .help(
+                    "Which tasks should be finished before this one can start? Give their ids \
+                     separated by commas.",
+                ),
+        )
+        .arg(
+            Arg::new("no-check")
+                .long("no-check")
+                .action(ArgAction::SetTrue)
+                .help(
+                    "Don't warn when the task takes longer than the time left until its deadline",
+                ),
         );
-    let rm = Command::new("rm")
-        .about("Removes a task")
+    let done = Command::new("done")
+        .about("Marks a task as done, so it isn't listed or scheduled anymore")
+        .alias("rm")
         .arg(Arg::new("task-id").required(true));
+    let purge = Command::new("purge")
+        .about("Deletes the tasks which are done for good")
+        .arg(
+            Arg::new("before")
+                .long("before")
+                .takes_value(true)
+                .value_name("DATE")
+                .help(
+                    "Only delete the tasks which were done before this moment, in the same \
+                     format as deadlines",
+                ),
+        );
     let set = Command::new("set")
-        .about("Changes the deadline, duration, importance or content of an existing task")
+        .about(
+            "Changes the deadline, duration, importance, confidence, content, time segment or \
+             dependencies of an existing task",
+        )
         .arg(
             Arg::new("property")
                 .required(true)
@@ -59,90 +134,1253 @@ fn cli(configuration: &Configuration) -> Command {
                     "deadline",
                     "duration",
                     "importance",
+                    "confidence",
+                    "time_segment_id",
+                    "depends_on",
                 ])),
         )
         .arg(Arg::new("task-id").required(true))
-        .arg(Arg::new("value").required(true));
-    let list = Command::new("tasks").about("Lists your tasks in the order you added them");
+        .arg(Arg::new("value").required(true))
+        .arg(
+            Arg::new("keep-schedule")
+                .long("keep-schedule")
+                .action(ArgAction::SetTrue)
+                .help(
+                    "Only for durations: keep the task at its place in the last schedule \
+                     instead of having to reschedule everything",
+                ),
+        );
+    let postpone = Command::new("postpone")
+        .about("Moves the deadline of a task back")
+        .arg(Arg::new("task-id").required(true))
+        .arg(
+            Arg::new("duration")
+                .required(true)
+                .help("How much later, in the same format as durations, e.g. '3d' or '2h'"),
+        )
+        .arg(
+            Arg::new("from-now")
+                .long("from-now")
+                .action(ArgAction::SetTrue)
+                .help(
+                    "Count from now instead of from the current deadline, e.g. when that has \
+                     already passed",
+                ),
+        );
+    let edit = Command::new("edit")
+        .about(
+            "Changes a task in your text editor, which also lets you write content over several \
+             lines",
+        )
+        .arg(Arg::new("task-id").required(true));
+    let undo = Command::new("undo").about("Undoes the last change to your tasks");
+    let color = Command::new("color")
+        .about("Shows a task in its own color instead of the color of its time segment")
+        .arg(Arg::new("task-id").required(true))
+        .arg(
+            Arg::new("hue")
+                .required(true)
+                .help("A hue from 0 to 359, or 'none' to use the time segment's color again"),
+        );
+    let pin = Command::new("pin")
+        .about("Puts a task first in its time segment, whatever its importance")
+        .arg(Arg::new("task-id").required(true));
+    let unpin = Command::new("unpin")
+        .about("Lets a pinned task be scheduled like the others again")
+        .arg(Arg::new("task-id").required(true));
+    let list = Command::new("tasks")
+        .about("Lists your tasks in the order you added them")
+        .arg(
+            Arg::new("tree")
+                .long("tree")
+                .action(ArgAction::SetTrue)
+                .help(
+                    "Show which tasks depend on which, with the tasks that depend on another \
+                     one indented beneath it",
+                ),
+        )
+        .arg(
+            Arg::new("compact")
+                .long("compact")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("tree")
+                .help("Show each task on a single line"),
+        )
+        .arg(
+            Arg::new("sort")
+                .long("sort")
+                .takes_value(true)
+                .value_parser(PossibleValuesParser::new(["added", "deadline", "importance"]))
+                .help("List the tasks in the order they were added, by deadline or most important first"),
+        )
+        .arg(
+            Arg::new("limit")
+                .long("limit")
+                .takes_value(true)
+                .value_name("N")
+                .help("Only list the first N tasks"),
+        )
+        .arg(
+            Arg::new("offset")
+                .long("offset")
+                .takes_value(true)
+                .value_name("M")
+                .help("Skip the first M tasks"),
+        )
+        .arg(
+            Arg::new("due-after")
+                .long("due-after")
+                .takes_value(true)
+                .value_name("DATE")
+                .help("Only list the tasks which are due at or after this, like a deadline"),
+        )
+        .arg(
+            Arg::new("due-before")
+                .long("due-before")
+                .takes_value(true)
+                .value_name("DATE")
+                .help("Only list the tasks which are due before this, like a deadline"),
+        )
+        .arg(format_arg());
+    let find = Command::new("find")
+        .about("Lists the tasks which mention some text, ignoring case")
+        .arg(Arg::new("query").required(true).help("What to look for"))
+        .arg(format_arg());
     let schedule = Command::new("schedule")
         .about("Lets Eva suggest a schedule for your tasks")
         .arg(
             Arg::new("strategy")
                 .long("strategy")
                 .takes_value(true)
-                .value_parser(PossibleValuesParser::new(["importance", "urgency"]))
-                .default_value(configuration.scheduling_strategy.as_str()),
+                .value_name("STRATEGY")
+                .value_parser(parse_strategy)
+                .help(
+                    "importance, urgency, balanced, deadline, which plans the task with the \
+                     earliest deadline first, or shuffled:SEED, which is like importance but \
+                     breaks ties by shuffling the tasks with the given seed. Defaults to the \
+                     scheduling_strategy setting in the configuration.",
+                ),
+        )
+        .arg(
+            Arg::new("per-segment-strategy")
+                .long("per-segment-strategy")
+                .action(ArgAction::SetTrue)
+                .help(
+                    "Schedule the tasks in time segments with a strategy of their own according \
+                     to it, and only the others according to --strategy",
+                ),
+        )
+        .arg(
+            Arg::new("max-tasks")
+                .long("max-tasks")
+                .takes_value(true)
+                .value_name("N")
+                .help(
+                    "Only plan the N most pressing tasks according to the strategy \
+                     and defer the rest",
+                ),
+        )
+        .arg(
+            Arg::new("from")
+                .long("from")
+                .takes_value(true)
+                .value_name("DATE")
+                .help(
+                    "Plan from this moment on instead of from now, in the same format as \
+                     deadlines",
+                ),
+        )
+        .arg(
+            Arg::new("horizon")
+                .long("horizon")
+                .takes_value(true)
+                .value_name("DURATION")
+                .help(
+                    "Only show what's planned this far ahead, like '7d', and defer the tasks \
+                     which wouldn't start before then",
+                ),
+        )
+        .arg(
+            Arg::new("busy")
+                .long("busy")
+                .takes_value(true)
+                .action(ArgAction::Append)
+                .value_name("WHEN")
+                .help(
+                    "Keep a fixed appointment free, like \"8 Jul 9:00-10:30\". Can be given \
+                     several times.",
+                ),
+        )
+        .arg(
+            Arg::new("best-effort")
+                .long("best-effort")
+                .action(ArgAction::SetTrue)
+                .help("Leave out the tasks which don't fit instead of failing, and list them"),
+        )
+        .arg(
+            Arg::new("allow-late")
+                .long("allow-late")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("best-effort")
+                .help(
+                    "Let the tasks which don't fit run past their deadline instead of failing, \
+                     and list how late they would be. This still fails when they don't fit \
+                     however late they are, e.g. in a time segment without any time.",
+                ),
+        )
+        .arg(
+            Arg::new("ignore-segments")
+                .long("ignore-segments")
+                .action(ArgAction::SetTrue)
+                .help("Plan all tasks in one continuous window, regardless of their time segments"),
+        )
+        .arg(
+            Arg::new("start-of-day")
+                .long("start-of-day")
+                .takes_value(true)
+                .value_name("HH:MM")
+                .requires("ignore-segments")
+                .help(
+                    "With --ignore-segments, only plan from this time of day until midnight, \
+                     e.g. to stay clear of the night",
+                ),
+        )
+        .arg(
+            Arg::new("diff-with-last")
+                .long("diff-with-last")
+                .action(ArgAction::SetTrue)
+                .help("Show what changed since the last time Eva made a schedule"),
+        )
+        .arg(
+            Arg::new("persist")
+                .long("persist")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(&["from", "ignore-segments", "busy"])
+                .help("Remember when the schedule plans each task, to show it with the tasks"),
+        )
+        .arg(
+            Arg::new("ics-calendar-name")
+                .long("ics-calendar-name")
+                .takes_value(true)
+                .value_name("NAME")
+                .help("The name calendar apps should show for the exported calendar"),
+        )
+        .arg(
+            Arg::new("group-by")
+                .long("group-by")
+                .takes_value(true)
+                .value_name("PERIOD")
+                .value_parser(PossibleValuesParser::new(["day"]))
+                .help("Show the human-readable schedule under a header for each day"),
+        )
+        .arg(
+            Arg::new("html")
+                .long("html")
+                .takes_value(true)
+                .value_name("PATH")
+                .help("Also write the schedule to this file as a web page, e.g. to share it"),
+        )
+        .arg(
+            format_arg()
+                .value_parser(PossibleValuesParser::new(["human", "json", "ical", "svg"]))
+                .help(
+                    "Print the output in a human-readable format, as JSON, as an iCalendar \
+                     file to import in your calendar app or as an SVG Gantt chart",
+                ),
+        );
+    let export = Command::new("export")
+        .about(
+            "Prints your tasks and time segments as JSON, e.g. as a backup or to sync them with \
+             another system",
+        )
+        .arg(
+            Arg::new("since")
+                .long("since")
+                .takes_value(true)
+                .value_name("TIMESTAMP")
+                .help(
+                    "Only print the tasks added or changed since this moment, as an RFC 3339 \
+                     timestamp like '2019-08-01T14:12:50Z' or in the same format as deadlines",
+                ),
+        );
+    let import = Command::new("import")
+        .about("Adds the tasks and time segments of a file made by `eva export`")
+        .arg(Arg::new("file").required(true).help("The exported file"))
+        .arg(
+            Arg::new("force")
+                .long("force")
+                .action(ArgAction::SetTrue)
+                .help("Import the file even if there are tasks already"),
+        );
+    let next = Command::new("next").about(
+        "Shows what to do next, according to the last schedule as long as your tasks didn't change",
+    );
+    let why = Command::new("why")
+        .about("Explains why a task is scheduled where it is")
+        .arg(Arg::new("task-id").required(true))
+        .arg(
+            Arg::new("strategy")
+                .long("strategy")
+                .takes_value(true)
+                .value_name("STRATEGY")
+                .value_parser(parse_strategy)
+                .help("The strategy to schedule with, like for `eva schedule`"),
+        )
+        .arg(
+            Arg::new("per-segment-strategy")
+                .long("per-segment-strategy")
+                .action(ArgAction::SetTrue)
+                .help("Schedule time segments with a strategy of their own according to it"),
+        );
+    let capacity = Command::new("capacity").about(
+        "Checks whether each time segment has enough time for its tasks, and which tasks don't \
+         fit",
+    );
+    let stats = Command::new("stats")
+        .about("Sums up your tasks per importance and per time segment, with their deadlines");
+    let doctor = Command::new("doctor")
+        .about("Checks whether some time segments are overcommitted while others are idle");
+    let segment = Command::new("segment")
+        .about("Inspects and configures your time segments")
+        .subcommand_required(true)
+        .subcommand(Command::new("list").about("Lists your time segments and when they are"))
+        .subcommand(
+            Command::new("show")
+                .about("Shows when a time segment repeats and which color it has")
+                .arg(Arg::new("time-segment-id").required(true)),
+        )
+        .subcommand(
+            Command::new("add")
+                .about("Adds a time segment which repeats every week")
+                .arg(Arg::new("name").required(true))
+                .arg(Arg::new("ranges").required(true).help(
+                    "When it is each week, like 'Mon 9:00-12:00, Wed 14:00-17:00'. A range \
+                     which ends before it starts runs into the next day.",
+                )),
+        )
+        .subcommand(
+            Command::new("rename")
+                .about("Renames a time segment")
+                .arg(Arg::new("time-segment-id").required(true))
+                .arg(Arg::new("name").required(true)),
+        )
+        .subcommand(
+            Command::new("rm")
+                .about("Deletes a time segment")
+                .arg(Arg::new("time-segment-id").required(true))
+                .arg(
+                    Arg::new("move-tasks-to")
+                        .long("move-tasks-to")
+                        .takes_value(true)
+                        .value_name("ID")
+                        .help("Moves the time segment's tasks to this time segment first"),
+                ),
+        )
+        .subcommand(
+            Command::new("free")
+                .about("Shows when tasks in a time segment can and can't be scheduled")
+                .arg(Arg::new("time-segment-id").required(true))
+                .arg(
+                    Arg::new("until")
+                        .long("until")
+                        .takes_value(true)
+                        .value_name("DATE")
+                        .default_value("in 7 days")
+                        .help(
+                            "Until when to show the availability, in the same format as deadlines",
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("strategy")
+                .about(
+                    "Sets how to schedule the tasks in a time segment when scheduling with \
+                     --per-segment-strategy",
+                )
+                .arg(Arg::new("time-segment-id").required(true))
+                .arg(Arg::new("strategy").required(true).help(
+                    "importance, urgency, balanced, deadline, shuffled:SEED, or 'none' to use the \
+                     strategy for all segments again",
+                )),
         );
+    let migrate = Command::new("migrate").about("Brings the database schema up to date");
+    let maintenance = Command::new("maintenance")
+        .about("Shrinks and defragments the database, which grows after many changes");
 
     Command::new("eva")
         .version(env!("CARGO_PKG_VERSION"))
+        .after_help(exit_code::HELP)
         .subcommand_required(true)
         .arg_required_else_help(true)
-        .subcommands([add, rm, set, list, schedule])
+        .arg(
+            Arg::new("dry-run")
+                .long("dry-run")
+                .action(ArgAction::SetTrue)
+                .help("Don't save any changes, just show what would happen"),
+        )
+        .arg(
+            Arg::new("verbose")
+                .short('v')
+                .long("verbose")
+                .action(ArgAction::SetTrue)
+                .help("Explain on stderr how the schedule comes about"),
+        )
+        .arg(
+            Arg::new("no-migrations")
+                .long("no-migrations")
+                .action(ArgAction::SetTrue)
+                .help(
+                    "Don't migrate the database, e.g. when it is read-only. Use `eva migrate` to \
+                     do so explicitly.",
+                ),
+        )
+        .arg(
+            Arg::new("now")
+                .long("now")
+                .takes_value(true)
+                .hide(true)
+                .help(
+                    "Pretend it's this moment rather than the actual time, e.g. to get the same \
+                     schedule every time. A schedule from then on is only a preview.",
+                ),
+        )
+        .subcommands([
+            add,
+            done,
+            purge,
+            set,
+            postpone,
+            edit,
+            undo,
+            color,
+            pin,
+            unpin,
+            list,
+            find,
+            export,
+            import,
+            schedule,
+            next,
+            why,
+            capacity,
+            doctor,
+            stats,
+            segment,
+            migrate,
+            maintenance,
+        ])
+}
+
+fn format_arg<'a>() -> Arg<'a> {
+    Arg::new("format")
+        .long("format")
+        .takes_value(true)
+        .value_parser(PossibleValuesParser::new(["human", "json"]))
+        .default_value("human")
+        .help("Print the output in a human-readable format or as JSON")
+}
+
+/// Checks that the strategy exists, but leaves it as a string, like the rest of
+/// Eva passes it around.
+fn parse_strategy(strategy: &str) -> Result<String> {
+    strategy.parse::<SchedulingStrategy>()?;
+    Ok(strategy.to_owned())
 }
 
 fn dispatch(inputs: &ArgMatches, configuration: &Configuration) -> Result<()> {
-    match inputs.subcommand().unwrap() {
+    match arguments::subcommand(inputs)? {
         ("add", submatches) => {
-            let content = submatches.get_one::<String>("content").unwrap();
-            let deadline = submatches.get_one::<String>("deadline").unwrap();
-            let duration = submatches.get_one::<String>("duration").unwrap();
-            let importance = submatches.get_one::<String>("importance").unwrap();
+            let content = arguments::required(submatches, "content")?;
+            let deadline = arguments::required(submatches, "deadline")?;
+            let duration = arguments::optional(submatches, "duration")?
+                .map(parse::duration)
+                .transpose()?
+                .unwrap_or(configuration.task_defaults.duration);
+            let importance = arguments::optional(submatches, "importance")?
+                .map(parse::importance)
+                .transpose()?
+                .unwrap_or(configuration.task_defaults.importance);
+            let confidence = arguments::required(submatches, "confidence")?;
+            let depends_on = arguments::optional(submatches, "depends-on")?
+                .map(parse::task_ids)
+                .transpose()?
+                .unwrap_or_default();
             let new_task = eva::NewTask {
                 content: content.to_owned(),
-                deadline: parse::deadline(deadline)?,
-                duration: parse::duration(duration)?,
-                importance: parse::importance(importance)?,
-                time_segment_id: 0,
+                deadline: parse::deadline(deadline, configuration.deadline_format.as_deref())?,
+                duration,
+                importance,
+                confidence: parse::confidence(confidence)?,
+                time_segment_id: default_time_segment_id(configuration)?,
+                depends_on,
             };
-            let _task = block_on(eva::add_task(configuration, new_task))?;
+            let now = now(inputs, configuration)?;
+            let infeasible =
+                !arguments::flag(submatches, "no-check")? && new_task.is_trivially_infeasible(now);
+            let task = block_on(eva::add_task(configuration, new_task))?;
+            if infeasible {
+                let left = task.deadline - now;
+                let left = if left > chrono::Duration::zero() {
+                    format!("there's only {} left", left.pretty_print())
+                } else {
+                    "its deadline has already passed".to_owned()
+                };
+                eprintln!(
+                    "⚠ Task {} takes {}, but {left}, so it can't be finished in time.",
+                    task.id,
+                    task.duration.pretty_print()
+                );
+            }
             Ok(())
         }
-        ("rm", submatches) => {
-            let id = submatches.get_one::<String>("task-id").unwrap();
+        ("done", submatches) => {
+            let id = arguments::required(submatches, "task-id")?;
             let id = parse::id(id)?;
-            Ok(block_on(eva::delete_task(configuration, id))?)
+            Ok(block_on(eva::complete_task(configuration, id))?)
+        }
+        ("purge", submatches) => {
+            let before = arguments::optional(submatches, "before")?
+                .map(|deadline| parse::deadline(deadline, configuration.deadline_format.as_deref()))
+                .transpose()?;
+            match block_on(eva::clear_completed(configuration, before))? {
+                0 => println!("There are no tasks which are done to delete."),
+                1 => println!("Deleted 1 task which was done."),
+                count => println!("Deleted {count} tasks which were done."),
+            }
+            Ok(())
         }
         ("set", submatches) => {
-            let field = submatches.get_one::<String>("property").unwrap();
-            let id = submatches.get_one::<String>("task-id").unwrap();
-            let value = submatches.get_one::<String>("value").unwrap();
+            let field = arguments::required(submatches, "property")?;
+            let id = arguments::required(submatches, "task-id")?;
+            let value = arguments::required(submatches, "value")?;
             let id = parse::id(id)?;
+            if arguments::flag(submatches, "keep-schedule")? {
+                if field != "duration" {
+                    anyhow::bail!("I can only keep the schedule when you change a duration.");
+                }
+                let path = schedule_cache::path()?;
+                let mut schedule = schedule_cache::load(&path)?.ok_or_else(|| {
+                    anyhow::anyhow!("There is no schedule to keep yet. Run `eva schedule` first.")
+                })?;
+                let rescheduled = schedule
+                    .reschedule(id, parse::duration(value)?)
+                    .map_err(eva::Error::Schedule)?;
+                set_field(configuration, field, id, value)?;
+                if !arguments::flag(inputs, "dry-run")? {
+                    let tasks_per_segment =
+                        block_on(eva::tasks_per_nonempty_segment(configuration))?;
+                    let input_hash = schedule_cache::input_hash(
+                        &tasks_per_segment,
+                        &configuration.scheduling_strategy.to_string(),
+                        configuration.estimate_buffers,
+                        configuration.granularity,
+                        configuration.breaks(),
+                    );
+                    schedule_cache::store(&path, &schedule, input_hash)?;
+                }
+                println!("Rescheduled {}", rescheduled.pretty_print());
+                return Ok(());
+            }
             Ok(set_field(configuration, field, id, value)?)
         }
-        ("tasks", _submatches) => {
-            let tasks = block_on(eva::tasks(configuration))?;
-            if tasks.len() == 0 {
-                println!("No tasks left. Add one with `eva add`.");
+        ("postpone", submatches) => {
+            let id = parse::id(arguments::required(submatches, "task-id")?)?;
+            let duration = parse::duration(arguments::required(submatches, "duration")?)?;
+            let from = if arguments::flag(submatches, "from-now")? {
+                now(inputs, configuration)?
             } else {
+                block_on(eva::get_task(configuration, id))?.deadline
+            };
+            let changes = eva::TaskChanges {
+                deadline: Some(from + duration),
+                ..Default::default()
+            };
+            let task = block_on(eva::update_task_fields(configuration, id, changes))?;
+            println!(
+                "Postponed task {id} until {}.",
+                task.deadline.pretty_print()
+            );
+            Ok(())
+        }
+        ("edit", submatches) => {
+            let id = arguments::required(submatches, "task-id")?;
+            let id = parse::id(id)?;
+            let task = block_on(eva::get_task(configuration, id))?;
+            let document = editor::edit(&editor::to_document(
+                &task,
+                configuration.deadline_format.as_deref(),
+            ))?;
+            match editor::from_document(&document, &task, configuration.deadline_format.as_deref())?
+            {
+                Some(edited) if edited != task => {
+                    Ok(block_on(eva::update_task(configuration, edited))?)
+                }
+                _ => {
+                    println!("Task {id} is left as it was.");
+                    Ok(())
+                }
+            }
+        }
+        ("undo", _submatches) => {
+            match block_on(eva::undo(configuration))? {
+                Some(operation) => println!("Undid {}", operation.pretty_print()),
+                None => println!("There is nothing left to undo."),
+            }
+            Ok(())
+        }
+        ("color", submatches) => {
+            let id = parse::id(arguments::required(submatches, "task-id")?)?;
+            let color = parse::hue(arguments::required(submatches, "hue")?)?;
+            let mut task = block_on(eva::get_task(configuration, id))?;
+            task.color = color;
+            Ok(block_on(eva::update_task(configuration, task))?)
+        }
+        (command @ ("pin" | "unpin"), submatches) => {
+            let id = parse::id(arguments::required(submatches, "task-id")?)?;
+            let pinned = command == "pin";
+            let changes = eva::TaskChanges {
+                pinned: Some(pinned),
+                ..Default::default()
+            };
+            block_on(eva::update_task_fields(configuration, id, changes))?;
+            if pinned {
+                println!("Task {id} goes first in its time segment now.");
+            } else {
+                println!("Task {id} is scheduled like the others again.");
+            }
+            Ok(())
+        }
+        ("export", submatches) => {
+            let since = arguments::optional(submatches, "since")?
+                .map(|since| parse::timestamp(since, configuration.deadline_format.as_deref()))
+                .transpose()?;
+            let backup = backup::export(configuration, since)?;
+            println!("{}", serde_json::to_string_pretty(&backup)?);
+            Ok(())
+        }
+        ("import", submatches) => {
+            let path = arguments::required(submatches, "file")?;
+            let json = std::fs::read_to_string(path)
+                .with_context(|| format!("I couldn't read {path}."))?;
+            let backup = backup::parse(&json)?;
+            let force = arguments::flag(submatches, "force")?;
+            backup::import(configuration, backup, force)
+        }
+        ("tasks", submatches) => {
+            let sort = arguments::optional(submatches, "sort")?;
+            let limit = arguments::optional(submatches, "limit")?
+                .map(parse::count)
+                .transpose()?;
+            let offset = arguments::optional(submatches, "offset")?
+                .map(parse::count)
+                .transpose()?;
+            let due_after = arguments::optional(submatches, "due-after")?
+                .map(|deadline| parse::deadline(deadline, configuration.deadline_format.as_deref()))
+                .transpose()?;
+            let due_before = arguments::optional(submatches, "due-before")?
+                .map(|deadline| parse::deadline(deadline, configuration.deadline_format.as_deref()))
+                .transpose()?;
+            let order = match sort {
+                Some("deadline") => TaskOrder::Deadline,
+                Some("importance") => TaskOrder::Importance,
+                _ => TaskOrder::Added,
+            };
+            let filtered = due_after.is_some() || due_before.is_some();
+            let tasks = if filtered {
+                let mut tasks =
+                    block_on(eva::tasks_due_between(configuration, due_after, due_before))?;
+                // The tasks come in the order they were added and sorting is
+                // stable, so ties stay in that order
+                match order {
+                    TaskOrder::Added => {}
+                    TaskOrder::Deadline => tasks.sort_by_key(|task| task.deadline),
+                    TaskOrder::Importance => tasks.sort_by_key(|task| Reverse(task.importance)),
+                }
+                tasks
+                    .into_iter()
+                    .skip(offset.unwrap_or(0) as usize)
+                    .take(limit.unwrap_or(u32::MAX) as usize)
+                    .collect()
+            } else if sort.is_none() && limit.is_none() && offset.is_none() {
+                block_on(eva::tasks(configuration))?
+            } else {
+                block_on(eva::tasks_page(
+                    configuration,
+                    order,
+                    limit.unwrap_or(u32::MAX),
+                    offset.unwrap_or(0),
+                ))?
+            };
+            if arguments::required(submatches, "format")? == "json" {
+                println!("{}", serde_json::to_string_pretty(&tasks)?);
+            } else if tasks.is_empty() {
+                match block_on(eva::task_count(configuration))? {
+                    0 => println!("No tasks left. Add one with `eva add`."),
+                    count if filtered => {
+                        println!("No tasks are due then, out of {count} in total.")
+                    }
+                    count => println!("No tasks on this page, out of {count} in total."),
+                }
+            } else if arguments::flag(submatches, "tree")? {
+                println!(
+                    "Tasks:\n  {}",
+                    dependency_tree::render(&tasks).replace('\n', "\n  ")
+                );
+            } else if arguments::flag(submatches, "compact")? {
+                let palette = palette(configuration)?;
+                println!(
+                    "Tasks:\n  {}",
+                    pretty_print::pretty_print_compact(&tasks, palette.as_ref())
+                        .replace('\n', "\n  ")
+                );
+            } else {
+                let palette = palette(configuration)?;
+                println!("Tasks:");
+                for task in &tasks {
+                    let pretty_task = match &palette {
+                        Some(palette) => task.pretty_print_colored(palette),
+                        None => task.pretty_print(),
+                    };
+                    // Indent all lines of the task by two spaces
+                    println!("  {}", pretty_task.split("\n").join("\n  "));
+                }
+            }
+            Ok(())
+        }
+        ("find", submatches) => {
+            let query = arguments::required(submatches, "query")?;
+            let tasks = block_on(eva::search_tasks(configuration, query))?;
+            if arguments::required(submatches, "format")? == "json" {
+                println!("{}", serde_json::to_string_pretty(&tasks)?);
+            } else if tasks.is_empty() {
+                println!("No tasks mention \"{query}\".");
+            } else {
+                let palette = palette(configuration)?;
                 println!("Tasks:");
                 for task in &tasks {
-                    // Indent all lines of task.pretty_print() by two spaces
-                    println!("  {}", task.pretty_print().split("\n").join("\n  "));
+                    let pretty_task = match &palette {
+                        Some(palette) => task.pretty_print_colored(palette),
+                        None => task.pretty_print(),
+                    };
+                    println!("  {}", pretty_task.split("\n").join("\n  "));
                 }
             }
             Ok(())
         }
         ("schedule", submatches) => {
-            let strategy = submatches.get_one::<String>("strategy").unwrap().to_owned();
-            let schedule = block_on(eva::schedule(configuration, &strategy))?;
-            println!("{}", schedule.pretty_print());
+            let strategy = arguments::optional(submatches, "strategy")?.map_or_else(
+                || configuration.scheduling_strategy.to_string(),
+                str::to_owned,
+            );
+            let max_tasks = arguments::optional(submatches, "max-tasks")?
+                .map(parse::max_tasks)
+                .transpose()?;
+            let horizon = arguments::optional(submatches, "horizon")?
+                .map(parse::duration)
+                .transpose()?;
+            let tasks_per_segment = eva::with_segment_strategies(
+                block_on(eva::tasks_per_nonempty_segment(configuration))?,
+                arguments::flag(submatches, "per-segment-strategy")?,
+            );
+            let (tasks_per_segment, mut deferred) = match max_tasks {
+                Some(max_tasks) => triage::defer_excess_tasks(
+                    tasks_per_segment,
+                    strategy.parse()?,
+                    now(inputs, configuration)?,
+                    max_tasks,
+                ),
+                None => (tasks_per_segment, vec![]),
+            };
+            let input_hash = schedule_cache::input_hash(
+                &tasks_per_segment,
+                &strategy,
+                configuration.estimate_buffers,
+                configuration.granularity,
+                configuration.breaks(),
+            );
+            let start = match arguments::optional(submatches, "from")? {
+                Some(from) => Some(parse::deadline(
+                    from,
+                    configuration.deadline_format.as_deref(),
+                )?),
+                None => pinned_now(inputs, configuration)?,
+            };
+            let busy = arguments::many(submatches, "busy")?
+                .into_iter()
+                .map(parse::busy)
+                .collect::<Result<Vec<_>, _>>()?;
+            let ignore_segments = arguments::flag(submatches, "ignore-segments")?;
+            let overrun = if arguments::flag(submatches, "best-effort")? {
+                Overrun::LeaveOut
+            } else if arguments::flag(submatches, "allow-late")? {
+                Overrun::RunLate
+            } else {
+                Overrun::Fail
+            };
+            let (schedule, unscheduled, lateness) = if ignore_segments {
+                let window = match arguments::optional(submatches, "start-of-day")? {
+                    Some(start_of_day) => UnnamedTimeSegment::daily_from(
+                        &now(inputs, configuration)?.with_timezone(&Local),
+                        parse::time_of_day(start_of_day)?,
+                    ),
+                    None => UnnamedTimeSegment::anytime(now(inputs, configuration)?),
+                };
+                let tasks = tasks_per_segment
+                    .into_iter()
+                    .flat_map(|(_, tasks)| tasks)
+                    .collect();
+                schedule_tasks(
+                    configuration,
+                    &strategy,
+                    vec![(window, tasks)],
+                    start,
+                    &busy,
+                    overrun,
+                )?
+            } else {
+                schedule_tasks(
+                    configuration,
+                    &strategy,
+                    tasks_per_segment,
+                    start,
+                    &busy,
+                    overrun,
+                )?
+            };
+            let last_schedule = if arguments::flag(submatches, "diff-with-last")? {
+                Some(schedule_cache::load(&schedule_cache::path()?)?)
+            } else {
+                None
+            };
+            let (schedule, beyond_horizon) = match horizon {
+                Some(horizon) => {
+                    let from = start.map_or_else(|| now(inputs, configuration), Ok)?;
+                    schedule.clip(from + horizon)
+                }
+                None => (schedule, vec![]),
+            };
+            deferred.extend(beyond_horizon);
+            // A schedule from some other moment on, in other time segments,
+            // around busy times, without some tasks, past some deadlines or up
+            // to some horizon is only a preview
+            if !arguments::flag(inputs, "dry-run")?
+                && start.is_none()
+                && !ignore_segments
+                && busy.is_empty()
+                && unscheduled.is_empty()
+                && overrun != Overrun::RunLate
+                && horizon.is_none()
+            {
+                schedule_cache::store(&schedule_cache::path()?, &schedule, input_hash)?;
+            }
+            if arguments::flag(submatches, "persist")? {
+                block_on(eva::persist_schedule(configuration, &schedule))?;
+            }
+
+            if let Some(path) = arguments::optional(submatches, "html")? {
+                let time_segments = block_on(eva::time_segments(configuration))?;
+                let html = export::to_html(&schedule, &Palette::new(&time_segments));
+                std::fs::write(path, html)
+                    .with_context(|| format!("I couldn't write the schedule to {path}."))?;
+            }
+
+            let format = arguments::required(submatches, "format")?;
+            match format {
+                "json" => println!("{}", serde_json::to_string_pretty(&schedule)?),
+                "ical" => {
+                    let calendar_name = arguments::optional(submatches, "ics-calendar-name")?;
+                    let timezone = ical::Timezone::local();
+                    let ical = ical::schedule_to_ical(
+                        &schedule,
+                        now(inputs, configuration)?,
+                        calendar_name,
+                        timezone.as_ref(),
+                    );
+                    print!("{ical}");
+                }
+                "svg" => {
+                    let time_segments = block_on(eva::time_segments(configuration))?;
+                    let svg = svg::schedule_to_svg(
+                        &schedule,
+                        start.map_or_else(|| now(inputs, configuration), Ok)?,
+                        &Palette::new(&time_segments),
+                    );
+                    print!("{svg}");
+                }
+                _ => {
+                    let palette = palette(configuration)?;
+                    if arguments::optional(submatches, "group-by")?.is_some() {
+                        println!(
+                            "{}",
+                            pretty_print::pretty_print_schedule_by_day(&schedule, palette.as_ref())
+                        );
+                    } else {
+                        let breaks = configuration.breaks().map_or_else(Vec::new, |breaks| {
+                            schedule.breaks(breaks, configuration.granularity)
+                        });
+                        println!(
+                            "{}",
+                            pretty_print::pretty_print_schedule_with_breaks(
+                                &schedule,
+                                &breaks,
+                                palette.as_ref()
+                            )
+                        );
+                    }
+                }
+            }
+            // Keep stdout machine-readable
+            let report = |text: String| {
+                if format == "human" {
+                    println!("{text}");
+                } else {
+                    eprintln!("{text}");
+                }
+            };
+            if !deferred.is_empty() {
+                let deferred = deferred
+                    .iter()
+                    .map(|task| task.pretty_print().split("\n").join("\n  "))
+                    .join("\n  ");
+                report(format!("Deferred:\n  {deferred}"));
+            }
+            if !unscheduled.is_empty() {
+                let unscheduled = unscheduled
+                    .iter()
+                    .map(|task| task.pretty_print().split("\n").join("\n  "))
+                    .join("\n  ");
+                report(format!("Couldn't fit:\n  {unscheduled}"));
+            }
+            if overrun == Overrun::RunLate {
+                let late = lateness
+                    .iter()
+                    .filter(|lateness| lateness.lateness > chrono::Duration::zero())
+                    .map(PrettyPrint::pretty_print)
+                    .collect_vec();
+                if late.is_empty() {
+                    report("Every task is done before its deadline.".to_string());
+                } else {
+                    report(format!("Late:\n  {}", late.join("\n  ")));
+                }
+            }
+            match last_schedule {
+                Some(Some(last_schedule)) => report(last_schedule.diff(&schedule).pretty_print()),
+                Some(None) => report("There is no earlier schedule to compare with.".to_string()),
+                None => {}
+            }
+            Ok(())
+        }
+        ("next", _submatches) => {
+            let schedule = next_schedule(inputs, configuration)?;
+            let now = now(inputs, configuration)?;
+            let palette = palette(configuration)?;
+            let pretty_print = |scheduled: &eva::Scheduled<eva::Task>| match &palette {
+                Some(palette) => scheduled.pretty_print_colored(palette),
+                None => scheduled.pretty_print(),
+            };
+            match schedule.next_after(now) {
+                Some(scheduled) if scheduled.when <= now => {
+                    let remaining = scheduled.end() - now;
+                    println!(
+                        "In progress: {} ({} left)",
+                        pretty_print(scheduled),
+                        remaining.pretty_print()
+                    );
+                }
+                Some(scheduled) => println!("Next up: {}", pretty_print(scheduled)),
+                None => println!("No tasks left. Add one with `eva add`."),
+            }
+            Ok(())
+        }
+        ("capacity", _submatches) => {
+            let report = block_on(eva::capacity_report(configuration))?;
+            let segments = report
+                .iter()
+                .map(|capacity| capacity.pretty_print().replace('\n', "\n  "))
+                .join("\n  ");
+            let verdict = if report.iter().any(|capacity| capacity.deficit().is_some()) {
+                "Not all tasks fit in their time segments. You might want to relax their \
+                 deadlines, move them to another time segment or decide not to do some of them."
+            } else {
+                "All tasks fit in their time segments."
+            };
+            println!("Time segment capacity:\n  {segments}\n{verdict}");
             Ok(())
         }
-        _ => unreachable!(),
+        ("why", submatches) => {
+            let id = parse::id(arguments::required(submatches, "task-id")?)?;
+            let strategy = arguments::optional(submatches, "strategy")?.map_or_else(
+                || configuration.scheduling_strategy.to_string(),
+                str::to_owned,
+            );
+            let task = block_on(eva::get_task(configuration, id))?;
+            let tasks_per_segment = eva::with_segment_strategies(
+                block_on(eva::tasks_per_nonempty_segment(configuration))?,
+                arguments::flag(submatches, "per-segment-strategy")?,
+            );
+            let start = pinned_now(inputs, configuration)?;
+            match eva::explain_schedule(configuration, &strategy, tasks_per_segment, start, id)? {
+                Some(explanation) => println!("{}", explanation.pretty_print()),
+                None if task.done_at.is_some() => println!("Task {id} is done already."),
+                None => println!("Task {id} doesn't fit in the schedule, so it's left out."),
+            }
+            Ok(())
+        }
+        ("stats", _submatches) => {
+            let statistics = block_on(eva::statistics(configuration))?;
+            println!("{}", statistics.pretty_print());
+            Ok(())
+        }
+        ("doctor", _submatches) => {
+            let advice = block_on(eva::load_advice(configuration))?;
+            println!("{}", advice.pretty_print());
+            Ok(())
+        }
+        ("segment", submatches) => match arguments::subcommand(submatches)? {
+            ("list", _submatches) => {
+                let time_segments = block_on(eva::time_segments(configuration))?;
+                println!("Time segments:");
+                for time_segment in &time_segments {
+                    // Indent all lines of the time segment by two spaces
+                    println!("  {}", time_segment.pretty_print().split("\n").join("\n  "));
+                }
+                Ok(())
+            }
+            ("show", submatches) => {
+                let id = arguments::required(submatches, "time-segment-id")?;
+                let id = parse::time_segment_id(id)?;
+                let time_segment = block_on(eva::get_time_segment(configuration, id))?;
+                println!("{}", time_segment.pretty_print());
+                Ok(())
+            }
+            ("add", submatches) => {
+                let name = arguments::required(submatches, "name")?;
+                let ranges = parse::weekly_ranges(arguments::required(submatches, "ranges")?)?;
+                let time_segment = eva::time_segment::NewNamedTimeSegment {
+                    name: name.to_string(),
+                    // Any moment before the first range works to count the
+                    // weeks from
+                    start: ranges[0].start,
+                    ranges,
+                    period: chrono::Duration::weeks(1),
+                    hue: 0,
+                    strategy: None,
+                };
+                let time_segment = block_on(eva::add_time_segment(configuration, time_segment))?;
+                println!("Added time segment {}.", time_segment.id);
+                Ok(())
+            }
+            ("rename", submatches) => {
+                let id = arguments::required(submatches, "time-segment-id")?;
+                let id = parse::time_segment_id(id)?;
+                let name = arguments::required(submatches, "name")?;
+                block_on(eva::rename_time_segment(configuration, id, name))?;
+                println!("Renamed time segment {id} to {name}.");
+                Ok(())
+            }
+            ("rm", submatches) => {
+                let id = arguments::required(submatches, "time-segment-id")?;
+                let id = parse::time_segment_id(id)?;
+                if let Some(to) = arguments::optional(submatches, "move-tasks-to")? {
+                    let to = parse::time_segment_id(to)?;
+                    let amount_moved =
+                        block_on(eva::move_tasks_between_segments(configuration, id, to))?;
+                    println!("Moved {amount_moved} task(s) to time segment {to}.");
+                }
+                let time_segment = block_on(eva::get_time_segment(configuration, id))?;
+                block_on(eva::delete_time_segment(configuration, time_segment))?;
+                println!("Deleted time segment {id}.");
+                Ok(())
+            }
+            ("free", submatches) => {
+                let id = arguments::required(submatches, "time-segment-id")?;
+                let id = parse::time_segment_id(id)?;
+                let until = arguments::required(submatches, "until")?;
+                let until = parse::deadline(until, configuration.deadline_format.as_deref())?;
+                let time_segment = block_on(eva::get_time_segment(configuration, id))?;
+                let availability =
+                    availability::availability(&time_segment, now(inputs, configuration)?, until);
+                println!("{}:", time_segment.name);
+                for availability in &availability {
+                    println!("  {}", availability.pretty_print());
+                }
+                Ok(())
+            }
+            ("strategy", submatches) => {
+                let id = arguments::required(submatches, "time-segment-id")?;
+                let id = parse::time_segment_id(id)?;
+                let strategy = parse::strategy(arguments::required(submatches, "strategy")?)?;
+                let mut time_segment = block_on(eva::get_time_segment(configuration, id))?;
+                time_segment.strategy = strategy;
+                Ok(block_on(eva::update_time_segment(
+                    configuration,
+                    time_segment,
+                ))?)
+            }
+            (subcommand, _) => Err(arguments::unexpected("subcommand", subcommand).into()),
+        },
+        ("migrate", _submatches) => {
+            // The migrations already ran while reading the configuration
+            println!("The database is up to date.");
+            Ok(())
+        }
+        ("maintenance", _submatches) => {
+            block_on(eva::vacuum(configuration))?;
+            println!("The database is cleaned up.");
+            Ok(())
+        }
+        (subcommand, _) => Err(arguments::unexpected("subcommand", subcommand).into()),
     }
 }
 
+/// Returns the time segment new tasks go in: the Default one while it's there,
+/// or otherwise the oldest time segment which replaced it.
+fn default_time_segment_id(configuration: &Configuration) -> Result<u32> {
+    block_on(eva::time_segments(configuration))?
+        .iter()
+        .map(|time_segment| time_segment.id)
+        .min()
+        .context("There are no time segments to add tasks to.")
+}
+
 fn set_field(configuration: &Configuration, field: &str, id: u32, value: &str) -> Result<()> {
-    let mut task = block_on(eva::get_task(configuration, id))?;
+    let mut changes = eva::TaskChanges::default();
     match field {
-        "content" => task.content = value.to_string(),
-        "deadline" => task.deadline = parse::deadline(value)?,
-        "duration" => task.duration = parse::duration(value)?,
-        "importance" => task.importance = parse::importance(value)?,
-        _ => unreachable!(),
+        "content" => changes.content = Some(value.to_string()),
+        "deadline" => {
+            changes.deadline = Some(parse::deadline(
+                value,
+                configuration.deadline_format.as_deref(),
+            )?)
+        }
+        "duration" => changes.duration = Some(parse::duration(value)?),
+        "importance" => changes.importance = Some(parse::importance(value)?),
+        "confidence" => changes.confidence = Some(parse::confidence(value)?),
+        "time_segment_id" => changes.time_segment_id = Some(parse::time_segment_id(value)?),
+        "depends_on" => changes.depends_on = Some(parse::task_ids(value)?),
+        _ => return Err(arguments::unexpected("property", field).into()),
     };
-    Ok(block_on(eva::update_task(configuration, task))?)
+    block_on(eva::update_task_fields(configuration, id, changes))?;
+    Ok(())
+}
+
+/// What to do with the tasks which can't be done before their deadline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Overrun {
+    Fail,
+    LeaveOut,
+    RunLate,
+}
+
+/// A schedule, along with the tasks which were left out of it and how late
+/// each task would be
+type Outcome = (
+    eva::Schedule<eva::Task>,
+    Vec<eva::Task>,
+    Vec<eva::Lateness<eva::Task>>,
+);
+
+/// Schedules the tasks, along with the ones which were left out and how late
+/// each task would be, depending on what to do with the tasks which don't fit.
+fn schedule_tasks(
+    configuration: &Configuration,
+    strategy: &str,
+    tasks_per_segment: Vec<(impl TimeSegment, Vec<eva::Task>)>,
+    start: Option<DateTime<Utc>>,
+    busy: &[Range<DateTime<Utc>>],
+    overrun: Overrun,
+) -> Result<Outcome> {
+    match overrun {
+        Overrun::Fail => {
+            let schedule =
+                eva::schedule_tasks(configuration, strategy, tasks_per_segment, start, busy)?;
+            Ok((schedule, vec![], vec![]))
+        }
+        Overrun::LeaveOut => {
+            let (schedule, left_out) = eva::schedule_tasks_best_effort(
+                configuration,
+                strategy,
+                tasks_per_segment,
+                start,
+                busy,
+            )?;
+            Ok((schedule, left_out, vec![]))
+        }
+        Overrun::RunLate => {
+            let (schedule, lateness) = eva::schedule_tasks_allowing_lateness(
+                configuration,
+                strategy,
+                tasks_per_segment,
+                start,
+                busy,
+            )?;
+            Ok((schedule, vec![], lateness))
+        }
+    }
+}
+
+/// Makes the schedule `eva next` picks the next task from, which is the last
+/// one `eva schedule` made if nothing changed since.
+fn next_schedule(
+    inputs: &ArgMatches,
+    configuration: &Configuration,
+) -> Result<eva::Schedule<eva::Task>> {
+    let strategy = &configuration.scheduling_strategy.to_string();
+    // Like `eva schedule`, which only schedules time segments with a strategy
+    // of their own when asked to, so the plans agree
+    let tasks_per_segment = eva::with_segment_strategies(
+        block_on(eva::tasks_per_nonempty_segment(configuration))?,
+        false,
+    );
+    let input_hash = schedule_cache::input_hash(
+        &tasks_per_segment,
+        strategy,
+        configuration.estimate_buffers,
+        configuration.granularity,
+        configuration.breaks(),
+    );
+    Ok(match pinned_now(inputs, configuration)? {
+        Some(now) => {
+            eva::schedule_tasks(configuration, strategy, tasks_per_segment, Some(now), &[])?
+        }
+        // Reusing the last schedule keeps the advice stable from one run to the next
+        None => schedule_cache::load_or_compute(
+            &schedule_cache::path()?,
+            input_hash,
+            || {
+                Ok(eva::schedule_tasks(
+                    configuration,
+                    strategy,
+                    tasks_per_segment,
+                    None,
+                    &[],
+                )?)
+            },
+            !arguments::flag(inputs, "dry-run")?,
+        )?,
+    })
+}
+
+/// Returns the moment given with the hidden --now option, if any.
+fn pinned_now(inputs: &ArgMatches, configuration: &Configuration) -> Result<Option<DateTime<Utc>>> {
+    Ok(arguments::optional(inputs, "now")?
+        .map(|deadline| parse::deadline(deadline, configuration.deadline_format.as_deref()))
+        .transpose()?)
+}
+
+/// Returns the current time, unless it's pinned with --now.
+fn now(inputs: &ArgMatches, configuration: &Configuration) -> Result<DateTime<Utc>> {
+    Ok(pinned_now(inputs, configuration)?.unwrap_or_else(|| configuration.now()))
+}
+
+/// Returns the palette to color tasks with, unless the output shouldn't be
+/// colored.
+fn palette(configuration: &Configuration) -> Result<Option<Palette>> {
+    if !pretty_print::colors_enabled() {
+        return Ok(None);
+    }
+    let time_segments = block_on(eva::time_segments(configuration))?;
+    Ok(Some(Palette::new(&time_segments)))
 }
 
 fn handle_error(error: &Error) {
@@ -152,5 +1390,119 @@ fn handle_error(error: &Error) {
         eprintln!("\n{}", error.backtrace());
     }
 
-    process::exit(1);
+    process::exit(exit_code::of(error));
+}
+
+#[cfg(test)]
+mod tests {
+    use eva::configuration::{
+        default_break_length, default_granularity, Configuration, EstimateBuffers,
+        SchedulingStrategy, TaskDefaults,
+    };
+    use eva::database::memory::MemoryDatabase;
+
+    use super::*;
+
+    fn configuration() -> Configuration {
+        Configuration {
+            database: Box::new(MemoryDatabase::new()),
+            scheduling_strategy: SchedulingStrategy::Importance,
+            estimate_buffers: EstimateBuffers::default(),
+            granularity: default_granularity(),
+            deadline_format: None,
+            max_continuous: None,
+            break_length: default_break_length(),
+            task_defaults: TaskDefaults::default(),
+        }
+    }
+
+    fn run(configuration: &Configuration, arguments: &[&str]) {
+        let inputs = cli()
+            .try_get_matches_from([&["eva"], arguments].concat())
+            .unwrap();
+        dispatch(&inputs, configuration).unwrap();
+    }
+
+    #[test]
+    fn adds_tasks_with_the_configured_defaults() {
+        let configuration = Configuration {
+            task_defaults: TaskDefaults {
+                importance: 3,
+                duration: chrono::Duration::minutes(10),
+            },
+            ..configuration()
+        };
+        let add = |arguments: &[&str]| {
+            run(
+                &configuration,
+                &[&["add", "water plants", "tomorrow"], arguments].concat(),
+            )
+        };
+
+        add(&[]);
+        add(&["2h"]);
+        add(&["2h", "9"]);
+
+        let tasks = block_on(eva::tasks(&configuration)).unwrap();
+        let durations_and_importances = tasks
+            .iter()
+            .map(|task| (task.duration, task.importance))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            durations_and_importances,
+            [
+                (chrono::Duration::minutes(10), 3),
+                (chrono::Duration::hours(2), 3),
+                (chrono::Duration::hours(2), 9),
+            ]
+        );
+    }
+
+    #[test]
+    fn plans_the_next_task_like_the_schedule() {
+        let configuration = configuration();
+        run(
+            &configuration,
+            &["add", "low urgent", "1 Aug 2030 12:00", "1h", "1"],
+        );
+        run(
+            &configuration,
+            &["add", "important later", "3 Aug 2030 12:00", "1h", "9"],
+        );
+        // Only scheduling with --per-segment-strategy would put the urgent
+        // task first
+        run(&configuration, &["segment", "strategy", "0", "deadline"]);
+        let now = "1 Aug 2030 8:00";
+        run(&configuration, &["--now", now, "schedule", "--persist"]);
+
+        let tasks = block_on(eva::tasks(&configuration)).unwrap();
+        let first = tasks
+            .iter()
+            .min_by_key(|task| task.scheduled_for.unwrap())
+            .unwrap();
+        assert_eq!(first.content, "important later");
+        let inputs = cli()
+            .try_get_matches_from(["eva", "--now", now, "next"])
+            .unwrap();
+        let schedule = next_schedule(&inputs, &configuration).unwrap();
+        let next = schedule.iter().next().unwrap();
+        assert_eq!(next.task.id, first.id);
+        assert_eq!(Some(next.when), first.scheduled_for);
+    }
+
+    #[test]
+    fn adds_tasks_to_the_time_segment_which_replaced_the_default_one() {
+        let configuration = configuration();
+        run(
+            &configuration,
+            &["segment", "add", "Work", "Mon 9:00-17:00"],
+        );
+        run(&configuration, &["segment", "rm", "0"]);
+        run(&configuration, &["add", "water plants", "tomorrow"]);
+
+        let time_segments = block_on(eva::time_segments(&configuration)).unwrap();
+        assert_eq!(time_segments.len(), 1);
+        let tasks = block_on(eva::tasks(&configuration)).unwrap();
+        assert_eq!(tasks[0].time_segment_id, time_segments[0].id);
+    }
 }
@@ -1,7 +1,10 @@
 use std::fmt;
+use std::ops::Range;
 
 use chrono::prelude::*;
 use chrono::Duration;
+use eva::configuration::SchedulingStrategy;
+use eva::Confidence;
 
 #[derive(Debug)]
 pub struct Error {
@@ -36,39 +39,720 @@ pub fn id(id_str: &str) -> Result<u32> {
     })
 }
 
-pub fn importance(importance_str: &str) -> Result<u32> {
-    importance_str.parse::<u32>().map_err(|_| Error {
-        type_: "importance".to_owned(),
-        input: importance_str.to_owned(),
+/// Parses a comma-separated list of task ids. An empty list is fine too.
+pub fn task_ids(ids_str: &str) -> Result<Vec<u32>> {
+    ids_str
+        .split(',')
+        .map(str::trim)
+        .filter(|id_str| !id_str.is_empty())
+        .map(|id_str| id_str.parse::<u32>())
+        .collect::<std::result::Result<_, _>>()
+        .map_err(|_| Error {
+            type_: "list of task ids".to_owned(),
+            input: ids_str.to_owned(),
+            suggestion: "Try entering integers separated by commas, like '3,14'.".to_owned(),
+        })
+}
+
+pub fn time_segment_id(id_str: &str) -> Result<u32> {
+    id_str.parse::<u32>().map_err(|_| Error {
+        type_: "time segment id".to_owned(),
+        input: id_str.to_owned(),
         suggestion: "Try entering a valid integer.".to_owned(),
     })
 }
 
-pub fn duration(duration_hours: &str) -> Result<Duration> {
-    let hours = duration_hours.parse::<f64>().map_err(|_| Error {
-        type_: "duration".to_owned(),
-        input: duration_hours.to_owned(),
-        suggestion: "Try entering a valid, real number.".to_owned(),
-    })?;
+/// Parses a hue in degrees, or "none" for no hue at all.
+pub fn hue(hue_str: &str) -> Result<Option<u16>> {
+    match hue_str.parse::<u16>() {
+        _ if hue_str == "none" => Ok(None),
+        Ok(hue) if hue < 360 => Ok(Some(hue)),
+        _ => Err(Error {
+            type_: "hue".to_owned(),
+            input: hue_str.to_owned(),
+            suggestion: "Try entering an integer from 0 to 359, or \"none\".".to_owned(),
+        }),
+    }
+}
+
+pub fn strategy(strategy_str: &str) -> Result<Option<SchedulingStrategy>> {
+    match strategy_str.parse() {
+        _ if strategy_str == "none" => Ok(None),
+        Ok(strategy) => Ok(Some(strategy)),
+        Err(_) => Err(Error {
+            type_: "scheduling strategy".to_owned(),
+            input: strategy_str.to_owned(),
+            suggestion: "Try entering importance, urgency, balanced, deadline, shuffled:SEED or \
+                         \"none\"."
+                .to_owned(),
+        }),
+    }
+}
+
+pub fn max_tasks(max_tasks_str: &str) -> Result<usize> {
+    match max_tasks_str.parse::<usize>() {
+        Ok(max_tasks) if max_tasks > 0 => Ok(max_tasks),
+        _ => Err(Error {
+            type_: "maximum number of tasks".to_owned(),
+            input: max_tasks_str.to_owned(),
+            suggestion: "Try entering a positive integer.".to_owned(),
+        }),
+    }
+}
+
+/// Parses a number of tasks, like how many to list or skip.
+pub fn count(count_str: &str) -> Result<u32> {
+    count_str.parse::<u32>().map_err(|_| Error {
+        type_: "number of tasks".to_owned(),
+        input: count_str.to_owned(),
+        suggestion: "Try entering a whole number of at least 0.".to_owned(),
+    })
+}
+
+pub fn importance(importance_str: &str) -> Result<u32> {
+    importance_str
+        .parse::<u32>()
+        .ok()
+        .filter(|importance| eva::IMPORTANCE.contains(importance))
+        .ok_or_else(|| Error {
+            type_: "importance".to_owned(),
+            input: importance_str.to_owned(),
+            suggestion: "Try entering a whole number from 1 to 10.".to_owned(),
+        })
+}
+
+pub fn confidence(confidence_str: &str) -> Result<Confidence> {
+    match confidence_str {
+        "low" => Ok(Confidence::Low),
+        "medium" => Ok(Confidence::Medium),
+        "high" => Ok(Confidence::High),
+        _ => Err(Error {
+            type_: "confidence".to_owned(),
+            input: confidence_str.to_owned(),
+            suggestion: "Try entering low, medium or high.".to_owned(),
+        }),
+    }
+}
 
-    if hours <= 0.0 {
+/// Parses a duration, either as a (whole or decimal) number of hours like
+/// "1.5", with units like "90m", "1h30m" or "2d", or in ISO 8601 like
+/// "PT1H30M" or "P2D".
+pub fn duration(duration_str: &str) -> Result<Duration> {
+    let minutes = match duration_str
+        .parse::<f64>()
+        .map(|hours| 60.0 * hours)
+        .ok()
+        .or_else(|| minutes_with_units(duration_str))
+    {
+        Some(minutes) => Some(minutes),
+        None => iso8601_minutes(duration_str).transpose()?,
+    };
+    let minutes = minutes
+        .filter(|minutes| minutes.is_finite())
+        .ok_or_else(|| Error {
+            type_: "duration".to_owned(),
+            input: duration_str.to_owned(),
+            suggestion: "Try entering a valid, real number of hours, or something like \"90m\", \
+                         \"1h30m\", \"2d\" or \"PT1H30M\"."
+                .to_owned(),
+        })?;
+
+    if minutes <= 0.0 {
         return Err(Error {
             type_: "duration".to_owned(),
-            input: duration_hours.to_owned(),
+            input: duration_str.to_owned(),
             suggestion: "Try entering a positive number.".to_owned(),
         });
     }
 
-    Ok(Duration::minutes((60.0 * hours) as i64))
+    Ok(Duration::minutes(minutes as i64))
 }
 
-pub fn deadline(datetime: &str) -> Result<DateTime<Utc>> {
-    let local_datetime = Local
-        .datetime_from_str(datetime, "%-d %b %Y %-H:%M")
-        .map_err(|_| Error {
+/// Parses a duration with units, like "1h30m", into a number of minutes. Every
+/// unit can only be given once, in the order of days, hours and minutes.
+fn minutes_with_units(duration_str: &str) -> Option<f64> {
+    let mut units = [('d', 24.0 * 60.0), ('h', 60.0), ('m', 1.0)].iter();
+    let mut rest = duration_str.trim();
+    if rest.is_empty() {
+        return None;
+    }
+    let mut minutes = 0.0;
+    while !rest.is_empty() {
+        let unit_start = rest.find(|c: char| c.is_ascii_alphabetic())?;
+        let amount = rest[..unit_start].trim().parse::<f64>().ok()?;
+        let unit = rest[unit_start..].chars().next()?;
+        let (_, minutes_per_unit) = units.find(|(other, _)| *other == unit)?;
+        minutes += amount * minutes_per_unit;
+        rest = rest[unit_start + 1..].trim_start();
+    }
+    Some(minutes)
+}
+
+/// Parses an ISO 8601 duration, like "PT1H30M" or "P2D", into a number of
+/// minutes. Returns `None` if it isn't one, and an error if it has years or
+/// months, which don't have a fixed length.
+fn iso8601_minutes(duration_str: &str) -> Option<Result<f64>> {
+    let duration = duration_str.trim().to_ascii_uppercase();
+    let rest = duration.strip_prefix('P')?;
+    let (date, time) = match rest.split_once('T') {
+        Some((_, "")) => return None,
+        Some((date, time)) => (date, time),
+        None => (rest, ""),
+    };
+    if date.is_empty() && time.is_empty() {
+        return None;
+    }
+    let date_units = [
+        ('Y', None),
+        ('M', None),
+        ('W', Some(7.0 * 24.0 * 60.0)),
+        ('D', Some(24.0 * 60.0)),
+    ];
+    let time_units = [('H', Some(60.0)), ('M', Some(1.0)), ('S', Some(1.0 / 60.0))];
+    let mut minutes = 0.0;
+    for (part, units) in [(date, &date_units[..]), (time, &time_units[..])] {
+        let mut units = units.iter();
+        let mut rest = part;
+        while !rest.is_empty() {
+            let unit_start = rest.find(|c: char| c.is_ascii_alphabetic())?;
+            // Both a decimal point and a comma are allowed
+            let amount = rest[..unit_start].replace(',', ".").parse::<f64>().ok()?;
+            let unit = rest[unit_start..].chars().next()?;
+            match units.find(|(other, _)| *other == unit)? {
+                (_, Some(minutes_per_unit)) => minutes += amount * minutes_per_unit,
+                (_, None) => {
+                    return Some(Err(Error {
+                        type_: "duration".to_owned(),
+                        input: duration_str.to_owned(),
+                        suggestion: "Years and months don't always last equally long. Try \
+                                     entering the number of days instead, like \"P30D\"."
+                            .to_owned(),
+                    }))
+                }
+            }
+            rest = &rest[unit_start + 1..];
+        }
+    }
+    Some(Ok(minutes))
+}
+
+/// Parses a time of day like "8:30".
+pub fn time_of_day(time_str: &str) -> Result<NaiveTime> {
+    NaiveTime::parse_from_str(time_str, "%-H:%M").map_err(|_| Error {
+        type_: "time of day".to_owned(),
+        input: time_str.to_owned(),
+        suggestion: "Try entering something like \"8:30\" or \"14:00\".".to_owned(),
+    })
+}
+
+/// How absolute deadlines are entered unless the configuration says otherwise,
+/// like "4 Jul 2017 6:05"
+pub const DEFAULT_DEADLINE_FORMAT: &str = "%-d %b %Y %-H:%M";
+
+/// Parses a deadline, either as an absolute date and time in the given format
+/// (or by default like "4 Jul 2017 6:05"), or relative to now: "today",
+/// "tomorrow", "in N hours", "in N days" or "next monday". Relative days mean
+/// the end of that day.
+pub fn deadline(datetime: &str, format: Option<&str>) -> Result<DateTime<Utc>> {
+    deadline_relative_to(datetime, format, Local::now())
+}
+
+/// Checks that the chrono format string describes both a date and a time, so
+/// deadlines can be entered in it.
+pub fn deadline_format(format: &str) -> Result<String> {
+    let error = || Error {
+        type_: "deadline format".to_owned(),
+        input: format.to_owned(),
+        suggestion: "Try entering a chrono format with a date and a time, like \"%Y-%m-%d \
+                     %H:%M\"."
+            .to_owned(),
+    };
+    let items = chrono::format::StrftimeItems::new(format);
+    if items
+        .clone()
+        .any(|item| item == chrono::format::Item::Error)
+    {
+        return Err(error());
+    }
+    // Whatever can be shown in the format, should be understood in it as well
+    let example = example_deadline();
+    let shown = example.format_with_items(items).to_string();
+    match NaiveDateTime::parse_from_str(&shown, format) {
+        Ok(parsed) if parsed == example => Ok(format.to_owned()),
+        _ => Err(error()),
+    }
+}
+
+/// Returns the moment which error messages show as an example deadline.
+fn example_deadline() -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2017, 7, 4)
+        .and_then(|date| date.and_hms_opt(6, 5, 0))
+        .expect("the example deadline exists")
+}
+
+/// Parses a moment as an RFC 3339 timestamp like "2019-08-01T14:12:50Z", or
+/// like a deadline otherwise.
+pub fn timestamp(datetime: &str, deadline_format: Option<&str>) -> Result<DateTime<Utc>> {
+    match DateTime::parse_from_rfc3339(datetime) {
+        Ok(timestamp) => Ok(timestamp.with_timezone(&Utc)),
+        Err(_) => deadline(datetime, deadline_format).map_err(|_| Error {
+            type_: "timestamp".to_owned(),
+            input: datetime.to_owned(),
+            suggestion: "Try entering something like \"2019-08-01T14:12:50Z\", \"4 Jul 2017 \
+                         6:05\" or \"in 3 days\"."
+                .to_owned(),
+        }),
+    }
+}
+
+/// Parses a fixed appointment on a single day, like "8 Jul 9:00-10:30" or "8
+/// Jul 2019 9:00-10:30". Without a year, it's the first such day which isn't
+/// over yet.
+pub fn busy(busy_str: &str) -> Result<Range<DateTime<Utc>>> {
+    busy_relative_to(busy_str, Local::now())
+}
+
+/// Parses ranges which repeat every week, like "Mon 9:00-12:00, Wed
+/// 14:00-17:00", into those ranges in the current week, in chronological order.
+/// A range which ends before it starts runs into the next day.
+pub fn weekly_ranges(ranges_str: &str) -> Result<Vec<Range<DateTime<Utc>>>> {
+    weekly_ranges_relative_to(ranges_str, Local::now())
+}
+
+fn deadline_relative_to<Tz: TimeZone>(
+    datetime: &str,
+    format: Option<&str>,
+    now: DateTime<Tz>,
+) -> Result<DateTime<Utc>> {
+    let format = format.unwrap_or(DEFAULT_DEADLINE_FORMAT);
+    now.timezone()
+        .datetime_from_str(datetime, format)
+        .ok()
+        .or_else(|| relative_deadline(datetime, now))
+        .map(|deadline| deadline.with_timezone(&Utc))
+        .ok_or_else(|| Error {
             type_: "deadline".to_owned(),
             input: datetime.to_owned(),
-            suggestion: "Try entering something like \"4 Jul 2017 6:05\".".to_owned(),
-        })?;
-    Ok(local_datetime.with_timezone(&Utc))
+            suggestion: format!(
+                "Try entering something like \"{}\", \"tomorrow\", \"in 3 days\" or \"next \
+                 monday\".",
+                example_deadline().format(format)
+            ),
+        })
+}
+
+fn relative_deadline<Tz: TimeZone>(datetime: &str, now: DateTime<Tz>) -> Option<DateTime<Tz>> {
+    let lowercase = datetime.to_lowercase();
+    let words = lowercase.split_whitespace().collect::<Vec<_>>();
+    match words.as_slice() {
+        ["today"] => end_of_day(now, 0),
+        ["tomorrow"] => end_of_day(now, 1),
+        ["in", amount, unit] => {
+            let amount = amount.parse::<u32>().ok()?;
+            let duration = match *unit {
+                "hour" | "hours" => Duration::hours(amount.into()),
+                "day" | "days" => Duration::days(amount.into()),
+                _ => return None,
+            };
+            Some(now + duration)
+        }
+        ["next", weekday] => {
+            let weekday = weekday.parse::<Weekday>().ok()?;
+            let today = now.weekday();
+            let days_ahead =
+                (7 + weekday.num_days_from_monday() - today.num_days_from_monday()) % 7;
+            let days_ahead = if days_ahead == 0 { 7 } else { days_ahead };
+            end_of_day(now, days_ahead.into())
+        }
+        _ => None,
+    }
+}
+
+fn busy_relative_to<Tz: TimeZone>(
+    busy_str: &str,
+    now: DateTime<Tz>,
+) -> Result<Range<DateTime<Utc>>> {
+    let error = || Error {
+        type_: "busy time".to_owned(),
+        input: busy_str.to_owned(),
+        suggestion: "Try entering something like \"8 Jul 9:00-10:30\".".to_owned(),
+    };
+    let (day, times) = busy_str.trim().rsplit_once(' ').ok_or_else(error)?;
+    let (start, end) = times.split_once('-').ok_or_else(error)?;
+    let start = NaiveTime::parse_from_str(start, "%-H:%M").map_err(|_| error())?;
+    let end = NaiveTime::parse_from_str(end, "%-H:%M").map_err(|_| error())?;
+    if end <= start {
+        return Err(error());
+    }
+    let today = now.date_naive();
+    let date = NaiveDate::parse_from_str(day, "%-d %b %Y")
+        .ok()
+        .or_else(|| {
+            (today.year()..=today.year() + 1)
+                .filter_map(|year| {
+                    NaiveDate::parse_from_str(&format!("{day} {year}"), "%-d %b %Y").ok()
+                })
+                .find(|date| *date >= today)
+        })
+        .ok_or_else(error)?;
+    let at = |time| {
+        now.timezone()
+            .from_local_datetime(&date.and_time(time))
+            .earliest()
+            .map(|moment| moment.with_timezone(&Utc))
+            .ok_or_else(error)
+    };
+    Ok(at(start)?..at(end)?)
+}
+
+fn weekly_ranges_relative_to<Tz: TimeZone>(
+    ranges_str: &str,
+    now: DateTime<Tz>,
+) -> Result<Vec<Range<DateTime<Utc>>>> {
+    let monday = now.date_naive() - Duration::days(now.weekday().num_days_from_monday().into());
+    let mut ranges = ranges_str
+        .split(',')
+        .map(|range_str| {
+            let error = || Error {
+                type_: "weekly range".to_owned(),
+                input: range_str.trim().to_owned(),
+                suggestion: "Try entering a day and two times of day, like \"Mon 9:00-12:00\"."
+                    .to_owned(),
+            };
+            let (day, times) = range_str.trim().split_once(' ').ok_or_else(error)?;
+            let weekday = day.parse::<Weekday>().map_err(|_| error())?;
+            let (start, end) = times.trim().split_once('-').ok_or_else(error)?;
+            let start = NaiveTime::parse_from_str(start.trim(), "%-H:%M").map_err(|_| error())?;
+            let end = NaiveTime::parse_from_str(end.trim(), "%-H:%M").map_err(|_| error())?;
+            if start == end {
+                return Err(error());
+            }
+            let date = monday + Duration::days(weekday.num_days_from_monday().into());
+            let end_date = if end < start {
+                date + Duration::days(1)
+            } else {
+                date
+            };
+            let at = |date: NaiveDate, time| {
+                now.timezone()
+                    .from_local_datetime(&date.and_time(time))
+                    .earliest()
+                    .map(|moment| moment.with_timezone(&Utc))
+                    .ok_or_else(error)
+            };
+            Ok(at(date, start)?..at(end_date, end)?)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    ranges.sort_by_key(|range| range.start);
+    if ranges.windows(2).any(|pair| pair[1].start < pair[0].end) {
+        return Err(Error {
+            type_: "weekly ranges".to_owned(),
+            input: ranges_str.to_owned(),
+            suggestion: "Make sure the ranges don't overlap.".to_owned(),
+        });
+    }
+    Ok(ranges)
+}
+
+/// Returns midnight at the end of the day which is `days` days after `now`.
+fn end_of_day<Tz: TimeZone>(now: DateTime<Tz>, days: i64) -> Option<DateTime<Tz>> {
+    let midnight = (now.date_naive() + Duration::days(days + 1)).and_hms_opt(0, 0, 0)?;
+    now.timezone().from_local_datetime(&midnight).earliest()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn now() -> DateTime<Utc> {
+        // A Thursday
+        Utc.with_ymd_and_hms(2019, 8, 1, 14, 12, 50).unwrap()
+    }
+
+    #[test]
+    fn parses_durations_in_hours() {
+        assert_eq!(duration("2").unwrap(), Duration::hours(2));
+        assert_eq!(duration("1.5").unwrap(), Duration::minutes(90));
+        assert_eq!(duration("0.25").unwrap(), Duration::minutes(15));
+    }
+
+    #[test]
+    fn parses_durations_with_units() {
+        assert_eq!(duration("30m").unwrap(), Duration::minutes(30));
+        assert_eq!(duration("90m").unwrap(), Duration::minutes(90));
+        assert_eq!(duration("2h").unwrap(), Duration::hours(2));
+        assert_eq!(duration("1.5h").unwrap(), Duration::minutes(90));
+        assert_eq!(duration("1h30m").unwrap(), Duration::minutes(90));
+        assert_eq!(duration("1h 30m").unwrap(), Duration::minutes(90));
+        assert_eq!(duration("2d").unwrap(), Duration::days(2));
+        assert_eq!(duration("1d2h").unwrap(), Duration::hours(26));
+    }
+
+    #[test]
+    fn parses_iso8601_durations() {
+        assert_eq!(duration("PT30M").unwrap(), Duration::minutes(30));
+        assert_eq!(duration("PT1H30M").unwrap(), Duration::minutes(90));
+        assert_eq!(duration("P2D").unwrap(), Duration::days(2));
+        assert_eq!(duration("P1DT2H").unwrap(), Duration::hours(26));
+        assert_eq!(duration("P1W").unwrap(), Duration::weeks(1));
+        assert_eq!(duration("PT1,5H").unwrap(), Duration::minutes(90));
+        assert_eq!(duration("pt45m").unwrap(), Duration::minutes(45));
+
+        for duration_str in ["P1M", "P1Y", "P1Y2DT3H"] {
+            assert!(
+                duration(duration_str)
+                    .unwrap_err()
+                    .suggestion
+                    .starts_with("Years and months don't always last equally long."),
+                "{:?} should be rejected",
+                duration_str
+            );
+        }
+        for duration_str in ["P", "PT", "P1H", "PT1D", "P1DT", "PT30M1H"] {
+            assert!(
+                duration(duration_str)
+                    .unwrap_err()
+                    .suggestion
+                    .starts_with("Try entering a valid"),
+                "{:?} should be invalid",
+                duration_str
+            );
+        }
+        assert_eq!(
+            duration("PT0S").unwrap_err().suggestion,
+            "Try entering a positive number."
+        );
+    }
+
+    #[test]
+    fn parses_importances_from_1_to_10() {
+        assert_eq!(importance("1").unwrap(), 1);
+        assert_eq!(importance("10").unwrap(), 10);
+        for importance_str in ["0", "11", "-1", "high"] {
+            assert_eq!(
+                importance(importance_str).unwrap_err().suggestion,
+                "Try entering a whole number from 1 to 10.",
+                "{:?} should be rejected",
+                importance_str
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_durations() {
+        let suggestion = |duration_str| duration(duration_str).unwrap_err().suggestion;
+        for duration_str in ["", "h", "m30", "1x", "1h1h", "30m1h", "1h30", "inf", "soon"] {
+            assert!(
+                suggestion(duration_str).starts_with("Try entering a valid"),
+                "{:?} should be invalid",
+                duration_str
+            );
+        }
+        for duration_str in ["0", "-1", "0h0m", "-30m", "1h-90m"] {
+            assert_eq!(
+                suggestion(duration_str),
+                "Try entering a positive number.",
+                "{:?} should be rejected",
+                duration_str
+            );
+        }
+    }
+
+    #[test]
+    fn parses_times_of_day() {
+        assert_eq!(
+            time_of_day("8:30").unwrap(),
+            NaiveTime::from_hms_opt(8, 30, 0).unwrap()
+        );
+        assert_eq!(
+            time_of_day("07:05").unwrap(),
+            NaiveTime::from_hms_opt(7, 5, 0).unwrap()
+        );
+        assert!(time_of_day("24:00").is_err());
+        assert!(time_of_day("8").is_err());
+        assert!(time_of_day("morning").is_err());
+    }
+
+    #[test]
+    fn parses_hues() {
+        assert_eq!(hue("0").unwrap(), Some(0));
+        assert_eq!(hue("359").unwrap(), Some(359));
+        assert_eq!(hue("none").unwrap(), None);
+        assert!(hue("360").is_err());
+        assert!(hue("red").is_err());
+    }
+
+    #[test]
+    fn parses_timestamps() {
+        assert_eq!(
+            timestamp("2019-08-01T16:12:50+02:00", None).unwrap(),
+            Utc.with_ymd_and_hms(2019, 8, 1, 14, 12, 50).unwrap()
+        );
+        assert!(timestamp("tomorrow", None).is_ok());
+        assert!(timestamp("2019-08-01", None).is_err());
+    }
+
+    #[test]
+    fn parses_absolute_deadlines() {
+        assert_eq!(
+            deadline_relative_to("2 Aug 2019 19:00", None, now()).unwrap(),
+            Utc.with_ymd_and_hms(2019, 8, 2, 19, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn parses_deadlines_in_a_custom_format() {
+        let format = deadline_format("%Y-%m-%d %H:%M").unwrap();
+        assert_eq!(
+            deadline_relative_to("2019-08-02 19:00", Some(&format), now()).unwrap(),
+            Utc.with_ymd_and_hms(2019, 8, 2, 19, 0, 0).unwrap()
+        );
+        // Relative deadlines still work
+        assert_eq!(
+            deadline_relative_to("tomorrow", Some(&format), now()).unwrap(),
+            Utc.with_ymd_and_hms(2019, 8, 3, 0, 0, 0).unwrap()
+        );
+        // The default format doesn't anymore
+        let error = deadline_relative_to("2 Aug 2019 19:00", Some(&format), now()).unwrap_err();
+        assert!(error.to_string().contains("\"2017-07-04 06:05\""));
+    }
+
+    #[test]
+    fn rejects_deadline_formats_without_a_date_and_time() {
+        assert!(deadline_format(DEFAULT_DEADLINE_FORMAT).is_ok());
+        assert!(deadline_format("%d/%m/%Y %Hh%M").is_ok());
+        for format in ["%Y-%m-%d", "%H:%M", "%Y-%m-%d %H:%Q", "no placeholders", ""] {
+            let error = deadline_format(format).unwrap_err();
+            assert!(error.to_string().contains("%Y-%m-%d %H:%M"), "{}", format);
+        }
+    }
+
+    #[test]
+    fn parses_relative_deadlines() {
+        let parse = |datetime| deadline_relative_to(datetime, None, now()).unwrap();
+        assert_eq!(
+            parse("today"),
+            Utc.with_ymd_and_hms(2019, 8, 2, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            parse("Tomorrow"),
+            Utc.with_ymd_and_hms(2019, 8, 3, 0, 0, 0).unwrap()
+        );
+        assert_eq!(parse("in 3 hours"), now() + Duration::hours(3));
+        assert_eq!(parse("in 1 day"), now() + Duration::days(1));
+        assert_eq!(parse("in 3 days"), now() + Duration::days(3));
+        assert_eq!(
+            parse("next monday"),
+            Utc.with_ymd_and_hms(2019, 8, 6, 0, 0, 0).unwrap()
+        );
+        // Next Thursday is a week from now, not today
+        assert_eq!(
+            parse("next thursday"),
+            Utc.with_ymd_and_hms(2019, 8, 9, 0, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_deadlines() {
+        for datetime in [
+            "yesterday",
+            "in three days",
+            "in 3 weeks",
+            "next week",
+            "2019-08-02",
+        ] {
+            let error = deadline_relative_to(datetime, None, now()).unwrap_err();
+            assert!(error.to_string().contains("next monday"));
+        }
+    }
+
+    #[test]
+    fn parses_busy_times() {
+        let parse = |busy_str| busy_relative_to(busy_str, now()).unwrap();
+        let at = |month, day, hour, minute| {
+            Utc.with_ymd_and_hms(2019, month, day, hour, minute, 0)
+                .unwrap()
+        };
+        assert_eq!(parse("8 Aug 9:00-10:30"), at(8, 8, 9, 0)..at(8, 8, 10, 30));
+        assert_eq!(
+            parse("1 Aug 2019 8:00-9:00"),
+            at(8, 1, 8, 0)..at(8, 1, 9, 0)
+        );
+        // Today isn't over yet, but a day earlier in the year is
+        assert_eq!(parse("1 Aug 8:00-9:00"), at(8, 1, 8, 0)..at(8, 1, 9, 0));
+        assert_eq!(
+            parse("31 Jul 8:00-9:00"),
+            Utc.with_ymd_and_hms(2020, 7, 31, 8, 0, 0).unwrap()
+                ..Utc.with_ymd_and_hms(2020, 7, 31, 9, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_busy_times() {
+        for busy_str in [
+            "8 Aug",
+            "8 Aug 9:00",
+            "8 Aug 10:30-9:00",
+            "tomorrow 9:00-10:30",
+            "8 Aug 9:00-10:30am",
+        ] {
+            let error = busy_relative_to(busy_str, now()).unwrap_err();
+            assert_eq!(error.type_, "busy time", "{busy_str:?} should be rejected");
+        }
+    }
+
+    #[test]
+    fn parses_weekly_ranges() {
+        let parse = |ranges_str| weekly_ranges_relative_to(ranges_str, now()).unwrap();
+        // The week of now starts on Monday 29 July 2019
+        let at = |month, day, hour, minute| {
+            Utc.with_ymd_and_hms(2019, month, day, hour, minute, 0)
+                .unwrap()
+        };
+        assert_eq!(parse("Mon 9:00-12:00"), [at(7, 29, 9, 0)..at(7, 29, 12, 0)]);
+        assert_eq!(
+            parse("Wed 14:00-17:30, mon 9:00-12:00,Sunday 8:00-9:00"),
+            [
+                at(7, 29, 9, 0)..at(7, 29, 12, 0),
+                at(7, 31, 14, 0)..at(7, 31, 17, 30),
+                at(8, 4, 8, 0)..at(8, 4, 9, 0),
+            ]
+        );
+        // A range which ends before it starts runs into the next day
+        assert_eq!(
+            parse("Fri 22:00-2:00, Sun 23:00-1:00"),
+            [
+                at(8, 2, 22, 0)..at(8, 3, 2, 0),
+                at(8, 4, 23, 0)..at(8, 5, 1, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_weekly_ranges() {
+        for (ranges_str, span) in [
+            ("Mon 9:00-12:00, Moo 14:00-17:00", "Moo 14:00-17:00"),
+            ("Mon", "Mon"),
+            ("Tue 9:00", "Tue 9:00"),
+            ("Tue 9:00-9:00", "Tue 9:00-9:00"),
+            ("Tue 9:00-25:00", "Tue 9:00-25:00"),
+            ("", ""),
+        ] {
+            let error = weekly_ranges_relative_to(ranges_str, now()).unwrap_err();
+            assert_eq!(error.input, span, "{ranges_str:?} should be rejected");
+            assert!(error.suggestion.contains("Mon 9:00-12:00"));
+        }
+        let error =
+            weekly_ranges_relative_to("Mon 9:00-12:00, Mon 11:00-13:00", now()).unwrap_err();
+        assert_eq!(error.suggestion, "Make sure the ranges don't overlap.");
+    }
+
+    #[test]
+    fn parses_task_ids() {
+        assert_eq!(task_ids("3").unwrap(), [3]);
+        assert_eq!(task_ids("3, 14,15").unwrap(), [3, 14, 15]);
+        assert!(task_ids("").unwrap().is_empty());
+        assert!(task_ids("3;14").is_err());
+        assert!(task_ids("-1").is_err());
+    }
 }
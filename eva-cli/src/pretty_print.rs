@@ -1,3 +1,8 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
+use std::io::IsTerminal;
+use std::ops::Range;
+
 use chrono::prelude::*;
 use itertools::Itertools;
 
@@ -5,22 +10,436 @@ pub(crate) trait PrettyPrint {
     fn pretty_print(&self) -> String;
 }
 
+/// Like `PrettyPrint`, but shows tasks in their color.
+pub(crate) trait PrettyPrintColored {
+    fn pretty_print_colored(&self, palette: &Palette) -> String;
+}
+
+/// Knows which hue to show each task in: its own color if it has one, or
+/// otherwise the hue of its time segment.
+pub(crate) struct Palette {
+    segment_hues: HashMap<u32, u16>,
+}
+
+impl Palette {
+    pub(crate) fn new(time_segments: &[eva::time_segment::NamedTimeSegment]) -> Self {
+        Palette {
+            segment_hues: time_segments
+                .iter()
+                .map(|time_segment| (time_segment.id, time_segment.hue))
+                .collect(),
+        }
+    }
+
+    pub(crate) fn hue_of(&self, task: &eva::Task) -> Option<u16> {
+        task.color
+            .or_else(|| self.segment_hues.get(&task.time_segment_id).copied())
+    }
+
+    /// Wraps the text in the escape codes to show it in the hue of the task.
+    fn paint(&self, task: &eva::Task, text: String) -> String {
+        match self.hue_of(task) {
+            Some(hue) => {
+                let (red, green, blue) = hue_to_rgb(hue);
+                format!("\x1b[38;2;{red};{green};{blue}m{text}\x1b[0m")
+            }
+            None => text,
+        }
+    }
+}
+
+/// Returns whether the output goes to a terminal which should show colors.
+/// See https://no-color.org.
+pub(crate) fn colors_enabled() -> bool {
+    std::io::stdout().is_terminal()
+        && std::env::var_os("NO_COLOR").is_none_or(|no_color| no_color.is_empty())
+}
+
+/// Converts a hue in degrees to a color which is readable on both dark and
+/// light backgrounds.
+fn hue_to_rgb(hue: u16) -> (u8, u8, u8) {
+    let (saturation, lightness) = (0.7, 0.55);
+    let chroma = (1.0 - (2.0 * lightness - 1.0_f64).abs()) * saturation;
+    let sector = f64::from(hue % 360) / 60.0;
+    let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+    let (red, green, blue) = match sector as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let lightest = lightness - chroma / 2.0;
+    let to_byte = |value: f64| ((value + lightest) * 255.0).round() as u8;
+    (to_byte(red), to_byte(green), to_byte(blue))
+}
+
 impl PrettyPrint for eva::Schedule<eva::Task> {
     fn pretty_print(&self) -> String {
-        if self.0.len() == 0 {
-            format!("No tasks left. Add one with `eva add`.")
-        } else {
-            format!(
-                "Schedule:\n  {}",
-                self.0.iter().map(PrettyPrint::pretty_print).join("\n  ")
+        pretty_print_schedule(self, &[], PrettyPrint::pretty_print)
+    }
+}
+
+impl PrettyPrintColored for eva::Schedule<eva::Task> {
+    fn pretty_print_colored(&self, palette: &Palette) -> String {
+        pretty_print_schedule(self, &[], |scheduled| {
+            scheduled.pretty_print_colored(palette)
+        })
+    }
+}
+
+/// Shows the schedule like its `PrettyPrint` implementation, with the given
+/// breaks on lines of their own between the tasks, colored if given a palette.
+pub(crate) fn pretty_print_schedule_with_breaks(
+    schedule: &eva::Schedule<eva::Task>,
+    breaks: &[Range<DateTime<Utc>>],
+    palette: Option<&Palette>,
+) -> String {
+    pretty_print_schedule(schedule, breaks, |scheduled| match palette {
+        Some(palette) => scheduled.pretty_print_colored(palette),
+        None => scheduled.pretty_print(),
+    })
+}
+
+fn pretty_print_schedule(
+    schedule: &eva::Schedule<eva::Task>,
+    breaks: &[Range<DateTime<Utc>>],
+    pretty_print_scheduled: impl Fn(&eva::Scheduled<eva::Task>) -> String,
+) -> String {
+    if schedule.is_empty() {
+        "No tasks left. Add one with `eva add`.".to_string()
+    } else {
+        let (busy, idle) = schedule.utilisation(DateTime::<Utc>::MAX_UTC);
+        // Tasks planned to end less than an hour before their deadline leave
+        // little room for delays
+        let tight = schedule.tight_tasks(chrono::Duration::hours(1));
+        let tasks = schedule.iter().map(|scheduled| {
+            let line = pretty_print_scheduled(scheduled);
+            let line = if tight.contains(&scheduled) {
+                format!("{} ⚠ cutting it close", line)
+            } else {
+                line
+            };
+            (scheduled.when, line)
+        });
+        let breaks = breaks.iter().map(|range| {
+            let line = format!(
+                "{}: Break ({})",
+                range.start.pretty_print(),
+                (range.end - range.start).pretty_print()
+            );
+            (range.start, line)
+        });
+        let mut lines = tasks
+            .merge_by(breaks, |(task, _), (break_, _)| task < break_)
+            .map(|(_, line)| line);
+        format!(
+            "Schedule:\n  {}\nTotal: {} busy, {} free",
+            lines.join("\n  "),
+            busy.pretty_print(),
+            idle.pretty_print()
+        )
+    }
+}
+
+/// Shows each task on a single line, like "42 [imp 8] due Fri 5 Jul 14:00 (2h):
+/// write report", with the columns aligned over all lines and colored if given
+/// a palette.
+pub(crate) fn pretty_print_compact(tasks: &[eva::Task], palette: Option<&Palette>) -> String {
+    let columns = tasks
+        .iter()
+        .map(|task| {
+            (
+                task.id.to_string(),
+                task.importance.to_string(),
+                format!(
+                    "{} ({})",
+                    task.deadline.pretty_print(),
+                    task.duration.pretty_print()
+                ),
             )
+        })
+        .collect_vec();
+    let widest = |column: fn(&(String, String, String)) -> &String| {
+        columns
+            .iter()
+            .map(|row| column(row).chars().count())
+            .max()
+            .unwrap_or(0)
+    };
+    let id_width = widest(|row| &row.0);
+    let importance_width = widest(|row| &row.1);
+    let due_width = widest(|row| &row.2) + 1;
+    tasks
+        .iter()
+        .zip(columns.iter())
+        .map(|(task, (id, importance, due))| {
+            let line = format!(
+                "{:>id_width$} [imp {:>importance_width$}] due {:<due_width$} {}",
+                id,
+                importance,
+                format!("{due}:"),
+                task.content.replace('\n', " "),
+            );
+            match palette {
+                Some(palette) => palette.paint(task, line),
+                None => line,
+            }
+        })
+        .join("\n")
+}
+
+/// Shows the schedule day by day: a header for each date with the tasks planned
+/// on it beneath, colored if given a palette.
+pub(crate) fn pretty_print_schedule_by_day(
+    schedule: &eva::Schedule<eva::Task>,
+    palette: Option<&Palette>,
+) -> String {
+    schedule_by_day(schedule, &Local, |task, line| match palette {
+        Some(palette) => palette.paint(task, line),
+        None => line,
+    })
+}
+
+/// Does the work for `pretty_print_schedule_by_day` in the given time zone. A
+/// task which runs past midnight is split over the days it spans.
+fn schedule_by_day<Tz: TimeZone>(
+    schedule: &eva::Schedule<eva::Task>,
+    timezone: &Tz,
+    paint: impl Fn(&eva::Task, String) -> String,
+) -> String
+where
+    Tz::Offset: Display,
+{
+    if schedule.is_empty() {
+        return "No tasks left. Add one with `eva add`.".to_string();
+    }
+    let tight = schedule.tight_tasks(chrono::Duration::hours(1));
+    let mut days = BTreeMap::<NaiveDate, Vec<String>>::new();
+    for scheduled in schedule {
+        let end = scheduled.end().with_timezone(timezone);
+        let mut from = scheduled.when.with_timezone(timezone);
+        let mut continued = false;
+        loop {
+            let day = from.date_naive();
+            let midnight = day.succ_opt().and_then(|next_day| {
+                timezone
+                    .from_local_datetime(&next_day.and_time(NaiveTime::MIN))
+                    .earliest()
+            });
+            let until = match midnight {
+                Some(midnight) if midnight < end => midnight,
+                _ => end.clone(),
+            };
+            let mut line = format!(
+                "{}–{} {}",
+                from.format("%H:%M"),
+                until.format("%H:%M"),
+                scheduled.task.content
+            );
+            if let Some(part) = scheduled.part {
+                line.push(' ');
+                line.push_str(&part.pretty_print());
+            }
+            if continued {
+                line.push_str(" (continued)");
+            }
+            let finished = until == end;
+            if finished && tight.contains(&scheduled) {
+                line.push_str(" ⚠ cutting it close");
+            }
+            days.entry(day)
+                .or_default()
+                .push(paint(&scheduled.task, line));
+            if finished {
+                break;
+            }
+            from = until;
+            continued = true;
         }
     }
+    let this_year = Utc::now().with_timezone(timezone).year();
+    let days = days.into_iter().map(|(day, lines)| {
+        let format = if day.year() == this_year {
+            "%A %-d %B"
+        } else {
+            "%A %-d %B %Y"
+        };
+        format!("{}\n    {}", day.format(format), lines.join("\n    "))
+    });
+    let (busy, idle) = schedule.utilisation(DateTime::<Utc>::MAX_UTC);
+    format!(
+        "Schedule:\n  {}\nTotal: {} busy, {} free",
+        days.format("\n  "),
+        busy.pretty_print(),
+        idle.pretty_print()
+    )
+}
+
+impl PrettyPrint for eva::Operation {
+    fn pretty_print(&self) -> String {
+        let task = self.task();
+        let verb = match self {
+            eva::Operation::Add { .. } => "adding",
+            eva::Operation::Delete { .. } => "removing",
+            eva::Operation::Update { .. } => "changing",
+        };
+        format!("{verb} {}. {}", task.id, task.content)
+    }
 }
 
 impl PrettyPrint for eva::Scheduled<eva::Task> {
     fn pretty_print(&self) -> String {
-        format!("{}: {}", self.when.pretty_print(), self.task.pretty_print())
+        let part = match self.part {
+            Some(part) => format!(" {}", part.pretty_print()),
+            None => String::new(),
+        };
+        format!(
+            "{}{}: {}",
+            self.when.pretty_print(),
+            part,
+            self.task.pretty_print()
+        )
+    }
+}
+
+impl PrettyPrint for eva::Part {
+    fn pretty_print(&self) -> String {
+        format!("(part {} of {})", self.number, self.of)
+    }
+}
+
+impl PrettyPrintColored for eva::Scheduled<eva::Task> {
+    fn pretty_print_colored(&self, palette: &Palette) -> String {
+        palette.paint(&self.task, self.pretty_print())
+    }
+}
+
+impl PrettyPrint for eva::ScheduleDiff<eva::Task> {
+    fn pretty_print(&self) -> String {
+        if self.is_empty() {
+            return "Nothing changed since the last schedule.".to_string();
+        }
+        let moved = self.moved.iter().map(|moved| {
+            format!(
+                "Moved from {} to {}: {}",
+                moved.from.pretty_print(),
+                moved.to.pretty_print(),
+                moved.task.pretty_print()
+            )
+        });
+        let added = self
+            .added
+            .iter()
+            .map(|scheduled| format!("Added {}", scheduled.pretty_print()));
+        let removed = self
+            .removed
+            .iter()
+            .map(|scheduled| format!("Removed {}", scheduled.pretty_print()));
+        format!(
+            "Changes since the last schedule:\n  {}",
+            moved.chain(added).chain(removed).join("\n  ")
+        )
+    }
+}
+
+impl PrettyPrint for eva::Explanation {
+    fn pretty_print(&self) -> String {
+        let task = &self.scheduled.task;
+        let sort_key = match self.sort_key {
+            eva::SortKey::Pinned { deadline } => format!(
+                "It's pinned, so it goes first, and then by its deadline ({}).",
+                deadline.pretty_print()
+            ),
+            eva::SortKey::Importance {
+                importance,
+                time_left,
+            } => format!(
+                "It's sorted on importance ({importance}) and then on the time left until its \
+                 deadline ({}).",
+                time_left.pretty_print()
+            ),
+            eva::SortKey::Shuffled { importance } => {
+                format!("It's sorted on importance ({importance}) and then at random.")
+            }
+            eva::SortKey::Balanced { score } => {
+                format!("It's sorted on importance and urgency together, which score {score}.")
+            }
+            eva::SortKey::Deadline {
+                deadline,
+                importance,
+            } => format!(
+                "It's sorted on its deadline ({}) and then on importance ({importance}).",
+                deadline.pretty_print()
+            ),
+        };
+        let preceded_by = if self.preceded_by.is_empty() {
+            "Nothing goes before it in its time segment.".to_string()
+        } else {
+            let reasons = self.preceded_by.iter().map(|precedence| {
+                let other = &precedence.task;
+                let reason = match precedence.reason {
+                    eva::Reason::Pinned => "it's pinned".to_string(),
+                    eva::Reason::DependedOn => format!("task {} depends on it", task.id),
+                    eva::Reason::MoreImportant => format!(
+                        "it's more important ({} vs {})",
+                        other.importance, task.importance
+                    ),
+                    eva::Reason::MoreUrgent => {
+                        format!("it's more urgent (due {})", other.deadline.pretty_print())
+                    }
+                    eva::Reason::HigherScore => "it scores higher".to_string(),
+                    eva::Reason::Tight => {
+                        format!(
+                            "it can't wait until after task {} to be done in time",
+                            task.id
+                        )
+                    }
+                    eva::Reason::Gap => format!("it fits before task {} does", task.id),
+                };
+                format!("{}. {}: {reason}", other.id, other.content)
+            });
+            format!(
+                "Before it in its time segment:\n  {}",
+                reasons.into_iter().join("\n  ")
+            )
+        };
+        format!(
+            "{}. {} is planned for {}.\n{sort_key}\nThat leaves {} to spare \
+             before its deadline.\n{preceded_by}",
+            task.id,
+            task.content,
+            self.scheduled.when.pretty_print(),
+            self.slack.pretty_print()
+        )
+    }
+}
+
+impl PrettyPrint for eva::Lateness<eva::Task> {
+    fn pretty_print(&self) -> String {
+        format!(
+            "{} late: {}. {} (deadline: {})",
+            self.lateness.pretty_print(),
+            self.task.id,
+            self.task.content,
+            self.task.deadline.pretty_print()
+        )
+    }
+}
+
+impl PrettyPrint for crate::availability::Availability {
+    fn pretty_print(&self) -> String {
+        format!(
+            "{} - {}: {}",
+            self.range.start.pretty_print(),
+            self.range.end.pretty_print(),
+            if self.available {
+                "available"
+            } else {
+                "blocked"
+            }
+        )
     }
 }
 
@@ -39,24 +458,655 @@ impl PrettyPrint for DateTime<Utc> {
 impl PrettyPrint for eva::Task {
     fn pretty_print(&self) -> String {
         let prefix = format!("{}. ", self.id);
+        let confidence = match self.confidence {
+            eva::Confidence::High => String::new(),
+            confidence => format!(", confidence: {}", confidence.as_str()),
+        };
+        let depends_on = if self.depends_on.is_empty() {
+            String::new()
+        } else {
+            format!(", depends on: {}", self.depends_on.iter().join(", "))
+        };
+        let pinned = if self.pinned { ", pinned" } else { "" };
+        let scheduled_for = match self.scheduled_for {
+            Some(when) => format!(", planned for: {}", when.pretty_print()),
+            None => String::new(),
+        };
         format!(
-            "{}{}\n{}(deadline: {}, duration: {}, importance: {})",
+            "{}{}\n{}(deadline: {}, duration: {}, importance: {}{}{}{}{})",
             prefix,
             self.content,
             " ".repeat(prefix.len()),
             self.deadline.pretty_print(),
             self.duration.pretty_print(),
-            self.importance
+            self.importance,
+            confidence,
+            depends_on,
+            pinned,
+            scheduled_for
         )
     }
 }
 
+impl PrettyPrintColored for eva::Task {
+    fn pretty_print_colored(&self, palette: &Palette) -> String {
+        palette.paint(self, self.pretty_print())
+    }
+}
+
+/// Shows when a time segment is in each period the way `eva segment add` reads
+/// it, like "Mon 9:00-12:00". In daily segments, only the times are shown.
+impl PrettyPrint for eva::time_segment::NamedTimeSegment {
+    fn pretty_print(&self) -> String {
+        let prefix = format!("{}. ", self.id);
+        let indentation = " ".repeat(prefix.len());
+        let format = if self.period == chrono::Duration::days(1) {
+            "%-H:%M"
+        } else {
+            "%a %-H:%M"
+        };
+        let ranges = self.ranges.iter().map(|range| {
+            let start = range.start.with_timezone(&Local);
+            let end = range.end.with_timezone(&Local);
+            // Leave out the day of the end when it's the same as the start's
+            let end_format = if end.date_naive() == start.date_naive() {
+                "%-H:%M"
+            } else {
+                format
+            };
+            format!(
+                "{}{}-{}",
+                indentation,
+                start.format(format),
+                end.format(end_format)
+            )
+        });
+        let ranges = if self.ranges.is_empty() {
+            format!("{}never", indentation)
+        } else {
+            ranges.format("\n").to_string()
+        };
+        let strategy = match self.strategy {
+            Some(strategy) => format!(", strategy: {}", strategy),
+            None => String::new(),
+        };
+        format!(
+            "{}{}\n{}(repeats every {}, hue: {}{})\n{}",
+            prefix,
+            self.name,
+            indentation,
+            pretty_print_period(self.period),
+            self.hue,
+            strategy,
+            ranges
+        )
+    }
+}
+
+/// Shows a period in whole weeks or days if it is one, like "1 week" or
+/// "3 days".
+fn pretty_print_period(period: chrono::Duration) -> String {
+    let plural = |amount: i64, unit: &str| match amount {
+        1 => format!("1 {}", unit),
+        amount => format!("{} {}s", amount, unit),
+    };
+    if period > chrono::Duration::zero() && period.num_seconds() % (7 * 24 * 3600) == 0 {
+        plural(period.num_weeks(), "week")
+    } else if period > chrono::Duration::zero() && period.num_seconds() % (24 * 3600) == 0 {
+        plural(period.num_days(), "day")
+    } else {
+        period.pretty_print()
+    }
+}
+
+/// Shows the days, hours and minutes of a duration, leaving out the ones which
+/// are zero, like "3d 4h" or "45m". Seconds only show for less than a minute.
 impl PrettyPrint for chrono::Duration {
     fn pretty_print(&self) -> String {
-        if self.num_minutes() > 0 {
-            format!("{}h{}", self.num_hours(), self.num_minutes() % 60)
+        if *self < chrono::Duration::zero() {
+            return format!("-{}", (-*self).pretty_print());
+        }
+        if *self < chrono::Duration::minutes(1) {
+            return match self.num_seconds() {
+                0 => "0m".to_string(),
+                seconds => format!("{}s", seconds),
+            };
+        }
+        [
+            (self.num_days(), "d"),
+            (self.num_hours() % 24, "h"),
+            (self.num_minutes() % 60, "m"),
+        ]
+        .iter()
+        .filter(|(amount, _)| *amount > 0)
+        .map(|(amount, unit)| format!("{}{}", amount, unit))
+        .join(" ")
+    }
+}
+
+impl PrettyPrint for eva::load_balancing::LoadAdvice {
+    fn pretty_print(&self) -> String {
+        let loads = self
+            .loads
+            .iter()
+            .map(PrettyPrint::pretty_print)
+            .join("\n  ");
+        let advice = if self.suggestions.is_empty() {
+            "Your time segments look balanced.".to_string()
         } else {
-            format!("{}h", self.num_hours())
+            let segment_name = |id| {
+                self.loads
+                    .iter()
+                    .find(|load| load.time_segment.id == id)
+                    .map_or("?", |load| load.time_segment.name.as_str())
+            };
+            let suggestions = self
+                .suggestions
+                .iter()
+                .map(|suggestion| {
+                    format!(
+                        "Move {}. {} ({}) from {} to {}",
+                        suggestion.task.id,
+                        suggestion.task.content,
+                        suggestion.task.duration.pretty_print(),
+                        segment_name(suggestion.task.time_segment_id),
+                        segment_name(suggestion.to_time_segment_id),
+                    )
+                })
+                .join("\n  ");
+            format!(
+                "Some time segments are overcommitted while others are idle. \
+                 You might want to:\n  {}",
+                suggestions
+            )
+        };
+        format!("Time segment load:\n  {}\n{}", loads, advice)
+    }
+}
+
+impl PrettyPrint for eva::load_balancing::SegmentLoad {
+    fn pretty_print(&self) -> String {
+        format!(
+            "{}: {:.0}% ({} planned of {} available)",
+            self.time_segment.name,
+            100.0 * self.utilisation(),
+            self.planned.pretty_print(),
+            self.available.pretty_print()
+        )
+    }
+}
+
+/// Shows the tasks of a segment which is over capacity beneath it, since
+/// those are the ones to reconsider.
+impl PrettyPrint for eva::SegmentCapacity {
+    fn pretty_print(&self) -> String {
+        let summary = format!(
+            "{}: {} needed of {} available",
+            self.time_segment.name,
+            self.required.pretty_print(),
+            self.available.pretty_print()
+        );
+        match self.deficit() {
+            None => summary,
+            Some(deficit) => {
+                let tasks = self
+                    .tasks
+                    .iter()
+                    .map(|task| task.pretty_print().replace('\n', "\n  "))
+                    .join("\n  ");
+                format!("{summary} ({} short)\n  {tasks}", deficit.pretty_print())
+            }
         }
     }
 }
+
+impl PrettyPrint for eva::statistics::Statistics {
+    fn pretty_print(&self) -> String {
+        let deadline = |deadline: Option<DateTime<Utc>>| {
+            deadline.map_or_else(|| "none".to_string(), |deadline| deadline.pretty_print())
+        };
+        let summary = format!(
+            "Tasks: {} ({})\nNearest deadline: {}\nFurthest deadline: {}",
+            self.tasks.count,
+            self.tasks.duration.pretty_print(),
+            deadline(self.nearest_deadline),
+            deadline(self.furthest_deadline),
+        );
+        let per_importance = self
+            .per_importance
+            .iter()
+            .map(|(bucket, count)| (format!("{}-{}", bucket.start(), bucket.end()), count))
+            .collect_vec();
+        let per_time_segment = self
+            .per_time_segment
+            .iter()
+            .map(|(time_segment, count)| (time_segment.name.clone(), count))
+            .collect_vec();
+        // Both tables share their columns
+        let rows = per_importance.iter().chain(&per_time_segment).collect_vec();
+        let label_width = rows.iter().map(|(label, _)| label.chars().count()).max();
+        let count_width = rows
+            .iter()
+            .map(|(_, count)| count.count.to_string().len())
+            .max();
+        let duration_width = rows
+            .iter()
+            .map(|(_, count)| count.duration.pretty_print().len())
+            .max();
+        let table = |rows: &[(String, &eva::statistics::TaskCount)]| {
+            rows.iter()
+                .map(|(label, count)| {
+                    format!(
+                        "  {:<label_width$}  {:>count_width$}  {:>duration_width$}",
+                        label,
+                        count.count,
+                        count.duration.pretty_print(),
+                        label_width = label_width.unwrap_or(0),
+                        count_width = count_width.unwrap_or(0),
+                        duration_width = duration_width.unwrap_or(0),
+                    )
+                })
+                .join("\n")
+        };
+        format!(
+            "{summary}\nBy importance:\n{}\nBy time segment:\n{}",
+            table(&per_importance),
+            table(&per_time_segment)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    fn time_segment(id: u32, hue: u16) -> eva::time_segment::NamedTimeSegment {
+        let start = Utc.with_ymd_and_hms(2019, 8, 1, 9, 0, 0).unwrap();
+        eva::time_segment::NamedTimeSegment {
+            id,
+            name: format!("segment {}", id),
+            ranges: vec![start..start + Duration::hours(8)],
+            start,
+            period: Duration::days(1),
+            hue,
+            strategy: None,
+        }
+    }
+
+    fn task(color: Option<u16>) -> eva::Task {
+        eva::Task {
+            id: 1,
+            content: "Find some good pipe-weed".to_string(),
+            deadline: Utc.with_ymd_and_hms(2019, 8, 4, 0, 0, 0).unwrap(),
+            duration: Duration::hours(1),
+            importance: 8,
+            confidence: eva::Confidence::High,
+            time_segment_id: 1,
+            depends_on: vec![],
+            color,
+            scheduled_for: None,
+            done_at: None,
+            pinned: false,
+        }
+    }
+
+    #[test]
+    fn shows_when_time_segments_are_in_each_period() {
+        // A Monday, in local time so the weekdays and times don't depend on
+        // the time zone
+        let monday = |hour| {
+            Local
+                .with_ymd_and_hms(2019, 8, 5, hour, 0, 0)
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        let mut time_segment = eva::time_segment::NamedTimeSegment {
+            id: 3,
+            name: "Work".to_string(),
+            ranges: vec![
+                monday(9)..monday(12),
+                monday(14) + Duration::days(2)..monday(17) + Duration::days(2),
+            ],
+            start: monday(0),
+            period: Duration::weeks(1),
+            hue: 120,
+            strategy: None,
+        };
+        assert_eq!(
+            time_segment.pretty_print(),
+            "3. Work\n   (repeats every 1 week, hue: 120)\n   Mon 9:00-12:00\n   Wed 14:00-17:00"
+        );
+
+        time_segment.ranges = vec![];
+        time_segment.period = Duration::weeks(2);
+        assert_eq!(
+            time_segment.pretty_print(),
+            "3. Work\n   (repeats every 2 weeks, hue: 120)\n   never"
+        );
+
+        time_segment.ranges = vec![monday(22)..monday(22) + Duration::hours(4)];
+        time_segment.period = Duration::days(1);
+        assert_eq!(
+            time_segment.pretty_print(),
+            "3. Work\n   (repeats every 1 day, hue: 120)\n   22:00-2:00"
+        );
+    }
+
+    #[test]
+    fn explains_which_tasks_go_first() {
+        let mut pipe_weed = task(None);
+        pipe_weed.importance = 2;
+        let task = |id, content: &str, importance| eva::Task {
+            id,
+            content: content.to_string(),
+            importance,
+            ..pipe_weed.clone()
+        };
+        let when = pipe_weed.deadline - Duration::hours(5);
+        let explanation = eva::Explanation {
+            scheduled: eva::Scheduled {
+                task: pipe_weed.clone(),
+                when,
+                duration: pipe_weed.duration,
+                part: None,
+            },
+            sort_key: eva::SortKey::Balanced { score: -10 },
+            slack: Duration::hours(4),
+            preceded_by: vec![
+                eva::Precedence {
+                    task: task(2, "Write a speech", 9),
+                    reason: eva::Reason::HigherScore,
+                },
+                eva::Precedence {
+                    task: task(3, "Buy a watering can", 1),
+                    reason: eva::Reason::DependedOn,
+                },
+            ],
+        };
+        assert_eq!(
+            explanation.pretty_print(),
+            format!(
+                "1. Find some good pipe-weed is planned for {}.\nIt's sorted on importance and \
+                 urgency together, which score -10.\nThat leaves 4h to spare before its \
+                 deadline.\nBefore it in its time segment:\n  2. Write a speech: it scores \
+                 higher\n  3. Buy a watering can: task 1 depends on it",
+                when.pretty_print()
+            )
+        );
+    }
+
+    #[test]
+    fn tells_how_late_a_task_would_be() {
+        let pipe_weed = task(None);
+        let lateness = eva::Lateness {
+            task: pipe_weed.clone(),
+            lateness: Duration::minutes(90),
+        };
+        assert_eq!(
+            lateness.pretty_print(),
+            format!(
+                "1h 30m late: 1. Find some good pipe-weed (deadline: {})",
+                pipe_weed.deadline.pretty_print()
+            )
+        );
+    }
+
+    #[test]
+    fn shows_tasks_in_the_hue_of_their_time_segment() {
+        let palette = Palette::new(&[time_segment(0, 0), time_segment(1, 120)]);
+        let task = task(None);
+        assert_eq!(
+            task.pretty_print_colored(&palette),
+            format!("\x1b[38;2;60;221;60m{}\x1b[0m", task.pretty_print())
+        );
+    }
+
+    #[test]
+    fn shows_tasks_compactly_in_aligned_columns() {
+        let tasks = [
+            eva::Task {
+                id: 9,
+                importance: 10,
+                duration: Duration::minutes(30),
+                ..task(None)
+            },
+            eva::Task {
+                id: 42,
+                content: "Write the report\nand send it".to_string(),
+                deadline: Utc.with_ymd_and_hms(2019, 8, 14, 13, 0, 0).unwrap(),
+                duration: Duration::hours(26),
+                ..task(None)
+            },
+        ];
+        let due = |task: &eva::Task| {
+            format!(
+                "{} ({}):",
+                task.deadline.pretty_print(),
+                task.duration.pretty_print()
+            )
+        };
+        let width = due(&tasks[0]).len().max(due(&tasks[1]).len());
+        assert_eq!(
+            pretty_print_compact(&tasks, None),
+            format!(
+                " 9 [imp 10] due {:<width$} Find some good pipe-weed\n\
+                 42 [imp  8] due {:<width$} Write the report and send it",
+                due(&tasks[0]),
+                due(&tasks[1]),
+            )
+        );
+        // With the deadlines as they're shown in UTC
+        if tasks[0].deadline.pretty_print() == "Sun 4 Aug 2019 0:00" {
+            assert_eq!(
+                pretty_print_compact(&tasks, None),
+                " 9 [imp 10] due Sun 4 Aug 2019 0:00 (30m):     Find some good pipe-weed\n\
+                 42 [imp  8] due Wed 14 Aug 2019 13:00 (1d 2h): Write the report and send it"
+            );
+        }
+    }
+
+    #[test]
+    fn shows_tasks_in_their_own_color() {
+        let palette = Palette::new(&[time_segment(0, 0), time_segment(1, 120)]);
+        let scheduled = eva::Scheduled {
+            task: task(Some(0)),
+            when: Utc.with_ymd_and_hms(2019, 8, 2, 12, 0, 0).unwrap(),
+            duration: Duration::hours(1),
+            part: None,
+        };
+        assert_eq!(
+            scheduled.pretty_print_colored(&palette),
+            format!("\x1b[38;2;221;60;60m{}\x1b[0m", scheduled.pretty_print())
+        );
+    }
+
+    #[test]
+    fn points_out_tasks_cutting_it_close() {
+        let deadline = task(None).deadline;
+        let schedule = eva::Schedule(vec![
+            eva::Scheduled {
+                task: task(None),
+                when: deadline - Duration::days(1),
+                duration: Duration::hours(1),
+                part: None,
+            },
+            eva::Scheduled {
+                task: task(None),
+                when: deadline - Duration::hours(1),
+                duration: Duration::hours(1),
+                part: None,
+            },
+        ]);
+        let lines = schedule.pretty_print();
+        let lines = lines.lines().collect::<Vec<_>>();
+        assert!(!lines[2].contains("cutting it close"));
+        assert!(lines[4].ends_with(") ⚠ cutting it close"));
+    }
+
+    #[test]
+    fn shows_breaks_between_tasks() {
+        let start = task(None).deadline - Duration::days(1);
+        let scheduled = |when| eva::Scheduled {
+            task: task(None),
+            when,
+            duration: Duration::hours(2),
+            part: None,
+        };
+        let schedule = eva::Schedule(vec![
+            scheduled(start),
+            scheduled(start + Duration::minutes(150)),
+        ]);
+        let breaks = [start + Duration::hours(2)..start + Duration::minutes(150)];
+        let lines = pretty_print_schedule_with_breaks(&schedule, &breaks, None);
+        let lines = lines.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[3],
+            format!("  {}: Break (30m)", breaks[0].start.pretty_print())
+        );
+        assert!(lines[4].starts_with(&format!("  {}", schedule.0[1].when.pretty_print())));
+    }
+
+    #[test]
+    fn groups_the_schedule_by_day() {
+        let timezone = FixedOffset::east_opt(2 * 60 * 60).unwrap();
+        let at = |day, hour, minute| {
+            timezone
+                .with_ymd_and_hms(2019, 8, day, hour, minute, 0)
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        let scheduled = |content: &str, when, hours| eva::Scheduled {
+            task: eva::Task {
+                content: content.to_string(),
+                duration: Duration::hours(hours),
+                ..task(None)
+            },
+            when,
+            duration: Duration::hours(hours),
+            part: None,
+        };
+        let schedule = eva::Schedule(vec![
+            scheduled("Pack", at(1, 9, 30), 1),
+            scheduled("Drive to the Shire", at(1, 22, 0), 3),
+            scheduled("Unpack", at(2, 10, 0), 1),
+        ]);
+        assert_eq!(
+            schedule_by_day(&schedule, &timezone, |_, line| line),
+            "Schedule:\n  \
+             Thursday 1 August 2019\n    \
+             09:30–10:30 Pack\n    \
+             22:00–00:00 Drive to the Shire\n  \
+             Friday 2 August 2019\n    \
+             00:00–01:00 Drive to the Shire (continued)\n    \
+             10:00–11:00 Unpack\n\
+             Total: 5h busy, 20h 30m free"
+        );
+    }
+
+    #[test]
+    fn labels_the_parts_of_split_tasks() {
+        let timezone = FixedOffset::east_opt(0).unwrap();
+        let at = |day, hour| Utc.with_ymd_and_hms(2019, 8, day, hour, 0, 0).unwrap();
+        let part = |day, hours, number| eva::Scheduled {
+            task: eva::Task {
+                content: "Write the report".to_string(),
+                duration: Duration::hours(20),
+                ..task(None)
+            },
+            when: at(day, 9),
+            duration: Duration::hours(hours),
+            part: Some(eva::Part { number, of: 3 }),
+        };
+        let schedule = eva::Schedule(vec![part(1, 8, 1), part(2, 8, 2), part(3, 4, 3)]);
+        assert!(schedule.0[1]
+            .pretty_print()
+            .contains(" (part 2 of 3): 1. Write the report"));
+        assert_eq!(
+            schedule_by_day(&schedule, &timezone, |_, line| line),
+            "Schedule:\n  \
+             Thursday 1 August 2019\n    \
+             09:00–17:00 Write the report (part 1 of 3)\n  \
+             Friday 2 August 2019\n    \
+             09:00–17:00 Write the report (part 2 of 3)\n  \
+             Saturday 3 August 2019\n    \
+             09:00–13:00 Write the report (part 3 of 3)\n\
+             Total: 20h busy, 1d 8h free"
+        );
+    }
+
+    #[test]
+    fn lists_the_tasks_of_segments_over_capacity() {
+        let capacity = |required| eva::SegmentCapacity {
+            time_segment: time_segment(0, 0),
+            tasks: vec![task(None)],
+            required: Duration::hours(required),
+            available: Duration::hours(8),
+        };
+        assert_eq!(
+            capacity(1).pretty_print(),
+            "segment 0: 1h needed of 8h available"
+        );
+        assert_eq!(
+            capacity(10).pretty_print(),
+            format!(
+                "segment 0: 10h needed of 8h available (2h short)\n  {}",
+                task(None).pretty_print().replace('\n', "\n  ")
+            )
+        );
+    }
+
+    #[test]
+    fn shows_statistics_in_aligned_columns() {
+        let time_segments = [time_segment(0, 0), time_segment(12, 0)];
+        let tasks = [
+            task(None),
+            eva::Task {
+                id: 2,
+                importance: 2,
+                duration: Duration::minutes(90),
+                time_segment_id: 12,
+                ..task(None)
+            },
+        ];
+        let statistics = eva::statistics::compute(&tasks, &time_segments);
+        let deadline = tasks[0].deadline.pretty_print();
+        assert_eq!(
+            statistics.pretty_print(),
+            format!(
+                "Tasks: 2 (2h 30m)\n\
+                 Nearest deadline: {deadline}\n\
+                 Furthest deadline: {deadline}\n\
+                 By importance:\n  \
+                   1-3         1  1h 30m\n  \
+                   4-7         0      0m\n  \
+                   8-10        1      1h\n\
+                 By time segment:\n  \
+                   segment 0   0      0m\n  \
+                   segment 12  1  1h 30m"
+            )
+        );
+    }
+
+    #[test]
+    fn pretty_prints_durations() {
+        assert_eq!(Duration::seconds(30).pretty_print(), "30s");
+        assert_eq!(Duration::minutes(45).pretty_print(), "45m");
+        assert_eq!(Duration::minutes(90).pretty_print(), "1h 30m");
+        assert_eq!(Duration::hours(26).pretty_print(), "1d 2h");
+        assert_eq!(Duration::days(10).pretty_print(), "10d");
+        assert_eq!(Duration::zero().pretty_print(), "0m");
+        assert_eq!(Duration::minutes(-90).pretty_print(), "-1h 30m");
+        // Seconds don't matter next to minutes
+        assert_eq!(Duration::seconds(90).pretty_print(), "1m");
+    }
+
+    #[test]
+    fn converts_hues_to_colors() {
+        assert_eq!(hue_to_rgb(0), (221, 60, 60));
+        assert_eq!(hue_to_rgb(120), (60, 221, 60));
+        assert_eq!(hue_to_rgb(240), (60, 60, 221));
+        assert_eq!(hue_to_rgb(360), hue_to_rgb(0));
+    }
+}
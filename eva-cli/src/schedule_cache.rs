@@ -0,0 +1,242 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::Duration;
+use eva::configuration::{Breaks, EstimateBuffers};
+use eva::time_segment::NamedTimeSegment;
+use serde_json::json;
+
+use crate::configuration;
+
+/// Returns where the last computed schedule is kept.
+pub fn path() -> Result<PathBuf> {
+    Ok(configuration::data_dir()?.join("last_schedule.json"))
+}
+
+/// Summarises everything a schedule is computed from, so a stored schedule
+/// can be reused as long as none of it changed.
+pub fn input_hash(
+    tasks_per_segment: &[(NamedTimeSegment, Vec<eva::Task>)],
+    strategy: &str,
+    estimate_buffers: EstimateBuffers,
+    granularity: Duration,
+    breaks: Option<Breaks>,
+) -> u64 {
+    // When the previous schedule planned the tasks doesn't change the next one
+    let tasks_per_segment = tasks_per_segment
+        .iter()
+        .map(|(time_segment, tasks)| {
+            let tasks = tasks
+                .iter()
+                .map(|task| eva::Task {
+                    scheduled_for: None,
+                    done_at: None,
+                    ..task.clone()
+                })
+                .collect::<Vec<_>>();
+            (time_segment, tasks)
+        })
+        .collect::<Vec<_>>();
+    let mut hasher = DefaultHasher::new();
+    tasks_per_segment.hash(&mut hasher);
+    strategy.hash(&mut hasher);
+    estimate_buffers.hash(&mut hasher);
+    granularity.hash(&mut hasher);
+    breaks.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Returns the schedule that was stored last, or `None` if no schedule was
+/// stored yet.
+pub fn load(path: &Path) -> Result<Option<eva::Schedule<eva::Task>>> {
+    Ok(read(path)?.map(|(schedule, _input_hash)| schedule))
+}
+
+/// Returns the schedule that was stored last if it was computed from inputs
+/// with the given hash, or otherwise computes a new one and stores that.
+pub fn load_or_compute(
+    path: &Path,
+    input_hash: u64,
+    compute: impl FnOnce() -> Result<eva::Schedule<eva::Task>>,
+    persist: bool,
+) -> Result<eva::Schedule<eva::Task>> {
+    if let Some((schedule, Some(stored_hash))) = read(path)? {
+        if stored_hash == input_hash {
+            return Ok(schedule);
+        }
+    }
+    let schedule = compute()?;
+    if persist {
+        store(path, &schedule, input_hash)?;
+    }
+    Ok(schedule)
+}
+
+pub fn store(path: &Path, schedule: &eva::Schedule<eva::Task>, input_hash: u64) -> Result<()> {
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory)?;
+    }
+    let json = json!({
+        "input_hash": input_hash,
+        "schedule": schedule,
+    });
+    fs::write(path, json.to_string())
+        .with_context(|| format!("I couldn't save the schedule ({})", path.display()))
+}
+
+fn read(path: &Path) -> Result<Option<(eva::Schedule<eva::Task>, Option<u64>)>> {
+    let json = match fs::read_to_string(path) {
+        Ok(json) => json,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => {
+            return Err(error)
+                .with_context(|| format!("I couldn't read the last schedule ({})", path.display()))
+        }
+    };
+    let understand = || {
+        format!(
+            "I couldn't understand the last schedule ({})",
+            path.display()
+        )
+    };
+    let mut json: serde_json::Value = serde_json::from_str(&json).with_context(understand)?;
+    // Schedules used to be stored without the hash of their inputs
+    let (mut schedule, input_hash) = if json.is_array() {
+        (json, None)
+    } else {
+        (json["schedule"].take(), json["input_hash"].as_u64())
+    };
+    // and without how long each task was planned for, which was just as long
+    // as the task itself
+    for scheduled in schedule.as_array_mut().into_iter().flatten() {
+        if scheduled.get("duration").is_none() {
+            scheduled["duration"] = scheduled["task"]["duration"].clone();
+        }
+    }
+    let schedule = serde_json::from_value(schedule).with_context(understand)?;
+    Ok(Some((schedule, input_hash)))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{SubsecRound, Utc};
+
+    use super::*;
+
+    #[test]
+    fn stores_and_loads_schedule() {
+        let path = std::env::temp_dir()
+            .join(format!("eva-test-{}", std::process::id()))
+            .join("last_schedule.json");
+        assert!(load(&path).unwrap().is_none());
+
+        let now = Utc::now().trunc_subsecs(0);
+        let scheduled = eva::Scheduled {
+            task: eva::Task {
+                id: 1,
+                content: "do me".to_string(),
+                deadline: now + Duration::days(1),
+                duration: Duration::hours(1),
+                importance: 5,
+                confidence: eva::Confidence::Low,
+                time_segment_id: 0,
+                depends_on: vec![],
+                color: None,
+                scheduled_for: None,
+                done_at: None,
+                pinned: false,
+            },
+            when: now,
+            duration: Duration::hours(1),
+            part: None,
+        };
+        store(&path, &eva::Schedule(vec![scheduled.clone()]), 42).unwrap();
+        assert_eq!(load(&path).unwrap().unwrap().0, vec![scheduled.clone()]);
+
+        // The stored schedule is reused as long as the inputs are the same
+        let schedule = load_or_compute(&path, 42, || panic!("recomputed"), true).unwrap();
+        assert_eq!(schedule.0, vec![scheduled.clone()]);
+
+        // After an edit, a new schedule is computed and stored
+        let mut rescheduled = scheduled.clone();
+        rescheduled.task.duration = Duration::hours(2);
+        let new_schedule = || Ok(eva::Schedule(vec![rescheduled.clone()]));
+        let schedule = load_or_compute(&path, 43, new_schedule, true).unwrap();
+        assert_eq!(schedule.0, vec![rescheduled.clone()]);
+        let schedule = load_or_compute(&path, 43, || panic!("recomputed"), true).unwrap();
+        assert_eq!(schedule.0, [rescheduled]);
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn loads_schedules_stored_without_durations() {
+        let path = std::env::temp_dir()
+            .join(format!("eva-test-legacy-{}", std::process::id()))
+            .join("last_schedule.json");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(
+            &path,
+            r#"[{"task":{"id":1,"content":"do me","deadline":"2019-08-02T19:00:00Z",
+                "duration":3600,"importance":5,"confidence":"high","time_segment_id":0,
+                "depends_on":[],"color":null,"scheduled_for":null},
+                "when":"2019-08-01T14:00:00Z"}]"#,
+        )
+        .unwrap();
+
+        let schedule = load(&path).unwrap().unwrap();
+        assert_eq!(schedule.0[0].duration, Duration::hours(1));
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn changes_input_hash_when_tasks_change() {
+        let now = Utc::now().trunc_subsecs(0);
+        let mut task = eva::Task {
+            id: 1,
+            content: "do me".to_string(),
+            deadline: now + Duration::days(1),
+            duration: Duration::hours(1),
+            importance: 5,
+            confidence: eva::Confidence::High,
+            time_segment_id: 0,
+            depends_on: vec![],
+            color: None,
+            scheduled_for: None,
+            done_at: None,
+            pinned: false,
+        };
+        let segment = NamedTimeSegment {
+            id: 0,
+            name: "Default".to_string(),
+            ranges: vec![now..now + Duration::hours(8)],
+            start: now,
+            period: Duration::days(1),
+            hue: 0,
+            strategy: None,
+        };
+        let buffers = EstimateBuffers::default();
+        let hash = |task: &eva::Task, strategy| {
+            input_hash(
+                &[(segment.clone(), vec![task.clone()])],
+                strategy,
+                buffers,
+                Duration::minutes(5),
+                None,
+            )
+        };
+
+        let original = hash(&task, "importance");
+        assert_eq!(hash(&task, "importance"), original);
+        assert_ne!(hash(&task, "urgency"), original);
+        task.scheduled_for = Some(now);
+        assert_eq!(hash(&task, "importance"), original);
+        task.duration = Duration::hours(2);
+        assert_ne!(hash(&task, "importance"), original);
+    }
+}
@@ -0,0 +1,233 @@
+use chrono::prelude::*;
+use itertools::Itertools;
+
+use crate::export::escape;
+use crate::pretty_print::{Palette, PrettyPrint};
+
+/// How wide the column with the task labels is, in pixels
+const LABEL_WIDTH: i64 = 220;
+/// How wide the time axis is, in pixels
+const CHART_WIDTH: i64 = 800;
+const ROW_HEIGHT: i64 = 24;
+/// How high the header with the first and last moment is, in pixels
+const HEADER_HEIGHT: i64 = 30;
+/// Labels longer than this many characters are cut off with an ellipsis.
+const MAX_LABEL_LENGTH: usize = 32;
+
+/// Renders the schedule as an SVG Gantt chart, with a row per task and time
+/// running from `start` until the last deadline. The bars are shown in the hue
+/// of their task and the tasks of a time segment are kept together, so tasks
+/// which overlap because they're in different segments are still readable.
+pub fn schedule_to_svg(
+    schedule: &eva::Schedule<eva::Task>,
+    start: DateTime<Utc>,
+    palette: &Palette,
+) -> String {
+    if schedule.is_empty() {
+        return "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"160\" height=\"30\">\n\
+                <text x=\"10\" y=\"20\" font-family=\"sans-serif\" font-size=\"12\">\
+                No tasks left.</text>\n\
+                </svg>\n"
+            .to_owned();
+    }
+    let start = std::cmp::min(
+        start,
+        schedule
+            .iter()
+            .map(|scheduled| scheduled.when)
+            .min()
+            .unwrap(),
+    );
+    let end = schedule
+        .iter()
+        .flat_map(|scheduled| [scheduled.end(), scheduled.task.deadline])
+        .max()
+        .unwrap();
+    let span = std::cmp::max((end - start).num_seconds(), 1);
+    let x =
+        |moment: DateTime<Utc>| LABEL_WIDTH + (moment - start).num_seconds() * CHART_WIDTH / span;
+
+    // Tasks which are split up get all of their parts on the same row
+    let rows = schedule
+        .iter()
+        .into_group_map_by(|scheduled| scheduled.task.id)
+        .into_values()
+        .sorted_by_key(|parts| (parts[0].task.time_segment_id, parts[0].when))
+        .collect_vec();
+    let width = LABEL_WIDTH + CHART_WIDTH + 10;
+    let height = HEADER_HEIGHT + ROW_HEIGHT * rows.len() as i64 + 10;
+    let mut lines = vec![
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" \
+             font-family=\"sans-serif\" font-size=\"12\">"
+        ),
+        format!(
+            "<text x=\"{LABEL_WIDTH}\" y=\"20\">{}</text>",
+            escape(&start.pretty_print())
+        ),
+        format!(
+            "<text x=\"{}\" y=\"20\" text-anchor=\"end\">{}</text>",
+            LABEL_WIDTH + CHART_WIDTH,
+            escape(&end.pretty_print())
+        ),
+    ];
+    for (index, parts) in rows.iter().enumerate() {
+        let task = &parts[0].task;
+        let y = HEADER_HEIGHT + ROW_HEIGHT * index as i64;
+        let color = match palette.hue_of(task) {
+            Some(hue) => format!("hsl({hue}, 70%, 55%)"),
+            None => "#999".to_owned(),
+        };
+        lines.push(format!(
+            "<text x=\"5\" y=\"{}\">{}</text>",
+            y + ROW_HEIGHT - 8,
+            escape(&truncate(&task.content))
+        ));
+        for scheduled in parts {
+            lines.push(format!(
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{color}\">\
+                 <title>{}</title></rect>",
+                x(scheduled.when),
+                y + 4,
+                std::cmp::max(x(scheduled.end()) - x(scheduled.when), 1),
+                ROW_HEIGHT - 8,
+                escape(&task.content)
+            ));
+        }
+        let deadline = x(task.deadline);
+        lines.push(format!(
+            "<line x1=\"{deadline}\" y1=\"{}\" x2=\"{deadline}\" y2=\"{}\" stroke=\"#c00\"/>",
+            y + 2,
+            y + ROW_HEIGHT - 2
+        ));
+    }
+    lines.push("</svg>".to_owned());
+    lines.join("\n") + "\n"
+}
+
+/// Returns the first line of the text, cut off with an ellipsis if it's too
+/// long for a label.
+fn truncate(text: &str) -> String {
+    let line = text.lines().next().unwrap_or("");
+    if line.chars().count() <= MAX_LABEL_LENGTH && !text.contains('\n') {
+        return line.to_owned();
+    }
+    let mut truncated = line.chars().take(MAX_LABEL_LENGTH - 1).collect::<String>();
+    truncated.push('…');
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    fn scheduled(
+        id: u32,
+        content: &str,
+        when: DateTime<Utc>,
+        duration: Duration,
+    ) -> eva::Scheduled<eva::Task> {
+        eva::Scheduled {
+            task: eva::Task {
+                id,
+                content: content.to_owned(),
+                deadline: Utc.with_ymd_and_hms(2019, 8, 2, 0, 0, 0).unwrap(),
+                duration,
+                importance: 5,
+                confidence: eva::Confidence::High,
+                time_segment_id: 0,
+                depends_on: vec![],
+                color: if id == 2 { Some(120) } else { None },
+                scheduled_for: None,
+                done_at: None,
+                pinned: false,
+            },
+            when,
+            duration,
+            part: None,
+        }
+    }
+
+    #[test]
+    fn draws_a_proportional_bar_per_task() {
+        let start = Utc.with_ymd_and_hms(2019, 8, 1, 0, 0, 0).unwrap();
+        let schedule = eva::Schedule(vec![
+            scheduled(1, "Call mom", start, Duration::hours(6)),
+            scheduled(
+                2,
+                "Write a <b>very</b> long report about everything",
+                start + Duration::hours(12),
+                Duration::hours(3),
+            ),
+        ]);
+        let svg = schedule_to_svg(&schedule, start, &Palette::new(&[]));
+
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.ends_with("</svg>\n"));
+        // A day is 800 pixels wide, starting after the labels
+        assert!(svg.contains(
+            "<rect x=\"220\" y=\"34\" width=\"200\" height=\"16\" fill=\"#999\"><title>Call mom"
+        ));
+        assert!(svg.contains(
+            "<rect x=\"620\" y=\"58\" width=\"100\" height=\"16\" fill=\"hsl(120, 70%, 55%)\">"
+        ));
+        assert!(svg.contains(">Write a &lt;b&gt;very&lt;/b&gt; long report…</text>"));
+        assert!(!svg.contains("<b>"));
+        assert_eq!(svg.matches("<line x1=\"1020\"").count(), 2);
+    }
+
+    #[test]
+    fn keeps_the_parts_of_a_task_and_its_time_segment_together() {
+        let start = Utc.with_ymd_and_hms(2019, 8, 1, 0, 0, 0).unwrap();
+        let mut other_segment = scheduled(3, "Elsewhere", start, Duration::hours(1));
+        other_segment.task.time_segment_id = 1;
+        let schedule = eva::Schedule(vec![
+            scheduled(1, "First part", start, Duration::hours(1)),
+            other_segment,
+            scheduled(
+                4,
+                "In between",
+                start + Duration::hours(2),
+                Duration::hours(1),
+            ),
+            scheduled(
+                1,
+                "Second part",
+                start + Duration::hours(4),
+                Duration::hours(1),
+            ),
+        ]);
+        let svg = schedule_to_svg(&schedule, start, &Palette::new(&[]));
+
+        let rows = svg
+            .lines()
+            .filter(|line| line.starts_with("<text x=\"5\""))
+            .collect_vec();
+        assert_eq!(
+            rows,
+            [
+                "<text x=\"5\" y=\"46\">First part</text>",
+                "<text x=\"5\" y=\"70\">In between</text>",
+                "<text x=\"5\" y=\"94\">Elsewhere</text>",
+            ]
+        );
+        assert_eq!(svg.matches(" y=\"34\" width=").count(), 2);
+    }
+
+    #[test]
+    fn draws_a_placeholder_for_an_empty_schedule() {
+        let svg = schedule_to_svg(&eva::Schedule(vec![]), Utc::now(), &Palette::new(&[]));
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.contains("No tasks left."));
+        assert!(!svg.contains("<rect"));
+    }
+
+    #[test]
+    fn truncates_labels() {
+        assert_eq!(truncate("Short"), "Short");
+        assert_eq!(truncate("First line\nSecond line"), "First line…");
+        assert_eq!(truncate(&"a".repeat(40)).chars().count(), MAX_LABEL_LENGTH);
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use eva::configuration::SchedulingStrategy;
+use eva::scheduling::sort_by_priority;
+use eva::time_segment::NamedTimeSegment;
+use eva::Task;
+use itertools::Itertools;
+
+/// Splits the given tasks into the `max_tasks` most pressing ones according to
+/// the given scheduling strategy, still grouped per time segment, and the
+/// remaining ones which should be deferred, most pressing first. A task is only
+/// kept along with the tasks it depends on, so it's deferred when they don't
+/// fit anymore.
+pub fn defer_excess_tasks(
+    tasks_per_segment: Vec<(NamedTimeSegment, Vec<Task>)>,
+    strategy: SchedulingStrategy,
+    start: DateTime<Utc>,
+    max_tasks: usize,
+) -> (Vec<(NamedTimeSegment, Vec<Task>)>, Vec<Task>) {
+    let by_id: HashMap<u32, &Task> = tasks_per_segment
+        .iter()
+        .flat_map(|(_, tasks)| tasks)
+        .map(|task| (task.id, task))
+        .collect();
+    let mut by_priority = tasks_per_segment
+        .iter()
+        .flat_map(|(_, tasks)| tasks)
+        .collect_vec();
+    sort_by_priority(&mut by_priority, strategy, start);
+    let mut kept_ids = HashSet::new();
+    for task in by_priority {
+        if kept_ids.contains(&task.id) {
+            continue;
+        }
+        let needed = with_prerequisites(task, &by_id)
+            .into_iter()
+            .filter(|id| !kept_ids.contains(id))
+            .collect_vec();
+        if kept_ids.len() + needed.len() <= max_tasks {
+            kept_ids.extend(needed);
+        }
+    }
+
+    let mut deferred = vec![];
+    let kept = tasks_per_segment
+        .into_iter()
+        .map(|(segment, tasks)| {
+            let (kept, deferred_in_segment): (Vec<_>, Vec<_>) = tasks
+                .into_iter()
+                .partition(|task| kept_ids.contains(&task.id));
+            deferred.extend(deferred_in_segment);
+            (segment, kept)
+        })
+        .collect();
+    sort_by_priority(&mut deferred, strategy, start);
+    (kept, deferred)
+}
+
+/// Returns the ids of the task and of the tasks it depends on, directly or
+/// through other tasks, among the given ones.
+fn with_prerequisites(task: &Task, by_id: &HashMap<u32, &Task>) -> HashSet<u32> {
+    let mut ids = HashSet::from([task.id]);
+    let mut to_visit = task.depends_on.clone();
+    while let Some(id) = to_visit.pop() {
+        if let Some(prerequisite) = by_id.get(&id) {
+            if ids.insert(id) {
+                to_visit.extend(&prerequisite.depends_on);
+            }
+        }
+    }
+    ids
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, SubsecRound, Utc};
+    use eva::configuration::{
+        default_break_length, default_granularity, Configuration, EstimateBuffers,
+        SchedulingStrategy, TaskDefaults,
+    };
+    use eva::Confidence;
+
+    use super::*;
+
+    #[test]
+    fn schedules_exactly_max_tasks_and_defers_the_rest() {
+        let configuration = Configuration {
+            database: Box::new(eva::database::sqlite::make_connection(":memory:").unwrap()),
+            scheduling_strategy: SchedulingStrategy::Importance,
+            estimate_buffers: EstimateBuffers::default(),
+            granularity: default_granularity(),
+            deadline_format: None,
+            max_continuous: None,
+            break_length: default_break_length(),
+            task_defaults: TaskDefaults::default(),
+        };
+        let now = Utc::now().trunc_subsecs(0);
+        let segment = NamedTimeSegment {
+            id: 0,
+            name: "Anytime".to_string(),
+            ranges: vec![now..now + Duration::days(1)],
+            start: now,
+            period: Duration::days(1),
+            hue: 0,
+            strategy: None,
+        };
+        let tasks = (1..=5)
+            .map(|i| Task {
+                id: i,
+                content: format!("task {i}"),
+                deadline: now + Duration::days(i.into()),
+                duration: Duration::hours(1),
+                importance: i,
+                confidence: Confidence::High,
+                time_segment_id: 0,
+                depends_on: vec![],
+                color: None,
+                scheduled_for: None,
+                done_at: None,
+                pinned: false,
+            })
+            .collect_vec();
+
+        for (strategy, deferred_ids) in [
+            (SchedulingStrategy::Importance, [2, 1]),
+            (SchedulingStrategy::Urgency, [4, 5]),
+        ] {
+            let (tasks_per_segment, deferred) =
+                defer_excess_tasks(vec![(segment.clone(), tasks.clone())], strategy, now, 3);
+            assert_eq!(
+                deferred.iter().map(|task| task.id).collect_vec(),
+                deferred_ids
+            );
+            let schedule = eva::schedule_tasks(
+                &configuration,
+                &strategy.to_string(),
+                tasks_per_segment,
+                None,
+                &[],
+            )
+            .unwrap();
+            assert_eq!(schedule.len(), 3);
+            for scheduled in &schedule {
+                assert!(!deferred.contains(&scheduled.task));
+            }
+        }
+
+        // Asking for more tasks than there are defers nothing
+        let (tasks_per_segment, deferred) = defer_excess_tasks(
+            vec![(segment, tasks.clone())],
+            SchedulingStrategy::Importance,
+            now,
+            10,
+        );
+        assert_eq!(tasks_per_segment[0].1, tasks);
+        assert!(deferred.is_empty());
+    }
+
+    #[test]
+    fn keeps_what_the_strategy_finds_most_pressing() {
+        let now = Utc::now().trunc_subsecs(0);
+        let segment = NamedTimeSegment {
+            id: 0,
+            name: "Anytime".to_string(),
+            ranges: vec![now..now + Duration::days(1)],
+            start: now,
+            period: Duration::days(1),
+            hue: 0,
+            strategy: None,
+        };
+        let task = |id, days_left, importance| Task {
+            id,
+            content: format!("task {id}"),
+            deadline: now + Duration::days(days_left),
+            duration: Duration::hours(1),
+            importance,
+            confidence: Confidence::High,
+            time_segment_id: 0,
+            depends_on: vec![],
+            color: None,
+            scheduled_for: None,
+            done_at: None,
+            pinned: false,
+        };
+        let tasks = vec![task(1, 1, 1), task(2, 30, 9), task(3, 3, 5)];
+        let kept_ids = |tasks: Vec<Task>, strategy, max_tasks| {
+            let (tasks_per_segment, _) =
+                defer_excess_tasks(vec![(segment.clone(), tasks)], strategy, now, max_tasks);
+            tasks_per_segment[0]
+                .1
+                .iter()
+                .map(|task| task.id)
+                .collect_vec()
+        };
+
+        assert_eq!(
+            kept_ids(tasks.clone(), SchedulingStrategy::Importance, 1),
+            [2]
+        );
+        assert_eq!(
+            kept_ids(tasks.clone(), SchedulingStrategy::Shuffled { seed: 42 }, 1),
+            [2]
+        );
+        assert_eq!(
+            kept_ids(tasks.clone(), SchedulingStrategy::Deadline, 1),
+            [1]
+        );
+        // Task 3 is important enough to make up for its later deadline
+        assert_eq!(
+            kept_ids(tasks.clone(), SchedulingStrategy::Balanced, 1),
+            [3]
+        );
+
+        // Pinned tasks go first, whatever the strategy
+        let mut pinned = tasks.clone();
+        pinned[0].pinned = true;
+        assert_eq!(kept_ids(pinned, SchedulingStrategy::Importance, 1), [1]);
+
+        // Tasks are only kept along with the tasks they depend on
+        let mut dependent = tasks;
+        dependent[1].depends_on = vec![1];
+        assert_eq!(
+            kept_ids(dependent.clone(), SchedulingStrategy::Importance, 1),
+            [3]
+        );
+        assert_eq!(
+            kept_ids(dependent, SchedulingStrategy::Importance, 2),
+            [1, 2]
+        );
+    }
+}
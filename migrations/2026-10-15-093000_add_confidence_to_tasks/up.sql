@@ -0,0 +1,3 @@
+-- 0 = low, 1 = medium, 2 = high; existing estimates are trusted as before
+ALTER TABLE tasks
+  ADD COLUMN confidence INTEGER NOT NULL DEFAULT 2;
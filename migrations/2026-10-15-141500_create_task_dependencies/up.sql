@@ -0,0 +1,5 @@
+CREATE TABLE task_dependencies (
+  task_id INTEGER NOT NULL,
+  dependency_id INTEGER NOT NULL,
+  PRIMARY KEY (task_id, dependency_id)
+);
@@ -0,0 +1,13 @@
+ALTER TABLE tasks RENAME TO oldTasks;
+CREATE TABLE tasks (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  content TEXT NOT NULL,
+  deadline TEXT NOT NULL,
+  duration INTEGER NOT NULL,
+  importance INTEGER NOT NULL,
+  time_segment_id INTEGER NOT NULL DEFAULT 0,
+  confidence INTEGER NOT NULL DEFAULT 2
+);
+INSERT INTO tasks (id, content, deadline, duration, importance, time_segment_id, confidence)
+  SELECT id, content, deadline, duration, importance, time_segment_id, confidence FROM oldTasks;
+DROP TABLE oldTasks;
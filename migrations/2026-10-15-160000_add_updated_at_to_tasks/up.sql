@@ -0,0 +1,3 @@
+-- Seconds since the epoch; existing tasks count as never updated
+ALTER TABLE tasks
+  ADD COLUMN updated_at INTEGER NOT NULL DEFAULT 0;
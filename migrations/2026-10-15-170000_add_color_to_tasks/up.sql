@@ -0,0 +1,3 @@
+-- A hue from 0 to 359, or NULL to use the hue of the task's time segment
+ALTER TABLE tasks
+  ADD COLUMN color INTEGER;
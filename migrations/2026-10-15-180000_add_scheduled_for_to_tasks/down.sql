@@ -0,0 +1,18 @@
+ALTER TABLE tasks RENAME TO oldTasks;
+CREATE TABLE tasks (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  content TEXT NOT NULL,
+  deadline TEXT NOT NULL,
+  duration INTEGER NOT NULL,
+  importance INTEGER NOT NULL,
+  time_segment_id INTEGER NOT NULL DEFAULT 0,
+  confidence INTEGER NOT NULL DEFAULT 2,
+  updated_at INTEGER NOT NULL DEFAULT 0,
+  color INTEGER
+);
+INSERT INTO tasks (id, content, deadline, duration, importance, time_segment_id, confidence,
+                   updated_at, color)
+  SELECT id, content, deadline, duration, importance, time_segment_id, confidence, updated_at,
+         color
+  FROM oldTasks;
+DROP TABLE oldTasks;
@@ -0,0 +1,4 @@
+-- When the last persisted schedule planned the task, as a UNIX timestamp, or
+-- NULL if it didn't
+ALTER TABLE tasks
+  ADD COLUMN scheduled_for INTEGER;
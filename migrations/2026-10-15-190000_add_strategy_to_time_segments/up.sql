@@ -0,0 +1,4 @@
+-- The scheduling strategy for the tasks in the segment, as written by
+-- SchedulingStrategy's Display, or NULL to use the global one
+ALTER TABLE time_segments
+  ADD COLUMN strategy TEXT;
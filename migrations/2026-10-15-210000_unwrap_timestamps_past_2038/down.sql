@@ -0,0 +1,6 @@
+UPDATE tasks SET deadline = deadline - 4294967296 WHERE deadline >= 2147483648;
+UPDATE tasks SET updated_at = updated_at - 4294967296 WHERE updated_at >= 2147483648;
+UPDATE tasks SET scheduled_for = scheduled_for - 4294967296 WHERE scheduled_for >= 2147483648;
+UPDATE time_segments SET start = start - 4294967296 WHERE start >= 2147483648;
+UPDATE time_segment_ranges SET start = start - 4294967296 WHERE start >= 2147483648;
+UPDATE time_segment_ranges SET end = end - 4294967296 WHERE end >= 2147483648;
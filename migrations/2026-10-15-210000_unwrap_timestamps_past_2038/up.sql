@@ -0,0 +1,10 @@
+-- Timestamps used to be cut down to 32 bits, which wrapped the ones past
+-- January 2038 around to negative numbers. SQLite's integers have 64 bits
+-- already, so only those wrapped timestamps need repairing. Eva doesn't deal in
+-- anything before 1970, so any negative timestamp must have wrapped.
+UPDATE tasks SET deadline = deadline + 4294967296 WHERE deadline < 0;
+UPDATE tasks SET updated_at = updated_at + 4294967296 WHERE updated_at < 0;
+UPDATE tasks SET scheduled_for = scheduled_for + 4294967296 WHERE scheduled_for < 0;
+UPDATE time_segments SET start = start + 4294967296 WHERE start < 0;
+UPDATE time_segment_ranges SET start = start + 4294967296 WHERE start < 0;
+UPDATE time_segment_ranges SET end = end + 4294967296 WHERE end < 0;
@@ -0,0 +1,20 @@
+-- The last changes to the tasks, so they can be undone. Each one keeps the task
+-- as it was before the change, or as it was added.
+CREATE TABLE operations (
+  id INTEGER PRIMARY KEY NOT NULL,
+  -- 'add', 'delete' or 'update'
+  kind VARCHAR NOT NULL,
+  task_id INTEGER NOT NULL,
+  content TEXT NOT NULL,
+  deadline BIGINT NOT NULL,
+  duration INTEGER NOT NULL,
+  importance INTEGER NOT NULL,
+  time_segment_id INTEGER NOT NULL,
+  confidence INTEGER NOT NULL,
+  color INTEGER,
+  scheduled_for BIGINT,
+  -- Comma-separated ids of the tasks this one depended on
+  depends_on VARCHAR NOT NULL,
+  -- For a deletion, comma-separated ids of the tasks which depended on this one
+  dependents VARCHAR NOT NULL
+);
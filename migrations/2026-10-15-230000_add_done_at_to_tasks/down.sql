@@ -0,0 +1,49 @@
+-- Without the column, tasks which are done would count as to do again
+DELETE FROM task_dependencies
+  WHERE task_id IN (SELECT id FROM tasks WHERE done_at IS NOT NULL)
+     OR dependency_id IN (SELECT id FROM tasks WHERE done_at IS NOT NULL);
+DELETE FROM tasks WHERE done_at IS NOT NULL;
+ALTER TABLE tasks RENAME TO oldTasks;
+CREATE TABLE tasks (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  content TEXT NOT NULL,
+  deadline TEXT NOT NULL,
+  duration INTEGER NOT NULL,
+  importance INTEGER NOT NULL,
+  time_segment_id INTEGER NOT NULL DEFAULT 0,
+  confidence INTEGER NOT NULL DEFAULT 2,
+  updated_at INTEGER NOT NULL DEFAULT 0,
+  color INTEGER,
+  scheduled_for INTEGER
+);
+INSERT INTO tasks (id, content, deadline, duration, importance, time_segment_id, confidence,
+                   updated_at, color, scheduled_for)
+  SELECT id, content, deadline, duration, importance, time_segment_id, confidence, updated_at,
+         color, scheduled_for
+  FROM oldTasks;
+DROP TABLE oldTasks;
+ALTER TABLE operations RENAME TO old_operations;
+CREATE TABLE operations (
+  id INTEGER PRIMARY KEY NOT NULL,
+  -- 'add', 'delete' or 'update'
+  kind VARCHAR NOT NULL,
+  task_id INTEGER NOT NULL,
+  content TEXT NOT NULL,
+  deadline BIGINT NOT NULL,
+  duration INTEGER NOT NULL,
+  importance INTEGER NOT NULL,
+  time_segment_id INTEGER NOT NULL,
+  confidence INTEGER NOT NULL,
+  color INTEGER,
+  scheduled_for BIGINT,
+  -- Comma-separated ids of the tasks this one depended on
+  depends_on VARCHAR NOT NULL,
+  -- For a deletion, comma-separated ids of the tasks which depended on this one
+  dependents VARCHAR NOT NULL
+);
+INSERT INTO operations (id, kind, task_id, content, deadline, duration, importance,
+                        time_segment_id, confidence, color, scheduled_for, depends_on, dependents)
+  SELECT id, kind, task_id, content, deadline, duration, importance, time_segment_id, confidence,
+         color, scheduled_for, depends_on, dependents
+  FROM old_operations;
+DROP TABLE old_operations;
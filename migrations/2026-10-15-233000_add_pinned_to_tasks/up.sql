@@ -0,0 +1,5 @@
+-- Whether the task goes first in its time segment, whatever its importance
+ALTER TABLE tasks
+  ADD COLUMN pinned BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE operations
+  ADD COLUMN pinned BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1,2 @@
+DROP TABLE time_segment_ranges;
+DROP TABLE time_segments;
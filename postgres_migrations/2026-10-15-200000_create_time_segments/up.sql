@@ -0,0 +1,35 @@
+-- The ids start at 1, which leaves 0 for the default time segment
+CREATE TABLE time_segments (
+  id SERIAL PRIMARY KEY,
+  name TEXT NOT NULL,
+  start TIMESTAMPTZ NOT NULL,
+  -- In seconds
+  period INTEGER NOT NULL,
+  hue INTEGER NOT NULL,
+  -- The scheduling strategy for the tasks in the segment, as written by
+  -- SchedulingStrategy's Display, or NULL to use the global one
+  strategy TEXT
+);
+
+CREATE TABLE time_segment_ranges (
+  segment_id INTEGER NOT NULL REFERENCES time_segments (id) ON DELETE CASCADE,
+  start TIMESTAMPTZ NOT NULL,
+  "end" TIMESTAMPTZ NOT NULL
+);
+CREATE INDEX time_segment_ranges_segment_id ON time_segment_ranges (segment_id);
+
+-- Add a default time segment: daily from 9 to 5 in the time zone of the session
+INSERT INTO time_segments
+VALUES (
+  0,
+  'Default',
+  date_trunc('week', now()) + INTERVAL '9 hours',
+  24 * 60 * 60,
+  floor(random() * 360)
+);
+INSERT INTO time_segment_ranges
+VALUES (
+  0,
+  date_trunc('week', now()) + INTERVAL '9 hours',
+  date_trunc('week', now()) + INTERVAL '17 hours'
+);
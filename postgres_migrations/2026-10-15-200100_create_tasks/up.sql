@@ -0,0 +1,22 @@
+CREATE TABLE tasks (
+  id SERIAL PRIMARY KEY,
+  content TEXT NOT NULL,
+  deadline TIMESTAMPTZ NOT NULL,
+  -- In seconds
+  duration INTEGER NOT NULL,
+  importance INTEGER NOT NULL,
+  time_segment_id INTEGER NOT NULL DEFAULT 0 REFERENCES time_segments (id),
+  -- 0 = low, 1 = medium, 2 = high
+  confidence INTEGER NOT NULL DEFAULT 2,
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  -- A hue from 0 to 359, or NULL to use the hue of the task's time segment
+  color INTEGER,
+  -- When the last persisted schedule planned the task, or NULL if it didn't
+  scheduled_for TIMESTAMPTZ
+);
+
+CREATE TABLE task_dependencies (
+  task_id INTEGER NOT NULL REFERENCES tasks (id) ON DELETE CASCADE,
+  dependency_id INTEGER NOT NULL REFERENCES tasks (id) ON DELETE CASCADE,
+  PRIMARY KEY (task_id, dependency_id)
+);
@@ -0,0 +1,20 @@
+-- The last changes to the tasks, so they can be undone. Each one keeps the task
+-- as it was before the change, or as it was added.
+CREATE TABLE operations (
+  id SERIAL PRIMARY KEY,
+  -- 'add', 'delete' or 'update'
+  kind VARCHAR NOT NULL,
+  task_id INTEGER NOT NULL,
+  content TEXT NOT NULL,
+  deadline TIMESTAMPTZ NOT NULL,
+  duration INTEGER NOT NULL,
+  importance INTEGER NOT NULL,
+  time_segment_id INTEGER NOT NULL,
+  confidence INTEGER NOT NULL,
+  color INTEGER,
+  scheduled_for TIMESTAMPTZ,
+  -- The tasks this one depended on
+  depends_on INTEGER[] NOT NULL,
+  -- For a deletion, the tasks which depended on this one
+  dependents INTEGER[] NOT NULL
+);
@@ -0,0 +1,6 @@
+-- Without the column, tasks which are done would count as to do again
+DELETE FROM tasks WHERE done_at IS NOT NULL;
+ALTER TABLE tasks
+  DROP COLUMN done_at;
+ALTER TABLE operations
+  DROP COLUMN done_at;
@@ -0,0 +1,6 @@
+-- When the task was marked as done, or NULL if it isn't done yet. Tasks which
+-- are done are kept until they're purged.
+ALTER TABLE tasks
+  ADD COLUMN done_at TIMESTAMPTZ;
+ALTER TABLE operations
+  ADD COLUMN done_at TIMESTAMPTZ;
@@ -0,0 +1,4 @@
+ALTER TABLE tasks
+  DROP COLUMN pinned;
+ALTER TABLE operations
+  DROP COLUMN pinned;
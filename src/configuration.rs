@@ -1,7 +1,12 @@
+use std::fmt;
+use std::str::FromStr;
+
 use cfg_if::cfg_if;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
+use thiserror::Error;
 
 use crate::database::Database;
+use crate::Confidence;
 
 cfg_if! {
     if #[cfg(feature = "clock")] {
@@ -9,32 +14,197 @@ cfg_if! {
         pub struct Configuration {
             pub database: Box<dyn Database>,
             pub scheduling_strategy: SchedulingStrategy,
+            pub estimate_buffers: EstimateBuffers,
+            /// Tasks are planned to start at and last a whole number of times
+            /// this, see `default_granularity`
+            pub granularity: Duration,
+            /// The chrono format in which deadlines are entered, instead of
+            /// the default like "4 Jul 2017 6:05"
+            pub deadline_format: Option<String>,
+            /// How long to work at most before taking a break, or `None` to
+            /// plan without breaks
+            pub max_continuous: Option<Duration>,
+            /// How long the breaks after `max_continuous` of work last, see
+            /// `default_break_length`
+            pub break_length: Duration,
+            /// The importance and duration of tasks which are added without
+            /// giving them
+            pub task_defaults: TaskDefaults,
         }
     } else {
         #[derive(Debug)]
         pub struct Configuration {
             pub database: Box<dyn Database>,
             pub scheduling_strategy: SchedulingStrategy,
+            pub estimate_buffers: EstimateBuffers,
+            /// Tasks are planned to start at and last a whole number of times
+            /// this, see `default_granularity`
+            pub granularity: Duration,
+            /// The chrono format in which deadlines are entered, instead of
+            /// the default like "4 Jul 2017 6:05"
+            pub deadline_format: Option<String>,
+            /// How long to work at most before taking a break, or `None` to
+            /// plan without breaks
+            pub max_continuous: Option<Duration>,
+            /// How long the breaks after `max_continuous` of work last, see
+            /// `default_break_length`
+            pub break_length: Duration,
+            /// The importance and duration of tasks which are added without
+            /// giving them
+            pub task_defaults: TaskDefaults,
             pub time_context: Box<dyn TimeContext>,
         }
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum SchedulingStrategy {
     Importance,
     Urgency,
+    Balanced,
+    /// Strictly the task with the earliest deadline first, regardless of
+    /// importance
+    Deadline,
+    /// Like `Importance`, but ties on importance are broken by shuffling the
+    /// tasks with the given seed instead of by their deadlines. The same seed
+    /// always gives the same schedule.
+    Shuffled {
+        seed: u64,
+    },
 }
 
 impl SchedulingStrategy {
+    /// Returns the name of the strategy, without its seed.
     pub fn as_str(&self) -> &'static str {
         match self {
             Self::Importance => "importance",
             Self::Urgency => "urgency",
+            Self::Balanced => "balanced",
+            Self::Deadline => "deadline",
+            Self::Shuffled { .. } => "shuffled",
+        }
+    }
+}
+
+/// Writes the strategy in the form `FromStr` reads, e.g. "shuffled:42".
+impl fmt::Display for SchedulingStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Shuffled { seed } => write!(f, "shuffled:{}", seed),
+            strategy => f.write_str(strategy.as_str()),
+        }
+    }
+}
+
+impl FromStr for SchedulingStrategy {
+    type Err = UnknownStrategy;
+
+    /// Reads "importance", "urgency", "balanced", "deadline", or "shuffled" optionally
+    /// followed by a colon and a seed, like "shuffled:42".
+    fn from_str(strategy: &str) -> Result<Self, Self::Err> {
+        match strategy.split_once(':') {
+            None => match strategy {
+                "importance" => Ok(Self::Importance),
+                "urgency" => Ok(Self::Urgency),
+                "balanced" => Ok(Self::Balanced),
+                "deadline" => Ok(Self::Deadline),
+                "shuffled" => Ok(Self::Shuffled { seed: 0 }),
+                _ => Err(UnknownStrategy(strategy.to_owned())),
+            },
+            Some(("shuffled", seed)) => seed
+                .parse()
+                .map(|seed| Self::Shuffled { seed })
+                .map_err(|_| UnknownStrategy(strategy.to_owned())),
+            Some(_) => Err(UnknownStrategy(strategy.to_owned())),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error(
+    "There is no scheduling strategy called {0:?}. Choose importance, urgency, balanced, \
+     deadline or shuffled:SEED."
+)]
+pub struct UnknownStrategy(pub String);
+
+/// The extra time, in percent of the estimated duration, that is planned for
+/// tasks of which the user isn't sure how long they will take. Tasks with a
+/// high confidence are scheduled with their estimate as is.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct EstimateBuffers {
+    pub low_confidence: u32,
+    pub medium_confidence: u32,
+}
+
+impl EstimateBuffers {
+    pub fn apply(&self, duration: Duration, confidence: Confidence) -> Duration {
+        let percentage = match confidence {
+            Confidence::Low => self.low_confidence,
+            Confidence::Medium => self.medium_confidence,
+            Confidence::High => 0,
+        };
+        duration + duration * percentage as i32 / 100
+    }
+}
+
+impl Default for EstimateBuffers {
+    fn default() -> Self {
+        EstimateBuffers {
+            low_confidence: 25,
+            medium_confidence: 10,
         }
     }
 }
 
+/// Planning to the minute, let alone to the second, cuts a schedule up in gaps
+/// too short to do anything in. Rounding to five minutes keeps it tidy while
+/// still following the estimates closely. A granularity of zero turns rounding
+/// off.
+pub fn default_granularity() -> Duration {
+    Duration::minutes(5)
+}
+
+/// The importance and duration to give a task when they aren't entered, e.g.
+/// to quickly add many small tasks.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TaskDefaults {
+    pub importance: u32,
+    pub duration: Duration,
+}
+
+impl Default for TaskDefaults {
+    fn default() -> Self {
+        TaskDefaults {
+            importance: 5,
+            duration: Duration::hours(1),
+        }
+    }
+}
+
+/// A break of a quarter of an hour is long enough to stretch your legs and
+/// short enough to get back into the work afterwards.
+pub fn default_break_length() -> Duration {
+    Duration::minutes(15)
+}
+
+/// Forces a break of `length` after every `max_continuous` of work, so long
+/// tasks and long series of tasks are interrupted.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Breaks {
+    pub max_continuous: Duration,
+    pub length: Duration,
+}
+
+impl Configuration {
+    /// Returns the breaks to plan, if the configuration asks for them.
+    pub fn breaks(&self) -> Option<Breaks> {
+        self.max_continuous.map(|max_continuous| Breaks {
+            max_continuous,
+            length: self.break_length,
+        })
+    }
+}
+
 cfg_if! {
     if #[cfg(feature = "clock")] {
         impl Configuration {
@@ -43,8 +213,6 @@ cfg_if! {
             }
         }
     } else {
-        use std::fmt;
-
         pub trait TimeContext {
             fn now(&self) -> DateTime<Utc>;
         }
@@ -55,6 +223,25 @@ cfg_if! {
             }
         }
 
+        /// A time context in which time stands still, so scheduling always
+        /// starts from the same moment, e.g. in tests.
+        #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+        pub struct FixedTimeContext {
+            pub now: DateTime<Utc>,
+        }
+
+        impl FixedTimeContext {
+            pub fn new(now: DateTime<Utc>) -> Self {
+                FixedTimeContext { now }
+            }
+        }
+
+        impl TimeContext for FixedTimeContext {
+            fn now(&self) -> DateTime<Utc> {
+                self.now
+            }
+        }
+
         impl Configuration {
             pub fn now(&self) -> DateTime<Utc> {
                 self.time_context.now()
@@ -62,3 +249,39 @@ cfg_if! {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_print_strategies() {
+        for strategy in [
+            SchedulingStrategy::Importance,
+            SchedulingStrategy::Urgency,
+            SchedulingStrategy::Balanced,
+            SchedulingStrategy::Deadline,
+            SchedulingStrategy::Shuffled { seed: 42 },
+        ] {
+            assert_eq!(
+                strategy.to_string().parse::<SchedulingStrategy>().unwrap(),
+                strategy
+            );
+        }
+        assert_eq!(
+            "shuffled".parse::<SchedulingStrategy>().unwrap(),
+            SchedulingStrategy::Shuffled { seed: 0 }
+        );
+        assert_eq!(
+            SchedulingStrategy::Shuffled { seed: 42 }.as_str(),
+            "shuffled"
+        );
+        for strategy in ["", "random", "shuffled:", "shuffled:-1", "importance:1"] {
+            assert!(
+                strategy.parse::<SchedulingStrategy>().is_err(),
+                "{}",
+                strategy
+            );
+        }
+    }
+}
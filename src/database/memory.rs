@@ -0,0 +1,1263 @@
+use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use cfg_if::cfg_if;
+use chrono::prelude::*;
+use chrono::Duration;
+
+use super::Database;
+use super::MAX_LOGGED_OPERATIONS;
+use super::{unexpected_amount, Error, NotFound, Result, SegmentConstraintError, TaskOrder};
+use crate::time_segment::{NamedTimeSegment as TimeSegment, NewNamedTimeSegment as NewTimeSegment};
+use crate::{NewTask, Operation, Task, TaskChanges};
+
+/// A database which only lives in memory. It's useful for testing and to try
+/// things out without persisting them.
+#[derive(Debug)]
+pub struct MemoryDatabase {
+    tasks: RefCell<Vec<Task>>,
+    time_segments: RefCell<Vec<TimeSegment>>,
+    /// When each task was last added or changed
+    updated_at: RefCell<HashMap<u32, DateTime<Utc>>>,
+    /// The last operations, the oldest one first
+    operations: RefCell<Vec<Operation>>,
+}
+
+impl MemoryDatabase {
+    /// Returns an empty database with only a default time segment, just like a
+    /// freshly created SQLite database.
+    pub fn new() -> Self {
+        MemoryDatabase {
+            tasks: RefCell::new(vec![]),
+            time_segments: RefCell::new(vec![default_time_segment()]),
+            updated_at: RefCell::new(HashMap::new()),
+            operations: RefCell::new(vec![]),
+        }
+    }
+
+    /// Returns an in-memory copy of everything in the given database. Since
+    /// databases don't tell when their tasks were updated, all tasks in the
+    /// copy count as updated just now.
+    pub async fn copy_of(database: &dyn Database) -> Result<Self> {
+        let tasks = database.all_tasks().await?;
+        let now = write_time();
+        let updated_at = tasks.iter().map(|task| (task.id, now)).collect();
+        Ok(MemoryDatabase {
+            tasks: RefCell::new(tasks),
+            time_segments: RefCell::new(database.all_time_segments().await?),
+            updated_at: RefCell::new(updated_at),
+            operations: RefCell::new(database.operations().await?),
+        })
+    }
+
+    fn ensure_time_segment_exists(&self, id: u32, context: &'static str) -> Result<()> {
+        if !self
+            .time_segments
+            .borrow()
+            .iter()
+            .any(|time_segment| time_segment.id == id)
+        {
+            return Err(Error(
+                context,
+                NotFound(format!("There is no time segment with id {}", id)).into(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn ensure_tasks_exist(&self, ids: &[u32], context: &'static str) -> Result<()> {
+        let tasks = self.tasks.borrow();
+        match ids
+            .iter()
+            .find(|&&id| !tasks.iter().any(|task| task.id == id))
+        {
+            Some(id) => Err(Error(
+                context,
+                NotFound(format!("There is no task with id {}", id)).into(),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Deletes the given tasks for good, along with the operations on them.
+    fn purge_tasks(&self, ids: &[u32]) {
+        self.tasks
+            .borrow_mut()
+            .retain(|task| !ids.contains(&task.id));
+        let mut updated_at = self.updated_at.borrow_mut();
+        // Nothing depends on these tasks anymore, which changes the tasks that did
+        for task in self.tasks.borrow_mut().iter_mut() {
+            if task.depends_on.iter().any(|id| ids.contains(id)) {
+                task.depends_on.retain(|id| !ids.contains(id));
+                updated_at.insert(task.id, write_time());
+            }
+        }
+        for id in ids {
+            updated_at.remove(id);
+        }
+        self.operations
+            .borrow_mut()
+            .retain(|operation| !ids.contains(&operation.task().id));
+    }
+
+    /// Returns clones of the tasks which aren't done yet.
+    fn tasks_to_do(&self) -> Vec<Task> {
+        self.tasks
+            .borrow()
+            .iter()
+            .filter(|task| task.done_at.is_none())
+            .cloned()
+            .collect()
+    }
+}
+
+impl Default for MemoryDatabase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait(?Send)]
+impl Database for MemoryDatabase {
+    async fn add_task(&self, task: NewTask) -> Result<Task> {
+        self.ensure_time_segment_exists(task.time_segment_id, "while trying to add a task")?;
+        self.ensure_tasks_exist(&task.depends_on, "while trying to add a task")?;
+        let mut tasks = self.tasks.borrow_mut();
+        let id = tasks.iter().map(|task| task.id + 1).max().unwrap_or(1);
+        let task = Task {
+            id,
+            content: task.content,
+            deadline: task.deadline,
+            duration: task.duration,
+            importance: task.importance,
+            confidence: task.confidence,
+            time_segment_id: task.time_segment_id,
+            depends_on: task.depends_on,
+            color: None,
+            scheduled_for: None,
+            done_at: None,
+            pinned: false,
+        };
+        tasks.push(task.clone());
+        self.updated_at.borrow_mut().insert(id, write_time());
+        Ok(task)
+    }
+
+    async fn bulk_add_tasks(&self, tasks: Vec<NewTask>) -> Result<Vec<Task>> {
+        for task in &tasks {
+            self.ensure_time_segment_exists(task.time_segment_id, "while trying to add tasks")?;
+            self.ensure_tasks_exist(&task.depends_on, "while trying to add tasks")?;
+        }
+        let mut added = Vec::with_capacity(tasks.len());
+        for task in tasks {
+            added.push(self.add_task(task).await?);
+        }
+        Ok(added)
+    }
+
+    async fn delete_task(&self, id: u32) -> Result<()> {
+        let mut tasks = self.tasks.borrow_mut();
+        let n_tasks = tasks.len();
+        tasks.retain(|task| task.id != id);
+        let amount_deleted = n_tasks - tasks.len();
+        if amount_deleted != 1 {
+            return Err(Error(
+                "while trying to delete a task",
+                unexpected_amount(
+                    amount_deleted,
+                    format!("{} task(s) were deleted", amount_deleted),
+                ),
+            ));
+        }
+        let mut updated_at = self.updated_at.borrow_mut();
+        updated_at.remove(&id);
+        // Nothing depends on this task anymore, which changes the tasks that did
+        for task in tasks.iter_mut() {
+            if task.depends_on.contains(&id) {
+                task.depends_on.retain(|&dependency_id| dependency_id != id);
+                updated_at.insert(task.id, write_time());
+            }
+        }
+        Ok(())
+    }
+
+    async fn complete_task(&self, id: u32, done_at: DateTime<Utc>) -> Result<()> {
+        match self
+            .tasks
+            .borrow_mut()
+            .iter_mut()
+            .find(|task| task.id == id)
+        {
+            Some(task) => {
+                self.updated_at.borrow_mut().insert(id, write_time());
+                task.done_at = Some(done_at);
+                Ok(())
+            }
+            None => Err(Error(
+                "while trying to complete a task",
+                NotFound("0 task(s) were completed".to_string()).into(),
+            )),
+        }
+    }
+
+    async fn clear_completed(&self, older_than: Option<DateTime<Utc>>) -> Result<u64> {
+        let ids = self
+            .tasks
+            .borrow()
+            .iter()
+            .filter(|task| match (task.done_at, older_than) {
+                (Some(done_at), Some(older_than)) => done_at < older_than,
+                (done_at, None) => done_at.is_some(),
+                (None, _) => false,
+            })
+            .map(|task| task.id)
+            .collect::<Vec<_>>();
+        self.purge_tasks(&ids);
+        Ok(ids.len() as u64)
+    }
+
+    async fn get_task(&self, id: u32) -> Result<Task> {
+        self.tasks
+            .borrow()
+            .iter()
+            .find(|task| task.id == id)
+            .cloned()
+            .ok_or_else(|| {
+                Error(
+                    "while trying to find a task",
+                    NotFound(format!("There is no task with id {}", id)).into(),
+                )
+            })
+    }
+
+    async fn get_tasks(&self, ids: &[u32]) -> Result<Vec<Task>> {
+        self.ensure_tasks_exist(ids, "while trying to find tasks")?;
+        let tasks = self.tasks.borrow();
+        Ok(ids
+            .iter()
+            .filter_map(|&id| tasks.iter().find(|task| task.id == id).cloned())
+            .collect())
+    }
+
+    async fn update_task(&self, task: Task) -> Result<()> {
+        self.ensure_time_segment_exists(task.time_segment_id, "while trying to update a task")?;
+        self.ensure_tasks_exist(&task.depends_on, "while trying to update a task")?;
+        let mut tasks = self.tasks.borrow_mut();
+        match tasks.iter_mut().find(|old_task| old_task.id == task.id) {
+            Some(old_task) => {
+                self.updated_at.borrow_mut().insert(task.id, write_time());
+                *old_task = task;
+                Ok(())
+            }
+            None => Err(Error(
+                "while trying to update a task",
+                NotFound("0 task(s) were updated".to_string()).into(),
+            )),
+        }
+    }
+
+    async fn update_task_fields(&self, id: u32, changes: TaskChanges) -> Result<Task> {
+        if let Some(time_segment_id) = changes.time_segment_id {
+            self.ensure_time_segment_exists(time_segment_id, "while trying to update a task")?;
+        }
+        if let Some(depends_on) = &changes.depends_on {
+            self.ensure_tasks_exist(depends_on, "while trying to update a task")?;
+        }
+        let mut tasks = self.tasks.borrow_mut();
+        match tasks.iter_mut().find(|task| task.id == id) {
+            Some(task) => {
+                self.updated_at.borrow_mut().insert(id, write_time());
+                changes.apply_to(task);
+                Ok(task.clone())
+            }
+            None => Err(Error(
+                "while trying to update a task",
+                NotFound(format!("There is no task with id {}", id)).into(),
+            )),
+        }
+    }
+
+    async fn set_scheduled_for(&self, scheduled: &[(u32, DateTime<Utc>)]) -> Result<()> {
+        let ids = scheduled.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+        self.ensure_tasks_exist(&ids, "while trying to persist a schedule")?;
+        for task in self.tasks.borrow_mut().iter_mut() {
+            task.scheduled_for = scheduled
+                .iter()
+                .find(|(id, _)| *id == task.id)
+                .map(|(_, when)| *when);
+        }
+        Ok(())
+    }
+
+    async fn all_tasks(&self) -> Result<Vec<Task>> {
+        Ok(self.tasks_to_do())
+    }
+
+    async fn tasks_updated_since(&self, since: DateTime<Utc>) -> Result<Vec<Task>> {
+        let updated_at = self.updated_at.borrow();
+        Ok(self
+            .tasks_to_do()
+            .into_iter()
+            .filter(|task| updated_at.get(&task.id).is_some_and(|&at| at >= since))
+            .collect())
+    }
+
+    async fn tasks_in_deadline_range(
+        &self,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> Result<Vec<Task>> {
+        Ok(self
+            .tasks_to_do()
+            .into_iter()
+            .filter(|task| from.is_none_or(|from| task.deadline >= from))
+            .filter(|task| to.is_none_or(|to| task.deadline < to))
+            .collect())
+    }
+
+    async fn search_tasks(&self, query: &str) -> Result<Vec<Task>> {
+        let query = query.to_lowercase();
+        Ok(self
+            .tasks_to_do()
+            .into_iter()
+            .filter(|task| task.content.to_lowercase().contains(&query))
+            .collect())
+    }
+
+    async fn tasks_page(&self, order: TaskOrder, limit: u32, offset: u32) -> Result<Vec<Task>> {
+        let mut tasks = self.tasks_to_do();
+        // Sorting is stable, so ties stay in the order the tasks were added
+        tasks.sort_by_key(|task| task.id);
+        match order {
+            TaskOrder::Added => {}
+            TaskOrder::Deadline => tasks.sort_by_key(|task| task.deadline),
+            TaskOrder::Importance => tasks.sort_by_key(|task| Reverse(task.importance)),
+        }
+        Ok(tasks
+            .into_iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .collect())
+    }
+
+    async fn count_tasks(&self) -> Result<u64> {
+        Ok(self.tasks_to_do().len() as u64)
+    }
+
+    async fn all_tasks_per_time_segment(&self) -> Result<Vec<(TimeSegment, Vec<Task>)>> {
+        let tasks = self.tasks_to_do();
+        Ok(self
+            .time_segments
+            .borrow()
+            .iter()
+            .map(|time_segment| {
+                let tasks_in_segment = tasks
+                    .iter()
+                    .filter(|task| task.time_segment_id == time_segment.id)
+                    .cloned()
+                    .collect();
+                (time_segment.clone(), tasks_in_segment)
+            })
+            .collect())
+    }
+
+    async fn tasks_per_nonempty_segment(&self) -> Result<Vec<(TimeSegment, Vec<Task>)>> {
+        let mut tasks_per_segment = self.all_tasks_per_time_segment().await?;
+        tasks_per_segment.retain(|(_, tasks)| !tasks.is_empty());
+        Ok(tasks_per_segment)
+    }
+
+    async fn restore_task(&self, task: Task) -> Result<()> {
+        self.ensure_time_segment_exists(task.time_segment_id, "while trying to restore a task")?;
+        self.ensure_tasks_exist(&task.depends_on, "while trying to restore a task")?;
+        let mut tasks = self.tasks.borrow_mut();
+        if tasks.iter().any(|other| other.id == task.id) {
+            return Err(Error(
+                "while trying to restore a task",
+                format!("There already is a task with id {}", task.id).into(),
+            ));
+        }
+        // Keep the tasks in the order they were added
+        let index = tasks
+            .iter()
+            .position(|other| other.id > task.id)
+            .unwrap_or(tasks.len());
+        self.updated_at.borrow_mut().insert(task.id, write_time());
+        tasks.insert(index, task);
+        Ok(())
+    }
+
+    async fn log_operation(&self, operation: Operation) -> Result<()> {
+        let mut operations = self.operations.borrow_mut();
+        operations.push(operation);
+        let n_forgotten = operations.len().saturating_sub(MAX_LOGGED_OPERATIONS);
+        operations.drain(..n_forgotten);
+        Ok(())
+    }
+
+    async fn operations(&self) -> Result<Vec<Operation>> {
+        Ok(self.operations.borrow().clone())
+    }
+
+    async fn pop_operation(&self) -> Result<Option<Operation>> {
+        Ok(self.operations.borrow_mut().pop())
+    }
+
+    async fn add_time_segment(&self, time_segment: NewTimeSegment) -> Result<TimeSegment> {
+        let mut time_segments = self.time_segments.borrow_mut();
+        let id = time_segments
+            .iter()
+            .map(|time_segment| time_segment.id + 1)
+            .max()
+            .unwrap_or(0);
+        let time_segment = TimeSegment {
+            id,
+            name: time_segment.name,
+            ranges: time_segment.ranges,
+            start: time_segment.start,
+            period: time_segment.period,
+            hue: time_segment.hue,
+            strategy: time_segment.strategy,
+        };
+        time_segments.push(time_segment.clone());
+        Ok(time_segment)
+    }
+
+    async fn delete_time_segment(&self, time_segment: TimeSegment) -> Result<()> {
+        // Assert that there are no tasks left to do in this time segment
+        let n_tasks = self
+            .tasks_to_do()
+            .iter()
+            .filter(|task| task.time_segment_id == time_segment.id)
+            .count();
+        if n_tasks > 0 {
+            return Err(Error(
+                "while trying to delete a time segment",
+                SegmentConstraintError::SegmentNotEmpty {
+                    remaining_tasks: n_tasks as u64,
+                }
+                .into(),
+            ));
+        }
+
+        // Assert that this isn't the last time segment
+        let mut time_segments = self.time_segments.borrow_mut();
+        if time_segments.len() <= 1 {
+            return Err(Error(
+                "while trying to delete a time segment",
+                SegmentConstraintError::LastTimeSegment.into(),
+            ));
+        }
+
+        // The tasks which are done go along with their time segment
+        let completed_ids = self
+            .tasks
+            .borrow()
+            .iter()
+            .filter(|task| task.time_segment_id == time_segment.id)
+            .map(|task| task.id)
+            .collect::<Vec<_>>();
+        self.purge_tasks(&completed_ids);
+
+        let n_time_segments = time_segments.len();
+        time_segments.retain(|other| other.id != time_segment.id);
+        let amount_deleted = n_time_segments - time_segments.len();
+        if amount_deleted != 1 {
+            return Err(Error(
+                "while trying to delete a time segment",
+                unexpected_amount(
+                    amount_deleted,
+                    format!("{} time segment(s) were deleted", amount_deleted),
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+
+    async fn update_time_segment(&self, time_segment: TimeSegment) -> Result<()> {
+        let mut time_segments = self.time_segments.borrow_mut();
+        match time_segments
+            .iter_mut()
+            .find(|old_time_segment| old_time_segment.id == time_segment.id)
+        {
+            Some(old_time_segment) => {
+                *old_time_segment = time_segment;
+                Ok(())
+            }
+            None => Err(Error(
+                "while trying to update a time segment",
+                NotFound("0 time segment(s) were updated".to_string()).into(),
+            )),
+        }
+    }
+
+    async fn rename_time_segment(&self, id: u32, new_name: &str) -> Result<()> {
+        let mut time_segments = self.time_segments.borrow_mut();
+        match time_segments
+            .iter_mut()
+            .find(|time_segment| time_segment.id == id)
+        {
+            Some(time_segment) => {
+                time_segment.name = new_name.to_string();
+                Ok(())
+            }
+            None => Err(Error(
+                "while trying to rename a time segment",
+                NotFound("0 time segment(s) were renamed".to_string()).into(),
+            )),
+        }
+    }
+
+    async fn move_tasks_between_segments(&self, from: u32, to: u32) -> Result<u64> {
+        let context = "while trying to move tasks between time segments";
+        self.ensure_time_segment_exists(from, context)?;
+        self.ensure_time_segment_exists(to, context)?;
+        let mut tasks = self.tasks.borrow_mut();
+        let mut updated_at = self.updated_at.borrow_mut();
+        let mut amount_moved = 0;
+        for task in tasks.iter_mut().filter(|task| task.time_segment_id == from) {
+            task.time_segment_id = to;
+            updated_at.insert(task.id, write_time());
+            amount_moved += 1;
+        }
+        Ok(amount_moved)
+    }
+
+    async fn vacuum(&self) -> Result<()> {
+        // There's nothing to clean up in memory
+        Ok(())
+    }
+
+    async fn get_time_segment(&self, id: u32) -> Result<TimeSegment> {
+        self.time_segments
+            .borrow()
+            .iter()
+            .find(|time_segment| time_segment.id == id)
+            .cloned()
+            .ok_or_else(|| {
+                Error(
+                    "while trying to find a time segment",
+                    NotFound(format!("There is no time segment with id {}", id)).into(),
+                )
+            })
+    }
+
+    async fn all_time_segments(&self) -> Result<Vec<TimeSegment>> {
+        Ok(self.time_segments.borrow().clone())
+    }
+}
+
+/// The moment of a write. Without a clock, tasks always count as just updated.
+fn write_time() -> DateTime<Utc> {
+    cfg_if! {
+        if #[cfg(feature = "clock")] {
+            Utc::now()
+        } else {
+            DateTime::<Utc>::MAX_UTC
+        }
+    }
+}
+
+/// The same default time segment the SQLite database starts with: daily from 9
+/// to 5.
+fn default_time_segment() -> TimeSegment {
+    cfg_if! {
+        if #[cfg(feature = "clock")] {
+            let start = Local
+                .with_ymd_and_hms(2019, 1, 7, 9, 0, 0)
+                .unwrap()
+                .with_timezone(&Utc);
+        } else {
+            let start = Utc.with_ymd_and_hms(2019, 1, 7, 9, 0, 0).unwrap();
+        }
+    }
+    TimeSegment {
+        id: 0,
+        name: "Default".to_string(),
+        ranges: vec![start..start + Duration::hours(8)],
+        start,
+        period: Duration::days(1),
+        hue: 0,
+        strategy: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_test::test;
+
+    use super::*;
+    use crate::Confidence;
+
+    #[test]
+    async fn test_insert_update_query_and_delete_single_task() {
+        let database = MemoryDatabase::new();
+        assert!(database.all_tasks().await.unwrap().is_empty());
+
+        let new_task = test_task();
+        let mut task = database.add_task(new_task.clone()).await.unwrap();
+        assert_eq!(task, new_task);
+        assert_eq!(database.all_tasks().await.unwrap(), [task.clone()]);
+        assert_eq!(database.count_tasks().await.unwrap(), 1);
+
+        task.content = "stuff".to_string();
+        task.importance = 100;
+        database.update_task(task.clone()).await.unwrap();
+        assert_eq!(database.get_task(task.id).await.unwrap(), task);
+
+        database.delete_task(task.id).await.unwrap();
+        assert!(database.all_tasks().await.unwrap().is_empty());
+        assert_eq!(database.count_tasks().await.unwrap(), 0);
+        assert!(database.get_task(task.id).await.is_err());
+        assert!(database.delete_task(task.id).await.is_err());
+    }
+
+    #[test]
+    async fn test_bulk_add_tasks() {
+        let database = MemoryDatabase::new();
+        let prerequisite = database.add_task(test_task()).await.unwrap();
+        let new_tasks = (1..=3)
+            .map(|number| NewTask {
+                content: format!("task {}", number),
+                depends_on: vec![prerequisite.id],
+                ..test_task()
+            })
+            .collect::<Vec<_>>();
+        let tasks = database.bulk_add_tasks(new_tasks.clone()).await.unwrap();
+        assert_eq!(tasks.len(), 3);
+        for (task, new_task) in tasks.iter().zip(&new_tasks) {
+            assert_eq!(task, new_task);
+        }
+        assert_eq!(database.count_tasks().await.unwrap(), 4);
+
+        // Nothing is added when a task is invalid
+        let mut invalid = test_task();
+        invalid.depends_on = vec![42];
+        assert!(database
+            .bulk_add_tasks(vec![test_task(), invalid])
+            .await
+            .is_err());
+        assert_eq!(database.count_tasks().await.unwrap(), 4);
+    }
+
+    #[test]
+    async fn test_update_task_fields() {
+        let database = MemoryDatabase::new();
+        let task = database.add_task(test_task()).await.unwrap();
+        let since = Utc::now();
+
+        let changes = TaskChanges {
+            importance: Some(7),
+            color: Some(Some(120)),
+            pinned: Some(true),
+            ..Default::default()
+        };
+        let updated = database.update_task_fields(task.id, changes).await.unwrap();
+        assert_eq!(
+            updated,
+            Task {
+                importance: 7,
+                color: Some(120),
+                pinned: true,
+                ..task.clone()
+            }
+        );
+        assert_eq!(database.get_task(task.id).await.unwrap(), updated);
+        assert_eq!(
+            database.tasks_updated_since(since).await.unwrap(),
+            [updated]
+        );
+
+        assert!(database
+            .update_task_fields(task.id + 1, TaskChanges::default())
+            .await
+            .unwrap_err()
+            .is_not_found());
+    }
+
+    #[test]
+    async fn test_task_dependencies() {
+        let database = MemoryDatabase::new();
+        let prerequisite = database.add_task(test_task()).await.unwrap();
+
+        // Tasks can only depend on existing tasks
+        let mut new_task = test_task();
+        new_task.depends_on = vec![prerequisite.id + 1];
+        assert!(database.add_task(new_task.clone()).await.is_err());
+        new_task.depends_on = vec![prerequisite.id];
+        let task = database.add_task(new_task).await.unwrap();
+        assert_eq!(
+            database.get_task(task.id).await.unwrap().depends_on,
+            [prerequisite.id]
+        );
+        assert_eq!(
+            database
+                .get_tasks(&[task.id, prerequisite.id])
+                .await
+                .unwrap(),
+            [task.clone(), prerequisite.clone()]
+        );
+        assert!(database
+            .get_tasks(&[task.id, 42])
+            .await
+            .unwrap_err()
+            .is_not_found());
+
+        // Deleting a prerequisite removes the dependency
+        database.delete_task(prerequisite.id).await.unwrap();
+        assert!(database
+            .get_task(task.id)
+            .await
+            .unwrap()
+            .depends_on
+            .is_empty());
+    }
+
+    #[test]
+    async fn test_log_operations() {
+        let database = MemoryDatabase::new();
+        assert_eq!(database.pop_operation().await.unwrap(), None);
+        let mut task = database.add_task(test_task()).await.unwrap();
+        let first = Operation::Add { task: task.clone() };
+        task.depends_on = vec![2, 3];
+        task.color = Some(120);
+        let deleted = Operation::Delete {
+            task: task.clone(),
+            dependents: vec![4],
+        };
+        let updated = Operation::Update { before: task };
+        database.log_operation(first.clone()).await.unwrap();
+        database.log_operation(deleted.clone()).await.unwrap();
+        database.log_operation(updated.clone()).await.unwrap();
+        assert_eq!(
+            database.operations().await.unwrap(),
+            [first.clone(), deleted.clone(), updated.clone()]
+        );
+
+        // The last operation comes out first
+        assert_eq!(database.pop_operation().await.unwrap(), Some(updated));
+        assert_eq!(database.pop_operation().await.unwrap(), Some(deleted));
+
+        // Only the last operations are remembered
+        for _ in 0..MAX_LOGGED_OPERATIONS {
+            database.log_operation(first.clone()).await.unwrap();
+        }
+        assert_eq!(
+            database.operations().await.unwrap().len(),
+            MAX_LOGGED_OPERATIONS
+        );
+        for _ in 0..MAX_LOGGED_OPERATIONS {
+            assert_eq!(database.pop_operation().await.unwrap(), Some(first.clone()));
+        }
+        assert_eq!(database.pop_operation().await.unwrap(), None);
+    }
+
+    #[test]
+    async fn test_restore_task() {
+        let database = MemoryDatabase::new();
+        let prerequisite = database.add_task(test_task()).await.unwrap();
+        let mut new_task = test_task();
+        new_task.depends_on = vec![prerequisite.id];
+        let task = database.add_task(new_task).await.unwrap();
+        database.delete_task(task.id).await.unwrap();
+
+        database.restore_task(task.clone()).await.unwrap();
+        assert_eq!(database.get_task(task.id).await.unwrap(), task);
+        // A task can't be restored over another one
+        assert!(database.restore_task(task).await.is_err());
+    }
+
+    #[test]
+    async fn test_search_tasks() {
+        let database = MemoryDatabase::new();
+        let mut tasks = vec![];
+        for content in [
+            "Call the dentist",
+            "Pay the DENTIST",
+            "Buy 100% cotton socks",
+            "Rename my_file",
+            "Walk the dog",
+        ] {
+            let mut task = test_task();
+            task.content = content.to_string();
+            tasks.push(database.add_task(task).await.unwrap());
+        }
+
+        let search = |query| database.search_tasks(query);
+        assert_eq!(search("dentist").await.unwrap(), tasks[0..2]);
+        assert_eq!(search("the do").await.unwrap(), [tasks[4].clone()]);
+        // Wildcards in the query only match themselves
+        assert_eq!(search("%").await.unwrap(), [tasks[2].clone()]);
+        assert_eq!(search("y_f").await.unwrap(), [tasks[3].clone()]);
+        assert!(search("e_t").await.unwrap().is_empty());
+        assert!(search("cat").await.unwrap().is_empty());
+    }
+
+    #[test]
+    async fn test_tasks_in_deadline_range() {
+        let database = MemoryDatabase::new();
+        let mut tasks = vec![];
+        let start = test_task().deadline;
+        let day = |n| start + Duration::days(n);
+        for days in 1..4 {
+            let mut task = test_task();
+            task.deadline = day(days);
+            tasks.push(database.add_task(task).await.unwrap());
+        }
+
+        let due = |from, to| database.tasks_in_deadline_range(from, to);
+        assert_eq!(due(None, None).await.unwrap(), tasks);
+        assert_eq!(due(Some(day(2)), None).await.unwrap(), tasks[1..]);
+        assert_eq!(due(None, Some(day(2))).await.unwrap(), tasks[..1]);
+        assert_eq!(due(Some(day(1)), Some(day(3))).await.unwrap(), tasks[..2]);
+        // Only a task which is due right at the start of the range is in it
+        let just_after = day(1) + Duration::milliseconds(1);
+        assert_eq!(due(Some(just_after), None).await.unwrap(), tasks[1..]);
+        assert!(due(Some(day(2)), Some(day(2))).await.unwrap().is_empty());
+    }
+
+    #[test]
+    async fn test_tasks_page() {
+        let database = MemoryDatabase::new();
+        let mut tasks = vec![];
+        for (days, importance) in [(3, 2), (1, 2), (2, 9)] {
+            let mut task = test_task();
+            task.deadline += Duration::days(days);
+            task.importance = importance;
+            tasks.push(database.add_task(task).await.unwrap());
+        }
+
+        let page = |order, limit, offset| database.tasks_page(order, limit, offset);
+        assert_eq!(page(TaskOrder::Added, 2, 0).await.unwrap(), tasks[0..2]);
+        // Asking for more tasks than are left just returns those
+        assert_eq!(page(TaskOrder::Added, 10, 1).await.unwrap(), tasks[1..]);
+        assert!(page(TaskOrder::Added, 10, 3).await.unwrap().is_empty());
+        let ids = |tasks: Vec<Task>| tasks.iter().map(|task| task.id).collect::<Vec<_>>();
+        assert_eq!(
+            ids(page(TaskOrder::Deadline, 10, 0).await.unwrap()),
+            [tasks[1].id, tasks[2].id, tasks[0].id]
+        );
+        // Equally important tasks stay in the order they were added
+        assert_eq!(
+            ids(page(TaskOrder::Importance, 2, 1).await.unwrap()),
+            [tasks[0].id, tasks[1].id]
+        );
+    }
+
+    #[test]
+    async fn test_complete_and_clear_tasks() {
+        let database = MemoryDatabase::new();
+        let first = database.add_task(test_task()).await.unwrap();
+        let second = database.add_task(test_task()).await.unwrap();
+        let mut third = test_task();
+        third.depends_on = vec![first.id];
+        let third = database.add_task(third).await.unwrap();
+        let last_week = Utc.with_ymd_and_hms(2019, 8, 1, 9, 0, 0).unwrap();
+        let yesterday = last_week + Duration::days(6);
+        database.complete_task(first.id, last_week).await.unwrap();
+        database.complete_task(second.id, yesterday).await.unwrap();
+        database
+            .log_operation(Operation::Update {
+                before: first.clone(),
+            })
+            .await
+            .unwrap();
+
+        // Tasks which are done are kept, but they aren't listed anymore
+        assert_eq!(
+            database.get_task(first.id).await.unwrap().done_at,
+            Some(last_week)
+        );
+        assert_eq!(
+            database.all_tasks().await.unwrap(),
+            std::slice::from_ref(&third)
+        );
+        assert_eq!(database.count_tasks().await.unwrap(), 1);
+        let (_, tasks) = database
+            .all_tasks_per_time_segment()
+            .await
+            .unwrap()
+            .remove(0);
+        assert_eq!(tasks, std::slice::from_ref(&third));
+        assert!(database
+            .complete_task(42, yesterday)
+            .await
+            .unwrap_err()
+            .is_not_found());
+
+        // Only the tasks which were done before the given moment are cleared,
+        // along with the dependencies and operations on them
+        assert_eq!(database.clear_completed(Some(yesterday)).await.unwrap(), 1);
+        assert!(database
+            .get_task(first.id)
+            .await
+            .unwrap_err()
+            .is_not_found());
+        assert!(database
+            .get_task(third.id)
+            .await
+            .unwrap()
+            .depends_on
+            .is_empty());
+        assert!(database.operations().await.unwrap().is_empty());
+        assert_eq!(database.clear_completed(None).await.unwrap(), 1);
+        assert!(database
+            .get_task(second.id)
+            .await
+            .unwrap_err()
+            .is_not_found());
+        assert_eq!(database.clear_completed(None).await.unwrap(), 0);
+    }
+
+    #[test]
+    async fn test_tasks_updated_since() {
+        let database = MemoryDatabase::new();
+        let first = database.add_task(test_task()).await.unwrap();
+        let mut second = database.add_task(test_task()).await.unwrap();
+        let mut third = test_task();
+        third.depends_on = vec![first.id];
+        let third = database.add_task(third).await.unwrap();
+
+        let since = Utc::now();
+        assert!(database
+            .tasks_updated_since(since)
+            .await
+            .unwrap()
+            .is_empty());
+
+        second.importance = 1;
+        database.update_task(second.clone()).await.unwrap();
+        assert_eq!(
+            database.tasks_updated_since(since).await.unwrap(),
+            [second.clone()]
+        );
+
+        // Deleting a prerequisite changes the tasks which depended on it
+        database.delete_task(first.id).await.unwrap();
+        let updated_ids = database
+            .tasks_updated_since(since)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|task| task.id)
+            .collect::<Vec<_>>();
+        assert_eq!(updated_ids, [second.id, third.id]);
+    }
+
+    #[test]
+    async fn test_set_scheduled_for() {
+        let database = MemoryDatabase::new();
+        let first = database.add_task(test_task()).await.unwrap();
+        let second = database.add_task(test_task()).await.unwrap();
+        let since = Utc::now();
+
+        let when = Utc::now() + Duration::hours(1);
+        database
+            .set_scheduled_for(&[(second.id, when)])
+            .await
+            .unwrap();
+        assert_eq!(
+            database.get_task(first.id).await.unwrap().scheduled_for,
+            None
+        );
+        assert_eq!(
+            database.get_task(second.id).await.unwrap().scheduled_for,
+            Some(when)
+        );
+        assert!(database
+            .tasks_updated_since(since)
+            .await
+            .unwrap()
+            .is_empty());
+
+        database
+            .set_scheduled_for(&[(first.id, when)])
+            .await
+            .unwrap();
+        assert_eq!(
+            database.get_task(first.id).await.unwrap().scheduled_for,
+            Some(when)
+        );
+        assert_eq!(
+            database.get_task(second.id).await.unwrap().scheduled_for,
+            None
+        );
+        assert!(database
+            .set_scheduled_for(&[(42, when)])
+            .await
+            .unwrap_err()
+            .is_not_found());
+    }
+
+    #[test]
+    async fn test_default_time_segment() {
+        let database = MemoryDatabase::new();
+
+        let mut time_segments = database.all_time_segments().await.unwrap();
+        assert_eq!(time_segments.len(), 1);
+        let time_segment = time_segments.pop().unwrap();
+        assert_eq!(time_segment.id, 0);
+        assert_eq!(time_segment.name, "Default");
+        assert_eq!(time_segment.period, Duration::days(1));
+
+        // We shouldn't be able to delete the last time segment
+        let error = database
+            .delete_time_segment(time_segment)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.segment_constraint(),
+            Some(&SegmentConstraintError::LastTimeSegment)
+        );
+        assert_eq!(
+            error.to_string(),
+            "A database error occurred while trying to delete a time segment: If you remove the \
+             last time segment, when should I schedule things?"
+        );
+    }
+
+    #[test]
+    async fn test_move_all_tasks_between_time_segments() {
+        let connection = MemoryDatabase::new();
+        let start = Utc::now();
+        let from = connection
+            .add_time_segment(NewTimeSegment {
+                name: "2h weekly".to_string(),
+                ranges: vec![start..start + Duration::hours(2)],
+                start,
+                period: Duration::weeks(1),
+                hue: 0,
+                strategy: None,
+            })
+            .await
+            .unwrap();
+        let mut new_task = test_task();
+        new_task.time_segment_id = from.id;
+        for _ in 0..3 {
+            connection.add_task(new_task.clone()).await.unwrap();
+        }
+        let staying = connection.add_task(test_task()).await.unwrap();
+
+        assert_eq!(
+            connection
+                .move_tasks_between_segments(from.id, 0)
+                .await
+                .unwrap(),
+            3
+        );
+        let tasks = connection.all_tasks().await.unwrap();
+        assert_eq!(tasks.len(), 4);
+        assert!(tasks.iter().all(|task| task.time_segment_id == 0));
+        assert_eq!(connection.get_task(staying.id).await.unwrap(), staying);
+        // The source segment is now empty, so it can be deleted
+        connection.delete_time_segment(from.clone()).await.unwrap();
+
+        assert!(connection
+            .move_tasks_between_segments(0, from.id)
+            .await
+            .unwrap_err()
+            .is_not_found());
+    }
+
+    #[test]
+    async fn test_delete_default_time_segment_once_replaced() {
+        let connection = MemoryDatabase::new();
+        let start = Utc::now();
+        let default = connection.get_time_segment(0).await.unwrap();
+        let replacement = connection
+            .add_time_segment(NewTimeSegment {
+                name: "Work".to_string(),
+                ranges: vec![start..start + Duration::hours(8)],
+                start,
+                period: Duration::weeks(1),
+                hue: 0,
+                strategy: None,
+            })
+            .await
+            .unwrap();
+        let task = connection.add_task(test_task()).await.unwrap();
+
+        // The default time segment's tasks would be stranded without it
+        let error = connection
+            .delete_time_segment(default.clone())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.segment_constraint(),
+            Some(&SegmentConstraintError::SegmentNotEmpty { remaining_tasks: 1 })
+        );
+
+        // Once they've moved, it can go like any other time segment
+        connection
+            .move_tasks_between_segments(0, replacement.id)
+            .await
+            .unwrap();
+        connection.delete_time_segment(default).await.unwrap();
+        let time_segments = connection.all_time_segments().await.unwrap();
+        assert_eq!(time_segments.len(), 1);
+        assert_eq!(time_segments[0], replacement);
+        // The last time segment has to stay, even without tasks
+        connection.delete_task(task.id).await.unwrap();
+        let error = connection
+            .delete_time_segment(replacement)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.segment_constraint(),
+            Some(&SegmentConstraintError::LastTimeSegment)
+        );
+    }
+
+    #[test]
+    async fn test_tasks_per_nonempty_segment() {
+        let connection = MemoryDatabase::new();
+        let start = Utc::now();
+        let new_time_segment = NewTimeSegment {
+            name: "2h weekly".to_string(),
+            ranges: vec![start..start + Duration::hours(2)],
+            start,
+            period: Duration::weeks(1),
+            hue: 0,
+            strategy: None,
+        };
+        let populated = connection
+            .add_time_segment(new_time_segment.clone())
+            .await
+            .unwrap();
+        let _empty = connection
+            .add_time_segment(new_time_segment.clone())
+            .await
+            .unwrap();
+        let only_done = connection
+            .add_time_segment(new_time_segment.clone())
+            .await
+            .unwrap();
+        let mut in_populated = test_task();
+        in_populated.time_segment_id = populated.id;
+        let in_populated = connection.add_task(in_populated).await.unwrap();
+        let in_default = connection.add_task(test_task()).await.unwrap();
+        // Segments with only tasks which are done count as empty
+        let mut done = test_task();
+        done.time_segment_id = only_done.id;
+        let done = connection.add_task(done).await.unwrap();
+        connection.complete_task(done.id, Utc::now()).await.unwrap();
+
+        assert_eq!(
+            connection.all_tasks_per_time_segment().await.unwrap().len(),
+            4
+        );
+        let tasks_per_segment = connection.tasks_per_nonempty_segment().await.unwrap();
+        assert_eq!(
+            tasks_per_segment
+                .iter()
+                .map(|(segment, tasks)| (segment.id, tasks.clone()))
+                .collect::<Vec<_>>(),
+            [(0, vec![in_default]), (populated.id, vec![in_populated])]
+        );
+        assert_eq!(tasks_per_segment[1].0, populated);
+    }
+
+    #[test]
+    async fn test_insert_query_and_delete_time_segment() {
+        let database = MemoryDatabase::new();
+
+        let start = Utc::now();
+        let new_time_segment = NewTimeSegment {
+            name: "2h weekly".to_string(),
+            ranges: vec![start..start + Duration::hours(2)],
+            start,
+            period: Duration::weeks(1),
+            hue: 0,
+            strategy: None,
+        };
+        let added_time_segment = database
+            .add_time_segment(new_time_segment.clone())
+            .await
+            .unwrap();
+        let time_segments = database.all_time_segments().await.unwrap();
+        assert_eq!(time_segments.len(), 2);
+        assert_eq!(time_segments[1], new_time_segment);
+        assert_eq!(time_segments[1], added_time_segment);
+        let time_segment = time_segments[1].clone();
+        assert_eq!(database.get_time_segment(1).await.unwrap(), time_segment);
+        assert!(database
+            .get_time_segment(2)
+            .await
+            .unwrap_err()
+            .is_not_found());
+
+        // Tasks can only be added to existing time segments
+        let mut task = test_task();
+        task.time_segment_id = 2;
+        assert!(database.add_task(task.clone()).await.is_err());
+        task.time_segment_id = 1;
+        let task = database.add_task(task).await.unwrap();
+        let tasks_per_segment = database.all_tasks_per_time_segment().await.unwrap();
+        assert_eq!(tasks_per_segment.len(), 2);
+        assert!(tasks_per_segment[0].1.is_empty());
+        assert_eq!(tasks_per_segment[1].1, vec![task.clone()]);
+
+        // We shouldn't be able to delete the segment while there's still a
+        // task in it
+        let error = database
+            .delete_time_segment(time_segment.clone())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.segment_constraint(),
+            Some(&SegmentConstraintError::SegmentNotEmpty { remaining_tasks: 1 })
+        );
+        assert_eq!(
+            error.to_string(),
+            "A database error occurred while trying to delete a time segment: There are still 1 \
+             task(s) in this time segment. Please move them to another time segment or delete \
+             them before deleting this segment."
+        );
+
+        database.rename_time_segment(1, "renamed").await.unwrap();
+        let renamed = database.get_time_segment(1).await.unwrap();
+        assert_eq!(renamed.name, "renamed");
+        assert_eq!(renamed.ranges, time_segment.ranges);
+        assert!(database
+            .rename_time_segment(2, "renamed")
+            .await
+            .unwrap_err()
+            .is_not_found());
+
+        database.delete_task(task.id).await.unwrap();
+        database.delete_time_segment(time_segment).await.unwrap();
+        assert_eq!(database.all_time_segments().await.unwrap().len(), 1);
+    }
+
+    #[test]
+    async fn test_copy_of() {
+        let database = MemoryDatabase::new();
+        let task = database.add_task(test_task()).await.unwrap();
+
+        let copy = MemoryDatabase::copy_of(&database).await.unwrap();
+        assert_eq!(copy.all_tasks().await.unwrap(), vec![task.clone()]);
+        assert_eq!(
+            copy.all_time_segments().await.unwrap(),
+            database.all_time_segments().await.unwrap()
+        );
+
+        // Changing the copy leaves the original untouched
+        copy.delete_task(task.id).await.unwrap();
+        assert_eq!(database.all_tasks().await.unwrap(), [task]);
+    }
+
+    fn test_task() -> NewTask {
+        NewTask {
+            content: "do me".to_string(),
+            deadline: Utc::now(),
+            duration: Duration::seconds(6),
+            importance: 42,
+            confidence: Confidence::Medium,
+            time_segment_id: 0,
+            depends_on: vec![],
+        }
+    }
+}
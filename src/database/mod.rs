@@ -1,11 +1,15 @@
 use std::fmt;
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use thiserror::Error;
 
 use crate::time_segment::{NamedTimeSegment as TimeSegment, NewNamedTimeSegment as NewTimeSegment};
-use crate::{NewTask, Task};
+use crate::{NewTask, Operation, Task, TaskChanges};
 
+pub mod memory;
+#[cfg(feature = "postgres")]
+pub mod postgres;
 #[cfg(feature = "sqlite")]
 pub mod sqlite;
 
@@ -16,21 +20,158 @@ pub struct Error(
     #[source] pub Box<dyn std::error::Error + Send + Sync>,
 );
 
+impl Error {
+    /// Returns whether the error occurred because a task or time segment
+    /// doesn't exist.
+    pub fn is_not_found(&self) -> bool {
+        self.1.is::<NotFound>()
+    }
+
+    /// Returns which constraint on time segments the operation would have
+    /// broken, if that's why it failed.
+    pub fn segment_constraint(&self) -> Option<&SegmentConstraintError> {
+        self.1.downcast_ref()
+    }
+}
+
+/// The cause of an error when a task or time segment doesn't exist.
+#[derive(Debug, Error)]
+#[error("{0}")]
+pub struct NotFound(pub String);
+
+/// The cause of an error when deleting a time segment would leave the
+/// database in a state Eva can't work with.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SegmentConstraintError {
+    #[error("If you remove the last time segment, when should I schedule things?")]
+    LastTimeSegment,
+    #[error(
+        "There are still {remaining_tasks} task(s) in this time segment. Please move them to \
+         another time segment or delete them before deleting this segment."
+    )]
+    SegmentNotEmpty { remaining_tasks: u64 },
+}
+
+/// The cause of an error when a query affected `amount` rows instead of one.
+fn unexpected_amount(amount: usize, message: String) -> Box<dyn std::error::Error + Send + Sync> {
+    if amount == 0 {
+        NotFound(message).into()
+    } else {
+        message.into()
+    }
+}
+
+/// Turns a search query into a LIKE pattern which matches it anywhere. `%`
+/// and `_` in the query are escaped with a backslash so they match literally.
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+fn like_pattern(query: &str) -> String {
+    let mut pattern = String::from("%");
+    for character in query.chars() {
+        if matches!(character, '%' | '_' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(character);
+    }
+    pattern.push('%');
+    pattern
+}
+
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// How many operations are remembered to undo.
+pub const MAX_LOGGED_OPERATIONS: usize = 50;
+
+/// The order to page through tasks in. Ties are broken by the order the tasks
+/// were added in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TaskOrder {
+    /// The order the tasks were added in
+    #[default]
+    Added,
+    /// The task with the first deadline first
+    Deadline,
+    /// The most important task first
+    Importance,
+}
+
 #[async_trait(?Send)]
 pub trait Database {
     async fn add_task(&self, task: NewTask) -> Result<Task>;
+    /// Adds all tasks at once, which is a lot faster than adding them one by
+    /// one. Either all of them are added or none are. Returns the new tasks in
+    /// the same order.
+    async fn bulk_add_tasks(&self, tasks: Vec<NewTask>) -> Result<Vec<Task>>;
     async fn delete_task(&self, id: u32) -> Result<()>;
+    /// Marks a task as done at `done_at`. It's kept, but left out of
+    /// `all_tasks` and the other queries which list tasks, so it isn't
+    /// scheduled anymore either.
+    async fn complete_task(&self, id: u32, done_at: DateTime<Utc>) -> Result<()>;
+    /// Deletes the tasks which were done before `older_than`, or all of them
+    /// if it's `None`, and returns how many there were. The operations on
+    /// them are forgotten, since they can't be undone anymore.
+    async fn clear_completed(&self, older_than: Option<DateTime<Utc>>) -> Result<u64>;
     async fn get_task(&self, id: u32) -> Result<Task>;
+    /// Returns the tasks with the given ids in the same order, or an error if
+    /// any of them doesn't exist.
+    async fn get_tasks(&self, ids: &[u32]) -> Result<Vec<Task>>;
     async fn update_task(&self, task: Task) -> Result<()>;
+    /// Changes only the given fields of a task at once, so changes made to its
+    /// other fields in the meantime aren't overwritten. Returns the task as it
+    /// is after the changes.
+    async fn update_task_fields(&self, id: u32, changes: TaskChanges) -> Result<Task>;
+    /// Remembers when a schedule plans each of the given tasks, and forgets it
+    /// for all other tasks, all at once. This doesn't count as changing them.
+    async fn set_scheduled_for(&self, scheduled: &[(u32, DateTime<Utc>)]) -> Result<()>;
+    /// Returns the tasks which aren't done yet.
     async fn all_tasks(&self) -> Result<Vec<Task>>;
+    /// Returns the tasks which were added or changed at or after `since`.
+    async fn tasks_updated_since(&self, since: DateTime<Utc>) -> Result<Vec<Task>>;
+    /// Returns the tasks which are due at or after `from` and before `to`, in
+    /// the order they were added. Either bound can be left out.
+    async fn tasks_in_deadline_range(
+        &self,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> Result<Vec<Task>>;
+    /// Returns the tasks whose content contains `query`, ignoring case.
+    async fn search_tasks(&self, query: &str) -> Result<Vec<Task>>;
+    /// Returns at most `limit` tasks in the given order, after skipping the
+    /// first `offset` ones.
+    async fn tasks_page(&self, order: TaskOrder, limit: u32, offset: u32) -> Result<Vec<Task>>;
+    async fn count_tasks(&self) -> Result<u64>;
     async fn all_tasks_per_time_segment(&self) -> Result<Vec<(TimeSegment, Vec<Task>)>>;
+    /// Like `all_tasks_per_time_segment`, but leaves out the time segments
+    /// without any tasks to do, e.g. because there's nothing to schedule in
+    /// them.
+    async fn tasks_per_nonempty_segment(&self) -> Result<Vec<(TimeSegment, Vec<Task>)>>;
+    /// Adds a task which was deleted before again, with the same id. The
+    /// tasks which depended on it are left as they are.
+    async fn restore_task(&self, task: Task) -> Result<()>;
 
-    async fn add_time_segment(&self, time_segment: NewTimeSegment) -> Result<()>;
+    /// Remembers an operation to undo later, and forgets the oldest ones
+    /// beyond the last `MAX_LOGGED_OPERATIONS`.
+    async fn log_operation(&self, operation: Operation) -> Result<()>;
+    /// Returns the remembered operations, the oldest one first.
+    async fn operations(&self) -> Result<Vec<Operation>>;
+    /// Forgets the operation which was logged last and returns it, or `None`
+    /// if there is none.
+    async fn pop_operation(&self) -> Result<Option<Operation>>;
+
+    async fn add_time_segment(&self, time_segment: NewTimeSegment) -> Result<TimeSegment>;
     async fn delete_time_segment(&self, time_segment: TimeSegment) -> Result<()>;
     async fn update_time_segment(&self, time_segment: TimeSegment) -> Result<()>;
+    /// Only changes the name of a time segment, leaving its ranges alone.
+    async fn rename_time_segment(&self, id: u32, new_name: &str) -> Result<()>;
+    /// Moves all tasks in the time segment `from` to the time segment `to` at
+    /// once, e.g. to empty a segment before deleting it. Returns how many
+    /// tasks were moved.
+    async fn move_tasks_between_segments(&self, from: u32, to: u32) -> Result<u64>;
+    async fn get_time_segment(&self, id: u32) -> Result<TimeSegment>;
     async fn all_time_segments(&self) -> Result<Vec<TimeSegment>>;
+
+    /// Shrinks and defragments the database and refreshes the statistics its
+    /// query planner uses, for databases which don't do so by themselves.
+    async fn vacuum(&self) -> Result<()>;
 }
 
 impl fmt::Debug for dyn Database {
@@ -0,0 +1,1947 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use chrono::prelude::*;
+use chrono::Duration;
+use diesel::prelude::*;
+use diesel::r2d2;
+use diesel_migrations::MigrationConnection;
+use itertools::Itertools;
+
+use super::Database;
+use super::MAX_LOGGED_OPERATIONS;
+use super::{
+    like_pattern, unexpected_amount, Error, NotFound, Result, SegmentConstraintError, TaskOrder,
+};
+use crate::time_segment::{
+    NamedTimeSegment as CrateTimeSegment, NewNamedTimeSegment as CrateNewTimeSegment,
+};
+use crate::{Confidence, Operation};
+
+use self::tasks::dsl::tasks as task_table;
+use self::time_segment_ranges::dsl::time_segment_ranges as time_segment_range_table;
+use self::time_segments::dsl::time_segments as time_segment_table;
+
+pub struct DbConnection(r2d2::Pool<r2d2::ConnectionManager<PgConnection>>);
+
+#[derive(
+    Debug, Clone, PartialEq, Queryable, Insertable, Identifiable, AsChangeset, Associations,
+)]
+#[belongs_to(TimeSegment)]
+#[table_name = "tasks"]
+// Clearing the color of a task should clear it in the database too
+#[changeset_options(treat_none_as_null = "true")]
+struct Task {
+    pub id: i32,
+    pub content: String,
+    pub deadline: DateTime<Utc>,
+    pub duration: i32,
+    pub importance: i32,
+    pub time_segment_id: i32,
+    pub confidence: i32,
+    pub updated_at: DateTime<Utc>,
+    pub color: Option<i32>,
+    pub scheduled_for: Option<DateTime<Utc>>,
+    pub done_at: Option<DateTime<Utc>>,
+    pub pinned: bool,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "tasks"]
+struct NewTask {
+    pub content: String,
+    pub deadline: DateTime<Utc>,
+    pub duration: i32,
+    pub importance: i32,
+    pub time_segment_id: i32,
+    pub confidence: i32,
+    pub updated_at: DateTime<Utc>,
+}
+
+table! {
+    tasks (id) {
+        id -> Integer,
+        content -> Text,
+        deadline -> Timestamptz,
+        duration -> Integer,
+        importance -> Integer,
+        time_segment_id -> Integer,
+        confidence -> Integer,
+        updated_at -> Timestamptz,
+        color -> Nullable<Integer>,
+        scheduled_for -> Nullable<Timestamptz>,
+        done_at -> Nullable<Timestamptz>,
+        pinned -> Bool,
+    }
+}
+
+#[derive(Debug, Queryable, Identifiable, AsChangeset)]
+#[table_name = "time_segments"]
+// Clearing the strategy of a time segment should clear it in the database too
+#[changeset_options(treat_none_as_null = "true")]
+struct TimeSegment {
+    pub id: i32,
+    pub name: String,
+    pub start: DateTime<Utc>,
+    pub period: i32,
+    pub hue: i32,
+    pub strategy: Option<String>,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "time_segments"]
+struct NewTimeSegment {
+    pub name: String,
+    pub start: DateTime<Utc>,
+    pub period: i32,
+    pub hue: i32,
+    pub strategy: Option<String>,
+}
+
+table! {
+    time_segments (id) {
+        id -> Integer,
+        name -> Text,
+        start -> Timestamptz,
+        period -> Integer,
+        hue -> Integer,
+        strategy -> Nullable<Text>,
+    }
+}
+
+// For the subqueries which look for time segments with tasks
+allow_tables_to_appear_in_same_query!(tasks, time_segments);
+
+#[derive(Debug, Insertable, Queryable, Identifiable, Associations)]
+#[belongs_to(TimeSegment, foreign_key = "segment_id")]
+#[table_name = "time_segment_ranges"]
+#[primary_key(start)]
+struct TimeSegmentRange {
+    pub segment_id: i32,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+table! {
+    time_segment_ranges (start) {
+        segment_id -> Integer,
+        start -> Timestamptz,
+        end -> Timestamptz,
+    }
+}
+
+#[derive(Debug, Insertable, Queryable)]
+#[table_name = "task_dependencies"]
+struct TaskDependency {
+    pub task_id: i32,
+    pub dependency_id: i32,
+}
+
+table! {
+    task_dependencies (task_id, dependency_id) {
+        task_id -> Integer,
+        dependency_id -> Integer,
+    }
+}
+
+// Postgres has a schema of its own, so it keeps its own migrations too
+#[derive(Debug, Queryable)]
+struct LoggedOperation {
+    pub id: i32,
+    pub kind: String,
+    pub task_id: i32,
+    pub content: String,
+    pub deadline: DateTime<Utc>,
+    pub duration: i32,
+    pub importance: i32,
+    pub time_segment_id: i32,
+    pub confidence: i32,
+    pub color: Option<i32>,
+    pub scheduled_for: Option<DateTime<Utc>>,
+    pub depends_on: Vec<i32>,
+    pub dependents: Vec<i32>,
+    pub done_at: Option<DateTime<Utc>>,
+    pub pinned: bool,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "operations"]
+struct NewLoggedOperation {
+    pub kind: String,
+    pub task_id: i32,
+    pub content: String,
+    pub deadline: DateTime<Utc>,
+    pub duration: i32,
+    pub importance: i32,
+    pub time_segment_id: i32,
+    pub confidence: i32,
+    pub color: Option<i32>,
+    pub scheduled_for: Option<DateTime<Utc>>,
+    pub depends_on: Vec<i32>,
+    pub dependents: Vec<i32>,
+    pub done_at: Option<DateTime<Utc>>,
+    pub pinned: bool,
+}
+
+table! {
+    operations (id) {
+        id -> Integer,
+        kind -> VarChar,
+        task_id -> Integer,
+        content -> Text,
+        deadline -> Timestamptz,
+        duration -> Integer,
+        importance -> Integer,
+        time_segment_id -> Integer,
+        confidence -> Integer,
+        color -> Nullable<Integer>,
+        scheduled_for -> Nullable<Timestamptz>,
+        depends_on -> Array<Integer>,
+        dependents -> Array<Integer>,
+        done_at -> Nullable<Timestamptz>,
+        pinned -> Bool,
+    }
+}
+
+embed_migrations!("postgres_migrations");
+
+#[async_trait(?Send)]
+impl Database for DbConnection {
+    async fn add_task(&self, task: crate::NewTask) -> Result<crate::Task> {
+        self.ensure_time_segment_exists(task.time_segment_id, "while trying to add a task")?;
+        self.ensure_tasks_exist(&task.depends_on, "while trying to add a task")?;
+        let depends_on = task.depends_on.clone();
+        let db_task = diesel::insert_into(task_table)
+            .values(&NewTask::from(task))
+            .get_result::<Task>(&self.get_connection()?)
+            .map_err(|e| Error("while trying to add a task", e.into()))?;
+        let id = db_task.id as u32;
+        self.set_dependencies(id, &depends_on, "while trying to add a task")?;
+        let task = self
+            .get_task(id)
+            .await
+            .map_err(|e| Error("while trying to fetch the newly created task", e.into()))?;
+        Ok(task)
+    }
+
+    async fn bulk_add_tasks(&self, tasks: Vec<crate::NewTask>) -> Result<Vec<crate::Task>> {
+        let context = "while trying to add tasks";
+        for time_segment_id in tasks.iter().map(|task| task.time_segment_id).unique() {
+            self.ensure_time_segment_exists(time_segment_id, context)?;
+        }
+        let dependency_ids = tasks
+            .iter()
+            .flat_map(|task| task.depends_on.iter().copied())
+            .unique()
+            .collect_vec();
+        self.ensure_tasks_exist(&dependency_ids, context)?;
+        if tasks.is_empty() {
+            return Ok(vec![]);
+        }
+        let new_tasks = tasks.iter().cloned().map(NewTask::from).collect_vec();
+        let connection = self.get_connection()?;
+        let ids: Vec<i32> = connection
+            .transaction::<_, diesel::result::Error, _>(|| {
+                // The rows come back in the order they were inserted
+                let ids = diesel::insert_into(task_table)
+                    .values(&new_tasks)
+                    .returning(tasks::id)
+                    .get_results::<i32>(&connection)?;
+                let dependencies = tasks
+                    .iter()
+                    .zip(&ids)
+                    .flat_map(|(task, &id)| {
+                        task.depends_on
+                            .iter()
+                            .unique()
+                            .map(move |&dependency_id| TaskDependency {
+                                task_id: id,
+                                dependency_id: dependency_id as i32,
+                            })
+                    })
+                    .collect_vec();
+                diesel::insert_into(task_dependencies::table)
+                    .values(&dependencies)
+                    .execute(&connection)?;
+                Ok(ids)
+            })
+            .map_err(|e| Error(context, e.into()))?;
+        // Give the connection back, since fetching the tasks needs one too
+        drop(connection);
+        let ids = ids.into_iter().map(|id| id as u32).collect_vec();
+        self.get_tasks(&ids)
+            .await
+            .map_err(|e| Error("while trying to fetch the newly created tasks", e.into()))
+    }
+
+    async fn delete_task(&self, id: u32) -> Result<()> {
+        // Nothing will depend on this task anymore, which changes the tasks
+        // that did. Deleting the task takes its dependencies along, so mark
+        // them before.
+        let dependent_ids = task_dependencies::table
+            .filter(task_dependencies::dependency_id.eq(id as i32))
+            .select(task_dependencies::task_id)
+            .load::<i32>(&self.get_connection()?)
+            .map_err(|e| Error("while trying to delete a task", e.into()))?;
+        let connection = self.get_connection()?;
+        let amount_deleted = connection
+            .transaction::<_, diesel::result::Error, _>(|| {
+                diesel::update(task_table.filter(tasks::id.eq_any(dependent_ids)))
+                    .set(tasks::updated_at.eq(Utc::now()))
+                    .execute(&connection)?;
+                diesel::delete(task_table.find(id as i32)).execute(&connection)
+            })
+            .map_err(|e| Error("while trying to delete a task", e.into()))?;
+        if amount_deleted != 1 {
+            return Err(Error(
+                "while trying to delete a task",
+                unexpected_amount(
+                    amount_deleted,
+                    format!("{} task(s) were deleted", amount_deleted),
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    async fn complete_task(&self, id: u32, done_at: DateTime<Utc>) -> Result<()> {
+        let amount_updated = diesel::update(task_table.find(id as i32))
+            .set((tasks::done_at.eq(done_at), tasks::updated_at.eq(Utc::now())))
+            .execute(&self.get_connection()?)
+            .map_err(|e| Error("while trying to complete a task", e.into()))?;
+        if amount_updated != 1 {
+            return Err(Error(
+                "while trying to complete a task",
+                unexpected_amount(
+                    amount_updated,
+                    format!("{} task(s) were completed", amount_updated),
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    async fn clear_completed(&self, older_than: Option<DateTime<Utc>>) -> Result<u64> {
+        let mut query = task_table
+            .select(tasks::id)
+            .filter(tasks::done_at.is_not_null())
+            .into_boxed();
+        if let Some(older_than) = older_than {
+            query = query.filter(tasks::done_at.lt(older_than));
+        }
+        let ids = query
+            .load::<i32>(&self.get_connection()?)
+            .map_err(|e| Error("while trying to clear completed tasks", e.into()))?;
+        self.purge_tasks(&ids, "while trying to clear completed tasks")?;
+        Ok(ids.len() as u64)
+    }
+
+    async fn get_task(&self, id: u32) -> Result<crate::Task> {
+        let db_task = task_table
+            .find(id as i32)
+            .get_result::<Task>(&self.get_connection()?)
+            .map_err(|e| match e {
+                diesel::result::Error::NotFound => Error(
+                    "while trying to find a task",
+                    NotFound(format!("There is no task with id {}", id)).into(),
+                ),
+                e => Error("while trying to find a task", e.into()),
+            })?;
+        let mut task = crate::Task::from(db_task);
+        task.depends_on = task_dependencies::table
+            .filter(task_dependencies::task_id.eq(id as i32))
+            .select(task_dependencies::dependency_id)
+            .order(task_dependencies::dependency_id)
+            .load::<i32>(&self.get_connection()?)
+            .map_err(|e| Error("while trying to find a task", e.into()))?
+            .into_iter()
+            .map(|dependency_id| dependency_id as u32)
+            .collect();
+        Ok(task)
+    }
+
+    async fn get_tasks(&self, ids: &[u32]) -> Result<Vec<crate::Task>> {
+        let db_ids = ids.iter().map(|&id| id as i32).collect_vec();
+        let db_tasks = task_table
+            .filter(tasks::id.eq_any(&db_ids))
+            .load::<Task>(&self.get_connection()?)
+            .map_err(|e| Error("while trying to find tasks", e.into()))?;
+        let mut dependencies = task_dependencies::table
+            .filter(task_dependencies::task_id.eq_any(&db_ids))
+            .order((task_dependencies::task_id, task_dependencies::dependency_id))
+            .load::<TaskDependency>(&self.get_connection()?)
+            .map_err(|e| Error("while trying to find tasks", e.into()))?
+            .into_iter()
+            .map(|dependency| (dependency.task_id, dependency.dependency_id as u32))
+            .into_group_map();
+        let tasks: HashMap<u32, crate::Task> = db_tasks
+            .into_iter()
+            .map(|db_task| with_dependencies(db_task, &mut dependencies))
+            .map(|task| (task.id, task))
+            .collect();
+        ids.iter()
+            .map(|id| {
+                tasks.get(id).cloned().ok_or_else(|| {
+                    Error(
+                        "while trying to find tasks",
+                        NotFound(format!("There is no task with id {}", id)).into(),
+                    )
+                })
+            })
+            .collect()
+    }
+
+    async fn update_task(&self, task: crate::Task) -> Result<()> {
+        self.ensure_time_segment_exists(task.time_segment_id, "while trying to update a task")?;
+        self.ensure_tasks_exist(&task.depends_on, "while trying to update a task")?;
+        let (id, depends_on) = (task.id, task.depends_on.clone());
+        let db_task = Task::from(task);
+        let amount_updated = diesel::update(&db_task)
+            .set(&db_task)
+            .execute(&self.get_connection()?)
+            .map_err(|e| Error("while trying to update a task", e.into()))?;
+        if amount_updated != 1 {
+            return Err(Error(
+                "while trying to update a task",
+                unexpected_amount(
+                    amount_updated,
+                    format!("{} task(s) were updated", amount_updated),
+                ),
+            ));
+        }
+        self.set_dependencies(id, &depends_on, "while trying to update a task")
+    }
+
+    async fn update_task_fields(
+        &self,
+        id: u32,
+        changes: crate::TaskChanges,
+    ) -> Result<crate::Task> {
+        let context = "while trying to update a task";
+        if let Some(time_segment_id) = changes.time_segment_id {
+            self.ensure_time_segment_exists(time_segment_id, context)?;
+        }
+        if let Some(depends_on) = &changes.depends_on {
+            self.ensure_tasks_exist(depends_on, context)?;
+        }
+        // The fields which don't change are left out of the query
+        let amount_updated = diesel::update(task_table.find(id as i32))
+            .set((
+                changes.content.map(|content| tasks::content.eq(content)),
+                changes
+                    .deadline
+                    .map(|deadline| tasks::deadline.eq(deadline)),
+                changes
+                    .duration
+                    .map(|duration| tasks::duration.eq(duration.num_seconds() as i32)),
+                changes
+                    .importance
+                    .map(|importance| tasks::importance.eq(importance as i32)),
+                changes
+                    .confidence
+                    .map(|confidence| tasks::confidence.eq(confidence_to_i32(confidence))),
+                changes
+                    .time_segment_id
+                    .map(|time_segment_id| tasks::time_segment_id.eq(time_segment_id as i32)),
+                changes
+                    .color
+                    .map(|color| tasks::color.eq(color.map(i32::from))),
+                changes.pinned.map(|pinned| tasks::pinned.eq(pinned)),
+                tasks::updated_at.eq(Utc::now()),
+            ))
+            .execute(&self.get_connection()?)
+            .map_err(|e| Error(context, e.into()))?;
+        if amount_updated != 1 {
+            return Err(Error(
+                context,
+                unexpected_amount(
+                    amount_updated,
+                    format!("{} task(s) were updated", amount_updated),
+                ),
+            ));
+        }
+        if let Some(depends_on) = changes.depends_on {
+            self.set_dependencies(id, &depends_on, context)?;
+        }
+        self.get_task(id).await
+    }
+
+    async fn set_scheduled_for(&self, scheduled: &[(u32, DateTime<Utc>)]) -> Result<()> {
+        let ids = scheduled.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+        self.ensure_tasks_exist(&ids, "while trying to persist a schedule")?;
+        // Either the whole schedule is persisted or none of it, so a failure
+        // halfway doesn't leave a mix of two schedules behind
+        let connection = self.get_connection()?;
+        connection
+            .transaction::<_, diesel::result::Error, _>(|| {
+                diesel::update(task_table)
+                    .set(tasks::scheduled_for.eq(None::<DateTime<Utc>>))
+                    .execute(&connection)?;
+                for (id, when) in scheduled {
+                    diesel::update(task_table.find(*id as i32))
+                        .set(tasks::scheduled_for.eq(when))
+                        .execute(&connection)?;
+                }
+                Ok(())
+            })
+            .map_err(|e| Error("while trying to persist a schedule", e.into()))
+    }
+
+    async fn all_tasks(&self) -> Result<Vec<crate::Task>> {
+        let db_tasks = task_table
+            .filter(tasks::done_at.is_null())
+            .order(tasks::id)
+            .load::<Task>(&self.get_connection()?)
+            .map_err(|e| Error("while trying to retrieve tasks", e.into()))?;
+        let mut dependencies = self.all_dependencies()?;
+        Ok(db_tasks
+            .into_iter()
+            .map(|db_task| with_dependencies(db_task, &mut dependencies))
+            .collect())
+    }
+
+    async fn tasks_updated_since(&self, since: DateTime<Utc>) -> Result<Vec<crate::Task>> {
+        let db_tasks = task_table
+            .filter(tasks::updated_at.ge(since))
+            .filter(tasks::done_at.is_null())
+            .order(tasks::id)
+            .load::<Task>(&self.get_connection()?)
+            .map_err(|e| Error("while trying to retrieve updated tasks", e.into()))?;
+        let mut dependencies = self.all_dependencies()?;
+        Ok(db_tasks
+            .into_iter()
+            .map(|db_task| with_dependencies(db_task, &mut dependencies))
+            .collect())
+    }
+
+    async fn tasks_in_deadline_range(
+        &self,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> Result<Vec<crate::Task>> {
+        let mut query = task_table.filter(tasks::done_at.is_null()).into_boxed();
+        if let Some(from) = from {
+            query = query.filter(tasks::deadline.ge(from));
+        }
+        if let Some(to) = to {
+            query = query.filter(tasks::deadline.lt(to));
+        }
+        let db_tasks = query
+            .order(tasks::id)
+            .load::<Task>(&self.get_connection()?)
+            .map_err(|e| Error("while trying to retrieve tasks by deadline", e.into()))?;
+        let mut dependencies = self.all_dependencies()?;
+        Ok(db_tasks
+            .into_iter()
+            .map(|db_task| with_dependencies(db_task, &mut dependencies))
+            .collect())
+    }
+
+    async fn search_tasks(&self, query: &str) -> Result<Vec<crate::Task>> {
+        let db_tasks = task_table
+            .filter(tasks::content.ilike(like_pattern(query)))
+            .filter(tasks::done_at.is_null())
+            .order(tasks::id)
+            .load::<Task>(&self.get_connection()?)
+            .map_err(|e| Error("while trying to search tasks", e.into()))?;
+        let mut dependencies = self.all_dependencies()?;
+        Ok(db_tasks
+            .into_iter()
+            .map(|db_task| with_dependencies(db_task, &mut dependencies))
+            .collect())
+    }
+
+    async fn tasks_page(
+        &self,
+        order: TaskOrder,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<crate::Task>> {
+        let query = task_table.filter(tasks::done_at.is_null()).into_boxed();
+        let query = match order {
+            TaskOrder::Added => query.order(tasks::id),
+            TaskOrder::Deadline => query.order((tasks::deadline, tasks::id)),
+            TaskOrder::Importance => query.order((tasks::importance.desc(), tasks::id)),
+        };
+        let db_tasks = query
+            .limit(i64::from(limit))
+            .offset(i64::from(offset))
+            .load::<Task>(&self.get_connection()?)
+            .map_err(|e| Error("while trying to retrieve a page of tasks", e.into()))?;
+        let mut dependencies = self.all_dependencies()?;
+        Ok(db_tasks
+            .into_iter()
+            .map(|db_task| with_dependencies(db_task, &mut dependencies))
+            .collect())
+    }
+
+    async fn count_tasks(&self) -> Result<u64> {
+        let n_tasks = task_table
+            .filter(tasks::done_at.is_null())
+            .count()
+            .get_result::<i64>(&self.get_connection()?)
+            .map_err(|e| Error("while trying to count tasks", e.into()))?;
+        Ok(n_tasks as u64)
+    }
+
+    async fn all_tasks_per_time_segment(
+        &self,
+    ) -> Result<Vec<(CrateTimeSegment, Vec<crate::Task>)>> {
+        let db_time_segments = time_segments::table
+            .order(time_segments::id)
+            .load::<TimeSegment>(&self.get_connection()?)
+            .map_err(|e| Error("while trying to retrieve time segments", e.into()))?;
+        self.with_tasks_to_do(db_time_segments)
+    }
+
+    async fn tasks_per_nonempty_segment(
+        &self,
+    ) -> Result<Vec<(CrateTimeSegment, Vec<crate::Task>)>> {
+        // Leave the empty segments out in the query already, so their ranges
+        // aren't loaded for nothing
+        let nonempty = task_table
+            .filter(tasks::done_at.is_null())
+            .select(tasks::time_segment_id);
+        let db_time_segments = time_segments::table
+            .filter(time_segments::id.eq_any(nonempty))
+            .order(time_segments::id)
+            .load::<TimeSegment>(&self.get_connection()?)
+            .map_err(|e| Error("while trying to retrieve time segments", e.into()))?;
+        self.with_tasks_to_do(db_time_segments)
+    }
+
+    async fn restore_task(&self, task: crate::Task) -> Result<()> {
+        let context = "while trying to restore a task";
+        self.ensure_time_segment_exists(task.time_segment_id, context)?;
+        self.ensure_tasks_exist(&task.depends_on, context)?;
+        let (id, depends_on) = (task.id, task.depends_on.clone());
+        diesel::insert_into(task_table)
+            .values(&Task::from(task))
+            .execute(&self.get_connection()?)
+            .map_err(|e| Error(context, e.into()))?;
+        self.set_dependencies(id, &depends_on, context)
+    }
+
+    async fn log_operation(&self, operation: Operation) -> Result<()> {
+        let context = "while trying to log an operation";
+        diesel::insert_into(operations::table)
+            .values(&NewLoggedOperation::from(operation))
+            .execute(&self.get_connection()?)
+            .map_err(|e| Error(context, e.into()))?;
+        let forgotten = operations::table
+            .select(operations::id)
+            .order(operations::id.desc())
+            .offset(MAX_LOGGED_OPERATIONS as i64)
+            .first::<i32>(&self.get_connection()?)
+            .optional()
+            .map_err(|e| Error(context, e.into()))?;
+        if let Some(forgotten) = forgotten {
+            diesel::delete(operations::table.filter(operations::id.le(forgotten)))
+                .execute(&self.get_connection()?)
+                .map_err(|e| Error(context, e.into()))?;
+        }
+        Ok(())
+    }
+
+    async fn operations(&self) -> Result<Vec<Operation>> {
+        operations::table
+            .order(operations::id)
+            .load::<LoggedOperation>(&self.get_connection()?)
+            .map_err(|e| Error("while trying to retrieve operations", e.into()))?
+            .into_iter()
+            .map(LoggedOperation::into_operation)
+            .collect()
+    }
+
+    async fn pop_operation(&self) -> Result<Option<Operation>> {
+        let connection = self.get_connection()?;
+        let logged_operation = connection
+            .transaction::<_, diesel::result::Error, _>(|| {
+                let logged_operation = operations::table
+                    .order(operations::id.desc())
+                    .for_update()
+                    .first::<LoggedOperation>(&connection)
+                    .optional()?;
+                if let Some(logged_operation) = &logged_operation {
+                    diesel::delete(operations::table.find(logged_operation.id))
+                        .execute(&connection)?;
+                }
+                Ok(logged_operation)
+            })
+            .map_err(|e| Error("while trying to retrieve the last operation", e.into()))?;
+        logged_operation
+            .map(LoggedOperation::into_operation)
+            .transpose()
+    }
+
+    async fn add_time_segment(
+        &self,
+        time_segment: CrateNewTimeSegment,
+    ) -> Result<CrateTimeSegment> {
+        let db_time_segment = diesel::insert_into(time_segment_table)
+            .values(&NewTimeSegment::from(time_segment.clone()))
+            .get_result::<TimeSegment>(&self.get_connection()?)
+            .map_err(|e| Error("while trying to add a time segment", e.into()))?;
+        self.insert_ranges(
+            db_time_segment.id,
+            &time_segment.ranges,
+            "while trying to add a time segment",
+        )?;
+        Ok(CrateTimeSegment {
+            id: db_time_segment.id as u32,
+            name: time_segment.name,
+            ranges: time_segment.ranges,
+            start: time_segment.start,
+            period: time_segment.period,
+            hue: time_segment.hue,
+            strategy: time_segment.strategy,
+        })
+    }
+
+    async fn delete_time_segment(&self, time_segment: CrateTimeSegment) -> Result<()> {
+        let db_time_segment = TimeSegment::from(time_segment);
+
+        // Assert that there are no tasks left to do in this time segment
+        let n_tasks = Task::belonging_to(&db_time_segment)
+            .filter(tasks::done_at.is_null())
+            .count()
+            .get_result::<i64>(&self.get_connection()?)
+            .map_err(|e| Error("while trying to delete a time segment", e.into()))?;
+        if n_tasks > 0 {
+            Err(Error(
+                "while trying to delete a time segment",
+                SegmentConstraintError::SegmentNotEmpty {
+                    remaining_tasks: n_tasks as u64,
+                }
+                .into(),
+            ))?
+        }
+
+        // Assert that this isn't the last time segment
+        let n_time_segments = time_segments::table
+            .count()
+            .get_result::<i64>(&self.get_connection()?)
+            .map_err(|e| Error("while trying to count time segments", e.into()))?;
+        if n_time_segments <= 1 {
+            Err(Error(
+                "while trying to delete a time segment",
+                SegmentConstraintError::LastTimeSegment.into(),
+            ))?
+        }
+
+        // The tasks which are done go along with their time segment
+        let completed_ids = Task::belonging_to(&db_time_segment)
+            .filter(tasks::done_at.is_not_null())
+            .select(tasks::id)
+            .load::<i32>(&self.get_connection()?)
+            .map_err(|e| Error("while trying to delete a time segment", e.into()))?;
+        self.purge_tasks(&completed_ids, "while trying to delete a time segment")?;
+        // The ranges of the time segment are deleted along with it
+        let amount_deleted = diesel::delete(&db_time_segment)
+            .execute(&self.get_connection()?)
+            .map_err(|e| Error("while trying to delete a time segment", e.into()))?;
+        if amount_deleted != 1 {
+            Err(Error(
+                "while trying to delete a time segment",
+                unexpected_amount(
+                    amount_deleted,
+                    format!("{} time segment(s) were deleted", amount_deleted),
+                ),
+            ))?
+        }
+
+        Ok(())
+    }
+
+    async fn update_time_segment(&self, time_segment: CrateTimeSegment) -> Result<()> {
+        let db_time_segment = TimeSegment::from(time_segment.clone());
+        let ranges = TimeSegmentRange::belonging_to(&db_time_segment);
+        diesel::delete(ranges)
+            .execute(&self.get_connection()?)
+            .map_err(|e| Error("while trying to update a time segment", e.into()))?;
+        self.insert_ranges(
+            db_time_segment.id,
+            &time_segment.ranges,
+            "while trying to update a time segment",
+        )?;
+        let amount_updated = diesel::update(&db_time_segment)
+            .set(&db_time_segment)
+            .execute(&self.get_connection()?)
+            .map_err(|e| Error("while trying to update a time segment", e.into()))?;
+        if amount_updated != 1 {
+            Err(Error(
+                "while trying to update a time segment",
+                unexpected_amount(
+                    amount_updated,
+                    format!("{} time segment(s) were updated", amount_updated),
+                ),
+            ))?
+        }
+
+        Ok(())
+    }
+
+    async fn rename_time_segment(&self, id: u32, new_name: &str) -> Result<()> {
+        let amount_updated = diesel::update(time_segment_table.find(id as i32))
+            .set(time_segments::name.eq(new_name))
+            .execute(&self.get_connection()?)
+            .map_err(|e| Error("while trying to rename a time segment", e.into()))?;
+        if amount_updated != 1 {
+            return Err(Error(
+                "while trying to rename a time segment",
+                unexpected_amount(
+                    amount_updated,
+                    format!("{} time segment(s) were renamed", amount_updated),
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    async fn move_tasks_between_segments(&self, from: u32, to: u32) -> Result<u64> {
+        let context = "while trying to move tasks between time segments";
+        self.ensure_time_segment_exists(from, context)?;
+        self.ensure_time_segment_exists(to, context)?;
+        let amount_moved =
+            diesel::update(task_table.filter(tasks::time_segment_id.eq(from as i32)))
+                .set((
+                    tasks::time_segment_id.eq(to as i32),
+                    tasks::updated_at.eq(Utc::now()),
+                ))
+                .execute(&self.get_connection()?)
+                .map_err(|e| Error(context, e.into()))?;
+        Ok(amount_moved as u64)
+    }
+
+    async fn vacuum(&self) -> Result<()> {
+        // Postgres' autovacuum daemon takes care of this
+        Ok(())
+    }
+
+    async fn get_time_segment(&self, id: u32) -> Result<CrateTimeSegment> {
+        let db_time_segment = time_segment_table
+            .find(id as i32)
+            .get_result::<TimeSegment>(&self.get_connection()?)
+            .map_err(|e| match e {
+                diesel::result::Error::NotFound => Error(
+                    "while trying to find a time segment",
+                    NotFound(format!("There is no time segment with id {}", id)).into(),
+                ),
+                e => Error("while trying to find a time segment", e.into()),
+            })?;
+        self.construct_time_segments(vec![db_time_segment])?
+            .next()
+            .ok_or_else(|| {
+                Error(
+                    "while trying to find a time segment",
+                    "The time segment disappeared".into(),
+                )
+            })
+    }
+
+    async fn all_time_segments(&self) -> Result<Vec<CrateTimeSegment>> {
+        let db_time_segments = time_segments::table
+            .order(time_segments::id)
+            .load::<TimeSegment>(&self.get_connection()?)
+            .map_err(|e| Error("while trying to retrieve time segments", e.into()))?;
+        Ok(self.construct_time_segments(db_time_segments)?.collect())
+    }
+}
+
+impl DbConnection {
+    pub fn get_connection(
+        &self,
+    ) -> Result<r2d2::PooledConnection<r2d2::ConnectionManager<PgConnection>>> {
+        self.0
+            .get()
+            .map_err(|e| Error("while connecting to the database", e.into()))
+    }
+
+    /// Runs the migrations which haven't been run on this database yet and
+    /// returns their versions. When the schema is already up to date, nothing
+    /// is run and the result is empty.
+    pub fn run_pending_migrations(&self) -> Result<Vec<String>> {
+        let context = "while running database migrations";
+        let connection = self.get_connection()?;
+        diesel_migrations::setup_database(&*connection).map_err(|e| Error(context, e.into()))?;
+        let previous_versions = connection
+            .previously_run_migration_versions()
+            .map_err(|e| Error(context, e.into()))?;
+        embedded_migrations::run(&*connection).map_err(|e| Error(context, e.into()))?;
+        let mut new_versions = connection
+            .previously_run_migration_versions()
+            .map_err(|e| Error(context, e.into()))?
+            .into_iter()
+            .filter(|version| !previous_versions.contains(version))
+            .collect::<Vec<_>>();
+        new_versions.sort();
+        Ok(new_versions)
+    }
+
+    fn ensure_tasks_exist(&self, ids: &[u32], context: &'static str) -> Result<()> {
+        for &id in ids {
+            let n_tasks = task_table
+                .find(id as i32)
+                .count()
+                .get_result::<i64>(&self.get_connection()?)
+                .map_err(|e| Error(context, e.into()))?;
+            if n_tasks == 0 {
+                return Err(Error(
+                    context,
+                    NotFound(format!("There is no task with id {}", id)).into(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn set_dependencies(&self, id: u32, depends_on: &[u32], context: &'static str) -> Result<()> {
+        diesel::delete(task_dependencies::table.filter(task_dependencies::task_id.eq(id as i32)))
+            .execute(&self.get_connection()?)
+            .map_err(|e| Error(context, e.into()))?;
+        let dependencies = depends_on
+            .iter()
+            .unique()
+            .map(|&dependency_id| TaskDependency {
+                task_id: id as i32,
+                dependency_id: dependency_id as i32,
+            })
+            .collect_vec();
+        diesel::insert_into(task_dependencies::table)
+            .values(&dependencies)
+            .execute(&self.get_connection()?)
+            .map_err(|e| Error(context, e.into()))?;
+        Ok(())
+    }
+
+    /// Deletes the given tasks for good, along with their dependencies and the
+    /// operations on them, all at once.
+    fn purge_tasks(&self, ids: &[i32], context: &'static str) -> Result<()> {
+        let connection = self.get_connection()?;
+        connection
+            .transaction::<_, diesel::result::Error, _>(|| {
+                // Nothing depends on these tasks anymore, which changes the
+                // tasks that did. Deleting the tasks takes their dependencies
+                // along, so mark them before.
+                let dependent_ids = task_dependencies::table
+                    .filter(task_dependencies::dependency_id.eq_any(ids))
+                    .select(task_dependencies::task_id)
+                    .load::<i32>(&connection)?;
+                diesel::update(task_table.filter(tasks::id.eq_any(dependent_ids)))
+                    .set(tasks::updated_at.eq(Utc::now()))
+                    .execute(&connection)?;
+                diesel::delete(operations::table.filter(operations::task_id.eq_any(ids)))
+                    .execute(&connection)?;
+                diesel::delete(task_table.filter(tasks::id.eq_any(ids))).execute(&connection)?;
+                Ok(())
+            })
+            .map_err(|e| Error(context, e.into()))
+    }
+
+    /// Returns the ids of the tasks each task depends on.
+    fn all_dependencies(&self) -> Result<HashMap<i32, Vec<u32>>> {
+        let dependencies = task_dependencies::table
+            .order((task_dependencies::task_id, task_dependencies::dependency_id))
+            .load::<TaskDependency>(&self.get_connection()?)
+            .map_err(|e| Error("while trying to retrieve task dependencies", e.into()))?;
+        Ok(dependencies
+            .into_iter()
+            .map(|dependency| (dependency.task_id, dependency.dependency_id as u32))
+            .into_group_map())
+    }
+
+    fn ensure_time_segment_exists(&self, id: u32, context: &'static str) -> Result<()> {
+        let n_time_segments = time_segment_table
+            .find(id as i32)
+            .count()
+            .get_result::<i64>(&self.get_connection()?)
+            .map_err(|e| Error(context, e.into()))?;
+        if n_time_segments == 0 {
+            return Err(Error(
+                context,
+                NotFound(format!("There is no time segment with id {}", id)).into(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn insert_ranges(
+        &self,
+        segment_id: i32,
+        ranges: &[std::ops::Range<DateTime<Utc>>],
+        context: &'static str,
+    ) -> Result<()> {
+        let ranges = ranges
+            .iter()
+            .map(|range| TimeSegmentRange {
+                segment_id,
+                start: range.start,
+                end: range.end,
+            })
+            .collect_vec();
+        diesel::insert_into(time_segment_range_table)
+            .values(&ranges)
+            .execute(&self.get_connection()?)
+            .map_err(|e| Error(context, e.into()))?;
+        Ok(())
+    }
+
+    fn with_tasks_to_do(
+        &self,
+        db_time_segments: Vec<TimeSegment>,
+    ) -> Result<Vec<(CrateTimeSegment, Vec<crate::Task>)>> {
+        let tasks = Task::belonging_to(&db_time_segments)
+            .filter(tasks::done_at.is_null())
+            .order(tasks::id)
+            .load::<Task>(&self.get_connection()?)
+            .map_err(|e| Error("while trying to retrieve tasks", e.into()))?
+            .grouped_by(&db_time_segments);
+        let mut dependencies = self.all_dependencies()?;
+        let tasks = tasks.into_iter().map(|db_tasks| {
+            db_tasks
+                .into_iter()
+                .map(|db_task| with_dependencies(db_task, &mut dependencies))
+                .collect()
+        });
+        Ok(self
+            .construct_time_segments(db_time_segments)?
+            .zip(tasks)
+            .collect())
+    }
+
+    fn construct_time_segments(
+        &self,
+        db_time_segments: Vec<TimeSegment>,
+    ) -> Result<impl Iterator<Item = CrateTimeSegment>> {
+        let ranges = TimeSegmentRange::belonging_to(&db_time_segments)
+            .order((time_segment_ranges::start, time_segment_ranges::end))
+            .load::<TimeSegmentRange>(&self.get_connection()?)
+            .map_err(|e| Error("while trying to retrieve time segments", e.into()))?
+            .grouped_by(&db_time_segments)
+            .into_iter()
+            .map(|ranges| ranges.into_iter().map(|range| range.start..range.end));
+        Ok(db_time_segments
+            .into_iter()
+            .zip(ranges)
+            .map(|(segment, ranges)| CrateTimeSegment {
+                id: segment.id as u32,
+                name: segment.name,
+                ranges: ranges.collect(),
+                start: segment.start,
+                period: i32_to_duration(segment.period),
+                hue: segment.hue as u16,
+                // Only strategies Eva wrote itself are stored
+                strategy: segment.strategy.and_then(|strategy| strategy.parse().ok()),
+            }))
+    }
+}
+
+impl From<crate::NewTask> for NewTask {
+    fn from(task: crate::NewTask) -> NewTask {
+        NewTask {
+            content: task.content,
+            deadline: task.deadline,
+            duration: task.duration.num_seconds() as i32,
+            importance: task.importance as i32,
+            time_segment_id: task.time_segment_id as i32,
+            confidence: confidence_to_i32(task.confidence),
+            // Tasks are only converted to be written
+            updated_at: Utc::now(),
+        }
+    }
+}
+
+impl From<Task> for crate::Task {
+    fn from(task: Task) -> crate::Task {
+        crate::Task {
+            id: task.id as u32,
+            content: task.content,
+            deadline: task.deadline,
+            duration: i32_to_duration(task.duration),
+            // Tasks may have been stored before their importance was validated
+            importance: crate::clamp_importance(task.importance),
+            confidence: i32_to_confidence(task.confidence),
+            time_segment_id: task.time_segment_id as u32,
+            // Dependencies are stored in a separate table
+            depends_on: vec![],
+            color: task.color.map(|color| color as u16),
+            scheduled_for: task.scheduled_for,
+            done_at: task.done_at,
+            pinned: task.pinned,
+        }
+    }
+}
+
+impl From<crate::Task> for Task {
+    fn from(task: crate::Task) -> Task {
+        Task {
+            id: task.id as i32,
+            content: task.content,
+            deadline: task.deadline,
+            duration: task.duration.num_seconds() as i32,
+            importance: task.importance as i32,
+            time_segment_id: task.time_segment_id as i32,
+            confidence: confidence_to_i32(task.confidence),
+            // Tasks are only converted to be written
+            updated_at: Utc::now(),
+            color: task.color.map(i32::from),
+            scheduled_for: task.scheduled_for,
+            done_at: task.done_at,
+            pinned: task.pinned,
+        }
+    }
+}
+
+impl From<Operation> for NewLoggedOperation {
+    fn from(operation: Operation) -> NewLoggedOperation {
+        let (kind, task, dependents) = match operation {
+            Operation::Add { task } => ("add", task, vec![]),
+            Operation::Delete { task, dependents } => ("delete", task, dependents),
+            Operation::Update { before } => ("update", before, vec![]),
+        };
+        let to_i32s = |ids: &[u32]| ids.iter().map(|&id| id as i32).collect();
+        NewLoggedOperation {
+            kind: kind.to_string(),
+            task_id: task.id as i32,
+            content: task.content,
+            deadline: task.deadline,
+            duration: task.duration.num_seconds() as i32,
+            importance: task.importance as i32,
+            time_segment_id: task.time_segment_id as i32,
+            confidence: confidence_to_i32(task.confidence),
+            color: task.color.map(i32::from),
+            scheduled_for: task.scheduled_for,
+            depends_on: to_i32s(&task.depends_on),
+            dependents: to_i32s(&dependents),
+            done_at: task.done_at,
+            pinned: task.pinned,
+        }
+    }
+}
+
+impl LoggedOperation {
+    fn into_operation(self) -> Result<Operation> {
+        let to_u32s = |ids: Vec<i32>| ids.into_iter().map(|id| id as u32).collect();
+        let task = crate::Task {
+            id: self.task_id as u32,
+            content: self.content,
+            deadline: self.deadline,
+            duration: i32_to_duration(self.duration),
+            importance: crate::clamp_importance(self.importance),
+            confidence: i32_to_confidence(self.confidence),
+            time_segment_id: self.time_segment_id as u32,
+            depends_on: to_u32s(self.depends_on),
+            color: self.color.map(|color| color as u16),
+            scheduled_for: self.scheduled_for,
+            done_at: self.done_at,
+            pinned: self.pinned,
+        };
+        match self.kind.as_str() {
+            "add" => Ok(Operation::Add { task }),
+            "delete" => Ok(Operation::Delete {
+                task,
+                dependents: to_u32s(self.dependents),
+            }),
+            "update" => Ok(Operation::Update { before: task }),
+            kind => Err(Error(
+                "while trying to read an operation",
+                format!("I don't know operations of kind {kind:?}").into(),
+            )),
+        }
+    }
+}
+
+impl From<CrateNewTimeSegment> for NewTimeSegment {
+    fn from(time_segment: CrateNewTimeSegment) -> NewTimeSegment {
+        NewTimeSegment {
+            name: time_segment.name,
+            start: time_segment.start,
+            period: time_segment.period.num_seconds() as i32,
+            hue: time_segment.hue as i32,
+            strategy: time_segment.strategy.map(|strategy| strategy.to_string()),
+        }
+    }
+}
+
+impl From<CrateTimeSegment> for TimeSegment {
+    fn from(time_segment: CrateTimeSegment) -> TimeSegment {
+        TimeSegment {
+            id: time_segment.id as i32,
+            name: time_segment.name,
+            start: time_segment.start,
+            period: time_segment.period.num_seconds() as i32,
+            hue: time_segment.hue as i32,
+            strategy: time_segment.strategy.map(|strategy| strategy.to_string()),
+        }
+    }
+}
+
+/// Connects to the database and brings its schema up to date if needed.
+pub fn make_connection(database_url: &str) -> Result<DbConnection> {
+    let connection = connect(database_url)?;
+    connection.run_pending_migrations()?;
+    Ok(connection)
+}
+
+/// Connects to the database without touching its schema. This is useful when
+/// the database is read-only, but then it's up to the caller to make sure it
+/// has been migrated before.
+pub fn connect(database_url: &str) -> Result<DbConnection> {
+    let connection_manager = r2d2::ConnectionManager::new(database_url);
+    let connection_pool = r2d2::Pool::builder()
+        .max_size(1)
+        // Don't keep a user waiting for a server which isn't there
+        .connection_timeout(std::time::Duration::from_secs(5))
+        .build(connection_manager)
+        .map_err(|e| Error("while trying to connect to the database", e.into()))?;
+    Ok(DbConnection(connection_pool))
+}
+
+fn with_dependencies(db_task: Task, dependencies: &mut HashMap<i32, Vec<u32>>) -> crate::Task {
+    let depends_on = dependencies.remove(&db_task.id).unwrap_or_default();
+    crate::Task {
+        depends_on,
+        ..crate::Task::from(db_task)
+    }
+}
+
+fn i32_to_duration(duration: i32) -> Duration {
+    Duration::seconds(i64::from(duration))
+}
+
+fn confidence_to_i32(confidence: Confidence) -> i32 {
+    match confidence {
+        Confidence::Low => 0,
+        Confidence::Medium => 1,
+        Confidence::High => 2,
+    }
+}
+
+fn i32_to_confidence(confidence: i32) -> Confidence {
+    match confidence {
+        0 => Confidence::Low,
+        1 => Confidence::Medium,
+        _ => Confidence::High,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Once;
+
+    use futures_test::test;
+
+    use super::*;
+    use crate::configuration::SchedulingStrategy;
+
+    static MIGRATIONS: Once = Once::new();
+
+    /// Connects to the database in `EVA_POSTGRES_TEST_URL`, or returns None to
+    /// skip the test when it isn't set. Everything happens in a transaction
+    /// which is never committed, so the tests don't see each other's changes
+    /// and leave the database as they found it.
+    fn test_connection() -> Option<DbConnection> {
+        let database_url = std::env::var("EVA_POSTGRES_TEST_URL").ok()?;
+        let connection = connect(&database_url).unwrap();
+        MIGRATIONS.call_once(|| {
+            connection.run_pending_migrations().unwrap();
+        });
+        connection
+            .get_connection()
+            .unwrap()
+            .begin_test_transaction()
+            .unwrap();
+        Some(connection)
+    }
+
+    #[test]
+    async fn test_insert_query_and_delete_single_task() {
+        let Some(connection) = test_connection() else {
+            return;
+        };
+        let n_tasks = connection.count_tasks().await.unwrap();
+
+        // Inserting a task and querying for it, returns the same one
+        let new_task = test_task();
+        let task = connection.add_task(new_task.clone()).await.unwrap();
+        assert_eq!(task, new_task);
+        assert_eq!(connection.count_tasks().await.unwrap(), n_tasks + 1);
+        assert_eq!(connection.all_tasks().await.unwrap().pop().unwrap(), task);
+        assert_eq!(connection.get_task(task.id).await.unwrap(), task);
+
+        // Deleting a task removes it again
+        connection.delete_task(task.id).await.unwrap();
+        assert!(connection
+            .get_task(task.id)
+            .await
+            .unwrap_err()
+            .is_not_found());
+        assert_eq!(connection.count_tasks().await.unwrap(), n_tasks);
+    }
+
+    #[test]
+    async fn test_insert_update_query_single_task() {
+        let Some(connection) = test_connection() else {
+            return;
+        };
+        let mut task = connection.add_task(test_task()).await.unwrap();
+
+        // Timestamps keep their microseconds
+        task.content = "stuff".to_string();
+        task.deadline =
+            Utc.with_ymd_and_hms(2015, 9, 5, 23, 56, 4).unwrap() + Duration::microseconds(123_456);
+        task.duration = Duration::minutes(7);
+        task.importance = 10;
+        task.color = Some(200);
+        connection.update_task(task.clone()).await.unwrap();
+        assert_eq!(connection.get_task(task.id).await.unwrap(), task);
+
+        // Colors can be cleared again
+        task.color = None;
+        connection.update_task(task.clone()).await.unwrap();
+        assert_eq!(connection.get_task(task.id).await.unwrap().color, None);
+
+        // Tasks can't be moved to a non-existent time segment
+        task.time_segment_id = 4242;
+        let result = connection.update_task(task).await;
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "A database error occurred while trying to update a task: There is no time segment \
+             with id 4242"
+        );
+    }
+
+    #[test]
+    async fn test_update_task_fields() {
+        let Some(connection) = test_connection() else {
+            return;
+        };
+        let task = connection.add_task(test_task()).await.unwrap();
+        let mut changed_elsewhere = task.clone();
+        changed_elsewhere.content = "do me first".to_string();
+        connection
+            .update_task(changed_elsewhere.clone())
+            .await
+            .unwrap();
+
+        let changes = crate::TaskChanges {
+            importance: Some(7),
+            color: Some(Some(120)),
+            pinned: Some(true),
+            ..Default::default()
+        };
+        let updated = connection
+            .update_task_fields(task.id, changes)
+            .await
+            .unwrap();
+        assert_eq!(
+            updated,
+            crate::Task {
+                importance: 7,
+                color: Some(120),
+                pinned: true,
+                ..changed_elsewhere
+            }
+        );
+        assert_eq!(connection.get_task(task.id).await.unwrap(), updated);
+        assert!(connection
+            .update_task_fields(task.id + 42, crate::TaskChanges::default())
+            .await
+            .unwrap_err()
+            .is_not_found());
+    }
+
+    #[test]
+    async fn test_bulk_add_tasks() {
+        let Some(connection) = test_connection() else {
+            return;
+        };
+        let prerequisite = connection.add_task(test_task()).await.unwrap();
+        let mut new_tasks = (1..=500)
+            .map(|number| crate::NewTask {
+                content: format!("task {}", number),
+                ..test_task()
+            })
+            .collect_vec();
+        new_tasks[499].depends_on = vec![prerequisite.id];
+        let tasks = connection.bulk_add_tasks(new_tasks.clone()).await.unwrap();
+
+        assert_eq!(tasks.len(), 500);
+        assert_eq!(connection.count_tasks().await.unwrap(), 501);
+        for (task, new_task) in tasks.iter().zip(&new_tasks) {
+            assert_eq!(task, new_task);
+        }
+        assert_eq!(tasks[499].depends_on, [prerequisite.id]);
+    }
+
+    #[test]
+    async fn test_task_dependencies() {
+        let Some(connection) = test_connection() else {
+            return;
+        };
+        let first = connection.add_task(test_task()).await.unwrap();
+        let second = connection.add_task(test_task()).await.unwrap();
+
+        // Dependencies are stored alongside the task
+        let mut new_task = test_task();
+        new_task.depends_on = vec![second.id, first.id, second.id];
+        let mut task = connection.add_task(new_task).await.unwrap();
+        assert_eq!(task.depends_on, [first.id, second.id]);
+        assert_eq!(
+            connection
+                .get_tasks(&[task.id, first.id, task.id])
+                .await
+                .unwrap(),
+            [task.clone(), first.clone(), task.clone()]
+        );
+
+        // Tasks can only depend on existing tasks
+        task.depends_on = vec![second.id + 42];
+        assert!(connection
+            .update_task(task.clone())
+            .await
+            .unwrap_err()
+            .is_not_found());
+
+        // Deleting a prerequisite removes the dependency and changes the tasks
+        // which depended on it
+        task.depends_on = vec![second.id];
+        connection.update_task(task.clone()).await.unwrap();
+        let since = Utc::now();
+        connection.delete_task(second.id).await.unwrap();
+        assert!(connection
+            .get_task(task.id)
+            .await
+            .unwrap()
+            .depends_on
+            .is_empty());
+        let updated_ids = connection
+            .tasks_updated_since(since)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|task| task.id)
+            .collect::<Vec<_>>();
+        assert_eq!(updated_ids, [task.id]);
+    }
+
+    #[test]
+    async fn test_log_operations() {
+        let Some(connection) = test_connection() else {
+            return;
+        };
+        assert_eq!(connection.pop_operation().await.unwrap(), None);
+        let mut task = connection.add_task(test_task()).await.unwrap();
+        let first = Operation::Add { task: task.clone() };
+        task.depends_on = vec![2, 3];
+        task.color = Some(120);
+        let deleted = Operation::Delete {
+            task: task.clone(),
+            dependents: vec![4],
+        };
+        let updated = Operation::Update { before: task };
+        connection.log_operation(first.clone()).await.unwrap();
+        connection.log_operation(deleted.clone()).await.unwrap();
+        connection.log_operation(updated.clone()).await.unwrap();
+        assert_eq!(
+            connection.operations().await.unwrap(),
+            [first.clone(), deleted.clone(), updated.clone()]
+        );
+
+        // The last operation comes out first
+        assert_eq!(connection.pop_operation().await.unwrap(), Some(updated));
+        assert_eq!(connection.pop_operation().await.unwrap(), Some(deleted));
+
+        // Only the last operations are remembered
+        for _ in 0..MAX_LOGGED_OPERATIONS {
+            connection.log_operation(first.clone()).await.unwrap();
+        }
+        assert_eq!(
+            connection.operations().await.unwrap().len(),
+            MAX_LOGGED_OPERATIONS
+        );
+        for _ in 0..MAX_LOGGED_OPERATIONS {
+            assert_eq!(
+                connection.pop_operation().await.unwrap(),
+                Some(first.clone())
+            );
+        }
+        assert_eq!(connection.pop_operation().await.unwrap(), None);
+    }
+
+    #[test]
+    async fn test_restore_task() {
+        let Some(connection) = test_connection() else {
+            return;
+        };
+        let prerequisite = connection.add_task(test_task()).await.unwrap();
+        let mut new_task = test_task();
+        new_task.depends_on = vec![prerequisite.id];
+        let task = connection.add_task(new_task).await.unwrap();
+        connection.delete_task(task.id).await.unwrap();
+
+        connection.restore_task(task.clone()).await.unwrap();
+        assert_eq!(connection.get_task(task.id).await.unwrap(), task);
+        // A task can't be restored over another one
+        assert!(connection.restore_task(task).await.is_err());
+    }
+
+    #[test]
+    async fn test_search_tasks() {
+        let Some(connection) = test_connection() else {
+            return;
+        };
+        let mut tasks = vec![];
+        for content in [
+            "Call the dentist",
+            "Pay the DENTIST",
+            "Buy 100% cotton socks",
+            "Rename my_file",
+            "Walk the dog",
+        ] {
+            let mut task = test_task();
+            task.content = content.to_string();
+            tasks.push(connection.add_task(task).await.unwrap());
+        }
+
+        let search = |query| connection.search_tasks(query);
+        assert_eq!(search("dentist").await.unwrap(), tasks[0..2]);
+        assert_eq!(search("the do").await.unwrap(), [tasks[4].clone()]);
+        // Wildcards in the query only match themselves
+        assert_eq!(search("%").await.unwrap(), [tasks[2].clone()]);
+        assert_eq!(search("y_f").await.unwrap(), [tasks[3].clone()]);
+        assert!(search("e_t").await.unwrap().is_empty());
+        assert!(search("cat").await.unwrap().is_empty());
+    }
+
+    #[test]
+    async fn test_tasks_in_deadline_range() {
+        let Some(connection) = test_connection() else {
+            return;
+        };
+        let mut tasks = vec![];
+        let start = test_task().deadline;
+        let day = |n| start + Duration::days(n);
+        for days in 1..4 {
+            let mut task = test_task();
+            task.deadline = day(days);
+            tasks.push(connection.add_task(task).await.unwrap());
+        }
+
+        let due = |from, to| connection.tasks_in_deadline_range(from, to);
+        assert_eq!(due(None, None).await.unwrap(), tasks);
+        assert_eq!(due(Some(day(2)), None).await.unwrap(), tasks[1..]);
+        assert_eq!(due(None, Some(day(2))).await.unwrap(), tasks[..1]);
+        assert_eq!(due(Some(day(1)), Some(day(3))).await.unwrap(), tasks[..2]);
+        // Only a task which is due right at the start of the range is in it
+        let just_after = day(1) + Duration::milliseconds(1);
+        assert_eq!(due(Some(just_after), None).await.unwrap(), tasks[1..]);
+        assert!(due(Some(day(2)), Some(day(2))).await.unwrap().is_empty());
+    }
+
+    #[test]
+    async fn test_tasks_page() {
+        let Some(connection) = test_connection() else {
+            return;
+        };
+        let mut tasks = vec![];
+        for (days, importance) in [(3, 2), (1, 2), (2, 9)] {
+            let mut task = test_task();
+            task.deadline += Duration::days(days);
+            task.importance = importance;
+            tasks.push(connection.add_task(task).await.unwrap());
+        }
+
+        let page = |order, limit, offset| connection.tasks_page(order, limit, offset);
+        assert_eq!(page(TaskOrder::Added, 2, 0).await.unwrap(), tasks[0..2]);
+        // Asking for more tasks than are left just returns those
+        assert_eq!(page(TaskOrder::Added, 10, 1).await.unwrap(), tasks[1..]);
+        assert!(page(TaskOrder::Added, 10, 3).await.unwrap().is_empty());
+        let ids = |tasks: Vec<crate::Task>| tasks.iter().map(|task| task.id).collect::<Vec<_>>();
+        assert_eq!(
+            ids(page(TaskOrder::Deadline, 10, 0).await.unwrap()),
+            [tasks[1].id, tasks[2].id, tasks[0].id]
+        );
+        // Equally important tasks stay in the order they were added
+        assert_eq!(
+            ids(page(TaskOrder::Importance, 2, 1).await.unwrap()),
+            [tasks[0].id, tasks[1].id]
+        );
+    }
+
+    #[test]
+    async fn test_complete_and_clear_tasks() {
+        let Some(connection) = test_connection() else {
+            return;
+        };
+        let first = connection.add_task(test_task()).await.unwrap();
+        let second = connection.add_task(test_task()).await.unwrap();
+        let mut third = test_task();
+        third.depends_on = vec![first.id];
+        let third = connection.add_task(third).await.unwrap();
+        let last_week = Utc.with_ymd_and_hms(2019, 8, 1, 9, 0, 0).unwrap();
+        let yesterday = last_week + Duration::days(6);
+        connection.complete_task(first.id, last_week).await.unwrap();
+        connection
+            .complete_task(second.id, yesterday)
+            .await
+            .unwrap();
+        connection
+            .log_operation(Operation::Update {
+                before: first.clone(),
+            })
+            .await
+            .unwrap();
+
+        // Tasks which are done are kept, but they aren't listed anymore
+        assert_eq!(
+            connection.get_task(first.id).await.unwrap().done_at,
+            Some(last_week)
+        );
+        assert_eq!(
+            connection.all_tasks().await.unwrap(),
+            std::slice::from_ref(&third)
+        );
+        assert_eq!(connection.count_tasks().await.unwrap(), 1);
+        let (_, tasks) = connection
+            .all_tasks_per_time_segment()
+            .await
+            .unwrap()
+            .remove(0);
+        assert_eq!(tasks, std::slice::from_ref(&third));
+        assert!(connection
+            .complete_task(42, yesterday)
+            .await
+            .unwrap_err()
+            .is_not_found());
+
+        // Only the tasks which were done before the given moment are cleared,
+        // along with the dependencies and operations on them
+        assert_eq!(
+            connection.clear_completed(Some(yesterday)).await.unwrap(),
+            1
+        );
+        assert!(connection
+            .get_task(first.id)
+            .await
+            .unwrap_err()
+            .is_not_found());
+        assert!(connection
+            .get_task(third.id)
+            .await
+            .unwrap()
+            .depends_on
+            .is_empty());
+        assert!(connection.operations().await.unwrap().is_empty());
+        assert_eq!(connection.clear_completed(None).await.unwrap(), 1);
+        assert!(connection
+            .get_task(second.id)
+            .await
+            .unwrap_err()
+            .is_not_found());
+        assert_eq!(connection.clear_completed(None).await.unwrap(), 0);
+    }
+
+    #[test]
+    async fn test_set_scheduled_for() {
+        let Some(connection) = test_connection() else {
+            return;
+        };
+        let first = connection.add_task(test_task()).await.unwrap();
+        let second = connection.add_task(test_task()).await.unwrap();
+        let when = Utc::now().with_nanosecond(0).unwrap() + Duration::hours(1);
+        connection
+            .set_scheduled_for(&[(first.id, when), (second.id, when)])
+            .await
+            .unwrap();
+        assert_eq!(
+            connection.get_task(first.id).await.unwrap().scheduled_for,
+            Some(when)
+        );
+
+        // Tasks which are left out aren't planned anymore, and persisting a
+        // schedule isn't a change to the tasks
+        let since = Utc::now();
+        connection
+            .set_scheduled_for(&[(second.id, when)])
+            .await
+            .unwrap();
+        assert_eq!(
+            connection.get_task(first.id).await.unwrap().scheduled_for,
+            None
+        );
+        assert!(connection
+            .tasks_updated_since(since)
+            .await
+            .unwrap()
+            .is_empty());
+
+        // Nothing is persisted if any of the tasks doesn't exist
+        let error = connection
+            .set_scheduled_for(&[(first.id, when), (second.id + 42, when)])
+            .await
+            .unwrap_err();
+        assert!(error.is_not_found());
+        assert_eq!(
+            connection.get_task(first.id).await.unwrap().scheduled_for,
+            None
+        );
+    }
+
+    #[test]
+    async fn test_default_time_segment() {
+        let Some(connection) = test_connection() else {
+            return;
+        };
+        let time_segment = connection.get_time_segment(0).await.unwrap();
+        assert_eq!(time_segment.name, "Default");
+        assert_eq!(time_segment.ranges.len(), 1);
+        assert_eq!(time_segment.period, Duration::days(1));
+        assert_eq!(time_segment.start, time_segment.ranges[0].start);
+        assert_eq!(
+            time_segment.ranges[0].end - time_segment.ranges[0].start,
+            Duration::hours(8)
+        );
+        assert!(time_segment.hue < 360);
+
+        // We shouldn't be able to delete the last time segment
+        for other in connection.all_time_segments().await.unwrap() {
+            if other.id != 0 {
+                diesel::delete(time_segment_table.find(other.id as i32))
+                    .execute(&connection.get_connection().unwrap())
+                    .unwrap();
+            }
+        }
+        let error = connection
+            .delete_time_segment(time_segment)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.segment_constraint(),
+            Some(&SegmentConstraintError::LastTimeSegment)
+        );
+    }
+
+    #[test]
+    async fn test_move_all_tasks_between_time_segments() {
+        let Some(connection) = test_connection() else {
+            return;
+        };
+        let from = connection
+            .add_time_segment(test_time_segment())
+            .await
+            .unwrap();
+        let mut new_task = test_task();
+        new_task.time_segment_id = from.id;
+        for _ in 0..3 {
+            connection.add_task(new_task.clone()).await.unwrap();
+        }
+        let staying = connection.add_task(test_task()).await.unwrap();
+
+        assert_eq!(
+            connection
+                .move_tasks_between_segments(from.id, 0)
+                .await
+                .unwrap(),
+            3
+        );
+        let tasks = connection.all_tasks().await.unwrap();
+        assert_eq!(tasks.len(), 4);
+        assert!(tasks.iter().all(|task| task.time_segment_id == 0));
+        assert_eq!(connection.get_task(staying.id).await.unwrap(), staying);
+        // The source segment is now empty, so it can be deleted
+        connection.delete_time_segment(from.clone()).await.unwrap();
+
+        assert!(connection
+            .move_tasks_between_segments(0, from.id)
+            .await
+            .unwrap_err()
+            .is_not_found());
+    }
+
+    #[test]
+    async fn test_delete_default_time_segment_once_replaced() {
+        let Some(connection) = test_connection() else {
+            return;
+        };
+        let default = connection.get_time_segment(0).await.unwrap();
+        let replacement = connection
+            .add_time_segment(test_time_segment())
+            .await
+            .unwrap();
+        let task = connection.add_task(test_task()).await.unwrap();
+
+        // The default time segment's tasks would be stranded without it
+        let error = connection
+            .delete_time_segment(default.clone())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.segment_constraint(),
+            Some(&SegmentConstraintError::SegmentNotEmpty { remaining_tasks: 1 })
+        );
+
+        // Once they've moved, it can go like any other time segment
+        connection
+            .move_tasks_between_segments(0, replacement.id)
+            .await
+            .unwrap();
+        connection.delete_time_segment(default).await.unwrap();
+        let time_segments = connection.all_time_segments().await.unwrap();
+        assert_eq!(time_segments.len(), 1);
+        assert_eq!(time_segments[0], replacement);
+        // The last time segment has to stay, even without tasks
+        connection.delete_task(task.id).await.unwrap();
+        let error = connection
+            .delete_time_segment(replacement)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.segment_constraint(),
+            Some(&SegmentConstraintError::LastTimeSegment)
+        );
+    }
+
+    #[test]
+    async fn test_tasks_per_nonempty_segment() {
+        let Some(connection) = test_connection() else {
+            return;
+        };
+        let populated = connection
+            .add_time_segment(test_time_segment())
+            .await
+            .unwrap();
+        let _empty = connection
+            .add_time_segment(test_time_segment())
+            .await
+            .unwrap();
+        let only_done = connection
+            .add_time_segment(test_time_segment())
+            .await
+            .unwrap();
+        let mut in_populated = test_task();
+        in_populated.time_segment_id = populated.id;
+        let in_populated = connection.add_task(in_populated).await.unwrap();
+        let in_default = connection.add_task(test_task()).await.unwrap();
+        // Segments with only tasks which are done count as empty
+        let mut done = test_task();
+        done.time_segment_id = only_done.id;
+        let done = connection.add_task(done).await.unwrap();
+        connection.complete_task(done.id, Utc::now()).await.unwrap();
+
+        assert_eq!(
+            connection.all_tasks_per_time_segment().await.unwrap().len(),
+            4
+        );
+        let tasks_per_segment = connection.tasks_per_nonempty_segment().await.unwrap();
+        assert_eq!(
+            tasks_per_segment
+                .iter()
+                .map(|(segment, tasks)| (segment.id, tasks.clone()))
+                .collect::<Vec<_>>(),
+            [(0, vec![in_default]), (populated.id, vec![in_populated])]
+        );
+        assert_eq!(tasks_per_segment[1].0, populated);
+    }
+
+    #[test]
+    async fn test_insert_query_update_and_delete_time_segment() {
+        let Some(connection) = test_connection() else {
+            return;
+        };
+        let time_segment = test_time_segment();
+        let mut added = connection
+            .add_time_segment(time_segment.clone())
+            .await
+            .unwrap();
+        assert_eq!(added, time_segment);
+        assert_eq!(connection.get_time_segment(added.id).await.unwrap(), added);
+        assert_eq!(
+            connection.all_time_segments().await.unwrap().pop().unwrap(),
+            added
+        );
+
+        // We should be able to query a task we add to a certain segment
+        let mut task = test_task();
+        task.time_segment_id = added.id;
+        let added_task = connection.add_task(task.clone()).await.unwrap();
+        let (segment, tasks) = connection
+            .all_tasks_per_time_segment()
+            .await
+            .unwrap()
+            .pop()
+            .unwrap();
+        assert_eq!(segment, added);
+        assert_eq!(tasks, [task]);
+
+        let start = Utc::now().with_nanosecond(0).unwrap() + Duration::days(1);
+        added.name = "changed name".to_string();
+        added.start = start;
+        added.ranges = vec![
+            start..start + Duration::minutes(3),
+            start + Duration::hours(1)..start + Duration::hours(2),
+        ];
+        added.period = Duration::minutes(42);
+        added.hue = 200;
+        added.strategy = Some(SchedulingStrategy::Shuffled { seed: 7 });
+        connection.update_time_segment(added.clone()).await.unwrap();
+        assert_eq!(connection.get_time_segment(added.id).await.unwrap(), added);
+
+        connection
+            .rename_time_segment(added.id, "renamed")
+            .await
+            .unwrap();
+        added.name = "renamed".to_string();
+        assert_eq!(connection.get_time_segment(added.id).await.unwrap(), added);
+
+        // We shouldn't be able to delete the segment while there's still a
+        // task in it
+        let error = connection
+            .delete_time_segment(added.clone())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.segment_constraint(),
+            Some(&SegmentConstraintError::SegmentNotEmpty { remaining_tasks: 1 })
+        );
+        connection.delete_task(added_task.id).await.unwrap();
+        connection.delete_time_segment(added.clone()).await.unwrap();
+        assert!(connection
+            .get_time_segment(added.id)
+            .await
+            .unwrap_err()
+            .is_not_found());
+    }
+
+    #[test]
+    async fn test_migrations_only_run_when_needed() {
+        let Some(connection) = test_connection() else {
+            return;
+        };
+        assert!(connection.run_pending_migrations().unwrap().is_empty());
+    }
+
+    fn test_task() -> crate::NewTask {
+        crate::NewTask {
+            content: "do me".to_string(),
+            deadline: Utc::now().with_nanosecond(0).unwrap(),
+            duration: Duration::seconds(6),
+            importance: 4,
+            confidence: Confidence::Low,
+            time_segment_id: 0,
+            depends_on: vec![],
+        }
+    }
+
+    fn test_time_segment() -> CrateNewTimeSegment {
+        let start = Utc::now().with_nanosecond(0).unwrap();
+        CrateNewTimeSegment {
+            name: "2h weekly".to_string(),
+            ranges: vec![start..start + Duration::hours(2)],
+            start,
+            period: Duration::weeks(1),
+            hue: 0,
+            strategy: None,
+        }
+    }
+}
@@ -1,100 +1,209 @@
-use std::io;
+use std::collections::HashMap;
 
 use async_trait::async_trait;
 use chrono::prelude::*;
 use chrono::Duration;
+use diesel::connection::{SimpleConnection, TransactionManager};
 use diesel::prelude::*;
 use diesel::r2d2;
+use diesel_migrations::MigrationConnection;
+use itertools::Itertools;
+use log::debug;
 
 use super::Database;
-use super::{Error, Result};
+use super::MAX_LOGGED_OPERATIONS;
+use super::{
+    like_pattern, unexpected_amount, Error, NotFound, Result, SegmentConstraintError, TaskOrder,
+};
 use crate::time_segment::{
     NamedTimeSegment as CrateTimeSegment, NewNamedTimeSegment as CrateNewTimeSegment,
 };
+use crate::{Confidence, Operation};
 
 use self::tasks::dsl::tasks as task_table;
 use self::time_segment_ranges::dsl::time_segment_ranges as time_segment_range_table;
 use self::time_segments::dsl::time_segments as time_segment_table;
 
-pub struct DbConnection(r2d2::Pool<r2d2::ConnectionManager<SqliteConnection>>);
+pub struct DbConnection(
+    r2d2::Pool<r2d2::ConnectionManager<SqliteConnection>>,
+    DbConfig,
+);
 
-#[derive(Debug, Clone, PartialEq, Queryable, Identifiable, AsChangeset, Associations)]
+#[derive(
+    Debug, Clone, PartialEq, Queryable, Insertable, Identifiable, AsChangeset, Associations,
+)]
 #[belongs_to(TimeSegment)]
 #[table_name = "tasks"]
+// Clearing the color of a task should clear it in the database too
+#[changeset_options(treat_none_as_null = "true")]
 struct Task {
     pub id: i32,
     pub content: String,
-    pub deadline: i32,
+    pub deadline: i64,
     pub duration: i32,
     pub importance: i32,
     pub time_segment_id: i32,
+    pub confidence: i32,
+    pub updated_at: i64,
+    pub color: Option<i32>,
+    pub scheduled_for: Option<i64>,
+    pub done_at: Option<i64>,
+    pub pinned: bool,
 }
 
 #[derive(Debug, Insertable)]
 #[table_name = "tasks"]
 struct NewTask {
     pub content: String,
-    pub deadline: i32,
+    pub deadline: i64,
     pub duration: i32,
     pub importance: i32,
     pub time_segment_id: i32,
+    pub confidence: i32,
+    pub updated_at: i64,
 }
 
 table! {
     tasks (id) {
         id -> Integer,
         content -> Text,
-        deadline -> Integer,
+        deadline -> BigInt,
         duration -> Integer,
         importance -> Integer,
         time_segment_id -> Integer,
+        confidence -> Integer,
+        updated_at -> BigInt,
+        color -> Nullable<Integer>,
+        scheduled_for -> Nullable<BigInt>,
+        done_at -> Nullable<BigInt>,
+        pinned -> Bool,
     }
 }
 
 #[derive(Debug, Queryable, Identifiable, AsChangeset)]
 #[table_name = "time_segments"]
+// Clearing the strategy of a time segment should clear it in the database too
+#[changeset_options(treat_none_as_null = "true")]
 struct TimeSegment {
     pub id: i32,
     pub name: String,
-    pub start: i32,
+    pub start: i64,
     pub period: i32,
     pub hue: i32,
+    pub strategy: Option<String>,
 }
 
 #[derive(Debug, Insertable)]
 #[table_name = "time_segments"]
 struct NewTimeSegment {
     pub name: String,
-    pub start: i32,
+    pub start: i64,
     pub period: i32,
     pub hue: i32,
+    pub strategy: Option<String>,
 }
 
 table! {
     time_segments (id) {
         id -> Integer,
         name -> VarChar,
-        start -> Integer,
+        start -> BigInt,
         period -> Integer,
         hue -> Integer,
+        strategy -> Nullable<VarChar>,
     }
 }
 
+// For the subqueries which look for time segments with tasks
+allow_tables_to_appear_in_same_query!(tasks, time_segments);
+
 #[derive(Debug, Insertable, Queryable, Identifiable, Associations)]
 #[belongs_to(TimeSegment, foreign_key = "segment_id")]
 #[table_name = "time_segment_ranges"]
 #[primary_key(start)]
 struct TimeSegmentRange {
     pub segment_id: i32,
-    pub start: i32,
-    pub end: i32,
+    pub start: i64,
+    pub end: i64,
 }
 
 table! {
     time_segment_ranges (start) {
         segment_id -> Integer,
-        start -> Integer,
-        end -> Integer,
+        start -> BigInt,
+        end -> BigInt,
+    }
+}
+
+#[derive(Debug, Insertable, Queryable)]
+#[table_name = "task_dependencies"]
+struct TaskDependency {
+    pub task_id: i32,
+    pub dependency_id: i32,
+}
+
+table! {
+    task_dependencies (task_id, dependency_id) {
+        task_id -> Integer,
+        dependency_id -> Integer,
+    }
+}
+
+#[derive(Debug, Queryable)]
+struct LoggedOperation {
+    pub id: i32,
+    pub kind: String,
+    pub task_id: i32,
+    pub content: String,
+    pub deadline: i64,
+    pub duration: i32,
+    pub importance: i32,
+    pub time_segment_id: i32,
+    pub confidence: i32,
+    pub color: Option<i32>,
+    pub scheduled_for: Option<i64>,
+    pub depends_on: String,
+    pub dependents: String,
+    pub done_at: Option<i64>,
+    pub pinned: bool,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "operations"]
+struct NewLoggedOperation {
+    pub kind: String,
+    pub task_id: i32,
+    pub content: String,
+    pub deadline: i64,
+    pub duration: i32,
+    pub importance: i32,
+    pub time_segment_id: i32,
+    pub confidence: i32,
+    pub color: Option<i32>,
+    pub scheduled_for: Option<i64>,
+    pub depends_on: String,
+    pub dependents: String,
+    pub done_at: Option<i64>,
+    pub pinned: bool,
+}
+
+table! {
+    operations (id) {
+        id -> Integer,
+        kind -> VarChar,
+        task_id -> Integer,
+        content -> Text,
+        deadline -> BigInt,
+        duration -> Integer,
+        importance -> Integer,
+        time_segment_id -> Integer,
+        confidence -> Integer,
+        color -> Nullable<Integer>,
+        scheduled_for -> Nullable<BigInt>,
+        depends_on -> VarChar,
+        dependents -> VarChar,
+        done_at -> Nullable<BigInt>,
+        pinned -> Bool,
     }
 }
 
@@ -105,13 +214,28 @@ no_arg_sql_function!(last_insert_rowid, diesel::sql_types::Integer);
 #[async_trait(?Send)]
 impl Database for DbConnection {
     async fn add_task(&self, task: crate::NewTask) -> Result<crate::Task> {
-        diesel::insert_into(task_table)
-            .values(&NewTask::from(task))
-            .execute(&self.get_connection()?)
-            .map_err(|e| Error("while trying to add a task", e.into()))?;
-        let id = diesel::select(last_insert_rowid)
-            .get_result::<i32>(&self.get_connection()?)
-            .map_err(|e| Error("while trying to fetch the id of the new task", e.into()))?;
+        self.ensure_time_segment_exists(task.time_segment_id, "while trying to add a task")?;
+        self.ensure_tasks_exist(&task.depends_on, "while trying to add a task")?;
+        // The id of the new task is only known to the connection which added
+        // it, and only until it inserts something else
+        let id = self
+            .with_transaction(|connection| {
+                diesel::insert_into(task_table)
+                    .values(&NewTask::from(task.clone()))
+                    .execute(connection)
+                    .map_err(|e| Error("while trying to add a task", e.into()))?;
+                let id = diesel::select(last_insert_rowid)
+                    .get_result::<i32>(connection)
+                    .map_err(|e| Error("while trying to fetch the id of the new task", e.into()))?;
+                set_dependencies(
+                    connection,
+                    id as u32,
+                    &task.depends_on,
+                    "while trying to add a task",
+                )?;
+                Ok(id)
+            })
+            .await?;
         let task = self
             .get_task(id as u32)
             .await
@@ -119,172 +243,703 @@ impl Database for DbConnection {
         Ok(task)
     }
 
+    async fn bulk_add_tasks(&self, tasks: Vec<crate::NewTask>) -> Result<Vec<crate::Task>> {
+        let context = "while trying to add tasks";
+        for time_segment_id in tasks.iter().map(|task| task.time_segment_id).unique() {
+            self.ensure_time_segment_exists(time_segment_id, context)?;
+        }
+        let dependency_ids = tasks
+            .iter()
+            .flat_map(|task| task.depends_on.iter().copied())
+            .unique()
+            .collect_vec();
+        self.ensure_tasks_exist(&dependency_ids, context)?;
+        if tasks.is_empty() {
+            return Ok(vec![]);
+        }
+        let new_tasks = tasks.iter().cloned().map(NewTask::from).collect_vec();
+        // No other connection can add tasks during the transaction, so the new
+        // tasks get the ids leading up to the last one
+        let first_id = self
+            .with_transaction(|connection| {
+                diesel::insert_into(task_table)
+                    .values(&new_tasks)
+                    .execute(connection)
+                    .map_err(|e| Error(context, e.into()))?;
+                let last_id = diesel::select(last_insert_rowid)
+                    .get_result::<i32>(connection)
+                    .map_err(|e| {
+                        Error("while trying to fetch the ids of the new tasks", e.into())
+                    })?;
+                let first_id = last_id - tasks.len() as i32 + 1;
+                let dependencies = tasks
+                    .iter()
+                    .zip(first_id..)
+                    .flat_map(|(task, id)| {
+                        task.depends_on
+                            .iter()
+                            .unique()
+                            .map(move |&dependency_id| TaskDependency {
+                                task_id: id,
+                                dependency_id: dependency_id as i32,
+                            })
+                    })
+                    .collect_vec();
+                diesel::insert_into(task_dependencies::table)
+                    .values(&dependencies)
+                    .execute(connection)
+                    .map_err(|e| Error(context, e.into()))?;
+                Ok(first_id)
+            })
+            .await?;
+        let ids = (first_id..)
+            .take(tasks.len())
+            .map(|id| id as u32)
+            .collect_vec();
+        self.get_tasks(&ids)
+            .await
+            .map_err(|e| Error("while trying to fetch the newly created tasks", e.into()))
+    }
+
     async fn delete_task(&self, id: u32) -> Result<()> {
-        let amount_deleted = diesel::delete(task_table.find(id as i32))
+        let context = "while trying to delete a task";
+        self.with_transaction(|connection| {
+            let amount_deleted = diesel::delete(task_table.find(id as i32))
+                .execute(connection)
+                .map_err(|e| Error(context, e.into()))?;
+            if amount_deleted != 1 {
+                return Err(Error(
+                    context,
+                    unexpected_amount(
+                        amount_deleted,
+                        format!("{} task(s) were deleted", amount_deleted),
+                    ),
+                ));
+            }
+            // Nothing depends on this task anymore, which changes the tasks that did
+            let dependent_ids = task_dependencies::table
+                .filter(task_dependencies::dependency_id.eq(id as i32))
+                .select(task_dependencies::task_id)
+                .load::<i32>(connection)
+                .map_err(|e| Error(context, e.into()))?;
+            diesel::update(task_table.filter(tasks::id.eq_any(dependent_ids)))
+                .set(tasks::updated_at.eq(Utc::now().timestamp()))
+                .execute(connection)
+                .map_err(|e| Error(context, e.into()))?;
+            diesel::delete(
+                task_dependencies::table.filter(
+                    task_dependencies::task_id
+                        .eq(id as i32)
+                        .or(task_dependencies::dependency_id.eq(id as i32)),
+                ),
+            )
+            .execute(connection)
+            .map_err(|e| Error(context, e.into()))?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn complete_task(&self, id: u32, done_at: DateTime<Utc>) -> Result<()> {
+        let amount_updated = diesel::update(task_table.find(id as i32))
+            .set((
+                tasks::done_at.eq(done_at.timestamp()),
+                tasks::updated_at.eq(Utc::now().timestamp()),
+            ))
             .execute(&self.get_connection()?)
-            .map_err(|e| Error("while trying to delete a task", e.into()))?;
-        if amount_deleted != 1 {
+            .map_err(|e| Error("while trying to complete a task", e.into()))?;
+        if amount_updated != 1 {
             return Err(Error(
-                "while trying to delete a task",
-                format!("{} task(s) were deleted", amount_deleted).into(),
+                "while trying to complete a task",
+                unexpected_amount(
+                    amount_updated,
+                    format!("{} task(s) were completed", amount_updated),
+                ),
             ));
         }
         Ok(())
     }
 
+    async fn clear_completed(&self, older_than: Option<DateTime<Utc>>) -> Result<u64> {
+        let mut query = task_table
+            .select(tasks::id)
+            .filter(tasks::done_at.is_not_null())
+            .into_boxed();
+        if let Some(older_than) = older_than {
+            query = query.filter(tasks::done_at.lt(older_than.timestamp()));
+        }
+        let connection = self.get_connection()?;
+        let ids = query
+            .load::<i32>(&connection)
+            .map_err(|e| Error("while trying to clear completed tasks", e.into()))?;
+        purge_tasks(&connection, &ids, "while trying to clear completed tasks")?;
+        Ok(ids.len() as u64)
+    }
+
     async fn get_task(&self, id: u32) -> Result<crate::Task> {
-        let db_task = task_table
-            .find(id as i32)
-            .get_result::<Task>(&self.get_connection()?)
-            .map_err(|e| Error("while trying to find a task", e.into()))?;
-        Ok(crate::Task::from(db_task))
+        self.retrying(|| {
+            let db_task = task_table
+                .find(id as i32)
+                .get_result::<Task>(&self.get_connection()?)
+                .map_err(|e| match e {
+                    diesel::result::Error::NotFound => Error(
+                        "while trying to find a task",
+                        NotFound(format!("There is no task with id {}", id)).into(),
+                    ),
+                    e => Error("while trying to find a task", e.into()),
+                })?;
+            let mut task = crate::Task::from(db_task);
+            task.depends_on = task_dependencies::table
+                .filter(task_dependencies::task_id.eq(id as i32))
+                .select(task_dependencies::dependency_id)
+                .order(task_dependencies::dependency_id)
+                .load::<i32>(&self.get_connection()?)
+                .map_err(|e| Error("while trying to find a task", e.into()))?
+                .into_iter()
+                .map(|dependency_id| dependency_id as u32)
+                .collect();
+            Ok(task)
+        })
+    }
+
+    async fn get_tasks(&self, ids: &[u32]) -> Result<Vec<crate::Task>> {
+        let db_ids = ids.iter().map(|&id| id as i32).collect_vec();
+        let db_tasks = task_table
+            .filter(tasks::id.eq_any(&db_ids))
+            .load::<Task>(&self.get_connection()?)
+            .map_err(|e| Error("while trying to find tasks", e.into()))?;
+        let mut dependencies = task_dependencies::table
+            .filter(task_dependencies::task_id.eq_any(&db_ids))
+            .order((task_dependencies::task_id, task_dependencies::dependency_id))
+            .load::<TaskDependency>(&self.get_connection()?)
+            .map_err(|e| Error("while trying to find tasks", e.into()))?
+            .into_iter()
+            .map(|dependency| (dependency.task_id, dependency.dependency_id as u32))
+            .into_group_map();
+        let tasks: HashMap<u32, crate::Task> = db_tasks
+            .into_iter()
+            .map(|db_task| with_dependencies(db_task, &mut dependencies))
+            .map(|task| (task.id, task))
+            .collect();
+        ids.iter()
+            .map(|id| {
+                tasks.get(id).cloned().ok_or_else(|| {
+                    Error(
+                        "while trying to find tasks",
+                        NotFound(format!("There is no task with id {}", id)).into(),
+                    )
+                })
+            })
+            .collect()
     }
 
     async fn update_task(&self, task: crate::Task) -> Result<()> {
+        self.ensure_time_segment_exists(task.time_segment_id, "while trying to update a task")?;
+        self.ensure_tasks_exist(&task.depends_on, "while trying to update a task")?;
+        let (id, depends_on) = (task.id, task.depends_on.clone());
         let db_task = Task::from(task);
-        let amount_updated = diesel::update(&db_task)
-            .set(&db_task)
-            .execute(&self.get_connection()?)
-            .map_err(|e| Error("while trying to update a task", e.into()))?;
-        if amount_updated != 1 {
-            return Err(Error(
-                "while trying to update a task",
-                format!("{} task(s) were updated", amount_updated).into(),
-            ));
+        self.with_transaction(|connection| {
+            let amount_updated = diesel::update(&db_task)
+                .set(&db_task)
+                .execute(connection)
+                .map_err(|e| Error("while trying to update a task", e.into()))?;
+            if amount_updated != 1 {
+                return Err(Error(
+                    "while trying to update a task",
+                    unexpected_amount(
+                        amount_updated,
+                        format!("{} task(s) were updated", amount_updated),
+                    ),
+                ));
+            }
+            set_dependencies(connection, id, &depends_on, "while trying to update a task")
+        })
+        .await
+    }
+
+    async fn update_task_fields(
+        &self,
+        id: u32,
+        changes: crate::TaskChanges,
+    ) -> Result<crate::Task> {
+        let context = "while trying to update a task";
+        if let Some(time_segment_id) = changes.time_segment_id {
+            self.ensure_time_segment_exists(time_segment_id, context)?;
         }
-        Ok(())
+        if let Some(depends_on) = &changes.depends_on {
+            self.ensure_tasks_exist(depends_on, context)?;
+        }
+        // The fields which don't change are left out of the query
+        self.with_transaction(|connection| {
+            let amount_updated = diesel::update(task_table.find(id as i32))
+                .set((
+                    changes
+                        .content
+                        .clone()
+                        .map(|content| tasks::content.eq(content)),
+                    changes
+                        .deadline
+                        .map(|deadline| tasks::deadline.eq(deadline.timestamp())),
+                    changes
+                        .duration
+                        .map(|duration| tasks::duration.eq(duration.num_seconds() as i32)),
+                    changes
+                        .importance
+                        .map(|importance| tasks::importance.eq(importance as i32)),
+                    changes
+                        .confidence
+                        .map(|confidence| tasks::confidence.eq(confidence_to_i32(confidence))),
+                    changes
+                        .time_segment_id
+                        .map(|time_segment_id| tasks::time_segment_id.eq(time_segment_id as i32)),
+                    changes
+                        .color
+                        .map(|color| tasks::color.eq(color.map(i32::from))),
+                    changes.pinned.map(|pinned| tasks::pinned.eq(pinned)),
+                    tasks::updated_at.eq(Utc::now().timestamp()),
+                ))
+                .execute(connection)
+                .map_err(|e| Error(context, e.into()))?;
+            if amount_updated != 1 {
+                return Err(Error(
+                    context,
+                    unexpected_amount(
+                        amount_updated,
+                        format!("{} task(s) were updated", amount_updated),
+                    ),
+                ));
+            }
+            if let Some(depends_on) = &changes.depends_on {
+                set_dependencies(connection, id, depends_on, context)?;
+            }
+            Ok(())
+        })
+        .await?;
+        self.get_task(id).await
+    }
+
+    async fn set_scheduled_for(&self, scheduled: &[(u32, DateTime<Utc>)]) -> Result<()> {
+        let ids = scheduled.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+        self.ensure_tasks_exist(&ids, "while trying to persist a schedule")?;
+        // Either the whole schedule is persisted or none of it, so a failure
+        // halfway doesn't leave a mix of two schedules behind
+        let connection = self.get_connection()?;
+        connection
+            .transaction::<_, diesel::result::Error, _>(|| {
+                diesel::update(task_table)
+                    .set(tasks::scheduled_for.eq(None::<i64>))
+                    .execute(&connection)?;
+                for (id, when) in scheduled {
+                    diesel::update(task_table.find(*id as i32))
+                        .set(tasks::scheduled_for.eq(when.timestamp()))
+                        .execute(&connection)?;
+                }
+                Ok(())
+            })
+            .map_err(|e| Error("while trying to persist a schedule", e.into()))
     }
 
     async fn all_tasks(&self) -> Result<Vec<crate::Task>> {
+        self.retrying(|| {
+            let db_tasks = task_table
+                .filter(tasks::done_at.is_null())
+                .load::<Task>(&self.get_connection()?)
+                .map_err(|e| Error("while trying to retrieve tasks", e.into()))?;
+            let mut dependencies = self.all_dependencies()?;
+            Ok(db_tasks
+                .into_iter()
+                .map(|db_task| with_dependencies(db_task, &mut dependencies))
+                .collect())
+        })
+    }
+
+    async fn tasks_updated_since(&self, since: DateTime<Utc>) -> Result<Vec<crate::Task>> {
+        // Timestamps are stored in whole seconds, so round down to not miss
+        // any updates
         let db_tasks = task_table
+            .filter(tasks::updated_at.ge(since.timestamp()))
+            .filter(tasks::done_at.is_null())
             .load::<Task>(&self.get_connection()?)
-            .map_err(|e| Error("while trying to retrieve tasks", e.into()))?;
-        Ok(db_tasks.into_iter().map(crate::Task::from).collect())
+            .map_err(|e| Error("while trying to retrieve updated tasks", e.into()))?;
+        let mut dependencies = self.all_dependencies()?;
+        Ok(db_tasks
+            .into_iter()
+            .map(|db_task| with_dependencies(db_task, &mut dependencies))
+            .collect())
     }
 
-    async fn all_tasks_per_time_segment(
+    async fn tasks_in_deadline_range(
         &self,
-    ) -> Result<Vec<(CrateTimeSegment, Vec<crate::Task>)>> {
-        let db_time_segments = time_segments::table
-            .load::<TimeSegment>(&self.get_connection()?)
-            .map_err(|e| Error("while trying to retrieve time segments", e.into()))?;
-        let tasks = Task::belonging_to(&db_time_segments)
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> Result<Vec<crate::Task>> {
+        // Deadlines are stored in whole seconds, so a bound within a second
+        // counts from the next one
+        let whole_seconds =
+            |moment: DateTime<Utc>| moment.timestamp() + i64::from(moment.nanosecond() > 0);
+        let mut query = task_table.filter(tasks::done_at.is_null()).into_boxed();
+        if let Some(from) = from {
+            query = query.filter(tasks::deadline.ge(whole_seconds(from)));
+        }
+        if let Some(to) = to {
+            query = query.filter(tasks::deadline.lt(whole_seconds(to)));
+        }
+        let db_tasks = query
+            .order(tasks::id)
             .load::<Task>(&self.get_connection()?)
-            .map_err(|e| Error("while trying to retrieve tasks", e.into()))?
-            .grouped_by(&db_time_segments)
+            .map_err(|e| Error("while trying to retrieve tasks by deadline", e.into()))?;
+        let mut dependencies = self.all_dependencies()?;
+        Ok(db_tasks
             .into_iter()
-            .map(|db_tasks| db_tasks.into_iter().map(crate::Task::from).collect());
-        Ok(self
-            .construct_time_segments(db_time_segments)?
-            .zip(tasks)
+            .map(|db_task| with_dependencies(db_task, &mut dependencies))
             .collect())
     }
 
-    async fn add_time_segment(&self, time_segment: CrateNewTimeSegment) -> Result<()> {
-        diesel::insert_into(time_segment_table)
-            .values(&NewTimeSegment::from(time_segment.clone()))
-            .execute(&self.get_connection()?)
-            .map_err(|e| Error("while trying to add a time segment", e.into()))?;
-        let id = diesel::select(last_insert_rowid)
-            .get_result::<i32>(&self.get_connection()?)
-            .map_err(|e| Error("while trying to fetch the new time segment", e.into()))?;
-        for range in time_segment.ranges {
-            diesel::insert_into(time_segment_range_table)
-                .values(&TimeSegmentRange {
-                    segment_id: id,
-                    start: range.start.timestamp() as i32,
-                    end: range.end.timestamp() as i32,
-                })
-                .execute(&self.get_connection()?)
-                .map_err(|e| Error("while trying to add a time segment", e.into()))?;
-        }
-        Ok(())
+    async fn search_tasks(&self, query: &str) -> Result<Vec<crate::Task>> {
+        let db_tasks = task_table
+            .filter(tasks::content.like(like_pattern(query)).escape('\\'))
+            .filter(tasks::done_at.is_null())
+            .order(tasks::id)
+            .load::<Task>(&self.get_connection()?)
+            .map_err(|e| Error("while trying to search tasks", e.into()))?;
+        let mut dependencies = self.all_dependencies()?;
+        Ok(db_tasks
+            .into_iter()
+            .map(|db_task| with_dependencies(db_task, &mut dependencies))
+            .collect())
     }
 
-    async fn delete_time_segment(&self, time_segment: CrateTimeSegment) -> Result<()> {
-        let db_time_segment = TimeSegment::from(time_segment);
-        let ranges = TimeSegmentRange::belonging_to(&db_time_segment);
+    async fn tasks_page(
+        &self,
+        order: TaskOrder,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<crate::Task>> {
+        let query = task_table.filter(tasks::done_at.is_null()).into_boxed();
+        let query = match order {
+            TaskOrder::Added => query.order(tasks::id),
+            TaskOrder::Deadline => query.order((tasks::deadline, tasks::id)),
+            TaskOrder::Importance => query.order((tasks::importance.desc(), tasks::id)),
+        };
+        let db_tasks = query
+            .limit(i64::from(limit))
+            .offset(i64::from(offset))
+            .load::<Task>(&self.get_connection()?)
+            .map_err(|e| Error("while trying to retrieve a page of tasks", e.into()))?;
+        let mut dependencies = self.all_dependencies()?;
+        Ok(db_tasks
+            .into_iter()
+            .map(|db_task| with_dependencies(db_task, &mut dependencies))
+            .collect())
+    }
 
-        // Assert that there are no tasks in this time segment
-        let n_tasks = Task::belonging_to(&db_time_segment)
+    async fn count_tasks(&self) -> Result<u64> {
+        let n_tasks = task_table
+            .filter(tasks::done_at.is_null())
             .count()
             .get_result::<i64>(&self.get_connection()?)
-            .map_err(|e| Error("while trying to delete a time segment", e.into()))?;
-        if n_tasks > 0 {
-            Err(Error(
-                "while trying to delete a time segment",
-                format!(
-                    "There are still {} task(s) in this time segment. Please move them to \
-                        another time segment or delete them before deleting this segment.",
-                    n_tasks
-                )
-                .into(),
-            ))?
-        }
+            .map_err(|e| Error("while trying to count tasks", e.into()))?;
+        Ok(n_tasks as u64)
+    }
 
-        // Assert that this isn't the last time segment
-        let n_time_segments = time_segments::table
-            .count()
-            .get_result::<i64>(&self.get_connection()?)
-            .map_err(|e| Error("while trying to count time segments", e.into()))?;
-        if n_time_segments <= 1 {
-            Err(Error(
-                "while trying to delete a time segment",
-                "If you remove the last time segment, when should I schedule things?".into(),
-            ))?
-        }
+    async fn all_tasks_per_time_segment(
+        &self,
+    ) -> Result<Vec<(CrateTimeSegment, Vec<crate::Task>)>> {
+        self.retrying(|| {
+            let db_time_segments = time_segments::table
+                .load::<TimeSegment>(&self.get_connection()?)
+                .map_err(|e| Error("while trying to retrieve time segments", e.into()))?;
+            self.with_tasks_to_do(db_time_segments)
+        })
+    }
 
-        diesel::delete(ranges)
-            .execute(&self.get_connection()?)
-            .map_err(|e| Error("while trying to delete a time segment", e.into()))?;
-        let amount_deleted = diesel::delete(&db_time_segment)
+    async fn tasks_per_nonempty_segment(
+        &self,
+    ) -> Result<Vec<(CrateTimeSegment, Vec<crate::Task>)>> {
+        self.retrying(|| {
+            // Leave the empty segments out in the query already, so their
+            // ranges aren't loaded for nothing
+            let nonempty = task_table
+                .filter(tasks::done_at.is_null())
+                .select(tasks::time_segment_id);
+            let db_time_segments = time_segments::table
+                .filter(time_segments::id.eq_any(nonempty))
+                .load::<TimeSegment>(&self.get_connection()?)
+                .map_err(|e| Error("while trying to retrieve time segments", e.into()))?;
+            self.with_tasks_to_do(db_time_segments)
+        })
+    }
+
+    async fn restore_task(&self, task: crate::Task) -> Result<()> {
+        let context = "while trying to restore a task";
+        self.ensure_time_segment_exists(task.time_segment_id, context)?;
+        self.ensure_tasks_exist(&task.depends_on, context)?;
+        let (id, depends_on) = (task.id, task.depends_on.clone());
+        let db_task = Task::from(task);
+        self.with_transaction(|connection| {
+            diesel::insert_into(task_table)
+                .values(&db_task)
+                .execute(connection)
+                .map_err(|e| Error(context, e.into()))?;
+            set_dependencies(connection, id, &depends_on, context)
+        })
+        .await
+    }
+
+    async fn log_operation(&self, operation: Operation) -> Result<()> {
+        let context = "while trying to log an operation";
+        diesel::insert_into(operations::table)
+            .values(&NewLoggedOperation::from(operation))
             .execute(&self.get_connection()?)
-            .map_err(|e| Error("while trying to delete a time segment", e.into()))?;
-        if amount_deleted != 1 {
-            Err(Error(
-                "while trying to delete a time segment",
-                format!("{} time segment(s) were deleted", amount_deleted).into(),
-            ))?
+            .map_err(|e| Error(context, e.into()))?;
+        let forgotten = operations::table
+            .select(operations::id)
+            .order(operations::id.desc())
+            .offset(MAX_LOGGED_OPERATIONS as i64)
+            .first::<i32>(&self.get_connection()?)
+            .optional()
+            .map_err(|e| Error(context, e.into()))?;
+        if let Some(forgotten) = forgotten {
+            diesel::delete(operations::table.filter(operations::id.le(forgotten)))
+                .execute(&self.get_connection()?)
+                .map_err(|e| Error(context, e.into()))?;
         }
-
         Ok(())
     }
 
+    async fn operations(&self) -> Result<Vec<Operation>> {
+        operations::table
+            .order(operations::id)
+            .load::<LoggedOperation>(&self.get_connection()?)
+            .map_err(|e| Error("while trying to retrieve operations", e.into()))?
+            .into_iter()
+            .map(LoggedOperation::into_operation)
+            .collect()
+    }
+
+    async fn pop_operation(&self) -> Result<Option<Operation>> {
+        let connection = self.get_connection()?;
+        let logged_operation = connection
+            .transaction::<_, diesel::result::Error, _>(|| {
+                let logged_operation = operations::table
+                    .order(operations::id.desc())
+                    .first::<LoggedOperation>(&connection)
+                    .optional()?;
+                if let Some(logged_operation) = &logged_operation {
+                    diesel::delete(operations::table.find(logged_operation.id))
+                        .execute(&connection)?;
+                }
+                Ok(logged_operation)
+            })
+            .map_err(|e| Error("while trying to retrieve the last operation", e.into()))?;
+        logged_operation
+            .map(LoggedOperation::into_operation)
+            .transpose()
+    }
+
+    async fn add_time_segment(
+        &self,
+        time_segment: CrateNewTimeSegment,
+    ) -> Result<CrateTimeSegment> {
+        let context = "while trying to add a time segment";
+        let id = self
+            .with_transaction(|connection| {
+                diesel::insert_into(time_segment_table)
+                    .values(&NewTimeSegment::from(time_segment.clone()))
+                    .execute(connection)
+                    .map_err(|e| Error(context, e.into()))?;
+                let id = diesel::select(last_insert_rowid)
+                    .get_result::<i32>(connection)
+                    .map_err(|e| Error("while trying to fetch the new time segment", e.into()))?;
+                insert_ranges(connection, id, &time_segment.ranges, context)?;
+                Ok(id)
+            })
+            .await?;
+        Ok(CrateTimeSegment {
+            id: id as u32,
+            name: time_segment.name,
+            ranges: time_segment.ranges,
+            start: time_segment.start,
+            period: time_segment.period,
+            hue: time_segment.hue,
+            strategy: time_segment.strategy,
+        })
+    }
+
+    async fn delete_time_segment(&self, time_segment: CrateTimeSegment) -> Result<()> {
+        let context = "while trying to delete a time segment";
+        let db_time_segment = TimeSegment::from(time_segment);
+        self.with_transaction(|connection| {
+            let ranges = TimeSegmentRange::belonging_to(&db_time_segment);
+
+            // Assert that there are no tasks left to do in this time segment
+            let n_tasks = Task::belonging_to(&db_time_segment)
+                .filter(tasks::done_at.is_null())
+                .count()
+                .get_result::<i64>(connection)
+                .map_err(|e| Error(context, e.into()))?;
+            if n_tasks > 0 {
+                Err(Error(
+                    context,
+                    SegmentConstraintError::SegmentNotEmpty {
+                        remaining_tasks: n_tasks as u64,
+                    }
+                    .into(),
+                ))?
+            }
+
+            // Assert that this isn't the last time segment
+            let n_time_segments = time_segments::table
+                .count()
+                .get_result::<i64>(connection)
+                .map_err(|e| Error("while trying to count time segments", e.into()))?;
+            if n_time_segments <= 1 {
+                Err(Error(
+                    context,
+                    SegmentConstraintError::LastTimeSegment.into(),
+                ))?
+            }
+
+            // The tasks which are done go along with their time segment
+            let completed_ids = Task::belonging_to(&db_time_segment)
+                .filter(tasks::done_at.is_not_null())
+                .select(tasks::id)
+                .load::<i32>(connection)
+                .map_err(|e| Error(context, e.into()))?;
+            purge_tasks(connection, &completed_ids, context)?;
+            diesel::delete(ranges)
+                .execute(connection)
+                .map_err(|e| Error(context, e.into()))?;
+            let amount_deleted = diesel::delete(&db_time_segment)
+                .execute(connection)
+                .map_err(|e| Error(context, e.into()))?;
+            if amount_deleted != 1 {
+                Err(Error(
+                    context,
+                    unexpected_amount(
+                        amount_deleted,
+                        format!("{} time segment(s) were deleted", amount_deleted),
+                    ),
+                ))?
+            }
+
+            Ok(())
+        })
+        .await
+    }
+
     async fn update_time_segment(&self, time_segment: CrateTimeSegment) -> Result<()> {
+        let context = "while trying to update a time segment";
         let db_time_segment = TimeSegment::from(time_segment.clone());
-        let ranges = TimeSegmentRange::belonging_to(&db_time_segment);
-        diesel::delete(ranges)
-            .execute(&self.get_connection()?)
-            .map_err(|e| Error("while trying to update a time segment", e.into()))?;
-        for range in time_segment.ranges {
-            diesel::insert_into(time_segment_range_table)
-                .values(&TimeSegmentRange {
-                    segment_id: time_segment.id as i32,
-                    start: range.start.timestamp() as i32,
-                    end: range.end.timestamp() as i32,
-                })
-                .execute(&self.get_connection()?)
-                .map_err(|e| Error("while trying to update a time segment", e.into()))?;
-        }
-        let amount_updated = diesel::update(&db_time_segment)
-            .set(&db_time_segment)
+        self.with_transaction(|connection| {
+            let ranges = TimeSegmentRange::belonging_to(&db_time_segment);
+            diesel::delete(ranges)
+                .execute(connection)
+                .map_err(|e| Error(context, e.into()))?;
+            insert_ranges(
+                connection,
+                time_segment.id as i32,
+                &time_segment.ranges,
+                context,
+            )?;
+            let amount_updated = diesel::update(&db_time_segment)
+                .set(&db_time_segment)
+                .execute(connection)
+                .map_err(|e| Error(context, e.into()))?;
+            if amount_updated != 1 {
+                Err(Error(
+                    context,
+                    unexpected_amount(
+                        amount_updated,
+                        format!("{} time segment(s) were updated", amount_updated),
+                    ),
+                ))?
+            }
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn rename_time_segment(&self, id: u32, new_name: &str) -> Result<()> {
+        let amount_updated = diesel::update(time_segment_table.find(id as i32))
+            .set(time_segments::name.eq(new_name))
             .execute(&self.get_connection()?)
-            .map_err(|e| Error("while trying to update a time segment", e.into()))?;
+            .map_err(|e| Error("while trying to rename a time segment", e.into()))?;
         if amount_updated != 1 {
-            Err(Error(
-                "while trying to update a time segment",
-                format!("{} time segment(s) were updated", amount_updated).into(),
-            ))?
+            return Err(Error(
+                "while trying to rename a time segment",
+                unexpected_amount(
+                    amount_updated,
+                    format!("{} time segment(s) were renamed", amount_updated),
+                ),
+            ));
         }
-
         Ok(())
     }
 
+    async fn move_tasks_between_segments(&self, from: u32, to: u32) -> Result<u64> {
+        let context = "while trying to move tasks between time segments";
+        self.ensure_time_segment_exists(from, context)?;
+        self.ensure_time_segment_exists(to, context)?;
+        let amount_moved =
+            diesel::update(task_table.filter(tasks::time_segment_id.eq(from as i32)))
+                .set((
+                    tasks::time_segment_id.eq(to as i32),
+                    tasks::updated_at.eq(Utc::now().timestamp()),
+                ))
+                .execute(&self.get_connection()?)
+                .map_err(|e| Error(context, e.into()))?;
+        Ok(amount_moved as u64)
+    }
+
+    async fn vacuum(&self) -> Result<()> {
+        let context = "while trying to clean up the database";
+        let connection = self.get_connection()?;
+        // An in-memory database has no file to shrink
+        let file = diesel::dsl::sql::<diesel::sql_types::Text>(
+            "SELECT file FROM pragma_database_list WHERE name = 'main'",
+        )
+        .get_result::<String>(&connection)
+        .map_err(|e| Error(context, e.into()))?;
+        if file.is_empty() {
+            return Ok(());
+        }
+        self.retrying(|| {
+            connection
+                .batch_execute("VACUUM; ANALYZE;")
+                .map_err(|e| Error(context, e.into()))
+        })
+    }
+
+    async fn get_time_segment(&self, id: u32) -> Result<CrateTimeSegment> {
+        let db_time_segment = time_segment_table
+            .find(id as i32)
+            .get_result::<TimeSegment>(&self.get_connection()?)
+            .map_err(|e| match e {
+                diesel::result::Error::NotFound => Error(
+                    "while trying to find a time segment",
+                    NotFound(format!("There is no time segment with id {}", id)).into(),
+                ),
+                e => Error("while trying to find a time segment", e.into()),
+            })?;
+        self.construct_time_segments(vec![db_time_segment])?
+            .next()
+            .ok_or_else(|| {
+                Error(
+                    "while trying to find a time segment",
+                    "The time segment disappeared".into(),
+                )
+            })
+    }
+
     async fn all_time_segments(&self) -> Result<Vec<CrateTimeSegment>> {
-        let db_time_segments = time_segments::table
-            .load::<TimeSegment>(&self.get_connection()?)
-            .map_err(|e| Error("while trying to retrieve time segments", e.into()))?;
-        Ok(self.construct_time_segments(db_time_segments)?.collect())
+        self.retrying(|| {
+            let db_time_segments = time_segments::table
+                .load::<TimeSegment>(&self.get_connection()?)
+                .map_err(|e| Error("while trying to retrieve time segments", e.into()))?;
+            Ok(self.construct_time_segments(db_time_segments)?.collect())
+        })
     }
 }
 
@@ -297,6 +952,149 @@ impl DbConnection {
             .map_err(|e| Error("while connecting to the database", e.into()))
     }
 
+    /// Runs `f` in a transaction on a single connection, so either all of its
+    /// changes are made or, when it fails, none of them are. Everything `f`
+    /// does should go through the connection it's given, since the others in
+    /// the pool are outside of the transaction. When the database is locked,
+    /// the whole transaction is tried again, so `f` may run more than once.
+    pub async fn with_transaction<T, F>(&self, mut f: F) -> Result<T>
+    where
+        F: FnMut(&SqliteConnection) -> Result<T>,
+    {
+        let context = "while running a transaction";
+        self.retrying(|| {
+            let connection = self.get_connection()?;
+            let manager = connection.transaction_manager();
+            manager
+                .begin_transaction(&*connection)
+                .map_err(|e| Error(context, e.into()))?;
+            match f(&connection) {
+                Ok(value) => {
+                    manager
+                        .commit_transaction(&*connection)
+                        .map_err(|e| Error(context, e.into()))?;
+                    Ok(value)
+                }
+                Err(error) => {
+                    // What went wrong in the first place says more than a
+                    // failed rollback would
+                    let _ = manager.rollback_transaction(&*connection);
+                    Err(error)
+                }
+            }
+        })
+    }
+
+    /// Runs `operation` again when it fails because another connection has
+    /// locked the database, waiting twice as long before every next try, up to
+    /// the number of retries in the `DbConfig`. Only operations which can
+    /// safely be repeated should be retried, like reads and transactions.
+    fn retrying<T>(&self, mut operation: impl FnMut() -> Result<T>) -> Result<T> {
+        let mut delay = self.1.retry_delay;
+        for _ in 0..self.1.retries {
+            match operation() {
+                Err(error) if is_locked(&error) => {
+                    debug!("The database is locked, trying again in {:?}", delay);
+                    std::thread::sleep(delay);
+                    delay *= 2;
+                }
+                result => return result,
+            }
+        }
+        operation()
+    }
+
+    /// Runs the migrations which haven't been run on this database yet and
+    /// returns their versions. When the schema is already up to date, nothing
+    /// is run and the result is empty.
+    pub fn run_pending_migrations(&self) -> Result<Vec<String>> {
+        let context = "while running database migrations";
+        let connection = self.get_connection()?;
+        diesel_migrations::setup_database(&*connection).map_err(|e| Error(context, e.into()))?;
+        let previous_versions = connection
+            .previously_run_migration_versions()
+            .map_err(|e| Error(context, e.into()))?;
+        embedded_migrations::run(&*connection).map_err(|e| Error(context, e.into()))?;
+        let mut new_versions = connection
+            .previously_run_migration_versions()
+            .map_err(|e| Error(context, e.into()))?
+            .into_iter()
+            .filter(|version| !previous_versions.contains(version))
+            .collect::<Vec<_>>();
+        new_versions.sort();
+        Ok(new_versions)
+    }
+
+    fn ensure_tasks_exist(&self, ids: &[u32], context: &'static str) -> Result<()> {
+        for &id in ids {
+            let n_tasks = self.retrying(|| {
+                task_table
+                    .find(id as i32)
+                    .count()
+                    .get_result::<i64>(&self.get_connection()?)
+                    .map_err(|e| Error(context, e.into()))
+            })?;
+            if n_tasks == 0 {
+                return Err(Error(
+                    context,
+                    NotFound(format!("There is no task with id {}", id)).into(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the ids of the tasks each task depends on.
+    fn all_dependencies(&self) -> Result<HashMap<i32, Vec<u32>>> {
+        let dependencies = task_dependencies::table
+            .order((task_dependencies::task_id, task_dependencies::dependency_id))
+            .load::<TaskDependency>(&self.get_connection()?)
+            .map_err(|e| Error("while trying to retrieve task dependencies", e.into()))?;
+        Ok(dependencies
+            .into_iter()
+            .map(|dependency| (dependency.task_id, dependency.dependency_id as u32))
+            .into_group_map())
+    }
+
+    fn ensure_time_segment_exists(&self, id: u32, context: &'static str) -> Result<()> {
+        let n_time_segments = self.retrying(|| {
+            time_segment_table
+                .find(id as i32)
+                .count()
+                .get_result::<i64>(&self.get_connection()?)
+                .map_err(|e| Error(context, e.into()))
+        })?;
+        if n_time_segments == 0 {
+            return Err(Error(
+                context,
+                NotFound(format!("There is no time segment with id {}", id)).into(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn with_tasks_to_do(
+        &self,
+        db_time_segments: Vec<TimeSegment>,
+    ) -> Result<Vec<(CrateTimeSegment, Vec<crate::Task>)>> {
+        let tasks = Task::belonging_to(&db_time_segments)
+            .filter(tasks::done_at.is_null())
+            .load::<Task>(&self.get_connection()?)
+            .map_err(|e| Error("while trying to retrieve tasks", e.into()))?
+            .grouped_by(&db_time_segments);
+        let mut dependencies = self.all_dependencies()?;
+        let tasks = tasks.into_iter().map(|db_tasks| {
+            db_tasks
+                .into_iter()
+                .map(|db_task| with_dependencies(db_task, &mut dependencies))
+                .collect()
+        });
+        Ok(self
+            .construct_time_segments(db_time_segments)?
+            .zip(tasks)
+            .collect())
+    }
+
     fn construct_time_segments(
         &self,
         db_time_segments: Vec<TimeSegment>,
@@ -307,9 +1105,9 @@ impl DbConnection {
             .grouped_by(&db_time_segments)
             .into_iter()
             .map(|ranges| {
-                ranges
-                    .into_iter()
-                    .map(|range| i32_to_datetime(range.start)..i32_to_datetime(range.end))
+                ranges.into_iter().map(|range| {
+                    timestamp_to_datetime(range.start)..timestamp_to_datetime(range.end)
+                })
             });
         Ok(db_time_segments
             .into_iter()
@@ -318,21 +1116,104 @@ impl DbConnection {
                 id: segment.id as u32,
                 name: segment.name,
                 ranges: ranges.collect(),
-                start: i32_to_datetime(segment.start),
+                start: timestamp_to_datetime(segment.start),
                 period: i32_to_duration(segment.period),
                 hue: segment.hue as u16,
+                // Only strategies Eva wrote itself are stored
+                strategy: segment.strategy.and_then(|strategy| strategy.parse().ok()),
             }))
     }
 }
 
+/// Replaces the tasks the task depends on, through the given connection so it
+/// can be part of a transaction.
+fn set_dependencies(
+    connection: &SqliteConnection,
+    id: u32,
+    depends_on: &[u32],
+    context: &'static str,
+) -> Result<()> {
+    diesel::delete(task_dependencies::table.filter(task_dependencies::task_id.eq(id as i32)))
+        .execute(connection)
+        .map_err(|e| Error(context, e.into()))?;
+    let dependencies = depends_on
+        .iter()
+        .unique()
+        .map(|&dependency_id| TaskDependency {
+            task_id: id as i32,
+            dependency_id: dependency_id as i32,
+        })
+        .collect_vec();
+    diesel::insert_into(task_dependencies::table)
+        .values(&dependencies)
+        .execute(connection)
+        .map_err(|e| Error(context, e.into()))?;
+    Ok(())
+}
+
+/// Deletes the given tasks for good, along with their dependencies and the
+/// operations on them, all at once.
+fn purge_tasks(connection: &SqliteConnection, ids: &[i32], context: &'static str) -> Result<()> {
+    connection
+        .transaction::<_, diesel::result::Error, _>(|| {
+            // Nothing depends on these tasks anymore, which changes the
+            // tasks that did
+            let dependent_ids = task_dependencies::table
+                .filter(task_dependencies::dependency_id.eq_any(ids))
+                .select(task_dependencies::task_id)
+                .load::<i32>(connection)?;
+            diesel::update(task_table.filter(tasks::id.eq_any(dependent_ids)))
+                .set(tasks::updated_at.eq(Utc::now().timestamp()))
+                .execute(connection)?;
+            diesel::delete(
+                task_dependencies::table.filter(
+                    task_dependencies::task_id
+                        .eq_any(ids)
+                        .or(task_dependencies::dependency_id.eq_any(ids)),
+                ),
+            )
+            .execute(connection)?;
+            diesel::delete(operations::table.filter(operations::task_id.eq_any(ids)))
+                .execute(connection)?;
+            diesel::delete(task_table.filter(tasks::id.eq_any(ids))).execute(connection)?;
+            Ok(())
+        })
+        .map_err(|e| Error(context, e.into()))
+}
+
+/// Adds the ranges of a time segment, all in one statement.
+fn insert_ranges(
+    connection: &SqliteConnection,
+    segment_id: i32,
+    ranges: &[std::ops::Range<DateTime<Utc>>],
+    context: &'static str,
+) -> Result<()> {
+    let ranges = ranges
+        .iter()
+        .map(|range| TimeSegmentRange {
+            segment_id,
+            start: range.start.timestamp(),
+            end: range.end.timestamp(),
+        })
+        .collect_vec();
+    diesel::insert_into(time_segment_range_table)
+        .values(&ranges)
+        .execute(connection)
+        .map_err(|e| Error(context, e.into()))?;
+    Ok(())
+}
+
 impl From<crate::NewTask> for NewTask {
     fn from(task: crate::NewTask) -> NewTask {
         NewTask {
             content: task.content,
-            deadline: task.deadline.timestamp() as i32,
+            deadline: task.deadline.timestamp(),
             duration: task.duration.num_seconds() as i32,
             importance: task.importance as i32,
             time_segment_id: task.time_segment_id as i32,
+            confidence: confidence_to_i32(task.confidence),
+            // Tasks are only converted to be written
+            updated_at: Utc::now().timestamp(),
         }
     }
 }
@@ -342,10 +1223,18 @@ impl From<Task> for crate::Task {
         crate::Task {
             id: task.id as u32,
             content: task.content,
-            deadline: i32_to_datetime(task.deadline),
+            deadline: timestamp_to_datetime(task.deadline),
             duration: i32_to_duration(task.duration),
-            importance: task.importance as u32,
+            // Tasks may have been stored before their importance was validated
+            importance: crate::clamp_importance(task.importance),
+            confidence: i32_to_confidence(task.confidence),
             time_segment_id: task.time_segment_id as u32,
+            // Dependencies are stored in a separate table
+            depends_on: vec![],
+            color: task.color.map(|color| color as u16),
+            scheduled_for: task.scheduled_for.map(timestamp_to_datetime),
+            done_at: task.done_at.map(timestamp_to_datetime),
+            pinned: task.pinned,
         }
     }
 }
@@ -355,10 +1244,86 @@ impl From<crate::Task> for Task {
         Task {
             id: task.id as i32,
             content: task.content,
-            deadline: task.deadline.timestamp() as i32,
+            deadline: task.deadline.timestamp(),
+            duration: task.duration.num_seconds() as i32,
+            importance: task.importance as i32,
+            time_segment_id: task.time_segment_id as i32,
+            confidence: confidence_to_i32(task.confidence),
+            // Tasks are only converted to be written
+            updated_at: Utc::now().timestamp(),
+            color: task.color.map(i32::from),
+            scheduled_for: task
+                .scheduled_for
+                .map(|scheduled_for| scheduled_for.timestamp()),
+            done_at: task.done_at.map(|done_at| done_at.timestamp()),
+            pinned: task.pinned,
+        }
+    }
+}
+
+impl From<Operation> for NewLoggedOperation {
+    fn from(operation: Operation) -> NewLoggedOperation {
+        let (kind, task, dependents) = match operation {
+            Operation::Add { task } => ("add", task, vec![]),
+            Operation::Delete { task, dependents } => ("delete", task, dependents),
+            Operation::Update { before } => ("update", before, vec![]),
+        };
+        NewLoggedOperation {
+            kind: kind.to_string(),
+            task_id: task.id as i32,
+            content: task.content,
+            deadline: task.deadline.timestamp(),
             duration: task.duration.num_seconds() as i32,
             importance: task.importance as i32,
             time_segment_id: task.time_segment_id as i32,
+            confidence: confidence_to_i32(task.confidence),
+            color: task.color.map(i32::from),
+            scheduled_for: task
+                .scheduled_for
+                .map(|scheduled_for| scheduled_for.timestamp()),
+            depends_on: task.depends_on.iter().join(","),
+            dependents: dependents.iter().join(","),
+            done_at: task.done_at.map(|done_at| done_at.timestamp()),
+            pinned: task.pinned,
+        }
+    }
+}
+
+impl LoggedOperation {
+    fn into_operation(self) -> Result<Operation> {
+        let context = "while trying to read an operation";
+        let parse_ids = |ids: &str| {
+            ids.split(',')
+                .filter(|id| !id.is_empty())
+                .map(str::parse)
+                .collect::<std::result::Result<Vec<u32>, _>>()
+                .map_err(|e| Error(context, e.into()))
+        };
+        let task = crate::Task {
+            id: self.task_id as u32,
+            content: self.content,
+            deadline: timestamp_to_datetime(self.deadline),
+            duration: i32_to_duration(self.duration),
+            importance: crate::clamp_importance(self.importance),
+            confidence: i32_to_confidence(self.confidence),
+            time_segment_id: self.time_segment_id as u32,
+            depends_on: parse_ids(&self.depends_on)?,
+            color: self.color.map(|color| color as u16),
+            scheduled_for: self.scheduled_for.map(timestamp_to_datetime),
+            done_at: self.done_at.map(timestamp_to_datetime),
+            pinned: self.pinned,
+        };
+        match self.kind.as_str() {
+            "add" => Ok(Operation::Add { task }),
+            "delete" => Ok(Operation::Delete {
+                task,
+                dependents: parse_ids(&self.dependents)?,
+            }),
+            "update" => Ok(Operation::Update { before: task }),
+            kind => Err(Error(
+                context,
+                format!("I don't know operations of kind {kind:?}").into(),
+            )),
         }
     }
 }
@@ -367,9 +1332,10 @@ impl From<CrateNewTimeSegment> for NewTimeSegment {
     fn from(time_segment: CrateNewTimeSegment) -> NewTimeSegment {
         NewTimeSegment {
             name: time_segment.name,
-            start: time_segment.start.timestamp() as i32,
+            start: time_segment.start.timestamp(),
             period: time_segment.period.num_seconds() as i32,
             hue: time_segment.hue as i32,
+            strategy: time_segment.strategy.map(|strategy| strategy.to_string()),
         }
     }
 }
@@ -379,36 +1345,133 @@ impl From<CrateTimeSegment> for TimeSegment {
         TimeSegment {
             id: time_segment.id as i32,
             name: time_segment.name,
-            start: time_segment.start.timestamp() as i32,
+            start: time_segment.start.timestamp(),
             period: time_segment.period.num_seconds() as i32,
             hue: time_segment.hue as i32,
+            strategy: time_segment.strategy.map(|strategy| strategy.to_string()),
         }
     }
 }
 
+/// Connects to the database and brings its schema up to date if needed.
 pub fn make_connection(database_url: &str) -> Result<DbConnection> {
+    let connection = connect(database_url)?;
+    connection.run_pending_migrations()?;
+    Ok(connection)
+}
+
+/// Connects to the database without touching its schema. This is useful when
+/// the database is read-only, but then it's up to the caller to make sure it
+/// has been migrated before.
+pub fn connect(database_url: &str) -> Result<DbConnection> {
+    connect_with(database_url, DbConfig::for_url(database_url))
+}
+
+/// Like `connect`, but with a pool of connections of the given size.
+pub fn connect_with(database_url: &str, config: DbConfig) -> Result<DbConnection> {
     let connection_manager = r2d2::ConnectionManager::new(database_url);
     let connection_pool = r2d2::Pool::builder()
-        .max_size(1)
+        .max_size(config.pool_size)
+        .connection_timeout(config.timeout)
+        .connection_customizer(Box::new(BusyTimeout(config.timeout)))
         .build(connection_manager)
         .map_err(|e| Error("while trying to connect to the database", e.into()))?;
-    {
-        let connection = connection_pool
-            .get()
-            .map_err(|e| Error("while trying to connect to the database", e.into()))?;
-        // TODO run instead of run_with_output
-        embedded_migrations::run_with_output(&connection, &mut io::stderr())
-            .map_err(|e| Error("while running database migrations", e.into()))?;
+    Ok(DbConnection(connection_pool, config))
+}
+
+/// How many connections to keep open to a database, how long to wait when
+/// they are all in use or when another one is writing to the database, and how
+/// often to try again when it's still locked after that.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DbConfig {
+    pub pool_size: u32,
+    pub timeout: std::time::Duration,
+    pub retries: u32,
+    /// How long to wait before the first retry, which doubles for every next
+    /// one
+    pub retry_delay: std::time::Duration,
+}
+
+impl DbConfig {
+    /// Allows a connection per CPU, except for an in-memory or temporary
+    /// database. SQLite gives each connection to those a database of its own,
+    /// so a task added over one connection wouldn't show up over the others.
+    pub fn for_url(database_url: &str) -> Self {
+        let private_to_connection = database_url == ":memory:"
+            || database_url.is_empty()
+            || database_url.contains("mode=memory");
+        let pool_size = if private_to_connection {
+            1
+        } else {
+            std::thread::available_parallelism().map_or(1, |n_cpus| n_cpus.get() as u32)
+        };
+        DbConfig {
+            pool_size,
+            timeout: std::time::Duration::from_secs(30),
+            retries: 3,
+            retry_delay: std::time::Duration::from_millis(100),
+        }
+    }
+}
+
+/// Returns whether the operation failed because another connection has locked
+/// the database, even after waiting for the busy timeout.
+fn is_locked(error: &Error) -> bool {
+    match error.1.downcast_ref::<diesel::result::Error>() {
+        Some(diesel::result::Error::DatabaseError(_, info)) => {
+            info.message().contains("database is locked")
+                || info.message().contains("database table is locked")
+        }
+        _ => false,
+    }
+}
+
+/// Makes a connection wait for others to finish writing instead of failing
+/// right away because the database is locked.
+#[derive(Debug)]
+struct BusyTimeout(std::time::Duration);
+
+impl r2d2::CustomizeConnection<SqliteConnection, r2d2::Error> for BusyTimeout {
+    fn on_acquire(
+        &self,
+        connection: &mut SqliteConnection,
+    ) -> std::result::Result<(), r2d2::Error> {
+        connection
+            .batch_execute(&format!("PRAGMA busy_timeout = {};", self.0.as_millis()))
+            .map_err(r2d2::Error::QueryError)
+    }
+}
+
+fn with_dependencies(db_task: Task, dependencies: &mut HashMap<i32, Vec<u32>>) -> crate::Task {
+    let depends_on = dependencies.remove(&db_task.id).unwrap_or_default();
+    crate::Task {
+        depends_on,
+        ..crate::Task::from(db_task)
     }
-    Ok(DbConnection(connection_pool))
 }
 
 fn i32_to_duration(duration: i32) -> Duration {
     Duration::seconds(i64::from(duration))
 }
 
-fn i32_to_datetime(timestamp: i32) -> DateTime<Utc> {
-    let naive_datetime = NaiveDateTime::from_timestamp(i64::from(timestamp), 0);
+fn confidence_to_i32(confidence: Confidence) -> i32 {
+    match confidence {
+        Confidence::Low => 0,
+        Confidence::Medium => 1,
+        Confidence::High => 2,
+    }
+}
+
+fn i32_to_confidence(confidence: i32) -> Confidence {
+    match confidence {
+        0 => Confidence::Low,
+        1 => Confidence::Medium,
+        _ => Confidence::High,
+    }
+}
+
+fn timestamp_to_datetime(timestamp: i64) -> DateTime<Utc> {
+    let naive_datetime = NaiveDateTime::from_timestamp(timestamp, 0);
     Utc.from_utc_datetime(&naive_datetime)
 }
 
@@ -417,6 +1480,7 @@ mod tests {
     use futures_test::test;
 
     use super::*;
+    use crate::configuration::SchedulingStrategy;
 
     #[test]
     async fn test_insert_query_and_delete_single_task() {
@@ -430,6 +1494,7 @@ mod tests {
         connection.add_task(new_task.clone()).await.unwrap();
         let tasks = connection.all_tasks().await.unwrap();
         assert_eq!(tasks.len(), 1);
+        assert_eq!(connection.count_tasks().await.unwrap(), 1);
         assert_eq!(tasks[0], new_task);
         let same_task = connection.get_task(tasks[0].id).await.unwrap();
         assert_eq!(tasks[0], same_task);
@@ -437,6 +1502,7 @@ mod tests {
         // Deleting a task leaves the database empty
         connection.delete_task(tasks[0].id).await.unwrap();
         assert!(connection.all_tasks().await.unwrap().is_empty());
+        assert_eq!(connection.count_tasks().await.unwrap(), 0);
     }
 
     #[test]
@@ -454,43 +1520,752 @@ mod tests {
         task.content = "stuff".to_string();
         task.deadline = deadline;
         task.duration = Duration::minutes(7);
-        task.importance = 100;
+        task.importance = 10;
+        task.color = Some(200);
         connection.update_task(task.clone()).await.unwrap();
 
         let task_from_db = connection.get_task(task.id).await.unwrap();
         assert_eq!(task, task_from_db);
+
+        // Colors can be cleared again
+        task.color = None;
+        connection.update_task(task.clone()).await.unwrap();
+        assert_eq!(connection.get_task(task.id).await.unwrap().color, None);
     }
 
     #[test]
-    async fn test_default_time_segment() {
+    async fn test_update_task_fields() {
         let connection = make_connection(":memory:").unwrap();
+        let prerequisite = connection.add_task(test_task()).await.unwrap();
+        let task = connection.add_task(test_task()).await.unwrap();
 
-        let mut time_segments = connection.all_time_segments().await.unwrap();
-        assert_eq!(time_segments.len(), 1);
-        let time_segment = time_segments.pop().unwrap();
-        assert_eq!(time_segment.id, 0);
-        assert_eq!(time_segment.name, "Default");
-        assert_eq!(time_segment.ranges.len(), 1);
-        assert_eq!(time_segment.period, Duration::days(1));
-        assert_eq!(time_segment.start, time_segment.ranges[0].start);
-        assert_eq!(
-            time_segment
-                .start
-                .with_timezone(&Local)
-                .format("%H:%M:%S")
-                .to_string(),
-            "09:00:00"
-        );
+        // Someone else changes the content in the meantime
+        let mut changed_elsewhere = task.clone();
+        changed_elsewhere.content = "do me first".to_string();
+        connection
+            .update_task(changed_elsewhere.clone())
+            .await
+            .unwrap();
+
+        let changes = crate::TaskChanges {
+            importance: Some(7),
+            ..Default::default()
+        };
+        let updated = connection
+            .update_task_fields(task.id, changes)
+            .await
+            .unwrap();
         assert_eq!(
-            time_segment.ranges[0].end - time_segment.ranges[0].start,
-            Duration::hours(8)
+            updated,
+            crate::Task {
+                importance: 7,
+                ..changed_elsewhere.clone()
+            }
         );
-        assert!(time_segment.hue < 360);
+        assert_eq!(connection.get_task(task.id).await.unwrap(), updated);
+
+        // Colors can be cleared, and dependencies and pinning changed too
+        let changes = crate::TaskChanges {
+            color: Some(Some(120)),
+            depends_on: Some(vec![prerequisite.id]),
+            pinned: Some(true),
+            ..Default::default()
+        };
+        let updated = connection
+            .update_task_fields(task.id, changes)
+            .await
+            .unwrap();
+        assert_eq!(updated.color, Some(120));
+        assert_eq!(updated.depends_on, [prerequisite.id]);
+        assert!(updated.pinned);
+        let changes = crate::TaskChanges {
+            color: Some(None),
+            ..Default::default()
+        };
+        let updated = connection
+            .update_task_fields(task.id, changes)
+            .await
+            .unwrap();
+        assert_eq!(updated.color, None);
+        assert_eq!(updated.depends_on, [prerequisite.id]);
 
-        // We shouldn't be able to delete the last time segment
-        let result = connection.delete_time_segment(time_segment).await;
+        assert!(connection
+            .update_task_fields(42, crate::TaskChanges::default())
+            .await
+            .unwrap_err()
+            .is_not_found());
+        let changes = crate::TaskChanges {
+            time_segment_id: Some(42),
+            ..Default::default()
+        };
+        assert!(connection
+            .update_task_fields(task.id, changes)
+            .await
+            .unwrap_err()
+            .is_not_found());
+    }
+
+    #[test]
+    async fn test_clamp_importances_stored_before_validation() {
+        let connection = make_connection(":memory:").unwrap();
+        let task = connection.add_task(test_task()).await.unwrap();
+        for (stored, read) in [(0, 1), (-3, 1), (11, 10), (9999, 10)] {
+            diesel::update(task_table)
+                .set(tasks::importance.eq(stored))
+                .execute(&connection.get_connection().unwrap())
+                .unwrap();
+            assert_eq!(connection.get_task(task.id).await.unwrap().importance, read);
+        }
+    }
+
+    #[test]
+    async fn test_move_task_between_time_segments() {
+        let connection = make_connection(":memory:").unwrap();
+        connection
+            .add_time_segment(test_time_segment())
+            .await
+            .unwrap();
+        let mut task = connection.add_task(test_task()).await.unwrap();
+
+        // Moving a task to an existing time segment works
+        task.time_segment_id = 1;
+        connection.update_task(task.clone()).await.unwrap();
+        assert_eq!(connection.get_task(task.id).await.unwrap(), task);
+
+        // Moving it to a non-existent one doesn't
+        let mut moved_task = task.clone();
+        moved_task.time_segment_id = 42;
+        let result = connection.update_task(moved_task).await;
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "A database error occurred while trying to update a task: There is no time segment \
+             with id 42"
+        );
+        assert_eq!(connection.get_task(task.id).await.unwrap(), task);
+    }
+
+    #[test]
+    async fn test_move_all_tasks_between_time_segments() {
+        let connection = make_connection(":memory:").unwrap();
+        let from = connection
+            .add_time_segment(test_time_segment())
+            .await
+            .unwrap();
+        let mut new_task = test_task();
+        new_task.time_segment_id = from.id;
+        for _ in 0..3 {
+            connection.add_task(new_task.clone()).await.unwrap();
+        }
+        let staying = connection.add_task(test_task()).await.unwrap();
+
+        assert_eq!(
+            connection
+                .move_tasks_between_segments(from.id, 0)
+                .await
+                .unwrap(),
+            3
+        );
+        let tasks = connection.all_tasks().await.unwrap();
+        assert_eq!(tasks.len(), 4);
+        assert!(tasks.iter().all(|task| task.time_segment_id == 0));
+        assert_eq!(connection.get_task(staying.id).await.unwrap(), staying);
+        // The source segment is now empty, so it can be deleted
+        connection.delete_time_segment(from.clone()).await.unwrap();
+
+        assert!(connection
+            .move_tasks_between_segments(0, from.id)
+            .await
+            .unwrap_err()
+            .is_not_found());
+    }
+
+    #[test]
+    async fn test_delete_default_time_segment_once_replaced() {
+        let connection = make_connection(":memory:").unwrap();
+        let default = connection.get_time_segment(0).await.unwrap();
+        let replacement = connection
+            .add_time_segment(test_time_segment())
+            .await
+            .unwrap();
+        let task = connection.add_task(test_task()).await.unwrap();
+
+        // The default time segment's tasks would be stranded without it
+        let error = connection
+            .delete_time_segment(default.clone())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.segment_constraint(),
+            Some(&SegmentConstraintError::SegmentNotEmpty { remaining_tasks: 1 })
+        );
+
+        // Once they've moved, it can go like any other time segment
+        connection
+            .move_tasks_between_segments(0, replacement.id)
+            .await
+            .unwrap();
+        connection.delete_time_segment(default).await.unwrap();
+        let time_segments = connection.all_time_segments().await.unwrap();
+        assert_eq!(time_segments.len(), 1);
+        assert_eq!(time_segments[0], replacement);
+        // The last time segment has to stay, even without tasks
+        connection.delete_task(task.id).await.unwrap();
+        let error = connection
+            .delete_time_segment(replacement)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.segment_constraint(),
+            Some(&SegmentConstraintError::LastTimeSegment)
+        );
+    }
+
+    #[test]
+    async fn test_tasks_per_nonempty_segment() {
+        let connection = make_connection(":memory:").unwrap();
+        let populated = connection
+            .add_time_segment(test_time_segment())
+            .await
+            .unwrap();
+        let _empty = connection
+            .add_time_segment(test_time_segment())
+            .await
+            .unwrap();
+        let only_done = connection
+            .add_time_segment(test_time_segment())
+            .await
+            .unwrap();
+        let mut in_populated = test_task();
+        in_populated.time_segment_id = populated.id;
+        let in_populated = connection.add_task(in_populated).await.unwrap();
+        let in_default = connection.add_task(test_task()).await.unwrap();
+        // Segments with only tasks which are done count as empty
+        let mut done = test_task();
+        done.time_segment_id = only_done.id;
+        let done = connection.add_task(done).await.unwrap();
+        connection.complete_task(done.id, Utc::now()).await.unwrap();
+
+        assert_eq!(
+            connection.all_tasks_per_time_segment().await.unwrap().len(),
+            4
+        );
+        let tasks_per_segment = connection.tasks_per_nonempty_segment().await.unwrap();
+        assert_eq!(
+            tasks_per_segment
+                .iter()
+                .map(|(segment, tasks)| (segment.id, tasks.clone()))
+                .collect::<Vec<_>>(),
+            [(0, vec![in_default]), (populated.id, vec![in_populated])]
+        );
+        assert_eq!(tasks_per_segment[1].0, populated);
+    }
+
+    #[test]
+    async fn test_task_dependencies() {
+        let connection = make_connection(":memory:").unwrap();
+        let first = connection.add_task(test_task()).await.unwrap();
+        let second = connection.add_task(test_task()).await.unwrap();
+
+        // Dependencies are stored alongside the task
+        let mut new_task = test_task();
+        new_task.depends_on = vec![second.id, first.id];
+        let mut task = connection.add_task(new_task).await.unwrap();
+        assert_eq!(task.depends_on, [first.id, second.id]);
+        assert_eq!(connection.get_task(task.id).await.unwrap(), task);
+        assert_eq!(
+            connection
+                .get_tasks(&[task.id, first.id, task.id])
+                .await
+                .unwrap(),
+            [task.clone(), first.clone(), task.clone()]
+        );
+
+        // Tasks can only depend on existing tasks
+        task.depends_on = vec![42];
+        let result = connection.update_task(task.clone()).await;
         assert_eq!(
             result.unwrap_err().to_string(),
+            "A database error occurred while trying to update a task: There is no task with id 42"
+        );
+
+        task.depends_on = vec![second.id];
+        connection.update_task(task.clone()).await.unwrap();
+        assert_eq!(connection.get_task(task.id).await.unwrap(), task);
+
+        // Deleting a prerequisite removes the dependency
+        connection.delete_task(second.id).await.unwrap();
+        let tasks = connection.all_tasks().await.unwrap();
+        assert_eq!(tasks.len(), 2);
+        assert!(tasks.iter().all(|task| task.depends_on.is_empty()));
+    }
+
+    #[test]
+    async fn test_log_operations() {
+        let connection = make_connection(":memory:").unwrap();
+        assert_eq!(connection.pop_operation().await.unwrap(), None);
+        let mut task = connection.add_task(test_task()).await.unwrap();
+        let first = Operation::Add { task: task.clone() };
+        task.depends_on = vec![2, 3];
+        task.color = Some(120);
+        let deleted = Operation::Delete {
+            task: task.clone(),
+            dependents: vec![4],
+        };
+        let updated = Operation::Update { before: task };
+        connection.log_operation(first.clone()).await.unwrap();
+        connection.log_operation(deleted.clone()).await.unwrap();
+        connection.log_operation(updated.clone()).await.unwrap();
+        assert_eq!(
+            connection.operations().await.unwrap(),
+            [first.clone(), deleted.clone(), updated.clone()]
+        );
+
+        // The last operation comes out first
+        assert_eq!(connection.pop_operation().await.unwrap(), Some(updated));
+        assert_eq!(connection.pop_operation().await.unwrap(), Some(deleted));
+
+        // Only the last operations are remembered
+        for _ in 0..MAX_LOGGED_OPERATIONS {
+            connection.log_operation(first.clone()).await.unwrap();
+        }
+        assert_eq!(
+            connection.operations().await.unwrap().len(),
+            MAX_LOGGED_OPERATIONS
+        );
+        for _ in 0..MAX_LOGGED_OPERATIONS {
+            assert_eq!(
+                connection.pop_operation().await.unwrap(),
+                Some(first.clone())
+            );
+        }
+        assert_eq!(connection.pop_operation().await.unwrap(), None);
+    }
+
+    #[test]
+    async fn test_restore_task() {
+        let connection = make_connection(":memory:").unwrap();
+        let prerequisite = connection.add_task(test_task()).await.unwrap();
+        let mut new_task = test_task();
+        new_task.depends_on = vec![prerequisite.id];
+        let task = connection.add_task(new_task).await.unwrap();
+        connection.delete_task(task.id).await.unwrap();
+
+        connection.restore_task(task.clone()).await.unwrap();
+        assert_eq!(connection.get_task(task.id).await.unwrap(), task);
+        // A task can't be restored over another one
+        assert!(connection.restore_task(task).await.is_err());
+    }
+
+    #[test]
+    async fn test_search_tasks() {
+        let connection = make_connection(":memory:").unwrap();
+        let mut tasks = vec![];
+        for content in [
+            "Call the dentist",
+            "Pay the DENTIST",
+            "Buy 100% cotton socks",
+            "Rename my_file",
+            "Walk the dog",
+        ] {
+            let mut task = test_task();
+            task.content = content.to_string();
+            tasks.push(connection.add_task(task).await.unwrap());
+        }
+
+        let search = |query| connection.search_tasks(query);
+        assert_eq!(search("dentist").await.unwrap(), tasks[0..2]);
+        assert_eq!(search("the do").await.unwrap(), [tasks[4].clone()]);
+        // Wildcards in the query only match themselves
+        assert_eq!(search("%").await.unwrap(), [tasks[2].clone()]);
+        assert_eq!(search("y_f").await.unwrap(), [tasks[3].clone()]);
+        assert!(search("e_t").await.unwrap().is_empty());
+        assert!(search("cat").await.unwrap().is_empty());
+    }
+
+    #[test]
+    async fn test_tasks_in_deadline_range() {
+        let connection = make_connection(":memory:").unwrap();
+        let mut tasks = vec![];
+        let start = test_task().deadline;
+        let day = |n| start + Duration::days(n);
+        for days in 1..4 {
+            let mut task = test_task();
+            task.deadline = day(days);
+            tasks.push(connection.add_task(task).await.unwrap());
+        }
+
+        let due = |from, to| connection.tasks_in_deadline_range(from, to);
+        assert_eq!(due(None, None).await.unwrap(), tasks);
+        assert_eq!(due(Some(day(2)), None).await.unwrap(), tasks[1..]);
+        assert_eq!(due(None, Some(day(2))).await.unwrap(), tasks[..1]);
+        assert_eq!(due(Some(day(1)), Some(day(3))).await.unwrap(), tasks[..2]);
+        // Only a task which is due right at the start of the range is in it
+        let just_after = day(1) + Duration::milliseconds(1);
+        assert_eq!(due(Some(just_after), None).await.unwrap(), tasks[1..]);
+        assert!(due(Some(day(2)), Some(day(2))).await.unwrap().is_empty());
+    }
+
+    #[test]
+    async fn test_tasks_page() {
+        let connection = make_connection(":memory:").unwrap();
+        let mut tasks = vec![];
+        for (days, importance) in [(3, 2), (1, 2), (2, 9)] {
+            let mut task = test_task();
+            task.deadline += Duration::days(days);
+            task.importance = importance;
+            tasks.push(connection.add_task(task).await.unwrap());
+        }
+
+        let page = |order, limit, offset| connection.tasks_page(order, limit, offset);
+        assert_eq!(page(TaskOrder::Added, 2, 0).await.unwrap(), tasks[0..2]);
+        // Asking for more tasks than are left just returns those
+        assert_eq!(page(TaskOrder::Added, 10, 1).await.unwrap(), tasks[1..]);
+        assert!(page(TaskOrder::Added, 10, 3).await.unwrap().is_empty());
+        let ids = |tasks: Vec<crate::Task>| tasks.iter().map(|task| task.id).collect::<Vec<_>>();
+        assert_eq!(
+            ids(page(TaskOrder::Deadline, 10, 0).await.unwrap()),
+            [tasks[1].id, tasks[2].id, tasks[0].id]
+        );
+        // Equally important tasks stay in the order they were added
+        assert_eq!(
+            ids(page(TaskOrder::Importance, 2, 1).await.unwrap()),
+            [tasks[0].id, tasks[1].id]
+        );
+    }
+
+    #[test]
+    async fn test_complete_and_clear_tasks() {
+        let connection = make_connection(":memory:").unwrap();
+        let first = connection.add_task(test_task()).await.unwrap();
+        let second = connection.add_task(test_task()).await.unwrap();
+        let mut third = test_task();
+        third.depends_on = vec![first.id];
+        let third = connection.add_task(third).await.unwrap();
+        let last_week = Utc.with_ymd_and_hms(2019, 8, 1, 9, 0, 0).unwrap();
+        let yesterday = last_week + Duration::days(6);
+        connection.complete_task(first.id, last_week).await.unwrap();
+        connection
+            .complete_task(second.id, yesterday)
+            .await
+            .unwrap();
+        connection
+            .log_operation(Operation::Update {
+                before: first.clone(),
+            })
+            .await
+            .unwrap();
+
+        // Tasks which are done are kept, but they aren't listed anymore
+        assert_eq!(
+            connection.get_task(first.id).await.unwrap().done_at,
+            Some(last_week)
+        );
+        assert_eq!(
+            connection.all_tasks().await.unwrap(),
+            std::slice::from_ref(&third)
+        );
+        assert_eq!(connection.count_tasks().await.unwrap(), 1);
+        let (_, tasks) = connection
+            .all_tasks_per_time_segment()
+            .await
+            .unwrap()
+            .remove(0);
+        assert_eq!(tasks, std::slice::from_ref(&third));
+        assert!(connection
+            .complete_task(42, yesterday)
+            .await
+            .unwrap_err()
+            .is_not_found());
+
+        // Only the tasks which were done before the given moment are cleared,
+        // along with the dependencies and operations on them
+        assert_eq!(
+            connection.clear_completed(Some(yesterday)).await.unwrap(),
+            1
+        );
+        assert!(connection
+            .get_task(first.id)
+            .await
+            .unwrap_err()
+            .is_not_found());
+        assert!(connection
+            .get_task(third.id)
+            .await
+            .unwrap()
+            .depends_on
+            .is_empty());
+        assert!(connection.operations().await.unwrap().is_empty());
+        assert_eq!(connection.clear_completed(None).await.unwrap(), 1);
+        assert!(connection
+            .get_task(second.id)
+            .await
+            .unwrap_err()
+            .is_not_found());
+        assert_eq!(connection.clear_completed(None).await.unwrap(), 0);
+    }
+
+    #[test]
+    async fn test_tasks_updated_since() {
+        let connection = make_connection(":memory:").unwrap();
+        let first = connection.add_task(test_task()).await.unwrap();
+        let mut second = connection.add_task(test_task()).await.unwrap();
+        let mut third = test_task();
+        third.depends_on = vec![first.id];
+        let third = connection.add_task(third).await.unwrap();
+        assert_eq!(
+            connection
+                .tasks_updated_since(Utc::now() - Duration::minutes(1))
+                .await
+                .unwrap()
+                .len(),
+            3
+        );
+
+        // Pretend the tasks were last updated long ago
+        diesel::update(task_table)
+            .set(tasks::updated_at.eq(0))
+            .execute(&connection.get_connection().unwrap())
+            .unwrap();
+        let since = Utc::now() - Duration::minutes(1);
+        assert!(connection
+            .tasks_updated_since(since)
+            .await
+            .unwrap()
+            .is_empty());
+
+        second.importance = 1;
+        connection.update_task(second.clone()).await.unwrap();
+        assert_eq!(
+            connection.tasks_updated_since(since).await.unwrap(),
+            vec![second.clone()]
+        );
+
+        // Deleting a prerequisite changes the tasks which depended on it
+        connection.delete_task(first.id).await.unwrap();
+        let updated_ids = connection
+            .tasks_updated_since(since)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|task| task.id)
+            .collect::<Vec<_>>();
+        assert_eq!(updated_ids, [second.id, third.id]);
+    }
+
+    #[test]
+    async fn test_persist_schedule() {
+        let connection = make_connection(":memory:").unwrap();
+        let configuration = crate::configuration::Configuration {
+            // Both share the one connection to the in-memory database
+            database: Box::new(DbConnection(connection.0.clone(), connection.1)),
+            scheduling_strategy: crate::configuration::SchedulingStrategy::Importance,
+            estimate_buffers: Default::default(),
+            granularity: crate::configuration::default_granularity(),
+            deadline_format: None,
+            max_continuous: None,
+            break_length: crate::configuration::default_break_length(),
+            task_defaults: crate::configuration::TaskDefaults::default(),
+        };
+        let database = &configuration.database;
+        for importance in [1, 2, 3] {
+            let mut task = test_task();
+            task.deadline = Utc::now() + Duration::days(1);
+            task.importance = importance;
+            database.add_task(task).await.unwrap();
+        }
+        assert!(database
+            .all_tasks()
+            .await
+            .unwrap()
+            .iter()
+            .all(|task| task.scheduled_for.is_none()));
+
+        let tasks_per_segment = crate::tasks_per_time_segment(&configuration).await.unwrap();
+        let schedule =
+            crate::schedule_tasks(&configuration, "importance", tasks_per_segment, None, &[])
+                .unwrap();
+        // Pretend the tasks were last updated long ago
+        diesel::update(task_table)
+            .set(tasks::updated_at.eq(0))
+            .execute(&connection.get_connection().unwrap())
+            .unwrap();
+        crate::persist_schedule(&configuration, &schedule)
+            .await
+            .unwrap();
+        for scheduled in &schedule.0 {
+            let task = database.get_task(scheduled.task.id).await.unwrap();
+            assert_eq!(
+                task.scheduled_for.map(|when| when.timestamp()),
+                Some(scheduled.when.timestamp())
+            );
+        }
+        // Persisting a schedule isn't a change to the tasks
+        let since = Utc::now() - Duration::minutes(1);
+        assert!(database
+            .tasks_updated_since(since)
+            .await
+            .unwrap()
+            .is_empty());
+
+        // Tasks which are left out of the next schedule aren't planned anymore
+        let mut next_schedule = crate::Schedule(schedule.0.clone());
+        let left_out = next_schedule.0.pop().unwrap().task.id;
+        crate::persist_schedule(&configuration, &next_schedule)
+            .await
+            .unwrap();
+        assert_eq!(
+            database.get_task(left_out).await.unwrap().scheduled_for,
+            None
+        );
+        assert_eq!(
+            database
+                .all_tasks()
+                .await
+                .unwrap()
+                .iter()
+                .filter(|task| task.scheduled_for.is_some())
+                .count(),
+            2
+        );
+
+        // Nothing is persisted if any of the tasks doesn't exist
+        let error = database
+            .set_scheduled_for(&[(left_out, Utc::now()), (42, Utc::now())])
+            .await
+            .unwrap_err();
+        assert!(error.is_not_found());
+        assert_eq!(
+            database.get_task(left_out).await.unwrap().scheduled_for,
+            None
+        );
+    }
+
+    #[test]
+    async fn test_timestamps_past_2038() {
+        let connection = make_connection(":memory:").unwrap();
+        let in_2040 = Utc.with_ymd_and_hms(2040, 2, 29, 12, 0, 0).unwrap();
+
+        let mut new_task = test_task();
+        new_task.deadline = in_2040;
+        let mut task = connection.add_task(new_task).await.unwrap();
+        assert_eq!(task.deadline, in_2040);
+        connection
+            .set_scheduled_for(&[(task.id, in_2040 - Duration::days(1))])
+            .await
+            .unwrap();
+        task.scheduled_for = Some(in_2040 - Duration::days(1));
+        assert_eq!(connection.get_task(task.id).await.unwrap(), task);
+
+        let mut time_segment = test_time_segment();
+        time_segment.start = in_2040;
+        time_segment.ranges = vec![in_2040..in_2040 + Duration::hours(2)];
+        let time_segment = connection.add_time_segment(time_segment).await.unwrap();
+        assert_eq!(
+            connection.get_time_segment(time_segment.id).await.unwrap(),
+            time_segment
+        );
+    }
+
+    #[test]
+    async fn test_repair_timestamps_which_wrapped_around() {
+        let connection = make_connection(":memory:").unwrap();
+        let in_2040 = Utc.with_ymd_and_hms(2040, 2, 29, 12, 0, 0).unwrap();
+        let task = connection.add_task(test_task()).await.unwrap();
+        // This is what storing the deadline in 32 bits used to do
+        diesel::update(task_table)
+            .set(tasks::deadline.eq(i64::from(in_2040.timestamp() as i32)))
+            .execute(&connection.get_connection().unwrap())
+            .unwrap();
+        assert!(
+            connection
+                .get_task(task.id)
+                .await
+                .unwrap()
+                .deadline
+                .timestamp()
+                < 0
+        );
+
+        connection
+            .get_connection()
+            .unwrap()
+            .batch_execute(include_str!(
+                "../../migrations/2026-10-15-210000_unwrap_timestamps_past_2038/up.sql"
+            ))
+            .unwrap();
+        assert_eq!(
+            connection.get_task(task.id).await.unwrap(),
+            crate::Task {
+                deadline: in_2040,
+                ..task
+            }
+        );
+    }
+
+    #[test]
+    async fn test_missing_tasks_are_not_found() {
+        let connection = make_connection(":memory:").unwrap();
+        let result = connection.get_task(42).await;
+        assert_eq!(
+            result.as_ref().unwrap_err().to_string(),
+            "A database error occurred while trying to find a task: There is no task with id 42"
+        );
+        assert!(result.unwrap_err().is_not_found());
+        let first = connection.add_task(test_task()).await.unwrap();
+        let result = connection.get_tasks(&[first.id, 42]).await;
+        assert_eq!(
+            result.as_ref().unwrap_err().to_string(),
+            "A database error occurred while trying to find tasks: There is no task with id 42"
+        );
+        assert!(result.unwrap_err().is_not_found());
+        assert!(connection.get_tasks(&[]).await.unwrap().is_empty());
+        assert!(connection.delete_task(42).await.unwrap_err().is_not_found());
+        let mut task = connection.add_task(test_task()).await.unwrap();
+        task.id = 42;
+        assert!(connection
+            .update_task(task)
+            .await
+            .unwrap_err()
+            .is_not_found());
+    }
+
+    #[test]
+    async fn test_default_time_segment() {
+        let connection = make_connection(":memory:").unwrap();
+
+        let mut time_segments = connection.all_time_segments().await.unwrap();
+        assert_eq!(time_segments.len(), 1);
+        let time_segment = time_segments.pop().unwrap();
+        assert_eq!(time_segment.id, 0);
+        assert_eq!(time_segment.name, "Default");
+        assert_eq!(time_segment.ranges.len(), 1);
+        assert_eq!(time_segment.period, Duration::days(1));
+        assert_eq!(time_segment.start, time_segment.ranges[0].start);
+        assert_eq!(
+            time_segment
+                .start
+                .with_timezone(&Local)
+                .format("%H:%M:%S")
+                .to_string(),
+            "09:00:00"
+        );
+        assert_eq!(
+            time_segment.ranges[0].end - time_segment.ranges[0].start,
+            Duration::hours(8)
+        );
+        assert!(time_segment.hue < 360);
+
+        // We shouldn't be able to delete the last time segment
+        let error = connection
+            .delete_time_segment(time_segment)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.segment_constraint(),
+            Some(&SegmentConstraintError::LastTimeSegment)
+        );
+        assert_eq!(
+            error.to_string(),
             "A database error occurred while trying to delete a time segment: If you remove the \
              last time segment, when should I schedule things?"
         );
@@ -501,7 +2276,7 @@ mod tests {
         let connection = make_connection(":memory:").unwrap();
 
         let time_segment = test_time_segment();
-        connection
+        let added_time_segment = connection
             .add_time_segment(time_segment.clone())
             .await
             .unwrap();
@@ -511,6 +2286,18 @@ mod tests {
         assert_eq!(time_segments.len(), 2);
         assert_eq!(time_segments[0].name, "Default");
         assert_eq!(time_segments[1], time_segment);
+        assert_eq!(time_segments[1], added_time_segment);
+        assert_eq!(
+            connection.get_time_segment(1).await.unwrap(),
+            time_segments[1]
+        );
+        let result = connection.get_time_segment(2).await;
+        assert!(result.as_ref().unwrap_err().is_not_found());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "A database error occurred while trying to find a time segment: There is no time \
+             segment with id 2"
+        );
 
         // We should be able to query a task we add to a certain segment
         let mut task = test_task();
@@ -526,10 +2313,16 @@ mod tests {
         // We shouldn't be able to delete the segment because there's still a
         // task in it
         let time_segment = time_segments.pop().unwrap();
-        let result = connection.delete_time_segment(time_segment.clone()).await;
-        let error_message = format!("{}", result.unwrap_err());
+        let error = connection
+            .delete_time_segment(time_segment.clone())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.segment_constraint(),
+            Some(&SegmentConstraintError::SegmentNotEmpty { remaining_tasks: 1 })
+        );
         assert_eq!(
-            error_message,
+            error.to_string(),
             "A database error occurred while trying to delete a time segment: There are still 1 \
              task(s) in this time segment. Please move them to another time segment or delete \
              them before deleting this segment."
@@ -562,6 +2355,7 @@ mod tests {
         time_segment.ranges = vec![start..start + Duration::minutes(3)];
         time_segment.period = Duration::minutes(42);
         time_segment.hue = 200;
+        time_segment.strategy = Some(SchedulingStrategy::Shuffled { seed: 7 });
         connection
             .update_time_segment(time_segment.clone())
             .await
@@ -569,6 +2363,470 @@ mod tests {
 
         let time_segment_from_db = connection.all_time_segments().await.unwrap().pop().unwrap();
         assert_eq!(time_segment_from_db, time_segment);
+
+        // Clearing the strategy falls back to the one for all segments again
+        time_segment.strategy = None;
+        connection
+            .update_time_segment(time_segment.clone())
+            .await
+            .unwrap();
+        let time_segment_from_db = connection.all_time_segments().await.unwrap().pop().unwrap();
+        assert_eq!(time_segment_from_db.strategy, None);
+    }
+
+    #[test]
+    async fn test_rename_time_segment() {
+        let connection = make_connection(":memory:").unwrap();
+        let added = connection
+            .add_time_segment(test_time_segment())
+            .await
+            .unwrap();
+        // Any change to the ranges would fail
+        connection
+            .get_connection()
+            .unwrap()
+            .batch_execute(
+                "CREATE TRIGGER keep_ranges_on_delete BEFORE DELETE ON time_segment_ranges \
+                 BEGIN SELECT RAISE(ABORT, 'ranges were deleted'); END; \
+                 CREATE TRIGGER keep_ranges_on_insert BEFORE INSERT ON time_segment_ranges \
+                 BEGIN SELECT RAISE(ABORT, 'ranges were inserted'); END;",
+            )
+            .unwrap();
+
+        connection
+            .rename_time_segment(added.id, "renamed")
+            .await
+            .unwrap();
+        let renamed = connection.get_time_segment(added.id).await.unwrap();
+        assert_eq!(renamed.name, "renamed");
+        assert_eq!(renamed.ranges, added.ranges);
+        assert!(connection
+            .rename_time_segment(added.id + 1, "renamed")
+            .await
+            .unwrap_err()
+            .is_not_found());
+    }
+
+    #[test]
+    async fn test_roll_back_time_segments_when_a_range_fails() {
+        let connection = make_connection(":memory:").unwrap();
+        let mut time_segment = test_time_segment();
+        let start = time_segment.start;
+        let poisoned = start + Duration::hours(3)..start + Duration::hours(4);
+        // Make inserting this one range fail, after the segment itself and its
+        // first range have been inserted
+        connection
+            .get_connection()
+            .unwrap()
+            .batch_execute(&format!(
+                "CREATE TRIGGER fail_range BEFORE INSERT ON time_segment_ranges \
+                 WHEN NEW.start = {} BEGIN SELECT RAISE(ABORT, 'poisoned range'); END;",
+                poisoned.start.timestamp()
+            ))
+            .unwrap();
+
+        time_segment.ranges.push(poisoned.clone());
+        assert!(connection
+            .add_time_segment(time_segment.clone())
+            .await
+            .is_err());
+        let time_segments = connection.all_time_segments().await.unwrap();
+        assert_eq!(time_segments.len(), 1);
+        assert_eq!(time_segments[0].name, "Default");
+        let n_ranges = time_segment_range_table
+            .count()
+            .get_result::<i64>(&connection.get_connection().unwrap())
+            .unwrap();
+        assert_eq!(n_ranges, time_segments[0].ranges.len() as i64);
+
+        // An update which fails halfway leaves the old ranges in place
+        time_segment.ranges.pop();
+        let added = connection.add_time_segment(time_segment).await.unwrap();
+        let mut changed = added.clone();
+        changed.name = "changed name".to_string();
+        changed.ranges = vec![start..start + Duration::hours(1), poisoned];
+        assert!(connection.update_time_segment(changed).await.is_err());
+        assert_eq!(connection.get_time_segment(added.id).await.unwrap(), added);
+    }
+
+    #[test]
+    async fn test_roll_back_tasks_when_their_dependencies_fail() {
+        let connection = make_connection(":memory:").unwrap();
+        let prerequisite = connection.add_task(test_task()).await.unwrap();
+        let dependent = connection
+            .add_task(crate::NewTask {
+                depends_on: vec![prerequisite.id],
+                ..test_task()
+            })
+            .await
+            .unwrap();
+        // Make writing any dependency fail, after the task itself is written
+        connection
+            .get_connection()
+            .unwrap()
+            .batch_execute(
+                "CREATE TRIGGER fail_dependency BEFORE INSERT ON task_dependencies \
+                 BEGIN SELECT RAISE(ABORT, 'poisoned dependency'); END;",
+            )
+            .unwrap();
+
+        assert!(connection
+            .add_task(crate::NewTask {
+                depends_on: vec![prerequisite.id],
+                ..test_task()
+            })
+            .await
+            .is_err());
+        assert_eq!(connection.count_tasks().await.unwrap(), 2);
+
+        let mut changed = prerequisite.clone();
+        changed.content = "changed content".to_string();
+        changed.depends_on = vec![dependent.id];
+        assert!(connection.update_task(changed).await.is_err());
+        let changes = crate::TaskChanges {
+            content: Some("changed content".to_string()),
+            depends_on: Some(vec![dependent.id]),
+            ..Default::default()
+        };
+        assert!(connection
+            .update_task_fields(prerequisite.id, changes)
+            .await
+            .is_err());
+        assert_eq!(
+            connection.get_task(prerequisite.id).await.unwrap(),
+            prerequisite
+        );
+
+        // A delete which fails halfway leaves the task and what depends on it
+        connection
+            .get_connection()
+            .unwrap()
+            .batch_execute(
+                "CREATE TRIGGER keep_dependency BEFORE DELETE ON task_dependencies \
+                 BEGIN SELECT RAISE(ABORT, 'kept dependency'); END;",
+            )
+            .unwrap();
+        assert!(connection.delete_task(prerequisite.id).await.is_err());
+        assert_eq!(
+            connection.get_task(prerequisite.id).await.unwrap(),
+            prerequisite
+        );
+        assert_eq!(connection.get_task(dependent.id).await.unwrap(), dependent);
+    }
+
+    #[test]
+    async fn test_with_transaction() {
+        let connection = make_connection(":memory:").unwrap();
+        let count = || {
+            time_segment_table
+                .count()
+                .get_result::<i64>(&connection.get_connection().unwrap())
+                .unwrap()
+        };
+        let insert = |connection: &SqliteConnection| {
+            diesel::insert_into(time_segment_table)
+                .values(&NewTimeSegment::from(test_time_segment()))
+                .execute(connection)
+                .map_err(|e| Error("while testing transactions", e.into()))
+        };
+
+        connection.with_transaction(insert).await.unwrap();
+        assert_eq!(count(), 2);
+        let result = connection
+            .with_transaction(|connection| {
+                insert(connection)?;
+                Err::<(), _>(Error("while testing transactions", "oops".into()))
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(count(), 2);
+    }
+
+    #[test]
+    async fn test_migrations_only_run_when_needed() {
+        let path = std::env::temp_dir().join(format!("eva-test-{}.sqlite", std::process::id()));
+        let database_url = path.to_str().unwrap();
+
+        // Without migrations, there's no schema yet
+        let connection = connect(database_url).unwrap();
+        assert!(connection.all_tasks().await.is_err());
+
+        let versions = connection.run_pending_migrations().unwrap();
+        assert_eq!(
+            versions,
+            [
+                "20170507064915",
+                "20190131052443",
+                "20190728094607",
+                "20261015093000",
+                "20261015141500",
+                "20261015160000",
+                "20261015170000",
+                "20261015180000",
+                "20261015190000",
+                "20261015210000",
+                "20261015220000",
+                "20261015230000",
+                "20261015233000"
+            ]
+        );
+        connection.add_task(test_task()).await.unwrap();
+        drop(connection);
+
+        // A second connection finds the schema up to date and leaves it alone
+        let connection = connect(database_url).unwrap();
+        assert!(connection.run_pending_migrations().unwrap().is_empty());
+        assert_eq!(connection.all_tasks().await.unwrap().len(), 1);
+        drop(connection);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    async fn test_pool_of_connections() {
+        assert_eq!(DbConfig::for_url(":memory:").pool_size, 1);
+
+        let path =
+            std::env::temp_dir().join(format!("eva-test-pool-{}.sqlite", std::process::id()));
+        let database_url = path.to_str().unwrap();
+        let config = DbConfig {
+            pool_size: 2,
+            timeout: std::time::Duration::from_secs(1),
+            ..DbConfig::for_url(database_url)
+        };
+        let connection = connect_with(database_url, config).unwrap();
+        connection.run_pending_migrations().unwrap();
+
+        // While one connection is checked out, the other one still works and
+        // sees the same database
+        let checked_out = connection.get_connection().unwrap();
+        let task = connection.add_task(test_task()).await.unwrap();
+        assert_eq!(
+            task_table
+                .load::<Task>(&checked_out)
+                .unwrap()
+                .pop()
+                .unwrap()
+                .id,
+            task.id as i32
+        );
+        drop(checked_out);
+
+        drop(connection);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    async fn test_ids_of_adds_in_between_other_inserts() {
+        let path = std::env::temp_dir().join(format!("eva-test-ids-{}.sqlite", std::process::id()));
+        let database_url = path.to_str().unwrap();
+        let config = DbConfig {
+            pool_size: 2,
+            timeout: std::time::Duration::from_secs(1),
+            ..DbConfig::for_url(database_url)
+        };
+        let connection = connect_with(database_url, config).unwrap();
+        connection.run_pending_migrations().unwrap();
+
+        // Another connection adds tasks before and after each add, which
+        // shouldn't make either add return the id of someone else's task. This
+        // only checks the ids in sequence: the other connection can't get in
+        // between the insert and fetching its id within an add.
+        let other = connection.get_connection().unwrap();
+        let other_task = || {
+            diesel::insert_into(task_table)
+                .values(&NewTask::from(crate::NewTask {
+                    content: "not mine".to_string(),
+                    ..test_task()
+                }))
+                .execute(&other)
+                .unwrap();
+        };
+        other_task();
+        let first = connection
+            .add_task(crate::NewTask {
+                content: "first".to_string(),
+                ..test_task()
+            })
+            .await
+            .unwrap();
+        other_task();
+        let second = connection
+            .add_task(crate::NewTask {
+                content: "second".to_string(),
+                ..test_task()
+            })
+            .await
+            .unwrap();
+        other_task();
+
+        assert_eq!(first.content, "first");
+        assert_eq!(second.content, "second");
+        let stored = task_table.load::<Task>(&other).unwrap();
+        assert_eq!(stored.len(), 5);
+        for task in &[first, second] {
+            let row = stored.iter().find(|row| row.id == task.id as i32).unwrap();
+            assert_eq!(row.content, task.content);
+        }
+        drop(other);
+
+        drop(connection);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    async fn test_vacuum() {
+        let path =
+            std::env::temp_dir().join(format!("eva-test-vacuum-{}.sqlite", std::process::id()));
+        let database_url = path.to_str().unwrap();
+        let connection = make_connection(database_url).unwrap();
+        let new_tasks = (0..500)
+            .map(|number| crate::NewTask {
+                content: format!("task {} {}", number, "x".repeat(1000)),
+                ..test_task()
+            })
+            .collect_vec();
+        let tasks = connection.bulk_add_tasks(new_tasks).await.unwrap();
+        let kept = tasks[0].clone();
+        for task in &tasks[1..] {
+            connection.delete_task(task.id).await.unwrap();
+        }
+        let size_before = std::fs::metadata(&path).unwrap().len();
+
+        connection.vacuum().await.unwrap();
+        assert!(std::fs::metadata(&path).unwrap().len() < size_before);
+        assert_eq!(connection.all_tasks().await.unwrap(), [kept]);
+
+        // There's nothing to do for an in-memory database
+        make_connection(":memory:").unwrap().vacuum().await.unwrap();
+
+        drop(connection);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    async fn test_bulk_add_tasks() {
+        let path =
+            std::env::temp_dir().join(format!("eva-test-bulk-{}.sqlite", std::process::id()));
+        let database_url = path.to_str().unwrap();
+        let connection = connect_with(database_url, DbConfig::for_url(database_url)).unwrap();
+        connection.run_pending_migrations().unwrap();
+        let numbered = |number| crate::NewTask {
+            content: format!("task {}", number),
+            ..test_task()
+        };
+
+        let prerequisite = connection.add_task(numbered(0)).await.unwrap();
+        let mut new_tasks = (1..=500).map(numbered).collect_vec();
+        new_tasks[499].depends_on = vec![prerequisite.id];
+        let tasks = connection.bulk_add_tasks(new_tasks.clone()).await.unwrap();
+
+        assert_eq!(tasks.len(), 500);
+        assert_eq!(connection.count_tasks().await.unwrap(), 501);
+        for (task, new_task) in tasks.iter().zip(&new_tasks) {
+            assert_eq!(task, new_task);
+        }
+        assert!(tasks
+            .iter()
+            .tuple_windows()
+            .all(|(previous, next)| previous.id < next.id));
+        assert_eq!(
+            connection.get_task(tasks[499].id).await.unwrap().depends_on,
+            [prerequisite.id]
+        );
+
+        // Nothing is added when a task is invalid
+        let mut invalid = numbered(501);
+        invalid.depends_on = vec![42_000];
+        assert!(connection
+            .bulk_add_tasks(vec![numbered(502), invalid])
+            .await
+            .is_err());
+        assert_eq!(connection.count_tasks().await.unwrap(), 501);
+        assert!(connection.bulk_add_tasks(vec![]).await.unwrap().is_empty());
+
+        drop(connection);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    // Run with `cargo test --features sqlite -- --ignored bench_bulk_add_tasks`.
+    // It's left out of the normal run, since timings vary too much between
+    // machines to assert on.
+    #[test]
+    #[ignore]
+    async fn bench_bulk_add_tasks() {
+        let path =
+            std::env::temp_dir().join(format!("eva-bench-bulk-{}.sqlite", std::process::id()));
+        let database_url = path.to_str().unwrap();
+        let connection = connect_with(database_url, DbConfig::for_url(database_url)).unwrap();
+        connection.run_pending_migrations().unwrap();
+        let new_tasks = (0..1000)
+            .map(|number| crate::NewTask {
+                content: format!("task {}", number),
+                ..test_task()
+            })
+            .collect_vec();
+
+        let start = std::time::Instant::now();
+        for task in new_tasks.clone() {
+            connection.add_task(task).await.unwrap();
+        }
+        let one_by_one = start.elapsed();
+        let start = std::time::Instant::now();
+        connection.bulk_add_tasks(new_tasks).await.unwrap();
+        let in_bulk = start.elapsed();
+
+        println!("add_task in a loop: {one_by_one:?}, bulk_add_tasks: {in_bulk:?}");
+        assert!(in_bulk * 5 < one_by_one);
+
+        drop(connection);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    async fn test_retry_while_the_database_is_locked() {
+        let path =
+            std::env::temp_dir().join(format!("eva-test-locked-{}.sqlite", std::process::id()));
+        let database_url = path.to_str().unwrap().to_owned();
+        let config = DbConfig {
+            pool_size: 1,
+            timeout: std::time::Duration::from_millis(50),
+            retries: 0,
+            retry_delay: std::time::Duration::from_millis(50),
+        };
+        let impatient = connect_with(&database_url, config).unwrap();
+        impatient.run_pending_migrations().unwrap();
+
+        // Another process keeps the database locked for a while
+        let (locked, lock_taken) = std::sync::mpsc::channel();
+        let locker = {
+            let database_url = database_url.clone();
+            std::thread::spawn(move || {
+                let other = SqliteConnection::establish(&database_url).unwrap();
+                other.batch_execute("BEGIN EXCLUSIVE;").unwrap();
+                locked.send(()).unwrap();
+                std::thread::sleep(std::time::Duration::from_millis(300));
+                other.batch_execute("COMMIT;").unwrap();
+            })
+        };
+        lock_taken.recv().unwrap();
+
+        let error = impatient.add_task(test_task()).await.unwrap_err();
+        assert!(is_locked(&error));
+        let patient = connect_with(
+            &database_url,
+            DbConfig {
+                retries: 5,
+                ..config
+            },
+        )
+        .unwrap();
+        let task = patient.add_task(test_task()).await.unwrap();
+        assert_eq!(patient.all_tasks().await.unwrap(), [task]);
+        locker.join().unwrap();
+
+        drop((impatient, patient));
+        std::fs::remove_file(path).unwrap();
     }
 
     fn test_task() -> crate::NewTask {
@@ -576,8 +2834,10 @@ mod tests {
             content: "do me".to_string(),
             deadline: Utc::now().with_nanosecond(0).unwrap(),
             duration: Duration::seconds(6),
-            importance: 42,
+            importance: 4,
+            confidence: Confidence::Low,
             time_segment_id: 0,
+            depends_on: vec![],
         }
     }
 
@@ -589,6 +2849,7 @@ mod tests {
             start,
             period: Duration::weeks(1),
             hue: 0,
+            strategy: None,
         }
     }
 }
@@ -1,9 +1,9 @@
 #![feature(box_patterns)]
 
-#[cfg(feature = "sqlite")]
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
 #[macro_use]
 extern crate diesel;
-#[cfg(feature = "sqlite")]
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
 #[macro_use]
 extern crate diesel_migrations;
 
@@ -11,17 +11,30 @@ extern crate diesel_migrations;
 #[macro_use]
 extern crate assert_matches;
 
+use std::ops::{Range, RangeInclusive};
+
 use chrono::prelude::*;
 use chrono::Duration;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use crate::configuration::{Configuration, SchedulingStrategy};
+use crate::database::{Database, TaskOrder};
+use crate::time_segment::TimeSegment;
 
-pub use crate::scheduling::{Schedule, Scheduled};
+pub use crate::scheduling::{
+    Explanation, Lateness, Moved, Part, Precedence, Reason, Schedule, ScheduleDiff, Scheduled,
+    SegmentCapacity, SortKey,
+};
 
 pub mod configuration;
 pub mod database;
-mod scheduling;
+pub mod load_balancing;
+pub mod scheduling;
+#[cfg(feature = "serde")]
+mod serialization;
+pub mod statistics;
 pub mod time_segment;
 mod util;
 
@@ -31,27 +44,283 @@ pub enum Error {
     Database(#[from] crate::database::Error),
     #[error(transparent)]
     Schedule(#[from] crate::scheduling::Error<Task>),
+    #[error(transparent)]
+    TimeSegment(#[from] crate::time_segment::TimeSegmentError),
+    #[error(transparent)]
+    Validation(#[from] ValidationError),
+    #[error(transparent)]
+    Strategy(#[from] configuration::UnknownStrategy),
+}
+
+impl Error {
+    /// Returns whether the error occurred because a task or time segment
+    /// doesn't exist.
+    pub fn is_not_found(&self) -> bool {
+        match self {
+            Error::Database(error) => error.is_not_found(),
+            Error::Schedule(error) => matches!(error, scheduling::Error::NotScheduled { .. }),
+            Error::TimeSegment(_) | Error::Validation(_) | Error::Strategy(_) => false,
+        }
+    }
+
+    /// Returns which constraint on time segments the operation would have
+    /// broken, if that's why it failed.
+    pub fn segment_constraint(&self) -> Option<&crate::database::SegmentConstraintError> {
+        match self {
+            Error::Database(error) => error.segment_constraint(),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the tasks couldn't be scheduled because one of them
+    /// can't be finished before its deadline, even on its own.
+    pub fn is_deadline_missed(&self) -> bool {
+        matches!(
+            self,
+            Error::Schedule(scheduling::Error::DeadlineMissed { .. })
+        )
+    }
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
 
-#[derive(Debug, Clone)]
+/// The scale tasks are rated on for their importance.
+pub const IMPORTANCE: RangeInclusive<u32> = 1..=10;
+
+/// Why a task can't be added or changed as it is, or tasks can't be looked up
+/// like that.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ValidationError {
+    #[error("The importance of a task has to be from 1 to 10, but it's {importance}.")]
+    ImportanceOutOfRange { importance: u32 },
+    #[error("The start of the deadline range ({from}) comes after its end ({to}).")]
+    InvertedDeadlineRange {
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    },
+}
+
+fn validate_importance(importance: u32) -> std::result::Result<(), ValidationError> {
+    if IMPORTANCE.contains(&importance) {
+        Ok(())
+    } else {
+        Err(ValidationError::ImportanceOutOfRange { importance })
+    }
+}
+
+/// Brings an importance stored before it was validated back on the scale.
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+fn clamp_importance(importance: i32) -> u32 {
+    importance.clamp(*IMPORTANCE.start() as i32, *IMPORTANCE.end() as i32) as u32
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct NewTask {
     pub content: String,
     pub deadline: DateTime<Utc>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            serialize_with = "serialization::serialize_duration_as_seconds",
+            deserialize_with = "serialization::deserialize_duration_from_seconds"
+        )
+    )]
     pub duration: Duration,
     pub importance: u32,
+    pub confidence: Confidence,
     pub time_segment_id: u32,
+    /// The ids of the tasks which have to be finished before this one can start
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub depends_on: Vec<u32>,
+}
+
+impl NewTask {
+    /// Returns why the task can't be added as it is, if it can't.
+    pub fn validate(&self) -> std::result::Result<(), ValidationError> {
+        validate_importance(self.importance)
+    }
+
+    /// Tells whether the task takes longer than the time left until its
+    /// deadline, so no schedule starting at `now` can fit it. It ignores the
+    /// time segment and the other tasks, so a task which passes this check
+    /// might still not fit.
+    pub fn is_trivially_infeasible(&self, now: DateTime<Utc>) -> bool {
+        self.duration > self.deadline - now
+    }
+
+    // Tweak a copy of a task before adding it again, like
+    // `NewTask::from(&task).with_deadline(next_week)`.
+
+    pub fn with_content(self, content: impl Into<String>) -> Self {
+        NewTask {
+            content: content.into(),
+            ..self
+        }
+    }
+
+    pub fn with_deadline(self, deadline: DateTime<Utc>) -> Self {
+        NewTask { deadline, ..self }
+    }
+
+    pub fn with_duration(self, duration: Duration) -> Self {
+        NewTask { duration, ..self }
+    }
+
+    pub fn with_importance(self, importance: u32) -> Self {
+        NewTask { importance, ..self }
+    }
+
+    pub fn with_confidence(self, confidence: Confidence) -> Self {
+        NewTask { confidence, ..self }
+    }
+
+    pub fn with_time_segment_id(self, time_segment_id: u32) -> Self {
+        NewTask {
+            time_segment_id,
+            ..self
+        }
+    }
+
+    pub fn with_depends_on(self, depends_on: Vec<u32>) -> Self {
+        NewTask { depends_on, ..self }
+    }
+}
+
+/// Changes to some of the fields of a task. The fields which are `None` are
+/// left as they are.
+#[derive(Debug, Clone, Default)]
+pub struct TaskChanges {
+    pub content: Option<String>,
+    pub deadline: Option<DateTime<Utc>>,
+    pub duration: Option<Duration>,
+    pub importance: Option<u32>,
+    pub confidence: Option<Confidence>,
+    pub time_segment_id: Option<u32>,
+    pub depends_on: Option<Vec<u32>>,
+    /// `Some(None)` clears the color of the task
+    pub color: Option<Option<u16>>,
+    pub pinned: Option<bool>,
 }
 
-#[derive(Debug, Eq, PartialEq, Clone, Hash)]
+impl TaskChanges {
+    /// Makes the changes to the given task.
+    pub fn apply_to(self, task: &mut Task) {
+        if let Some(content) = self.content {
+            task.content = content;
+        }
+        if let Some(deadline) = self.deadline {
+            task.deadline = deadline;
+        }
+        if let Some(duration) = self.duration {
+            task.duration = duration;
+        }
+        if let Some(importance) = self.importance {
+            task.importance = importance;
+        }
+        if let Some(confidence) = self.confidence {
+            task.confidence = confidence;
+        }
+        if let Some(time_segment_id) = self.time_segment_id {
+            task.time_segment_id = time_segment_id;
+        }
+        if let Some(depends_on) = self.depends_on {
+            task.depends_on = depends_on;
+        }
+        if let Some(color) = self.color {
+            task.color = color;
+        }
+        if let Some(pinned) = self.pinned {
+            task.pinned = pinned;
+        }
+    }
+}
+
+/// A change to the tasks, with all it takes to undo it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operation {
+    /// The task was added.
+    Add { task: Task },
+    /// The task was deleted, so the tasks with the ids in `dependents` didn't
+    /// depend on it anymore.
+    Delete { task: Task, dependents: Vec<u32> },
+    /// A task was changed, and this is how it was before.
+    Update { before: Task },
+}
+
+impl Operation {
+    /// The task which was added, deleted or changed.
+    pub fn task(&self) -> &Task {
+        match self {
+            Operation::Add { task } | Operation::Delete { task, .. } => task,
+            Operation::Update { before } => before,
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Clone, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Task {
     pub id: u32,
     pub content: String,
     pub deadline: DateTime<Utc>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            serialize_with = "serialization::serialize_duration_as_seconds",
+            deserialize_with = "serialization::deserialize_duration_from_seconds"
+        )
+    )]
     pub duration: Duration,
     pub importance: u32,
+    pub confidence: Confidence,
     pub time_segment_id: u32,
+    /// The ids of the tasks which have to be finished before this one can start
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub depends_on: Vec<u32>,
+    /// The hue to show this task in, instead of the hue of its time segment.
+    /// It doesn't matter for scheduling.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub color: Option<u16>,
+    /// When the last persisted schedule planned this task, if it did. It
+    /// doesn't matter for scheduling either.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub scheduled_for: Option<DateTime<Utc>>,
+    /// When the task was marked as done, if it was. Tasks which are done are
+    /// kept for history, but they aren't listed or scheduled anymore.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub done_at: Option<DateTime<Utc>>,
+    /// Pinned tasks go first in their time segment, whatever their importance.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "std::ops::Not::not")
+    )]
+    pub pinned: bool,
+}
+
+/// How much the user trusts the estimated duration of a task. Less trusted
+/// estimates get some extra time when scheduling.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Confidence {
+    Low,
+    Medium,
+    #[default]
+    High,
+}
+
+impl Confidence {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+        }
+    }
 }
 
 impl PartialEq<NewTask> for Task {
@@ -60,22 +329,93 @@ impl PartialEq<NewTask> for Task {
             && self.deadline == other.deadline
             && self.duration == other.duration
             && self.importance == other.importance
+            && self.confidence == other.confidence
             && self.time_segment_id == other.time_segment_id
+            && self.depends_on == other.depends_on
+    }
+}
+
+/// Copies a task to add it again. Only what can be given when adding a task is
+/// copied: its id, color, when it was planned and done, and whether it's
+/// pinned are left behind.
+impl From<&Task> for NewTask {
+    fn from(task: &Task) -> NewTask {
+        task.clone().into_new()
+    }
+}
+
+impl Task {
+    /// Like `NewTask::from`, without copying the task.
+    pub fn into_new(self) -> NewTask {
+        NewTask {
+            content: self.content,
+            deadline: self.deadline,
+            duration: self.duration,
+            importance: self.importance,
+            confidence: self.confidence,
+            time_segment_id: self.time_segment_id,
+            depends_on: self.depends_on,
+        }
     }
 }
 
 pub async fn add_task(configuration: &Configuration, new_task: NewTask) -> Result<Task> {
-    configuration
-        .database
-        .add_task(new_task)
+    new_task.validate()?;
+    let database = &configuration.database;
+    let task = database.add_task(new_task).await.map_err(Error::Database)?;
+    database
+        .log_operation(Operation::Add { task: task.clone() })
         .await
-        .map_err(Error::Database)
+        .map_err(Error::Database)?;
+    Ok(task)
 }
 
 pub async fn delete_task(configuration: &Configuration, id: u32) -> Result<()> {
+    let database = &configuration.database;
+    let task = database.get_task(id).await.map_err(Error::Database)?;
+    let dependents = database
+        .all_tasks()
+        .await
+        .map_err(Error::Database)?
+        .into_iter()
+        .filter(|dependent| dependent.depends_on.contains(&id))
+        .map(|dependent| dependent.id)
+        .collect();
+    database.delete_task(id).await.map_err(Error::Database)?;
+    database
+        .log_operation(Operation::Delete { task, dependents })
+        .await
+        .map_err(Error::Database)
+}
+
+/// Marks a task as done, so it isn't listed or scheduled anymore. It's kept for
+/// history until it's cleared with `clear_completed`. A task which is done
+/// already is left as it was.
+pub async fn complete_task(configuration: &Configuration, id: u32) -> Result<()> {
+    let database = &configuration.database;
+    let before = database.get_task(id).await.map_err(Error::Database)?;
+    if before.done_at.is_some() {
+        return Ok(());
+    }
+    database
+        .complete_task(id, configuration.now())
+        .await
+        .map_err(Error::Database)?;
+    database
+        .log_operation(Operation::Update { before })
+        .await
+        .map_err(Error::Database)
+}
+
+/// Deletes the tasks which were done before `older_than`, or all tasks which
+/// are done if it's `None`, for good. Returns how many tasks were deleted.
+pub async fn clear_completed(
+    configuration: &Configuration,
+    older_than: Option<DateTime<Utc>>,
+) -> Result<u64> {
     configuration
         .database
-        .delete_task(id)
+        .clear_completed(older_than)
         .await
         .map_err(Error::Database)
 }
@@ -88,10 +428,107 @@ pub async fn get_task(configuration: &Configuration, id: u32) -> Result<Task> {
         .map_err(Error::Database)
 }
 
+/// Returns how much time the task would have to spare if it were started right
+/// now. It's negative when the task can't be finished before its deadline
+/// anymore.
+pub async fn task_slack(configuration: &Configuration, id: u32) -> Result<Duration> {
+    let task = get_task(configuration, id).await?;
+    Ok(scheduling::Task::slack(&task, configuration.now()))
+}
+
+/// Returns the tasks with the given ids, in the same order.
+pub async fn get_tasks(configuration: &Configuration, ids: &[u32]) -> Result<Vec<Task>> {
+    configuration
+        .database
+        .get_tasks(ids)
+        .await
+        .map_err(Error::Database)
+}
+
 pub async fn update_task(configuration: &Configuration, task: Task) -> Result<()> {
+    validate_importance(task.importance)?;
+    let database = &configuration.database;
+    let before = database.get_task(task.id).await.map_err(Error::Database)?;
+    database.update_task(task).await.map_err(Error::Database)?;
+    database
+        .log_operation(Operation::Update { before })
+        .await
+        .map_err(Error::Database)
+}
+
+/// Changes only the given fields of a task, leaving any other changes made to
+/// it in the meantime intact, and returns the task as it is now.
+pub async fn update_task_fields(
+    configuration: &Configuration,
+    id: u32,
+    changes: TaskChanges,
+) -> Result<Task> {
+    if let Some(importance) = changes.importance {
+        validate_importance(importance)?;
+    }
+    let database = &configuration.database;
+    let before = database.get_task(id).await.map_err(Error::Database)?;
+    let task = database
+        .update_task_fields(id, changes)
+        .await
+        .map_err(Error::Database)?;
+    database
+        .log_operation(Operation::Update { before })
+        .await
+        .map_err(Error::Database)?;
+    Ok(task)
+}
+
+/// Reverts the last change to the tasks which wasn't undone yet and returns
+/// it, or `None` if there is nothing left to undo. Only the last
+/// `database::MAX_LOGGED_OPERATIONS` changes can be undone.
+pub async fn undo(configuration: &Configuration) -> Result<Option<Operation>> {
+    let database = &configuration.database;
+    let operation = match database.pop_operation().await.map_err(Error::Database)? {
+        Some(operation) => operation,
+        None => return Ok(None),
+    };
+    if let Err(error) = revert(&**database, &operation).await {
+        // Keep the operation around to undo it later
+        database
+            .log_operation(operation)
+            .await
+            .map_err(Error::Database)?;
+        return Err(Error::Database(error));
+    }
+    Ok(Some(operation))
+}
+
+async fn revert(database: &dyn Database, operation: &Operation) -> database::Result<()> {
+    match operation {
+        Operation::Add { task } => database.delete_task(task.id).await,
+        Operation::Delete { task, dependents } => {
+            database.restore_task(task.clone()).await?;
+            for mut dependent in database.get_tasks(dependents).await? {
+                dependent.depends_on.push(task.id);
+                dependent.depends_on.sort_unstable();
+                database.update_task(dependent).await?;
+            }
+            Ok(())
+        }
+        Operation::Update { before } => database.update_task(before.clone()).await,
+    }
+}
+
+/// Stores when the schedule plans each of its tasks in their `scheduled_for`,
+/// and clears it for the tasks which aren't in the schedule.
+pub async fn persist_schedule(
+    configuration: &Configuration,
+    schedule: &Schedule<Task>,
+) -> Result<()> {
+    let scheduled = schedule
+        .0
+        .iter()
+        .map(|scheduled| (scheduled.task.id, scheduled.when))
+        .collect::<Vec<_>>();
     configuration
         .database
-        .update_task(task)
+        .set_scheduled_for(&scheduled)
         .await
         .map_err(Error::Database)
 }
@@ -104,27 +541,260 @@ pub async fn tasks(configuration: &Configuration) -> Result<Vec<Task>> {
         .map_err(Error::Database)
 }
 
-pub async fn schedule(configuration: &Configuration, strategy: &str) -> Result<Schedule<Task>> {
-    let strategy = match strategy {
-        "importance" => SchedulingStrategy::Importance,
-        "urgency" => SchedulingStrategy::Urgency,
-        _ => panic!("Unsupported scheduling strategy provided"),
-    };
-    // Ensure everything is scheduled for some time after the algorithm has
-    // finished.
-    let start = configuration.now() + Duration::minutes(1);
-    let tasks_per_segment = configuration
+pub async fn tasks_updated_since(
+    configuration: &Configuration,
+    since: DateTime<Utc>,
+) -> Result<Vec<Task>> {
+    configuration
+        .database
+        .tasks_updated_since(since)
+        .await
+        .map_err(Error::Database)
+}
+
+/// Returns the tasks which are due at or after `from` and before `to`, where
+/// either bound can be left out.
+pub async fn tasks_due_between(
+    configuration: &Configuration,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> Result<Vec<Task>> {
+    if let (Some(from), Some(to)) = (from, to) {
+        if from > to {
+            return Err(ValidationError::InvertedDeadlineRange { from, to }.into());
+        }
+    }
+    configuration
+        .database
+        .tasks_in_deadline_range(from, to)
+        .await
+        .map_err(Error::Database)
+}
+
+/// Returns at most `limit` tasks in the given order, after skipping the first
+/// `offset` ones.
+pub async fn tasks_page(
+    configuration: &Configuration,
+    order: TaskOrder,
+    limit: u32,
+    offset: u32,
+) -> Result<Vec<Task>> {
+    configuration
+        .database
+        .tasks_page(order, limit, offset)
+        .await
+        .map_err(Error::Database)
+}
+
+/// Finds the tasks whose content contains `query`, ignoring case.
+pub async fn search_tasks(configuration: &Configuration, query: &str) -> Result<Vec<Task>> {
+    configuration
+        .database
+        .search_tasks(query)
+        .await
+        .map_err(Error::Database)
+}
+
+pub async fn task_count(configuration: &Configuration) -> Result<u64> {
+    configuration
+        .database
+        .count_tasks()
+        .await
+        .map_err(Error::Database)
+}
+
+pub async fn tasks_per_time_segment(
+    configuration: &Configuration,
+) -> Result<Vec<(time_segment::NamedTimeSegment, Vec<Task>)>> {
+    configuration
         .database
         .all_tasks_per_time_segment()
         .await
-        .map_err(Error::Database)?;
-    Schedule::schedule(start, tasks_per_segment, strategy).map_err(Error::Schedule)
+        .map_err(Error::Database)
+}
+
+/// Like `tasks_per_time_segment`, but without the time segments which don't
+/// have any tasks to do.
+pub async fn tasks_per_nonempty_segment(
+    configuration: &Configuration,
+) -> Result<Vec<(time_segment::NamedTimeSegment, Vec<Task>)>> {
+    configuration
+        .database
+        .tasks_per_nonempty_segment()
+        .await
+        .map_err(Error::Database)
+}
+
+/// Leaves out the strategies of the time segments, unless `per_segment` is
+/// set, so all tasks are scheduled with the same strategy. Time segments are
+/// only scheduled with a strategy of their own when that's asked for.
+pub fn with_segment_strategies(
+    mut tasks_per_segment: Vec<(time_segment::NamedTimeSegment, Vec<Task>)>,
+    per_segment: bool,
+) -> Vec<(time_segment::NamedTimeSegment, Vec<Task>)> {
+    if !per_segment {
+        for (time_segment, _) in &mut tasks_per_segment {
+            time_segment.strategy = None;
+        }
+    }
+    tasks_per_segment
+}
+
+/// Schedules all tasks in the database, starting from `start` or, when that's
+/// not given, from right now.
+pub async fn schedule(
+    configuration: &Configuration,
+    strategy: &str,
+    start: Option<DateTime<Utc>>,
+) -> Result<Schedule<Task>> {
+    let tasks_per_segment =
+        with_segment_strategies(tasks_per_nonempty_segment(configuration).await?, false);
+    schedule_tasks(configuration, strategy, tasks_per_segment, start, &[])
+}
+
+/// Schedules only the given tasks instead of all tasks in the database, e.g.
+/// when the caller wants to leave some of them out. Nothing is scheduled during
+/// the `busy` ranges.
+pub fn schedule_tasks(
+    configuration: &Configuration,
+    strategy: &str,
+    tasks_per_segment: Vec<(impl time_segment::TimeSegment, Vec<Task>)>,
+    start: Option<DateTime<Utc>>,
+    busy: &[Range<DateTime<Utc>>],
+) -> Result<Schedule<Task>> {
+    let strategy: SchedulingStrategy = strategy.parse()?;
+    // Ensure everything is scheduled for some time after the algorithm has
+    // finished.
+    let start = start.unwrap_or_else(|| configuration.now() + Duration::minutes(1));
+    Schedule::schedule(
+        start,
+        tasks_per_segment,
+        strategy,
+        configuration.estimate_buffers,
+        configuration.granularity,
+        busy,
+        configuration.breaks(),
+    )
+    .map_err(Error::Schedule)
+}
+
+/// Schedules as many tasks in the database as fit from right now, instead of
+/// failing on the first one which doesn't. Returns the schedule along with the
+/// tasks which were left out.
+pub async fn schedule_best_effort(
+    configuration: &Configuration,
+    strategy: &str,
+) -> Result<(Schedule<Task>, Vec<Task>)> {
+    let tasks_per_segment =
+        with_segment_strategies(tasks_per_nonempty_segment(configuration).await?, false);
+    schedule_tasks_best_effort(configuration, strategy, tasks_per_segment, None, &[])
+}
+
+/// Like `schedule_best_effort`, but only for the given tasks, like
+/// `schedule_tasks`.
+pub fn schedule_tasks_best_effort(
+    configuration: &Configuration,
+    strategy: &str,
+    tasks_per_segment: Vec<(impl time_segment::TimeSegment, Vec<Task>)>,
+    start: Option<DateTime<Utc>>,
+    busy: &[Range<DateTime<Utc>>],
+) -> Result<(Schedule<Task>, Vec<Task>)> {
+    let strategy: SchedulingStrategy = strategy.parse()?;
+    let start = start.unwrap_or_else(|| configuration.now() + Duration::minutes(1));
+    Schedule::schedule_best_effort(
+        start,
+        tasks_per_segment,
+        strategy,
+        configuration.estimate_buffers,
+        configuration.granularity,
+        busy,
+        configuration.breaks(),
+    )
+    .map_err(Error::Schedule)
+}
+
+/// Schedules all of the given tasks like `schedule_tasks`, but lets them run
+/// past their deadline when there isn't enough time for them. Returns the
+/// schedule along with how late each task would be, the latest first.
+pub fn schedule_tasks_allowing_lateness(
+    configuration: &Configuration,
+    strategy: &str,
+    tasks_per_segment: Vec<(impl time_segment::TimeSegment, Vec<Task>)>,
+    start: Option<DateTime<Utc>>,
+    busy: &[Range<DateTime<Utc>>],
+) -> Result<(Schedule<Task>, Vec<Lateness<Task>>)> {
+    let strategy: SchedulingStrategy = strategy.parse()?;
+    let start = start.unwrap_or_else(|| configuration.now() + Duration::minutes(1));
+    Schedule::schedule_allowing_lateness(
+        start,
+        tasks_per_segment,
+        strategy,
+        configuration.estimate_buffers,
+        configuration.granularity,
+        busy,
+        configuration.breaks(),
+    )
+    .map_err(Error::Schedule)
+}
+
+/// Schedules as many of the given tasks as fit from `start` or, when that's
+/// not given, from right now, like `schedule_tasks_best_effort`, and explains
+/// why the task with the given id ends up where it is. Returns None if it
+/// doesn't fit in the schedule.
+pub fn explain_schedule(
+    configuration: &Configuration,
+    strategy: &str,
+    tasks_per_segment: Vec<(impl time_segment::TimeSegment, Vec<Task>)>,
+    start: Option<DateTime<Utc>>,
+    id: u32,
+) -> Result<Option<Explanation>> {
+    // The task is scheduled with the strategy of its own time segment, if any
+    let segment_strategy = tasks_per_segment
+        .iter()
+        .find(|(_, tasks)| tasks.iter().any(|task| task.id == id))
+        .and_then(|(time_segment, _)| time_segment.strategy());
+    let strategy_for_task = match segment_strategy {
+        Some(strategy) => strategy,
+        None => strategy.parse()?,
+    };
+    let start = start.unwrap_or_else(|| configuration.now() + Duration::minutes(1));
+    let (schedule, _left_out) =
+        schedule_tasks_best_effort(configuration, strategy, tasks_per_segment, Some(start), &[])?;
+    Ok(schedule.explain(id, start, strategy_for_task))
+}
+
+/// Checks for every time segment whether it has enough time for its tasks,
+/// without scheduling them.
+pub async fn capacity_report(configuration: &Configuration) -> Result<Vec<SegmentCapacity>> {
+    let tasks_per_segment = tasks_per_time_segment(configuration).await?;
+    Ok(Schedule::capacity_report(
+        configuration.now(),
+        tasks_per_segment,
+        configuration.estimate_buffers,
+    ))
+}
+
+pub async fn load_advice(configuration: &Configuration) -> Result<load_balancing::LoadAdvice> {
+    let tasks_per_segment = tasks_per_time_segment(configuration).await?;
+    Ok(load_balancing::advise(
+        configuration.now(),
+        tasks_per_segment,
+    ))
+}
+
+/// Sums up the tasks which are still to do, as a whole, per importance and per
+/// time segment.
+pub async fn statistics(configuration: &Configuration) -> Result<statistics::Statistics> {
+    let tasks = tasks(configuration).await?;
+    let time_segments = time_segments(configuration).await?;
+    Ok(statistics::compute(&tasks, &time_segments))
 }
 
 pub async fn add_time_segment(
     configuration: &Configuration,
     time_segment: time_segment::NewNamedTimeSegment,
-) -> Result<()> {
+) -> Result<time_segment::NamedTimeSegment> {
+    time_segment.validate()?;
     configuration
         .database
         .add_time_segment(time_segment)
@@ -147,6 +817,7 @@ pub async fn update_time_segment(
     configuration: &Configuration,
     time_segment: time_segment::NamedTimeSegment,
 ) -> Result<()> {
+    time_segment.validate()?;
     configuration
         .database
         .update_time_segment(time_segment)
@@ -154,6 +825,50 @@ pub async fn update_time_segment(
         .map_err(Error::Database)
 }
 
+/// Moves all tasks in one time segment to another, e.g. before deleting the
+/// first one. Returns how many tasks were moved.
+pub async fn move_tasks_between_segments(
+    configuration: &Configuration,
+    from: u32,
+    to: u32,
+) -> Result<u64> {
+    configuration
+        .database
+        .move_tasks_between_segments(from, to)
+        .await
+        .map_err(Error::Database)
+}
+
+/// Shrinks and defragments the database, e.g. after years of adding and
+/// deleting tasks.
+pub async fn vacuum(configuration: &Configuration) -> Result<()> {
+    configuration
+        .database
+        .vacuum()
+        .await
+        .map_err(Error::Database)
+}
+
+/// Renames a time segment without touching when it is.
+pub async fn rename_time_segment(configuration: &Configuration, id: u32, name: &str) -> Result<()> {
+    configuration
+        .database
+        .rename_time_segment(id, name)
+        .await
+        .map_err(Error::Database)
+}
+
+pub async fn get_time_segment(
+    configuration: &Configuration,
+    id: u32,
+) -> Result<time_segment::NamedTimeSegment> {
+    configuration
+        .database
+        .get_time_segment(id)
+        .await
+        .map_err(Error::Database)
+}
+
 pub async fn time_segments(
     configuration: &Configuration,
 ) -> Result<Vec<time_segment::NamedTimeSegment>> {
@@ -163,3 +878,405 @@ pub async fn time_segments(
         .await
         .map_err(Error::Database)
 }
+
+#[cfg(test)]
+mod tests {
+    use futures_test::test;
+
+    use super::*;
+    use crate::configuration::EstimateBuffers;
+    use crate::database::memory::MemoryDatabase;
+
+    fn configuration() -> Configuration {
+        Configuration {
+            database: Box::new(MemoryDatabase::new()),
+            scheduling_strategy: SchedulingStrategy::Importance,
+            estimate_buffers: EstimateBuffers::default(),
+            granularity: configuration::default_granularity(),
+            deadline_format: None,
+            max_continuous: None,
+            break_length: configuration::default_break_length(),
+            task_defaults: configuration::TaskDefaults::default(),
+        }
+    }
+
+    fn new_task(importance: u32) -> NewTask {
+        NewTask {
+            content: "do me".to_string(),
+            deadline: Utc::now() + Duration::days(1),
+            duration: Duration::hours(1),
+            importance,
+            confidence: Confidence::High,
+            time_segment_id: 0,
+            depends_on: vec![],
+        }
+    }
+
+    #[test]
+    async fn copy_tasks_to_add_them_again() {
+        let configuration = configuration();
+        let dependency = add_task(&configuration, new_task(2)).await.unwrap();
+        let original = NewTask {
+            confidence: Confidence::Low,
+            depends_on: vec![dependency.id],
+            ..new_task(7)
+        };
+        let mut task = add_task(&configuration, original.clone()).await.unwrap();
+        task.color = Some(120);
+        task.pinned = true;
+
+        // Everything but the id and what can't be given when adding is kept
+        let copy = add_task(&configuration, NewTask::from(&task))
+            .await
+            .unwrap();
+        assert_ne!(copy.id, task.id);
+        assert_eq!(copy, original);
+        assert_eq!(task.clone().into_new(), original);
+
+        let next_week = original.deadline + Duration::weeks(1);
+        let tweaked = task
+            .into_new()
+            .with_content("do me again")
+            .with_deadline(next_week)
+            .with_duration(Duration::minutes(30))
+            .with_importance(3)
+            .with_confidence(Confidence::Medium)
+            .with_time_segment_id(1)
+            .with_depends_on(vec![]);
+        assert_eq!(
+            tweaked,
+            NewTask {
+                content: "do me again".to_string(),
+                deadline: next_week,
+                duration: Duration::minutes(30),
+                importance: 3,
+                confidence: Confidence::Medium,
+                time_segment_id: 1,
+                depends_on: vec![],
+            }
+        );
+    }
+
+    #[test]
+    async fn only_accept_importances_from_1_to_10() {
+        let configuration = configuration();
+        for importance in [1, 10] {
+            assert_eq!(new_task(importance).validate(), Ok(()));
+            add_task(&configuration, new_task(importance))
+                .await
+                .unwrap();
+        }
+        for importance in [0, 11] {
+            let expected = ValidationError::ImportanceOutOfRange { importance };
+            assert_eq!(new_task(importance).validate(), Err(expected));
+            let error = add_task(&configuration, new_task(importance))
+                .await
+                .unwrap_err();
+            assert_matches!(
+                error,
+                Error::Validation(ValidationError::ImportanceOutOfRange { importance: i })
+                    if i == importance
+            );
+        }
+        assert_eq!(task_count(&configuration).await.unwrap(), 2);
+
+        let mut task = get_task(&configuration, 1).await.unwrap();
+        task.importance = 11;
+        assert_matches!(
+            update_task(&configuration, task).await,
+            Err(Error::Validation(_))
+        );
+        let changes = TaskChanges {
+            importance: Some(0),
+            ..Default::default()
+        };
+        assert_matches!(
+            update_task_fields(&configuration, 1, changes).await,
+            Err(Error::Validation(_))
+        );
+        assert_eq!(get_task(&configuration, 1).await.unwrap().importance, 1);
+    }
+
+    #[test]
+    async fn spot_tasks_which_take_longer_than_the_time_left() {
+        let now = Utc::now();
+        let mut task = new_task(5);
+        task.deadline = now + Duration::hours(2);
+        task.duration = Duration::hours(1);
+        assert!(!task.is_trivially_infeasible(now));
+        task.duration = Duration::hours(2);
+        assert!(!task.is_trivially_infeasible(now));
+        task.duration = Duration::hours(3);
+        assert!(task.is_trivially_infeasible(now));
+        // A deadline in the past can't be met at all
+        task.deadline = now - Duration::hours(1);
+        task.duration = Duration::minutes(1);
+        assert!(task.is_trivially_infeasible(now));
+    }
+
+    #[test]
+    async fn compute_the_slack_of_a_task() {
+        let configuration = configuration();
+        let task = add_task(&configuration, new_task(5)).await.unwrap();
+        let slack = task_slack(&configuration, task.id).await.unwrap();
+        assert!(slack <= Duration::hours(23));
+        assert!(slack > Duration::hours(22));
+        assert_matches!(
+            task_slack(&configuration, task.id + 1).await,
+            Err(Error::Database(_))
+        );
+    }
+
+    #[test]
+    async fn refuse_inverted_deadline_ranges() {
+        let configuration = configuration();
+        let task = add_task(&configuration, new_task(5)).await.unwrap();
+        let (before, after) = (
+            task.deadline - Duration::hours(1),
+            task.deadline + Duration::hours(1),
+        );
+        assert_eq!(
+            tasks_due_between(&configuration, Some(before), Some(after))
+                .await
+                .unwrap(),
+            [task]
+        );
+        assert_matches!(
+            tasks_due_between(&configuration, Some(after), Some(before)).await,
+            Err(Error::Validation(
+                ValidationError::InvertedDeadlineRange { .. }
+            ))
+        );
+    }
+
+    #[test]
+    async fn schedule_from_a_fixed_moment() {
+        let configuration = configuration();
+        let now = Utc.with_ymd_and_hms(2019, 8, 1, 9, 2, 0).unwrap();
+        let mut tasks = vec![];
+        for importance in [3, 7] {
+            let new_task = NewTask {
+                deadline: now + Duration::days(1),
+                ..new_task(importance)
+            };
+            tasks.push(add_task(&configuration, new_task).await.unwrap());
+        }
+        let segment = time_segment::UnnamedTimeSegment::anytime(now);
+
+        // The same moment always gives the same schedule, rounded to the granularity
+        for _ in 0..2 {
+            let schedule = schedule_tasks(
+                &configuration,
+                "importance",
+                vec![(segment.clone(), tasks.clone())],
+                Some(now),
+                &[],
+            )
+            .unwrap();
+            let when = schedule
+                .0
+                .iter()
+                .map(|scheduled| (scheduled.task.importance, scheduled.when))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                when,
+                [
+                    (7, Utc.with_ymd_and_hms(2019, 8, 1, 9, 5, 0).unwrap()),
+                    (3, Utc.with_ymd_and_hms(2019, 8, 1, 10, 5, 0).unwrap()),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    async fn explain_the_schedule_from_a_fixed_moment() {
+        let configuration = configuration();
+        let now = Utc.with_ymd_and_hms(2030, 8, 1, 8, 0, 0).unwrap();
+        let task = add_task(
+            &configuration,
+            NewTask {
+                deadline: Utc.with_ymd_and_hms(2030, 8, 5, 0, 0, 0).unwrap(),
+                duration: Duration::hours(1),
+                ..new_task(5)
+            },
+        )
+        .await
+        .unwrap();
+        let segment = time_segment::UnnamedTimeSegment::anytime(now);
+
+        let explanation = explain_schedule(
+            &configuration,
+            "importance",
+            vec![(segment, vec![task.clone()])],
+            Some(now),
+            task.id,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(explanation.scheduled.when, now);
+        assert_eq!(explanation.slack, task.deadline - now - task.duration);
+    }
+
+    #[test]
+    async fn leave_completed_tasks_out_of_the_schedule() {
+        let configuration = configuration();
+        let done = add_task(&configuration, new_task(8)).await.unwrap();
+        let to_do = add_task(&configuration, new_task(3)).await.unwrap();
+        complete_task(&configuration, done.id).await.unwrap();
+        assert!(get_task(&configuration, done.id)
+            .await
+            .unwrap()
+            .done_at
+            .is_some());
+
+        let schedule = schedule(&configuration, "importance", None).await.unwrap();
+        let ids = schedule
+            .0
+            .iter()
+            .map(|scheduled| scheduled.task.id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, [to_do.id]);
+
+        // Marking a task as done can be undone like any other change
+        undo(&configuration).await.unwrap();
+        assert_eq!(tasks(&configuration).await.unwrap(), [done, to_do]);
+    }
+
+    #[test]
+    async fn schedule_what_fits_and_report_the_rest() {
+        let configuration = configuration();
+        let fits = add_task(&configuration, new_task(8)).await.unwrap();
+        let too_long = add_task(
+            &configuration,
+            NewTask {
+                duration: Duration::hours(30),
+                ..new_task(5)
+            },
+        )
+        .await
+        .unwrap();
+        let dependent = add_task(
+            &configuration,
+            NewTask {
+                depends_on: vec![too_long.id],
+                ..new_task(9)
+            },
+        )
+        .await
+        .unwrap();
+        assert!(schedule(&configuration, "importance", None).await.is_err());
+
+        let (schedule, left_out) = schedule_best_effort(&configuration, "importance")
+            .await
+            .unwrap();
+        let ids = schedule
+            .0
+            .iter()
+            .map(|scheduled| scheduled.task.id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, [fits.id]);
+        // The task which depends on the one which doesn't fit can't be done either
+        assert_eq!(left_out, [too_long, dependent]);
+    }
+
+    #[test]
+    async fn refuse_unknown_scheduling_strategies() {
+        let configuration = configuration();
+        add_task(&configuration, new_task(5)).await.unwrap();
+        let error = schedule(&configuration, "randomly", None)
+            .await
+            .unwrap_err();
+        assert_matches!(&error, Error::Strategy(_));
+        assert_eq!(
+            error.to_string(),
+            "There is no scheduling strategy called \"randomly\". Choose importance, urgency, \
+             balanced, deadline or shuffled:SEED."
+        );
+        assert_matches!(
+            schedule_best_effort(&configuration, "shuffled:x").await,
+            Err(Error::Strategy(_))
+        );
+    }
+
+    #[test]
+    async fn undo_adding_a_task() {
+        let configuration = configuration();
+        let task = add_task(&configuration, new_task(5)).await.unwrap();
+
+        assert_eq!(
+            undo(&configuration).await.unwrap(),
+            Some(Operation::Add { task })
+        );
+        assert_eq!(task_count(&configuration).await.unwrap(), 0);
+        assert_eq!(undo(&configuration).await.unwrap(), None);
+    }
+
+    #[test]
+    async fn undo_deleting_a_task() {
+        let configuration = configuration();
+        let task = add_task(&configuration, new_task(5)).await.unwrap();
+        let dependent = add_task(
+            &configuration,
+            NewTask {
+                depends_on: vec![task.id],
+                ..new_task(3)
+            },
+        )
+        .await
+        .unwrap();
+        delete_task(&configuration, task.id).await.unwrap();
+        assert!(get_task(&configuration, dependent.id)
+            .await
+            .unwrap()
+            .depends_on
+            .is_empty());
+
+        assert_matches!(
+            undo(&configuration).await.unwrap(),
+            Some(Operation::Delete { dependents, .. }) if dependents == [dependent.id]
+        );
+        // The task is back with the same id, and so is the dependency on it
+        assert_eq!(tasks(&configuration).await.unwrap(), [task, dependent]);
+    }
+
+    #[test]
+    async fn undo_updating_a_task() {
+        let configuration = configuration();
+        let task = add_task(&configuration, new_task(5)).await.unwrap();
+        let mut updated = task.clone();
+        updated.content = "do me later".to_string();
+        update_task(&configuration, updated.clone()).await.unwrap();
+        let changes = TaskChanges {
+            importance: Some(8),
+            color: Some(Some(120)),
+            ..Default::default()
+        };
+        update_task_fields(&configuration, task.id, changes)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            undo(&configuration).await.unwrap(),
+            Some(Operation::Update {
+                before: updated.clone()
+            })
+        );
+        assert_eq!(get_task(&configuration, task.id).await.unwrap(), updated);
+        undo(&configuration).await.unwrap();
+        assert_eq!(get_task(&configuration, task.id).await.unwrap(), task);
+    }
+
+    #[test]
+    async fn keep_an_operation_which_could_not_be_undone() {
+        let configuration = configuration();
+        let task = add_task(&configuration, new_task(5)).await.unwrap();
+        // Deleting the task without logging it makes undoing the addition fail
+        configuration.database.delete_task(task.id).await.unwrap();
+
+        assert!(undo(&configuration).await.unwrap_err().is_not_found());
+        assert_eq!(
+            configuration.database.operations().await.unwrap(),
+            [Operation::Add { task }]
+        );
+    }
+}
@@ -0,0 +1,224 @@
+use chrono::{DateTime, Duration, Utc};
+use itertools::Itertools;
+
+use crate::time_segment::{NamedTimeSegment, TimeSegment};
+use crate::Task;
+
+/// Segments which are planned for more than this fraction of their available
+/// time are considered overcommitted.
+pub const OVERCOMMITTED_UTILISATION: f64 = 0.9;
+/// Segments which are planned for less than this fraction of their available
+/// time are considered underused.
+pub const UNDERUSED_UTILISATION: f64 = 0.2;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SegmentLoad {
+    pub time_segment: NamedTimeSegment,
+    /// The total duration of the tasks in the segment
+    pub planned: Duration,
+    /// The total time the segment covers between now and the last deadline
+    pub available: Duration,
+}
+
+impl SegmentLoad {
+    pub fn utilisation(&self) -> f64 {
+        if self.available <= Duration::zero() {
+            if self.planned <= Duration::zero() {
+                0.0
+            } else {
+                f64::INFINITY
+            }
+        } else {
+            self.planned.num_seconds() as f64 / self.available.num_seconds() as f64
+        }
+    }
+
+    fn spare_time(&self) -> Duration {
+        let comfortable = self.available.num_seconds() as f64 * OVERCOMMITTED_UTILISATION;
+        Duration::seconds(comfortable as i64) - self.planned
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MoveSuggestion {
+    /// The task to move, still in its current time segment
+    pub task: Task,
+    pub to_time_segment_id: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadAdvice {
+    pub loads: Vec<SegmentLoad>,
+    pub suggestions: Vec<MoveSuggestion>,
+}
+
+/// Computes how heavily each time segment is used between `start` and the last
+/// deadline of all tasks and, if some segments are overcommitted while others
+/// are underused, suggests tasks that could be moved to balance the load.
+///
+/// The least important tasks are suggested first, and longer tasks before
+/// shorter ones, since moving them has the most effect for the least risk.
+/// Nothing is moved: it's up to the user to follow the advice or not.
+pub fn advise(
+    start: DateTime<Utc>,
+    tasks_per_segment: Vec<(NamedTimeSegment, Vec<Task>)>,
+) -> LoadAdvice {
+    let horizon = tasks_per_segment
+        .iter()
+        .flat_map(|(_, tasks)| tasks)
+        .map(|task| task.deadline)
+        .max()
+        .unwrap_or(start);
+    let mut loads = vec![];
+    let mut tasks_per_load = vec![];
+    for (time_segment, tasks) in tasks_per_segment {
+        let available = time_segment
+            .generate_ranges(start, horizon)
+            .into_iter()
+            .fold(Duration::zero(), |total, range| {
+                total + (range.end - range.start)
+            });
+        let planned = tasks
+            .iter()
+            .fold(Duration::zero(), |total, task| total + task.duration);
+        loads.push(SegmentLoad {
+            time_segment,
+            planned,
+            available,
+        });
+        tasks_per_load.push(tasks);
+    }
+
+    let overcommitted = (0..loads.len())
+        .filter(|&i| loads[i].utilisation() > OVERCOMMITTED_UTILISATION)
+        .sorted_by(|&i, &j| {
+            loads[j]
+                .utilisation()
+                .partial_cmp(&loads[i].utilisation())
+                .expect("utilisation is never NaN")
+        })
+        .collect_vec();
+    let underused = (0..loads.len())
+        .filter(|&i| {
+            loads[i].available > Duration::zero() && loads[i].utilisation() < UNDERUSED_UTILISATION
+        })
+        .collect_vec();
+
+    // Keep track of the load as it would be after following the suggestions so far
+    let mut balanced_loads = loads.clone();
+    let mut suggestions = vec![];
+    for from in overcommitted {
+        let candidates = tasks_per_load[from]
+            .iter()
+            .sorted_by_key(|task| (task.importance, -task.duration));
+        for task in candidates {
+            if balanced_loads[from].utilisation() <= OVERCOMMITTED_UTILISATION {
+                break;
+            }
+            let to = underused
+                .iter()
+                .copied()
+                .filter(|&to| balanced_loads[to].spare_time() >= task.duration)
+                .max_by_key(|&to| balanced_loads[to].spare_time());
+            if let Some(to) = to {
+                balanced_loads[from].planned -= task.duration;
+                balanced_loads[to].planned += task.duration;
+                suggestions.push(MoveSuggestion {
+                    task: task.clone(),
+                    to_time_segment_id: loads[to].time_segment.id,
+                });
+            }
+        }
+    }
+
+    LoadAdvice { loads, suggestions }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::SubsecRound;
+
+    use super::*;
+    use crate::Confidence;
+
+    fn daily_segment(id: u32, start: DateTime<Utc>, hours: i64) -> NamedTimeSegment {
+        NamedTimeSegment {
+            id,
+            name: format!("segment {}", id),
+            ranges: vec![start..start + Duration::hours(hours)],
+            start,
+            period: Duration::days(1),
+            hue: 0,
+            strategy: None,
+        }
+    }
+
+    fn task(id: u32, start: DateTime<Utc>, hours: i64, importance: u32, segment: u32) -> Task {
+        Task {
+            id,
+            content: format!("task {}", id),
+            deadline: start + Duration::days(5),
+            duration: Duration::hours(hours),
+            importance,
+            confidence: Confidence::High,
+            time_segment_id: segment,
+            depends_on: vec![],
+            color: None,
+            scheduled_for: None,
+            done_at: None,
+            pinned: false,
+        }
+    }
+
+    #[test]
+    fn suggests_moving_tasks_out_of_overcommitted_segment() {
+        let start = Utc::now().trunc_subsecs(0);
+        // Both segments have 4 hours per day, so 20 hours until the deadline
+        let busy = daily_segment(0, start, 4);
+        let idle = daily_segment(1, start, 4);
+        let tasks_per_segment = vec![
+            (
+                busy,
+                vec![
+                    task(1, start, 8, 8, 0),
+                    task(2, start, 6, 3, 0),
+                    task(3, start, 5, 3, 0),
+                    task(4, start, 1, 3, 0),
+                ],
+            ),
+            (idle, vec![task(5, start, 2, 5, 1)]),
+        ];
+
+        let advice = advise(start, tasks_per_segment);
+
+        assert_eq!(advice.loads.len(), 2);
+        assert_eq!(advice.loads[0].available, Duration::hours(20));
+        assert_eq!(advice.loads[0].planned, Duration::hours(20));
+        assert!(advice.loads[0].utilisation() > OVERCOMMITTED_UTILISATION);
+        assert!(advice.loads[1].utilisation() < UNDERUSED_UTILISATION);
+        // Moving the longest of the least important tasks suffices
+        assert_eq!(advice.suggestions.len(), 1);
+        assert_eq!(advice.suggestions[0].task.id, 2);
+        assert_eq!(advice.suggestions[0].to_time_segment_id, 1);
+    }
+
+    #[test]
+    fn gives_no_suggestions_when_balanced() {
+        let start = Utc::now().trunc_subsecs(0);
+        let tasks_per_segment = vec![
+            (daily_segment(0, start, 4), vec![task(1, start, 10, 5, 0)]),
+            (daily_segment(1, start, 4), vec![task(2, start, 10, 5, 1)]),
+        ];
+        let advice = advise(start, tasks_per_segment);
+        assert!(advice.suggestions.is_empty());
+
+        // Without an underused segment, there is nowhere to move tasks to
+        let tasks_per_segment = vec![
+            (daily_segment(0, start, 4), vec![task(1, start, 25, 5, 0)]),
+            (daily_segment(1, start, 4), vec![task(2, start, 19, 5, 1)]),
+        ];
+        let advice = advise(start, tasks_per_segment);
+        assert!(advice.loads[0].utilisation() > 1.0);
+        assert!(advice.suggestions.is_empty());
+    }
+}
@@ -1,28 +1,69 @@
+use std::borrow::Borrow;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
 use std::fmt::{self, Debug, Display};
 use std::hash::Hash;
+use std::ops::Range;
 use std::rc::Rc;
 
 use chrono::prelude::*;
 use chrono::Duration;
 use itertools::Itertools;
+use log::{debug, log_enabled, warn, Level};
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::configuration::SchedulingStrategy;
-use crate::time_segment::TimeSegment;
+use crate::configuration::{Breaks, EstimateBuffers, SchedulingStrategy};
+use crate::time_segment::{NamedTimeSegment, TimeSegment};
+use crate::Confidence;
 
 use self::schedule_tree::{Entry, ScheduleTree};
 
+#[cfg(test)]
+mod proptests;
 mod schedule_tree;
 
 pub(crate) trait Task:
     Debug + Display + Send + Sync + PartialEq + Eq + Clone + Hash
 {
+    fn id(&self) -> u32;
     fn deadline(&self) -> DateTime<Utc>;
     fn duration(&self) -> Duration;
     fn importance(&self) -> u32;
+    fn confidence(&self) -> Confidence {
+        Confidence::High
+    }
+    /// The ids of the tasks which have to be finished before this one can start
+    fn dependencies(&self) -> &[u32] {
+        &[]
+    }
+    /// Whether the task goes first in its time segment, whatever the strategy
+    fn pinned(&self) -> bool {
+        false
+    }
+
+    /// How much time would be left to spare if the task were started at
+    /// `now`. It's negative when the task can't be finished in time anymore.
+    fn slack(&self, now: DateTime<Utc>) -> Duration {
+        self.deadline() - now - self.duration()
+    }
+
+    /// Ranks the task by how little time is left until its deadline, so the
+    /// most urgent tasks come last when sorting.
+    fn urgency_rank(&self, now: DateTime<Utc>) -> Reverse<Duration> {
+        Reverse(self.deadline() - now)
+    }
 }
 
 impl Task for crate::Task {
+    fn id(&self) -> u32 {
+        self.id
+    }
+
     fn deadline(&self) -> DateTime<Utc> {
         self.deadline
     }
@@ -34,6 +75,216 @@ impl Task for crate::Task {
     fn importance(&self) -> u32 {
         self.importance
     }
+
+    fn confidence(&self) -> Confidence {
+        self.confidence
+    }
+
+    fn dependencies(&self) -> &[u32] {
+        &self.depends_on
+    }
+
+    fn pinned(&self) -> bool {
+        self.pinned
+    }
+}
+
+/// A task with its duration inflated according to how much its estimate can be
+/// trusted, and its deadline moved forward to leave enough time for the tasks
+/// depending on it. Both are rounded to the granularity of the schedule, the
+/// duration up and the deadline down, so the task is never planned to end
+/// after its real deadline. The scheduling algorithms only get to see these.
+///
+/// A task which is longer than any window of its time segment is split into
+/// several parts, which share its id but are scheduled independently.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+struct Buffered<TaskT> {
+    task: TaskT,
+    duration: Duration,
+    deadline: DateTime<Utc>,
+    /// Tells apart the parts of a split task, which is zero for whole tasks
+    part: u32,
+    /// How many parts the task is split into, which is one for whole tasks
+    parts: u32,
+}
+
+impl<TaskT: Task> Buffered<TaskT> {
+    fn new(task: TaskT, estimate_buffers: EstimateBuffers, granularity: Duration) -> Self {
+        let duration = estimate_buffers.apply(task.duration(), task.confidence());
+        let duration = round_up(duration, granularity);
+        let deadline = floor(task.deadline(), granularity);
+        Buffered {
+            task,
+            duration,
+            deadline,
+            part: 0,
+            parts: 1,
+        }
+    }
+
+    /// Splits the task into parts which last at most `window`: as many whole
+    /// windows as it takes, followed by what's left. A task which fits is
+    /// left whole, and so is one which would take more than `MAX_PARTS`.
+    fn split(self, window: Duration) -> Vec<Self> {
+        if self.duration <= window || window <= Duration::zero() {
+            return vec![self];
+        }
+        let mut durations = vec![];
+        let mut left = self.duration;
+        while left > Duration::zero() {
+            if durations.len() == MAX_PARTS as usize {
+                warn!(
+                    "Task {} would have to be split into more than {} parts, so I left it whole.",
+                    self.task.id(),
+                    MAX_PARTS
+                );
+                return vec![self];
+            }
+            let duration = std::cmp::min(left, window);
+            durations.push(duration);
+            left -= duration;
+        }
+        let parts = durations.len() as u32;
+        durations
+            .into_iter()
+            .zip(0..)
+            .map(|(duration, part)| Buffered {
+                duration,
+                part,
+                parts,
+                ..self.clone()
+            })
+            .collect()
+    }
+}
+
+impl<TaskT: Task> Task for Buffered<TaskT> {
+    fn id(&self) -> u32 {
+        self.task.id()
+    }
+
+    fn deadline(&self) -> DateTime<Utc> {
+        self.deadline
+    }
+
+    fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    fn importance(&self) -> u32 {
+        self.task.importance()
+    }
+
+    fn confidence(&self) -> Confidence {
+        self.task.confidence()
+    }
+
+    fn dependencies(&self) -> &[u32] {
+        self.task.dependencies()
+    }
+
+    fn pinned(&self) -> bool {
+        self.task.pinned()
+    }
+}
+
+/// How many times to move the deadlines further back when tasks can't be
+/// finished, however late, before giving up on them.
+const MAX_RELAXATIONS: u32 = 8;
+
+/// Tasks aren't split into more parts than this. Short windows, like the ones
+/// between forced breaks, would otherwise cut a long task up in countless bits.
+const MAX_PARTS: u32 = 100;
+
+/// Rounds the duration up to a whole number of times the granularity. A
+/// granularity of zero leaves it as is.
+fn round_up(duration: Duration, granularity: Duration) -> Duration {
+    let (seconds, step) = (duration.num_seconds(), granularity.num_seconds());
+    if step <= 0 || (seconds % step == 0 && duration == Duration::seconds(seconds)) {
+        return duration;
+    }
+    Duration::seconds((seconds.div_euclid(step) + 1) * step)
+}
+
+/// Rounds the duration down like `round_up` rounds it up.
+fn round_down(duration: Duration, granularity: Duration) -> Duration {
+    let step = granularity.num_seconds();
+    if step <= 0 {
+        return duration;
+    }
+    Duration::seconds(duration.num_seconds().div_euclid(step) * step)
+}
+
+/// Rounds the moment down to a whole number of times the granularity since the
+/// UNIX epoch. A granularity of zero leaves it as is.
+fn floor(moment: DateTime<Utc>, granularity: Duration) -> DateTime<Utc> {
+    let step = granularity.num_seconds();
+    if step <= 0 {
+        return moment;
+    }
+    let seconds = moment.timestamp().div_euclid(step) * step;
+    Utc.timestamp_opt(seconds, 0).single().unwrap_or(moment)
+}
+
+/// Rounds the moment up like `floor` rounds it down.
+fn ceil(moment: DateTime<Utc>, granularity: Duration) -> DateTime<Utc> {
+    let floor = floor(moment, granularity);
+    if floor == moment {
+        moment
+    } else {
+        floor + granularity
+    }
+}
+
+impl<TaskT: Display> Display for Buffered<TaskT> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.task.fmt(f)
+    }
+}
+
+/// A task with its deadline moved back by `extra`, which lets the scheduler plan
+/// it to end late. Moving every deadline back by as much keeps the tasks in the
+/// same order of urgency.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+struct Relaxed<TaskT> {
+    task: TaskT,
+    extra: Duration,
+}
+
+impl<TaskT: Task> Task for Relaxed<TaskT> {
+    fn id(&self) -> u32 {
+        self.task.id()
+    }
+
+    fn deadline(&self) -> DateTime<Utc> {
+        self.task.deadline() + self.extra
+    }
+
+    fn duration(&self) -> Duration {
+        self.task.duration()
+    }
+
+    fn importance(&self) -> u32 {
+        self.task.importance()
+    }
+
+    fn confidence(&self) -> Confidence {
+        self.task.confidence()
+    }
+
+    fn dependencies(&self) -> &[u32] {
+        self.task.dependencies()
+    }
+
+    fn pinned(&self) -> bool {
+        self.task.pinned()
+    }
+}
+
+impl<TaskT: Display> Display for Relaxed<TaskT> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.task.fmt(f)
+    }
 }
 
 #[derive(Debug, Error)]
@@ -42,32 +293,125 @@ pub enum Error<TaskT: Debug + Display + Send + Sync + 'static> {
         "I could not schedule {task} because you {tense} the deadline.\n\
         You might want to postpone this task or remove it if it's not longer relevant"
     )]
-    DeadlineMissed { task: TaskT, tense: &'static str },
+    DeadlineMissed {
+        task: Box<TaskT>,
+        tense: &'static str,
+    },
     #[error(
         "I could not schedule {task} because you don't have enough time to do everything.\n\
         You might want to decide not to do some things or relax their deadlines"
     )]
-    NotEnoughTime { task: TaskT },
+    NotEnoughTime { task: Box<TaskT> },
+    #[error(
+        "I could not schedule {task} because it can only start after it has been finished \
+        itself, judging by its dependencies.\n\
+        You might want to remove some of its dependencies"
+    )]
+    DependencyCycle { task: Box<TaskT> },
+    #[error("There is no task with id {id} in the schedule")]
+    NotScheduled { id: u32 },
+    #[error(
+        "I could not change task {id} within the schedule because it is split into several parts.\n\
+        You might want to make a new schedule instead"
+    )]
+    Split { id: u32 },
     #[error("An internal error occurred -- this shouldn't happen: {0}")]
     Internal(&'static str),
 }
 
-#[derive(Debug, PartialEq)]
+impl<TaskT: Debug + Display + Send + Sync + 'static> Error<TaskT> {
+    /// Replaces the task the error is about, e.g. by the one it wraps.
+    fn map_task<OtherT: Debug + Display + Send + Sync + 'static>(
+        self,
+        f: impl FnOnce(TaskT) -> OtherT,
+    ) -> Error<OtherT> {
+        match self {
+            Error::DeadlineMissed { task, tense } => Error::DeadlineMissed {
+                task: Box::new(f(*task)),
+                tense,
+            },
+            Error::NotEnoughTime { task } => Error::NotEnoughTime {
+                task: Box::new(f(*task)),
+            },
+            Error::DependencyCycle { task } => Error::DependencyCycle {
+                task: Box::new(f(*task)),
+            },
+            Error::NotScheduled { id } => Error::NotScheduled { id },
+            Error::Split { id } => Error::Split { id },
+            Error::Internal(message) => Error::Internal(message),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Scheduled<T> {
     pub task: T,
     pub when: DateTime<Utc>,
+    /// How long the task is planned for, including the extra time for a less
+    /// trustworthy estimate
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            serialize_with = "crate::serialization::serialize_duration_as_seconds",
+            deserialize_with = "crate::serialization::deserialize_duration_from_seconds"
+        )
+    )]
+    pub duration: Duration,
+    /// Which part of the task this is, when the task is longer than any window
+    /// of its time segment and had to be split over several of them
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub part: Option<Part>,
+}
+
+/// Numbers the parts of a task which is split over several windows of its time
+/// segment, chronologically and starting from one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Part {
+    pub number: u32,
+    pub of: u32,
+}
+
+/// How long after its deadline a task is planned to be finished, which is zero
+/// for a task which is done in time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lateness<TaskT> {
+    pub task: TaskT,
+    pub lateness: Duration,
+}
+
+/// A schedule which lets tasks run late, along with how late each task is
+type LateSchedule<TaskT> = (Schedule<TaskT>, Vec<Lateness<TaskT>>);
+
+impl<T> Scheduled<T> {
+    /// Returns the moment the task is planned to be finished.
+    pub fn end(&self) -> DateTime<Utc> {
+        self.when + self.duration
+    }
+}
+
+/// Orders chronologically, and tasks which are scheduled at the same moment by
+/// the tasks themselves.
+impl<TaskT: Ord> Ord for Scheduled<TaskT> {
+    fn cmp(&self, other: &Scheduled<TaskT>) -> std::cmp::Ordering {
+        self.when
+            .cmp(&other.when)
+            .then_with(|| self.task.cmp(&other.task))
+    }
 }
 
-impl<TaskT: PartialEq> std::cmp::PartialOrd for Scheduled<TaskT> {
+impl<TaskT: Ord> PartialOrd for Scheduled<TaskT> {
     fn partial_cmp(&self, other: &Scheduled<TaskT>) -> Option<std::cmp::Ordering> {
-        match self.when.cmp(&other.when) {
-            std::cmp::Ordering::Equal => None,
-            strict_ordering => Some(strict_ordering),
-        }
+        Some(self.cmp(other))
     }
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Schedule<TaskT>(pub Vec<Scheduled<TaskT>>);
 
 impl<TaskT> Default for Schedule<TaskT> {
@@ -76,79 +420,501 @@ impl<TaskT> Default for Schedule<TaskT> {
     }
 }
 
+impl<TaskT> Schedule<TaskT> {
+    /// Iterates over the scheduled tasks, in the order they're scheduled.
+    ///
+    /// ```
+    /// use chrono::{Duration, TimeZone, Utc};
+    /// use eva::{Schedule, Scheduled};
+    ///
+    /// let at = |hour| Utc.with_ymd_and_hms(2019, 8, 1, hour, 0, 0).unwrap();
+    /// let scheduled = |task, hour| Scheduled {
+    ///     task,
+    ///     when: at(hour),
+    ///     duration: Duration::hours(1),
+    ///     part: None,
+    /// };
+    /// let schedule = Schedule(vec![scheduled("Pack", 9), scheduled("Leave", 10)]);
+    ///
+    /// let ends = schedule.iter().map(Scheduled::end).collect::<Vec<_>>();
+    /// assert_eq!(ends, [at(10), at(11)]);
+    /// for scheduled in &schedule {
+    ///     println!("{} at {}", scheduled.task, scheduled.when);
+    /// }
+    /// assert_eq!(schedule.len(), 2);
+    /// assert!(!schedule.is_empty());
+    /// ```
+    pub fn iter(&self) -> std::slice::Iter<'_, Scheduled<TaskT>> {
+        self.0.iter()
+    }
+
+    /// Returns how many tasks, or parts of tasks, are scheduled.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns the forced breaks between the scheduled tasks, i.e. the gaps
+    /// which last exactly as long as a break at this granularity.
+    pub fn breaks(&self, breaks: Breaks, granularity: Duration) -> Vec<Range<DateTime<Utc>>> {
+        let length = round_up(breaks.length, granularity);
+        self.0
+            .iter()
+            .tuple_windows()
+            .filter(|(previous, next)| next.when - previous.end() == length)
+            .map(|(previous, next)| previous.end()..next.when)
+            .collect()
+    }
+}
+
+impl<TaskT> IntoIterator for Schedule<TaskT> {
+    type Item = Scheduled<TaskT>;
+    type IntoIter = std::vec::IntoIter<Scheduled<TaskT>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a, TaskT> IntoIterator for &'a Schedule<TaskT> {
+    type Item = &'a Scheduled<TaskT>;
+    type IntoIter = std::slice::Iter<'a, Scheduled<TaskT>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
 impl<TaskT> Schedule<TaskT> {
     /// Schedules tasks according to the given strategy, using the tasks'
-    /// deadlines, importance and duration.
+    /// deadlines, importance, duration and dependencies.
     ///
     /// Args:
     ///     start: the moment when the first task can be scheduled
-    ///     tasks: iterable of tasks to schedule
-    ///     strategy: the scheduling algorithm to use
-    ///     time_segment: the time segment to schedule the tasks within
+    ///     tasks_per_segment: the tasks to schedule, grouped by the time
+    ///         segment they should be scheduled within
+    ///     strategy: the scheduling algorithm to use for the segments which
+    ///         don't have their own
+    ///     estimate_buffers: how much extra time to plan for tasks with a less
+    ///         trustworthy duration estimate
+    ///     granularity: tasks start at and last a whole number of times this,
+    ///         which keeps the schedule from getting cut up in tiny gaps, or
+    ///         zero to schedule to the second
+    ///     busy: fixed appointments, during which no task is scheduled in any
+    ///         time segment
+    ///     breaks: how long to work at most before a break and how long the
+    ///         breaks last, or None to plan without breaks
     /// Returns when successful an instance of Schedule which contains all
-    /// tasks, each bound to a certain date and time; returns None when not all
-    /// tasks could be scheduled.
+    /// tasks, each bound to a certain date and time; returns an error when not
+    /// all tasks could be scheduled.
     pub(crate) fn schedule(
         start: DateTime<Utc>,
         tasks_per_segment: impl IntoIterator<Item = (impl TimeSegment, impl IntoIterator<Item = TaskT>)>,
         strategy: SchedulingStrategy,
+        estimate_buffers: EstimateBuffers,
+        granularity: Duration,
+        busy: &[Range<DateTime<Utc>>],
+        breaks: Option<Breaks>,
     ) -> Result<Schedule<TaskT>, Error<TaskT>>
     where
         TaskT: Task,
     {
-        tasks_per_segment
+        let mut tasks = vec![];
+        let mut segments = vec![];
+        for (segment, tasks_in_segment) in tasks_per_segment {
+            let first_index = tasks.len();
+            tasks.extend(
+                tasks_in_segment
+                    .into_iter()
+                    .map(|task| Buffered::new(task, estimate_buffers, granularity)),
+            );
+            segments.push((segment, first_index..tasks.len()));
+        }
+        // Rounding shouldn't make a task which would just fit seem to miss its
+        // deadline, but there isn't enough time to plan it at this granularity
+        let rounded_start = ceil(start, granularity);
+        for task in &tasks {
+            let unrounded_duration =
+                estimate_buffers.apply(task.task.duration(), task.task.confidence());
+            if start + unrounded_duration <= task.task.deadline()
+                && rounded_start + task.duration > task.deadline
+            {
+                return Err(Error::NotEnoughTime {
+                    task: Box::new(task.task.clone()),
+                });
+            }
+        }
+        let start = rounded_start;
+        let order = dependency_order(&tasks).map_err(|task| Error::DependencyCycle {
+            task: Box::new(task.task),
+        })?;
+        leave_time_for_dependent_tasks(&mut tasks, &order);
+        // Segments without tasks have nothing to schedule
+        let segments = segments
             .into_iter()
-            .map(|(segment, tasks)| {
-                Schedule::schedule_within_segment(start, tasks, segment, strategy)
+            .filter(|(_, indices)| !indices.is_empty())
+            .map(|(segment, indices)| {
+                // Breaks block the time of their segment like busy ranges do
+                let mut blocked = busy.to_vec();
+                let last_deadline = tasks[indices.clone()]
+                    .iter()
+                    .map(|task| task.deadline)
+                    .max();
+                if let (Some(breaks), Some(last_deadline)) = (breaks, last_deadline) {
+                    blocked.extend(forced_breaks(
+                        &segment,
+                        start,
+                        last_deadline,
+                        granularity,
+                        busy,
+                        breaks,
+                    ));
+                }
+                let parts = split_over_windows(
+                    tasks[indices].to_vec(),
+                    &segment,
+                    start,
+                    granularity,
+                    &blocked,
+                );
+                (segment, parts, blocked)
             })
-            .fold(
-                Ok(Schedule::default()),
-                |acc_schedule, new_schedule| match (acc_schedule, new_schedule) {
-                    (Err(error), _) => Err(error),
-                    (_, Err(error)) => Err(error),
-                    (Ok(acc_schedule), Ok(new_schedule)) => Ok(Schedule(
-                        itertools::merge(acc_schedule.0, new_schedule.0).collect_vec(),
-                    )),
-                },
-            )
+            .collect_vec();
+
+        // Tasks can depend on tasks in other time segments, which might only
+        // be scheduled after them. Keep rescheduling with the latest known
+        // moments those tasks are finished, until all dependencies are met.
+        let mut finished = HashMap::new();
+        for _ in 0..=tasks.len() {
+            let mut schedule = Schedule::default();
+            for (segment, parts, blocked) in &segments {
+                let new_schedule = Schedule::schedule_within_segment(
+                    start,
+                    parts.clone(),
+                    segment.clone(),
+                    segment.strategy().unwrap_or(strategy),
+                    granularity,
+                    blocked,
+                    &finished,
+                )
+                .map_err(|error| error.map_task(|task| task.task))?;
+                finished.extend(
+                    new_schedule
+                        .0
+                        .iter()
+                        .map(|scheduled| (scheduled.task.id(), scheduled.end())),
+                );
+                // Tasks in different segments can start at the same moment
+                let chronologically =
+                    |scheduled: &Scheduled<Buffered<TaskT>>, other: &Scheduled<Buffered<TaskT>>| {
+                        (scheduled.when, scheduled.task.id()) <= (other.when, other.task.id())
+                    };
+                schedule = Schedule(
+                    schedule
+                        .0
+                        .into_iter()
+                        .merge_by(new_schedule.0, chronologically)
+                        .collect_vec(),
+                );
+            }
+            let dependencies_met = schedule.0.iter().all(|scheduled| {
+                scheduled.task.dependencies().iter().all(|id| {
+                    finished
+                        .get(id)
+                        .is_none_or(|finished| *finished <= scheduled.when)
+                })
+            });
+            if dependencies_met {
+                // Number the parts of split tasks chronologically
+                let mut numbered = HashMap::new();
+                return Ok(Schedule(
+                    schedule
+                        .0
+                        .into_iter()
+                        .map(|scheduled| {
+                            let part = (scheduled.task.parts > 1).then(|| {
+                                let number =
+                                    numbered.entry(scheduled.task.task.clone()).or_insert(0);
+                                *number += 1;
+                                Part {
+                                    number: *number,
+                                    of: scheduled.task.parts,
+                                }
+                            });
+                            Scheduled {
+                                task: scheduled.task.task,
+                                when: scheduled.when,
+                                duration: scheduled.duration,
+                                part,
+                            }
+                        })
+                        .collect(),
+                ));
+            }
+        }
+        Err(Error::Internal(
+            "I couldn't meet the dependencies between time segments",
+        ))
+    }
+
+    /// Schedules as many tasks as possible instead of failing on the first one
+    /// which doesn't fit. Returns the schedule along with the tasks which were
+    /// left out: the ones which would miss their deadline or for which there
+    /// isn't enough time, and the tasks which depend on them.
+    pub(crate) fn schedule_best_effort<SegmentT: TimeSegment>(
+        start: DateTime<Utc>,
+        mut tasks_per_segment: Vec<(SegmentT, Vec<TaskT>)>,
+        strategy: SchedulingStrategy,
+        estimate_buffers: EstimateBuffers,
+        granularity: Duration,
+        busy: &[Range<DateTime<Utc>>],
+        breaks: Option<Breaks>,
+    ) -> Result<(Schedule<TaskT>, Vec<TaskT>), Error<TaskT>>
+    where
+        TaskT: Task,
+    {
+        let mut left_out = vec![];
+        loop {
+            let error = match Schedule::schedule(
+                start,
+                tasks_per_segment.clone(),
+                strategy,
+                estimate_buffers,
+                granularity,
+                busy,
+                breaks,
+            ) {
+                Ok(schedule) => return Ok((schedule, left_out)),
+                Err(error) => error,
+            };
+            let failed = match &error {
+                Error::DeadlineMissed { task, .. } | Error::NotEnoughTime { task } => {
+                    (**task).clone()
+                }
+                _ => return Err(error),
+            };
+            let before = left_out.len();
+            for (_, tasks) in &mut tasks_per_segment {
+                if let Some(index) = tasks.iter().position(|task| *task == failed) {
+                    left_out.push(tasks.remove(index));
+                }
+            }
+            // Tasks can't be done before the ones they depend on
+            loop {
+                let ids: HashSet<u32> = left_out[before..].iter().map(Task::id).collect();
+                let count = left_out.len();
+                for (_, tasks) in &mut tasks_per_segment {
+                    let (out, kept): (Vec<_>, Vec<_>) = tasks
+                        .drain(..)
+                        .partition(|task| task.dependencies().iter().any(|id| ids.contains(id)));
+                    *tasks = kept;
+                    left_out.extend(out);
+                }
+                if left_out.len() == count {
+                    break;
+                }
+            }
+            // Every attempt has to leave out another task, or this never ends
+            if left_out.len() == before {
+                return Err(error);
+            }
+        }
+    }
+
+    /// Schedules all tasks like `schedule` does, but lets them run past their
+    /// deadline instead of failing when there isn't enough time for them.
+    /// Returns the schedule along with how late each task is planned to be
+    /// finished, the latest first. The deadlines are only moved back
+    /// `MAX_RELAXATIONS` times, so this still fails when the tasks don't fit
+    /// however late they are, e.g. in a time segment without any time.
+    pub(crate) fn schedule_allowing_lateness<SegmentT: TimeSegment>(
+        start: DateTime<Utc>,
+        tasks_per_segment: Vec<(SegmentT, Vec<TaskT>)>,
+        strategy: SchedulingStrategy,
+        estimate_buffers: EstimateBuffers,
+        granularity: Duration,
+        busy: &[Range<DateTime<Utc>>],
+        breaks: Option<Breaks>,
+    ) -> Result<LateSchedule<TaskT>, Error<TaskT>>
+    where
+        TaskT: Task,
+    {
+        let tasks = tasks_per_segment.iter().flat_map(|(_, tasks)| tasks);
+        let Some(earliest_deadline) = tasks.clone().map(Task::deadline).min() else {
+            return Ok((Schedule::default(), vec![]));
+        };
+        // All tasks surely fit when they may end as late as doing all of them
+        // from the deadline which is due first, unless the time segments leave
+        // too little room for that. Moving the deadlines further back then
+        // gives them more room, up to a point.
+        let total = tasks
+            .map(|task| estimate_buffers.apply(task.duration(), task.confidence()))
+            .fold(Duration::zero(), |total, duration| total + duration);
+        let mut extra = std::cmp::max(Duration::zero(), start - earliest_deadline)
+            + round_up(total, granularity)
+            + granularity;
+        let mut attempts = 0;
+        let schedule = loop {
+            let relaxed = tasks_per_segment.iter().map(|(segment, tasks)| {
+                let tasks = tasks.iter().map(|task| Relaxed {
+                    task: task.clone(),
+                    extra,
+                });
+                (segment.clone(), tasks.collect_vec())
+            });
+            attempts += 1;
+            match Schedule::schedule(
+                start,
+                relaxed,
+                strategy,
+                estimate_buffers,
+                granularity,
+                busy,
+                breaks,
+            ) {
+                Ok(schedule) => break schedule,
+                Err(Error::DeadlineMissed { .. } | Error::NotEnoughTime { .. })
+                    if attempts < MAX_RELAXATIONS =>
+                {
+                    extra = extra * 2
+                }
+                Err(error) => return Err(error.map_task(|task| task.task)),
+            }
+        };
+        let schedule = Schedule(
+            schedule
+                .into_iter()
+                .map(|scheduled| Scheduled {
+                    task: scheduled.task.task,
+                    when: scheduled.when,
+                    duration: scheduled.duration,
+                    part: scheduled.part,
+                })
+                .collect(),
+        );
+        // A task is finished when its last part is
+        let mut ends: HashMap<&TaskT, DateTime<Utc>> = HashMap::new();
+        for scheduled in &schedule {
+            let end = ends.entry(&scheduled.task).or_insert(scheduled.end());
+            *end = std::cmp::max(*end, scheduled.end());
+        }
+        let mut lateness = vec![];
+        for task in tasks_per_segment.into_iter().flat_map(|(_, tasks)| tasks) {
+            let end = *ends
+                .get(&task)
+                .ok_or(Error::Internal("A task went missing from the schedule"))?;
+            lateness.push(Lateness {
+                lateness: std::cmp::max(Duration::zero(), end - task.deadline()),
+                task,
+            });
+        }
+        lateness.sort_by_key(|lateness| Reverse(lateness.lateness));
+        Ok((schedule, lateness))
     }
 
+    /// Schedules the tasks within the time segment, outside of the `busy`
+    /// ranges. Tasks which depend on tasks in other time segments don't start
+    /// before the moment those are `finished`.
     fn schedule_within_segment(
         start: DateTime<Utc>,
-        tasks: impl IntoIterator<Item = TaskT>,
+        tasks: Vec<TaskT>,
         segment: impl TimeSegment,
         strategy: SchedulingStrategy,
+        granularity: Duration,
+        busy: &[Range<DateTime<Utc>>],
+        finished: &HashMap<u32, DateTime<Utc>>,
     ) -> Result<Schedule<TaskT>, Error<TaskT>>
     where
         TaskT: Task,
     {
-        let tasks: Vec<Rc<TaskT>> = tasks.into_iter().map(Rc::new).collect();
         if tasks.is_empty() {
-            Ok(Schedule::default())
-        } else {
-            let mut tree: ScheduleTree<DateTime<Utc>, Item<TaskT>> = ScheduleTree::new();
-            // Make sure things aren't scheduled before the algorithm is finished.
-            let last_deadline = tasks
-                .iter()
-                .map(|task| task.deadline())
-                .max()
-                .ok_or(Error::Internal("last deadline not found"))?;
-            let unscheduleables = segment.inverse().generate_ranges(start, last_deadline);
-            for unscheduleable in unscheduleables {
-                tree.schedule_exact(
-                    unscheduleable.start,
-                    unscheduleable.end - unscheduleable.start,
-                    Item::Nothing,
-                );
-            }
-            match strategy {
-                SchedulingStrategy::Importance => {
-                    tree.schedule_according_to_importance(start, tasks)
+            return Ok(Schedule::default());
+        }
+        let tree = Schedule::tree_within_segment(
+            start,
+            tasks,
+            segment,
+            strategy,
+            granularity,
+            busy,
+            finished,
+        )?;
+        Ok(Schedule::from_tree(tree))
+    }
+
+    /// Does the work for `schedule_within_segment`, which only has to read the
+    /// schedule from the tree.
+    fn tree_within_segment(
+        start: DateTime<Utc>,
+        tasks: Vec<TaskT>,
+        segment: impl TimeSegment,
+        strategy: SchedulingStrategy,
+        granularity: Duration,
+        busy: &[Range<DateTime<Utc>>],
+        finished: &HashMap<u32, DateTime<Utc>>,
+    ) -> Result<ScheduleTree<DateTime<Utc>, Item<TaskT>>, Error<TaskT>>
+    where
+        TaskT: Task,
+    {
+        let order = dependency_order(&tasks).map_err(|task| Error::DependencyCycle {
+            task: Box::new(task),
+        })?;
+        let tasks: Vec<Rc<TaskT>> = tasks.into_iter().map(Rc::new).collect();
+        let tasks_in_order = order.iter().map(|&i| Rc::clone(&tasks[i])).collect_vec();
+
+        let mut tree: ScheduleTree<DateTime<Utc>, Item<TaskT>> = ScheduleTree::new();
+        // Make sure things aren't scheduled before the algorithm is finished.
+        let last_deadline = tasks
+            .iter()
+            .map(|task| task.deadline())
+            .max()
+            .ok_or(Error::Internal("last deadline not found"))?;
+        // Busy ranges block the time just like the time outside the segment.
+        // The free slots in between are rounded inwards, so the ones shorter
+        // than the granularity disappear.
+        let busy = busy
+            .iter()
+            .filter(|range| range.end > start && range.start < last_deadline)
+            .map(|range| std::cmp::max(range.start, start)..range.end);
+        let unscheduleables = segment
+            .inverse()
+            .generate_ranges(start, last_deadline)
+            .into_iter()
+            .chain(busy)
+            .sorted_by_key(|range| range.start)
+            .map(|range| floor(range.start, granularity)..ceil(range.end, granularity))
+            .coalesce(|previous, range| {
+                if range.start <= previous.end {
+                    Ok(previous.start..std::cmp::max(previous.end, range.end))
+                } else {
+                    Err((previous, range))
                 }
-                SchedulingStrategy::Urgency => tree.schedule_according_to_myrjam(start, tasks),
-            }?;
-            Ok(Schedule::from_tree(tree))
+            });
+        for unscheduleable in unscheduleables {
+            tree.schedule_exact(
+                unscheduleable.start,
+                unscheduleable.end - unscheduleable.start,
+                Item::Nothing,
+            );
         }
+        // Pinned tasks get the first free time and the others fill in around
+        // them
+        let (pinned, tasks): (Vec<_>, Vec<_>) = tasks.into_iter().partition(|task| task.pinned());
+        tree.schedule_pinned(start, pinned)?;
+        match strategy {
+            SchedulingStrategy::Importance => tree.schedule_according_to_importance(start, tasks),
+            SchedulingStrategy::Urgency => tree.schedule_according_to_myrjam(start, tasks),
+            SchedulingStrategy::Balanced => tree.schedule_according_to_balanced(start, tasks),
+            SchedulingStrategy::Deadline => tree.schedule_according_to_deadline(start, tasks),
+            SchedulingStrategy::Shuffled { seed } => {
+                tree.schedule_according_to_shuffled_importance(start, tasks, seed)
+            }
+        }?;
+        tree.respect_dependencies(tasks_in_order, finished)?;
+        Ok(tree)
     }
 
     fn from_tree(tree: ScheduleTree<DateTime<Utc>, Item<TaskT>>) -> Schedule<TaskT>
@@ -162,6 +928,8 @@ impl<TaskT> Schedule<TaskT> {
                 Item::Task(task) => Some(Scheduled {
                     task: (*task).clone(),
                     when: entry.start,
+                    duration: entry.end - entry.start,
+                    part: None,
                 }),
             })
             .collect();
@@ -169,45 +937,832 @@ impl<TaskT> Schedule<TaskT> {
     }
 }
 
-#[derive(Debug, Hash, Clone)]
-enum Item<TaskT> {
-    Task(Rc<TaskT>),
-    Nothing,
-}
-
-impl<TaskT: PartialEq> PartialEq for Item<TaskT> {
-    fn eq(&self, other: &Item<TaskT>) -> bool {
-        match (self, other) {
-            (Self::Task(task), Self::Task(other)) => task.eq(other),
-            _ => false,
+impl Schedule<crate::Task> {
+    /// Changes the duration of a single scheduled task without touching the
+    /// rest of the schedule. The task keeps its start, so its new duration
+    /// should fit in the gap it leaves behind and it should still be finished
+    /// before its deadline.
+    ///
+    /// Since a schedule doesn't know about time segments, a task which is
+    /// scheduled last may grow beyond the end of its time segment.
+    ///
+    /// Returns the rescheduled task, or an error when it doesn't fit anymore or
+    /// is split into several parts, in which case the schedule is left
+    /// untouched.
+    pub fn reschedule(
+        &mut self,
+        task_id: u32,
+        new_duration: Duration,
+    ) -> Result<Scheduled<crate::Task>, Error<crate::Task>> {
+        let index = self
+            .0
+            .iter()
+            .position(|scheduled| scheduled.task.id == task_id)
+            .ok_or(Error::NotScheduled { id: task_id })?;
+        if self.0[index].part.is_some() {
+            return Err(Error::Split { id: task_id });
+        }
+        let mut tree: ScheduleTree<DateTime<Utc>, Item<crate::Task>> = ScheduleTree::new();
+        let items = self
+            .0
+            .iter()
+            .map(|scheduled| Item::Task(Rc::new(scheduled.task.clone())))
+            .collect_vec();
+        for (scheduled, item) in self.0.iter().zip(&items) {
+            if !tree.schedule_exact(scheduled.when, scheduled.duration, item.clone()) {
+                return Err(Error::Internal("scheduled tasks overlap"));
+            }
         }
-    }
-}
 
-// HACK: We're lying here. According to our implementation of PartialEq, the
-// equivalence relation not reflexive for Nothing. The ScheduleTree needs it for
-// its internal hash map which it uses for data lookups. So this hack will cause
-// e.g. all Nothings to be un-unscheduleable.
-impl<TaskT: PartialEq> Eq for Item<TaskT> {}
+        let when = *tree
+            .when_scheduled(&items[index])
+            .ok_or(Error::Internal("I couldn't find a scheduled task"))?;
+        let scheduled_entry = tree
+            .unschedule(&items[index])
+            .ok_or(Error::Internal("I couldn't unschedule a task"))?;
+        let mut task = self.0[index].task.clone();
+        task.duration = new_duration;
+        if when + new_duration > task.deadline
+            || !tree.schedule_exact(when, new_duration, scheduled_entry.data)
+        {
+            return Err(Error::NotEnoughTime {
+                task: Box::new(task),
+            });
+        }
 
-trait Scheduler<TaskT: Task> {
-    fn schedule_according_to_importance(
-        &mut self,
-        start: DateTime<Utc>,
-        tasks: Vec<Rc<TaskT>>,
-    ) -> Result<(), Error<TaskT>>;
-    fn schedule_according_to_myrjam(
-        &mut self,
-        start: DateTime<Utc>,
-        tasks: Vec<Rc<TaskT>>,
-    ) -> Result<(), Error<TaskT>>;
-}
+        self.0[index].task = task;
+        self.0[index].duration = new_duration;
+        Ok(self.0[index].clone())
+    }
 
-impl<TaskT: Task> Scheduler<TaskT> for ScheduleTree<DateTime<Utc>, Item<TaskT>> {
-    /// Schedules `tasks` according to importance while making sure all deadlines are met.
+    /// Keeps only what is scheduled before `until`, e.g. to look no further
+    /// ahead than a week. Tasks which run past `until` are cut short there.
     ///
-    /// First, all tasks --- starting with the least important until the most important --- are
-    /// scheduled as close as possible to their deadline. Next, all tasks --- starting with the
+    /// Returns the clipped schedule along with the tasks which wouldn't even
+    /// start before `until`, which are left out.
+    pub fn clip(self, until: DateTime<Utc>) -> (Schedule<crate::Task>, Vec<crate::Task>) {
+        let (within, beyond): (Vec<_>, Vec<_>) = self
+            .0
+            .into_iter()
+            .partition(|scheduled| scheduled.when < until);
+        // Split tasks of which some part starts in time aren't left out
+        let deferred = beyond
+            .into_iter()
+            .map(|scheduled| scheduled.task)
+            .filter(|task| !within.iter().any(|scheduled| scheduled.task.id == task.id))
+            .unique_by(|task| task.id)
+            .collect();
+        let within = within
+            .into_iter()
+            .map(|mut scheduled| {
+                scheduled.duration = std::cmp::min(scheduled.duration, until - scheduled.when);
+                scheduled
+            })
+            .collect();
+        (Schedule(within), deferred)
+    }
+
+    /// Sums how long tasks are scheduled before `until` and how much time is
+    /// left free in between them, not counting the time before the first
+    /// task or after the last one.
+    ///
+    /// Returns the busy and idle time, which are both zero for an empty
+    /// schedule.
+    pub fn utilisation(&self, until: DateTime<Utc>) -> (Duration, Duration) {
+        let ranges = self
+            .0
+            .iter()
+            .filter(|scheduled| scheduled.when < until)
+            .map(|scheduled| {
+                let end = std::cmp::min(scheduled.end(), until);
+                scheduled.when..end
+            })
+            .collect_vec();
+        let (first_start, last_end) = match (
+            ranges.iter().map(|range| range.start).min(),
+            ranges.iter().map(|range| range.end).max(),
+        ) {
+            (Some(first_start), Some(last_end)) => (first_start, last_end),
+            _ => return (Duration::zero(), Duration::zero()),
+        };
+        let busy = ranges.iter().fold(Duration::zero(), |total, range| {
+            total + (range.end - range.start)
+        });
+        let idle = std::cmp::max(last_end - first_start - busy, Duration::zero());
+        (busy, idle)
+    }
+
+    /// Returns the scheduled tasks which end no more than `threshold` before
+    /// their deadline, so a small delay would make them miss it. This is only
+    /// informational: the schedule is fine as it is.
+    pub fn tight_tasks(&self, threshold: Duration) -> Vec<&Scheduled<crate::Task>> {
+        self.0
+            .iter()
+            .filter(|scheduled| scheduled.task.deadline - scheduled.end() <= threshold)
+            .collect()
+    }
+
+    /// Returns the task which is in progress at `instant`, or otherwise the
+    /// first one scheduled after it. Tasks which already ended are skipped.
+    pub fn next_after(&self, instant: DateTime<Utc>) -> Option<&Scheduled<crate::Task>> {
+        self.0
+            .iter()
+            .filter(|scheduled| scheduled.when >= instant || scheduled.end() > instant)
+            .min_by_key(|scheduled| scheduled.when)
+    }
+}
+
+/// Checks whether all tasks can be scheduled from `start` on, without a
+/// database or configuration and without keeping the schedule. Tasks are
+/// planned to the second, without extra time for uncertain estimates and
+/// without breaks.
+///
+/// ```
+/// use chrono::{Duration, Utc};
+/// use eva::configuration::SchedulingStrategy;
+/// use eva::scheduling::{feasibility, Error};
+/// use eva::time_segment::UnnamedTimeSegment;
+/// use eva::{Confidence, Task};
+///
+/// let now = Utc::now();
+/// let report = Task {
+///     id: 1,
+///     content: "Write the report".to_string(),
+///     deadline: now + Duration::hours(3),
+///     duration: Duration::hours(2),
+///     importance: 5,
+///     confidence: Confidence::High,
+///     time_segment_id: 0,
+///     depends_on: vec![],
+///     color: None,
+///     scheduled_for: None,
+///     done_at: None,
+///     pinned: false,
+/// };
+/// let anytime = UnnamedTimeSegment::anytime(now);
+/// let strategy = SchedulingStrategy::Importance;
+/// assert!(feasibility(now, vec![(anytime.clone(), vec![report.clone()])], strategy).is_ok());
+///
+/// let slides = Task {
+///     id: 2,
+///     content: "Make the slides".to_string(),
+///     ..report.clone()
+/// };
+/// let result = feasibility(now, vec![(anytime, vec![report, slides])], strategy);
+/// assert!(matches!(result, Err(Error::NotEnoughTime { .. })));
+/// ```
+pub fn feasibility(
+    start: DateTime<Utc>,
+    tasks_per_segment: Vec<(impl TimeSegment, Vec<crate::Task>)>,
+    strategy: SchedulingStrategy,
+) -> Result<(), Error<crate::Task>> {
+    Schedule::schedule(
+        start,
+        tasks_per_segment,
+        strategy,
+        EstimateBuffers {
+            low_confidence: 0,
+            medium_confidence: 0,
+        },
+        Duration::zero(),
+        &[],
+        None,
+    )
+    .map(|_| ())
+}
+
+/// Splits the tasks which are longer than the longest window of the time
+/// segment before their deadline, so they can be spread over several windows.
+/// The busy ranges are cut out of the windows. Like the free slots of the
+/// schedule, the windows are rounded inwards to the granularity. Tasks which
+/// can't be finished before their deadline anyway are left whole, so they are
+/// reported as such.
+fn split_over_windows<TaskT: Task>(
+    tasks: Vec<Buffered<TaskT>>,
+    segment: &impl TimeSegment,
+    start: DateTime<Utc>,
+    granularity: Duration,
+    busy: &[Range<DateTime<Utc>>],
+) -> Vec<Buffered<TaskT>> {
+    let last_deadline = match tasks.iter().map(|task| task.deadline).max() {
+        Some(last_deadline) => last_deadline,
+        None => return tasks,
+    };
+    let windows = segment
+        .generate_ranges(start, last_deadline)
+        .into_iter()
+        .flat_map(|range| without_busy(std::cmp::max(range.start, start)..range.end, busy))
+        .map(|range| ceil(range.start, granularity)..range.end)
+        .collect_vec();
+    tasks
+        .into_iter()
+        .flat_map(|task| {
+            if task.deadline < start + task.duration {
+                return vec![task];
+            }
+            let longest = windows
+                .iter()
+                .map(|window| {
+                    floor(std::cmp::min(window.end, task.deadline), granularity) - window.start
+                })
+                .max()
+                .unwrap_or_else(Duration::zero);
+            task.split(longest)
+        })
+        .collect()
+}
+
+/// Plans a break after every `max_continuous` within the windows of the time
+/// segment, so no stretch of work is any longer. The free time between the busy
+/// ranges is rounded inwards like in `split_over_windows`, and the breaks are
+/// rounded to the granularity: the time to work down and the breaks up.
+fn forced_breaks(
+    segment: &impl TimeSegment,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    granularity: Duration,
+    busy: &[Range<DateTime<Utc>>],
+    breaks: Breaks,
+) -> Vec<Range<DateTime<Utc>>> {
+    let work = std::cmp::max(round_down(breaks.max_continuous, granularity), granularity);
+    let length = round_up(breaks.length, granularity);
+    if work <= Duration::zero() || length <= Duration::zero() {
+        return vec![];
+    }
+    segment
+        .generate_ranges(start, end)
+        .into_iter()
+        .flat_map(|range| without_busy(std::cmp::max(range.start, start)..range.end, busy))
+        .map(|range| ceil(range.start, granularity)..floor(range.end, granularity))
+        .filter(|range| range.start < range.end)
+        // Work goes on in windows which follow each other without a gap
+        .coalesce(|previous, range| {
+            if range.start <= previous.end {
+                Ok(previous.start..std::cmp::max(previous.end, range.end))
+            } else {
+                Err((previous, range))
+            }
+        })
+        .flat_map(|window| {
+            let mut breaks = vec![];
+            let mut from = window.start + work;
+            while from < window.end {
+                breaks.push(from..std::cmp::min(from + length, window.end));
+                from = from + length + work;
+            }
+            breaks
+        })
+        .collect()
+}
+
+/// Returns the parts of the window which aren't busy.
+fn without_busy(
+    window: Range<DateTime<Utc>>,
+    busy: &[Range<DateTime<Utc>>],
+) -> Vec<Range<DateTime<Utc>>> {
+    let mut free = vec![];
+    let mut from = window.start;
+    let overlapping = busy
+        .iter()
+        .filter(|range| range.start < window.end && range.end > window.start)
+        .sorted_by_key(|range| range.start);
+    for range in overlapping {
+        if range.start > from {
+            free.push(from..range.start);
+        }
+        from = std::cmp::max(from, range.end);
+    }
+    if from < window.end {
+        free.push(from..window.end);
+    }
+    free
+}
+
+/// How much time the tasks in a time segment need compared to how much time
+/// the segment has for them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SegmentCapacity {
+    pub time_segment: NamedTimeSegment,
+    pub tasks: Vec<crate::Task>,
+    /// The total duration of the tasks, including the extra time for less
+    /// trustworthy estimates
+    pub required: Duration,
+    /// The total time the segment covers between the start and the last
+    /// deadline of its tasks
+    pub available: Duration,
+}
+
+impl SegmentCapacity {
+    /// Returns how much time is lacking to fit all tasks in, if any.
+    pub fn deficit(&self) -> Option<Duration> {
+        (self.required > self.available).then(|| self.required - self.available)
+    }
+}
+
+impl Schedule<crate::Task> {
+    /// Compares the time the tasks in each time segment need with the time the
+    /// segment covers between `start` and their last deadline. Unlike
+    /// scheduling, this considers all tasks at once, so it can point out every
+    /// segment which is overcommitted rather than the first task which doesn't
+    /// fit.
+    ///
+    /// Enough time in a segment doesn't guarantee the tasks can be scheduled,
+    /// since they may still miss their deadlines.
+    pub fn capacity_report(
+        start: DateTime<Utc>,
+        tasks_per_segment: Vec<(NamedTimeSegment, Vec<crate::Task>)>,
+        estimate_buffers: EstimateBuffers,
+    ) -> Vec<SegmentCapacity> {
+        tasks_per_segment
+            .into_iter()
+            .map(|(time_segment, tasks)| {
+                let horizon = tasks
+                    .iter()
+                    .map(|task| task.deadline)
+                    .max()
+                    .unwrap_or(start);
+                let available = time_segment
+                    .generate_ranges(start, horizon)
+                    .into_iter()
+                    .fold(Duration::zero(), |total, range| {
+                        let end = std::cmp::min(range.end, horizon);
+                        let start = std::cmp::max(range.start, start);
+                        total + std::cmp::max(end - start, Duration::zero())
+                    });
+                let required = tasks.iter().fold(Duration::zero(), |total, task| {
+                    total + estimate_buffers.apply(task.duration, task.confidence)
+                });
+                SegmentCapacity {
+                    time_segment,
+                    tasks,
+                    required,
+                    available,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Orders the tasks so that every task comes after the tasks it depends on.
+/// Dependencies on tasks which aren't given are ignored.
+///
+/// Returns the indices of the tasks in that order, or a task which depends on
+/// itself, directly or indirectly.
+fn dependency_order<TaskT: Task>(tasks: &[TaskT]) -> Result<Vec<usize>, TaskT> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum State {
+        Unvisited,
+        Visiting,
+        Visited,
+    }
+
+    fn visit<TaskT: Task>(
+        i: usize,
+        tasks: &[TaskT],
+        indices_of: &HashMap<u32, Vec<usize>>,
+        states: &mut [State],
+        order: &mut Vec<usize>,
+    ) -> Result<(), usize> {
+        match states[i] {
+            State::Visited => return Ok(()),
+            State::Visiting => return Err(i),
+            State::Unvisited => {}
+        }
+        states[i] = State::Visiting;
+        for id in tasks[i].dependencies() {
+            for &dependency in indices_of.get(id).into_iter().flatten() {
+                visit(dependency, tasks, indices_of, states, order)?;
+            }
+        }
+        states[i] = State::Visited;
+        order.push(i);
+        Ok(())
+    }
+
+    // The parts of a split task share its id
+    let indices_of = tasks
+        .iter()
+        .enumerate()
+        .map(|(i, task)| (task.id(), i))
+        .into_group_map();
+    let mut states = vec![State::Unvisited; tasks.len()];
+    let mut order = Vec::with_capacity(tasks.len());
+    for i in 0..tasks.len() {
+        visit(i, tasks, &indices_of, &mut states, &mut order).map_err(|i| tasks[i].clone())?;
+    }
+    Ok(order)
+}
+
+/// Moves the deadlines of tasks forward so that the tasks depending on them can
+/// still be finished in time after them.
+fn leave_time_for_dependent_tasks<TaskT: Task>(tasks: &mut [Buffered<TaskT>], order: &[usize]) {
+    let index_of: HashMap<u32, usize> = tasks
+        .iter()
+        .enumerate()
+        .map(|(i, task)| (task.id(), i))
+        .collect();
+    // Handle dependent tasks before the tasks they depend on
+    for &i in order.iter().rev() {
+        let latest_start = tasks[i].deadline - tasks[i].duration;
+        for id in tasks[i].dependencies().to_vec() {
+            if let Some(&dependency) = index_of.get(&id) {
+                if latest_start < tasks[dependency].deadline {
+                    tasks[dependency].deadline = latest_start;
+                }
+            }
+        }
+    }
+}
+
+/// The differences between two schedules, with tasks matched by their id.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct ScheduleDiff<TaskT> {
+    /// Tasks which are only in the new schedule
+    pub added: Vec<Scheduled<TaskT>>,
+    /// Tasks which are only in the old schedule
+    pub removed: Vec<Scheduled<TaskT>>,
+    /// Tasks which are in both schedules, but at a different moment
+    pub moved: Vec<Moved<TaskT>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Moved<TaskT> {
+    /// The task as it is in the new schedule
+    pub task: TaskT,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+}
+
+impl<TaskT> ScheduleDiff<TaskT> {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.moved.is_empty()
+    }
+}
+
+impl Schedule<crate::Task> {
+    /// Compares this (old) schedule to a newer one. Changes to a task other
+    /// than when it is scheduled aren't reported, nor are moves of less than a
+    /// minute, which happen anyway since every schedule starts from now. The
+    /// parts of a split task are matched by their number, a whole task being
+    /// its own first part.
+    pub fn diff(&self, new: &Schedule<crate::Task>) -> ScheduleDiff<crate::Task> {
+        let find = |schedule: &Schedule<crate::Task>, other: &Scheduled<crate::Task>| {
+            schedule
+                .0
+                .iter()
+                .find(|scheduled| {
+                    scheduled.task.id == other.task.id
+                        && scheduled.part.map_or(1, |part| part.number)
+                            == other.part.map_or(1, |part| part.number)
+                })
+                .cloned()
+        };
+        let added = new
+            .0
+            .iter()
+            .filter(|scheduled| find(self, scheduled).is_none())
+            .cloned()
+            .collect();
+        let removed = self
+            .0
+            .iter()
+            .filter(|scheduled| find(new, scheduled).is_none())
+            .cloned()
+            .collect();
+        let moved = new
+            .0
+            .iter()
+            .filter_map(|scheduled| {
+                let old = find(self, scheduled)?;
+                let moved_by = scheduled.when - old.when;
+                (moved_by.num_minutes() != 0).then(|| Moved {
+                    task: scheduled.task.clone(),
+                    from: old.when,
+                    to: scheduled.when,
+                })
+            })
+            .collect();
+        ScheduleDiff {
+            added,
+            removed,
+            moved,
+        }
+    }
+}
+
+/// Why a task is scheduled where it is, as far as the strategy which
+/// scheduled it tells.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Explanation {
+    /// The task's first part, if it's split
+    pub scheduled: Scheduled<crate::Task>,
+    /// What the strategy sorted the task on
+    pub sort_key: SortKey,
+    /// How much time would be left to spare before the deadline if the task
+    /// were done in one go from when it's scheduled
+    pub slack: Duration,
+    /// The tasks in the same time segment which are scheduled before it
+    pub preceded_by: Vec<Precedence>,
+}
+
+/// What a strategy sorts a task on, from most to least significant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    /// Pinned tasks go before the others, whatever the strategy
+    Pinned {
+        deadline: DateTime<Utc>,
+    },
+    /// The importance and urgency strategies
+    Importance {
+        importance: u32,
+        time_left: Duration,
+    },
+    /// The shuffled strategy, which breaks ties at random
+    Shuffled {
+        importance: u32,
+    },
+    Balanced {
+        score: i64,
+    },
+    Deadline {
+        deadline: DateTime<Utc>,
+        importance: u32,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Precedence {
+    pub task: crate::Task,
+    pub reason: Reason,
+}
+
+/// Why a task is scheduled before another one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    Pinned,
+    /// The other task depends on it, possibly through other tasks
+    DependedOn,
+    MoreImportant,
+    MoreUrgent,
+    HigherScore,
+    /// It can't wait until after the other task without missing its deadline
+    Tight,
+    /// It fit in the time before the other task, which didn't fit there itself
+    Gap,
+}
+
+impl Schedule<crate::Task> {
+    /// Explains why the task with the given id is scheduled where it is, given
+    /// the moment and strategy it was scheduled with. This only looks at the
+    /// finished schedule, so it doesn't change where anything goes. Returns
+    /// None if the task isn't in the schedule.
+    pub fn explain(
+        &self,
+        id: u32,
+        start: DateTime<Utc>,
+        strategy: SchedulingStrategy,
+    ) -> Option<Explanation> {
+        let scheduled = self.0.iter().find(|scheduled| scheduled.task.id == id)?;
+        let task = &scheduled.task;
+        let sort_key = |task: &crate::Task| match strategy {
+            _ if task.pinned => SortKey::Pinned {
+                deadline: task.deadline,
+            },
+            SchedulingStrategy::Importance | SchedulingStrategy::Urgency => SortKey::Importance {
+                importance: task.importance,
+                time_left: task.urgency_rank(start).0,
+            },
+            SchedulingStrategy::Shuffled { .. } => SortKey::Shuffled {
+                importance: task.importance,
+            },
+            SchedulingStrategy::Balanced => SortKey::Balanced {
+                score: balanced_score(task, start),
+            },
+            SchedulingStrategy::Deadline => SortKey::Deadline {
+                deadline: task.deadline,
+                importance: task.importance,
+            },
+        };
+        // The tasks this one depends on, directly or through other tasks
+        let by_id: HashMap<u32, &crate::Task> = self
+            .0
+            .iter()
+            .map(|scheduled| (scheduled.task.id, &scheduled.task))
+            .collect();
+        let mut dependencies = HashSet::new();
+        let mut to_visit = task.depends_on.clone();
+        while let Some(dependency) = to_visit.pop() {
+            if dependencies.insert(dependency) {
+                if let Some(dependency) = by_id.get(&dependency) {
+                    to_visit.extend(&dependency.depends_on);
+                }
+            }
+        }
+        let reason = |other: &crate::Task| match (sort_key(other), sort_key(task)) {
+            _ if dependencies.contains(&other.id) => Reason::DependedOn,
+            (SortKey::Pinned { .. }, key) if !matches!(key, SortKey::Pinned { .. }) => {
+                Reason::Pinned
+            }
+            (SortKey::Pinned { deadline }, SortKey::Pinned { deadline: own })
+            | (SortKey::Deadline { deadline, .. }, SortKey::Deadline { deadline: own, .. })
+                if deadline < own =>
+            {
+                Reason::MoreUrgent
+            }
+            (
+                SortKey::Deadline {
+                    deadline,
+                    importance,
+                },
+                SortKey::Deadline {
+                    deadline: own,
+                    importance: own_importance,
+                },
+            ) if deadline == own && importance > own_importance => Reason::MoreImportant,
+            (
+                SortKey::Importance {
+                    importance,
+                    time_left,
+                },
+                SortKey::Importance {
+                    importance: own_importance,
+                    time_left: own_time_left,
+                },
+            ) if (importance, Reverse(time_left)) > (own_importance, Reverse(own_time_left)) => {
+                if importance > own_importance {
+                    Reason::MoreImportant
+                } else {
+                    Reason::MoreUrgent
+                }
+            }
+            (
+                SortKey::Shuffled { importance },
+                SortKey::Shuffled {
+                    importance: own_importance,
+                },
+            ) if importance > own_importance => Reason::MoreImportant,
+            (SortKey::Balanced { score }, SortKey::Balanced { score: own }) if score > own => {
+                Reason::HigherScore
+            }
+            _ if other.slack(scheduled.end()) < Duration::zero() => Reason::Tight,
+            _ => Reason::Gap,
+        };
+        let mut seen = HashSet::new();
+        let preceded_by = self
+            .0
+            .iter()
+            .take_while(|other| other.when < scheduled.when)
+            .filter(|other| {
+                other.task.time_segment_id == task.time_segment_id && other.task.id != id
+            })
+            // Split tasks only count once
+            .filter(|other| seen.insert(other.task.id))
+            .map(|other| Precedence {
+                task: other.task.clone(),
+                reason: reason(&other.task),
+            })
+            .collect();
+        Some(Explanation {
+            scheduled: scheduled.clone(),
+            sort_key: sort_key(task),
+            slack: task.slack(scheduled.when),
+            preceded_by,
+        })
+    }
+}
+
+#[derive(Debug, Hash, Clone)]
+enum Item<TaskT> {
+    Task(Rc<TaskT>),
+    Nothing,
+}
+
+impl<TaskT: PartialEq> PartialEq for Item<TaskT> {
+    fn eq(&self, other: &Item<TaskT>) -> bool {
+        match (self, other) {
+            (Self::Task(task), Self::Task(other)) => task.eq(other),
+            _ => false,
+        }
+    }
+}
+
+// HACK: We're lying here. According to our implementation of PartialEq, the
+// equivalence relation not reflexive for Nothing. The ScheduleTree needs it for
+// its internal hash map which it uses for data lookups. So this hack will cause
+// e.g. all Nothings to be un-unscheduleable.
+impl<TaskT: PartialEq> Eq for Item<TaskT> {}
+
+/// How many hours closer to its deadline a task has to be to weigh as much as
+/// one extra point of importance in the balanced strategy.
+const BALANCED_HOURS_PER_IMPORTANCE: i64 = 24;
+
+/// The score of a task in the balanced strategy: every point of importance counts as much as
+/// having `BALANCED_HOURS_PER_IMPORTANCE` hours less left until the deadline.
+fn balanced_score<TaskT: Task>(task: &TaskT, start: DateTime<Utc>) -> i64 {
+    i64::from(task.importance()) * BALANCED_HOURS_PER_IMPORTANCE
+        - (task.deadline() - start).num_hours()
+}
+
+/// Sorts the tasks from most to least pressing according to the strategy, when
+/// scheduling from `start`. Pinned tasks come first, the earliest deadline
+/// first, like they're scheduled. The urgency and deadline strategies sort the
+/// others by deadline first and importance second, the balanced strategy by
+/// its score and the other strategies by importance, like the schedulers do.
+pub fn sort_by_priority<T: Borrow<crate::Task>>(
+    tasks: &mut [T],
+    strategy: SchedulingStrategy,
+    start: DateTime<Utc>,
+) {
+    match strategy {
+        SchedulingStrategy::Importance => {
+            tasks.sort_by_key(|task| (Reverse(task.borrow().importance), task.borrow().deadline))
+        }
+        SchedulingStrategy::Urgency | SchedulingStrategy::Deadline => {
+            tasks.sort_by_key(|task| (task.borrow().deadline, Reverse(task.borrow().importance)))
+        }
+        SchedulingStrategy::Balanced => tasks.sort_by_key(|task| {
+            (
+                Reverse(balanced_score(task.borrow(), start)),
+                task.borrow().deadline,
+            )
+        }),
+        SchedulingStrategy::Shuffled { seed } => {
+            tasks.sort_by_key(|task| task.borrow().id);
+            tasks.shuffle(&mut ChaCha8Rng::seed_from_u64(seed));
+            tasks.sort_by_key(|task| Reverse(task.borrow().importance));
+        }
+    }
+    // The sort is stable, so the tasks which aren't pinned stay in order
+    tasks.sort_by_key(|task| {
+        let task = task.borrow();
+        (
+            Reverse(task.pinned),
+            task.pinned
+                .then_some((task.deadline, Reverse(task.importance))),
+        )
+    });
+}
+
+/// How many times, on average, each task may be moved towards the start of the schedule before
+/// Eva gives up on filling the gaps. Every move starts a new pass over the tasks.
+const MAX_PASSES_PER_TASK: usize = 10;
+
+trait Scheduler<TaskT: Task> {
+    fn schedule_according_to_importance(
+        &mut self,
+        start: DateTime<Utc>,
+        tasks: Vec<Rc<TaskT>>,
+    ) -> Result<(), Error<TaskT>>;
+    fn schedule_according_to_myrjam(
+        &mut self,
+        start: DateTime<Utc>,
+        tasks: Vec<Rc<TaskT>>,
+    ) -> Result<(), Error<TaskT>>;
+    fn schedule_according_to_balanced(
+        &mut self,
+        start: DateTime<Utc>,
+        tasks: Vec<Rc<TaskT>>,
+    ) -> Result<(), Error<TaskT>>;
+    fn schedule_according_to_deadline(
+        &mut self,
+        start: DateTime<Utc>,
+        tasks: Vec<Rc<TaskT>>,
+    ) -> Result<(), Error<TaskT>>;
+    fn schedule_according_to_shuffled_importance(
+        &mut self,
+        start: DateTime<Utc>,
+        tasks: Vec<Rc<TaskT>>,
+        seed: u64,
+    ) -> Result<(), Error<TaskT>>;
+    fn schedule_least_important_first(
+        &mut self,
+        start: DateTime<Utc>,
+        tasks: Vec<Rc<TaskT>>,
+    ) -> Result<(), Error<TaskT>>;
+    fn schedule_pinned(
+        &mut self,
+        start: DateTime<Utc>,
+        tasks: Vec<Rc<TaskT>>,
+    ) -> Result<(), Error<TaskT>>;
+    fn shift_towards_start(
+        &mut self,
+        start: DateTime<Utc>,
+        tasks: &[Rc<TaskT>],
+    ) -> Result<usize, Error<TaskT>>;
+    fn respect_dependencies(
+        &mut self,
+        tasks_in_order: Vec<Rc<TaskT>>,
+        finished: &HashMap<u32, DateTime<Utc>>,
+    ) -> Result<(), Error<TaskT>>;
+}
+
+impl<TaskT: Task> Scheduler<TaskT> for ScheduleTree<DateTime<Utc>, Item<TaskT>> {
+    /// Schedules `tasks` according to importance while making sure all deadlines are met.
+    ///
+    /// First, all tasks --- starting with the least important until the most important --- are
+    /// scheduled as close as possible to their deadline. Next, all tasks --- starting with the
     /// most important until the least important --- are put as close to the present as possible.
     /// For ties on importance, more urgent tasks are scheduled later in the first phase and sooner
     /// in the second phase.
@@ -218,18 +1773,39 @@ impl<TaskT: Task> Scheduler<TaskT> for ScheduleTree<DateTime<Utc>, Item<TaskT>>
         &mut self,
         start: DateTime<Utc>,
         mut tasks: Vec<Rc<TaskT>>,
+    ) -> Result<(), Error<TaskT>> {
+        tasks.sort_by_key(|task| (task.importance(), task.urgency_rank(start)));
+        self.schedule_least_important_first(start, tasks)
+    }
+
+    /// Schedules `tasks` like `schedule_according_to_importance`, but breaks ties on importance by
+    /// shuffling the tasks with the given seed instead of by urgency. The same seed and tasks
+    /// always give the same schedule, whichever order the tasks are given in.
+    fn schedule_according_to_shuffled_importance(
+        &mut self,
+        start: DateTime<Utc>,
+        mut tasks: Vec<Rc<TaskT>>,
+        seed: u64,
+    ) -> Result<(), Error<TaskT>> {
+        tasks.sort_by_key(|task| task.id());
+        tasks.shuffle(&mut ChaCha8Rng::seed_from_u64(seed));
+        // The sort is stable, so equally important tasks stay shuffled
+        tasks.sort_by_key(|task| task.importance());
+        self.schedule_least_important_first(start, tasks)
+    }
+
+    /// Does the work for `schedule_according_to_importance`, given the tasks ordered from least to
+    /// most important.
+    fn schedule_least_important_first(
+        &mut self,
+        start: DateTime<Utc>,
+        tasks: Vec<Rc<TaskT>>,
     ) -> Result<(), Error<TaskT>> {
         // Start by scheduling the least important tasks closest to the deadline, and so on.
-        tasks.sort_by_key(|task| {
-            (
-                task.importance(),
-                start.signed_duration_since(task.deadline()),
-            )
-        });
         for task in &tasks {
-            if task.deadline() < start + task.duration() {
+            if task.slack(start) < Duration::zero() {
                 return Err(Error::DeadlineMissed {
-                    task: (**task).clone(),
+                    task: Box::new((**task).clone()),
                     tense: if task.deadline() < start {
                         "missed"
                     } else {
@@ -244,39 +1820,15 @@ impl<TaskT: Task> Scheduler<TaskT> for ScheduleTree<DateTime<Utc>, Item<TaskT>>
                 Item::Task(Rc::clone(task)),
             ) {
                 return Err(Error::NotEnoughTime {
-                    task: (**task).clone(),
+                    task: Box::new((**task).clone()),
                 });
             }
+            log_placement(self, task);
         }
         // Next, shift the most important tasks towards today, and so on, filling up the gaps.
-        // Keep repeating that, until nothing changes anymore (i.e. all gaps are filled).
-        let mut changed = !self.is_empty();
-        while changed {
-            changed = false;
-            for task in tasks.iter().rev() {
-                let scheduled_entry = self
-                    .unschedule(&Item::Task(task.clone()))
-                    .ok_or_else(|| Error::Internal("I couldn't unschedule a task"))?;
-                if !self.schedule_close_after(
-                    start,
-                    task.duration(),
-                    Some(scheduled_entry.end),
-                    scheduled_entry.data,
-                ) {
-                    return Err(Error::Internal("I couldn't reschedule a task"));
-                }
-                let new_start =
-                    self.when_scheduled(&Item::Task(task.clone()))
-                        .ok_or_else(|| {
-                            Error::Internal("I couldn't find a task that was just scheduled")
-                        })?;
-                if scheduled_entry.start != *new_start {
-                    changed = true;
-                    break;
-                }
-            }
-        }
-        Ok(())
+        let most_important_first = tasks.into_iter().rev().collect_vec();
+        self.shift_towards_start(start, &most_important_first)
+            .map(|_passes| ())
     }
 
     /// Schedules `tasks` according to deadline first and then according to importance.
@@ -297,9 +1849,9 @@ impl<TaskT: Task> Scheduler<TaskT> for ScheduleTree<DateTime<Utc>, Item<TaskT>>
         // Start by scheduling the least important tasks closest to the deadline, and so on.
         tasks.sort_by_key(|task| task.importance());
         for task in tasks {
-            if task.deadline() < start + task.duration() {
+            if task.slack(start) < Duration::zero() {
                 return Err(Error::DeadlineMissed {
-                    task: (*task).clone(),
+                    task: Box::new((*task).clone()),
                     tense: if task.deadline() < start {
                         "missed"
                     } else {
@@ -314,9 +1866,10 @@ impl<TaskT: Task> Scheduler<TaskT> for ScheduleTree<DateTime<Utc>, Item<TaskT>>
                 Item::Task(Rc::clone(&task)),
             ) {
                 return Err(Error::NotEnoughTime {
-                    task: (*task).clone(),
+                    task: Box::new((*task).clone()),
                 });
             }
+            log_placement(self, &task);
         }
         // Next, shift the all tasks towards the present, filling up the gaps.
         let entries = self
@@ -340,20 +1893,287 @@ impl<TaskT: Task> Scheduler<TaskT> for ScheduleTree<DateTime<Utc>, Item<TaskT>>
                 ) {
                     return Err(Error::Internal("I couldn't reschedule a task"));
                 }
+                log_shift(self, task, scheduled_entry.start);
             }
         }
         Ok(())
     }
-}
-
-impl fmt::Display for crate::Task {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.content)
-    }
-}
 
-#[cfg(test)]
-mod tests {
+    /// Schedules `tasks` according to a mix of importance and urgency while making sure all
+    /// deadlines are met.
+    ///
+    /// First, all tasks --- starting with the least important until the most important --- are
+    /// scheduled as close as possible to their deadline. Next, all tasks are put as close to the
+    /// present as possible, in the order of their score: every point of importance counts as
+    /// much as having a day less left until the deadline. Since tasks are only ever moved
+    /// forward, pulling important tasks earlier never makes urgent ones miss their deadline.
+    fn schedule_according_to_balanced(
+        &mut self,
+        start: DateTime<Utc>,
+        mut tasks: Vec<Rc<TaskT>>,
+    ) -> Result<(), Error<TaskT>> {
+        // Start by scheduling the least important tasks closest to the deadline, and so on.
+        tasks.sort_by_key(|task| (task.importance(), task.urgency_rank(start)));
+        for task in &tasks {
+            if task.slack(start) < Duration::zero() {
+                return Err(Error::DeadlineMissed {
+                    task: Box::new((**task).clone()),
+                    tense: if task.deadline() < start {
+                        "missed"
+                    } else {
+                        "will miss"
+                    },
+                });
+            }
+            if !self.schedule_close_before(
+                task.deadline(),
+                task.duration(),
+                Some(start),
+                Item::Task(Rc::clone(task)),
+            ) {
+                return Err(Error::NotEnoughTime {
+                    task: Box::new((**task).clone()),
+                });
+            }
+            log_placement(self, task);
+        }
+        // Next, shift the tasks with the highest score towards today, and so on, filling up the
+        // gaps. Keep repeating that, until nothing changes anymore (i.e. all gaps are filled).
+        tasks.sort_by_key(|task| Reverse(balanced_score(&**task, start)));
+        if log_enabled!(Level::Debug) {
+            for task in &tasks {
+                debug!("Scored {} at {}", task, balanced_score(&**task, start));
+            }
+        }
+        self.shift_towards_start(start, &tasks).map(|_passes| ())
+    }
+
+    /// Schedules `tasks` strictly by deadline, also known as earliest deadline first.
+    ///
+    /// The task with the first deadline is put as close to the present as possible, then the task
+    /// with the next deadline as close after that as possible, and so on. Importance only breaks
+    /// ties on deadline, so it's simple to follow but it doesn't weigh what matters more.
+    fn schedule_according_to_deadline(
+        &mut self,
+        start: DateTime<Utc>,
+        mut tasks: Vec<Rc<TaskT>>,
+    ) -> Result<(), Error<TaskT>> {
+        tasks.sort_by_key(|task| (task.deadline(), Reverse(task.importance())));
+        for task in &tasks {
+            if task.slack(start) < Duration::zero() {
+                return Err(Error::DeadlineMissed {
+                    task: Box::new((**task).clone()),
+                    tense: if task.deadline() < start {
+                        "missed"
+                    } else {
+                        "will miss"
+                    },
+                });
+            }
+            if !self.schedule_close_after(
+                start,
+                task.duration(),
+                Some(task.deadline()),
+                Item::Task(Rc::clone(task)),
+            ) {
+                return Err(Error::NotEnoughTime {
+                    task: Box::new((**task).clone()),
+                });
+            }
+            log_placement(self, task);
+        }
+        Ok(())
+    }
+
+    /// Schedules pinned `tasks` as close to the present as possible, before any other task gets a
+    /// say, so they go first whatever the strategy. Among themselves, the task with the first
+    /// deadline goes first, and for the same deadline the more important one.
+    ///
+    /// Pinned tasks still have to meet their deadlines, but they can crowd out other tasks which
+    /// would otherwise have met theirs.
+    fn schedule_pinned(
+        &mut self,
+        start: DateTime<Utc>,
+        mut tasks: Vec<Rc<TaskT>>,
+    ) -> Result<(), Error<TaskT>> {
+        // The sort is stable, so the parts of a split task stay in order
+        tasks.sort_by_key(|task| (task.deadline(), Reverse(task.importance())));
+        for task in &tasks {
+            if task.slack(start) < Duration::zero() {
+                return Err(Error::DeadlineMissed {
+                    task: Box::new((**task).clone()),
+                    tense: if task.deadline() < start {
+                        "missed"
+                    } else {
+                        "will miss"
+                    },
+                });
+            }
+            if !self.schedule_close_after(
+                start,
+                task.duration(),
+                Some(task.deadline()),
+                Item::Task(Rc::clone(task)),
+            ) {
+                return Err(Error::NotEnoughTime {
+                    task: Box::new((**task).clone()),
+                });
+            }
+            log_placement(self, task);
+        }
+        Ok(())
+    }
+
+    /// Moves each task as close to `start` as it can go, in the given order of priority. Whenever
+    /// a task moves, it may have left a gap which a task with a higher priority fits in, so this
+    /// starts over from the first task until nothing moves anymore (i.e. all gaps are filled).
+    ///
+    /// Tasks only ever move towards `start`, so this always settles, but to be safe against
+    /// thrashing it gives up after `MAX_PASSES_PER_TASK` passes per task.
+    ///
+    /// Returns how many passes it took.
+    fn shift_towards_start(
+        &mut self,
+        start: DateTime<Utc>,
+        tasks: &[Rc<TaskT>],
+    ) -> Result<usize, Error<TaskT>> {
+        if self.is_empty() {
+            return Ok(0);
+        }
+        let max_passes = MAX_PASSES_PER_TASK * tasks.len() + 1;
+        for pass in 1..=max_passes {
+            let mut changed = false;
+            for task in tasks {
+                let scheduled_entry = self
+                    .unschedule(&Item::Task(task.clone()))
+                    .ok_or(Error::Internal("I couldn't unschedule a task"))?;
+                if !self.schedule_close_after(
+                    start,
+                    task.duration(),
+                    Some(scheduled_entry.end),
+                    scheduled_entry.data,
+                ) {
+                    return Err(Error::Internal("I couldn't reschedule a task"));
+                }
+                let new_start =
+                    self.when_scheduled(&Item::Task(task.clone()))
+                        .ok_or(Error::Internal(
+                            "I couldn't find a task that was just scheduled",
+                        ))?;
+                if scheduled_entry.start != *new_start {
+                    debug!(
+                        "Pass {}: shifted {} from {} to {}",
+                        pass, task, scheduled_entry.start, new_start
+                    );
+                    changed = true;
+                    break;
+                }
+            }
+            if !changed {
+                return Ok(pass);
+            }
+        }
+        Err(Error::Internal("I couldn't settle on a schedule"))
+    }
+
+    /// Moves tasks which are scheduled before the tasks they depend on are
+    /// finished to the first free moment after that. `tasks_in_order` should
+    /// list tasks after the tasks they depend on, and `finished` should contain
+    /// when tasks outside of this schedule are finished.
+    fn respect_dependencies(
+        &mut self,
+        tasks_in_order: Vec<Rc<TaskT>>,
+        finished: &HashMap<u32, DateTime<Utc>>,
+    ) -> Result<(), Error<TaskT>> {
+        // The parts of a split task share its id, and all of them have to be
+        // finished
+        let tasks_by_id: HashMap<u32, Vec<Rc<TaskT>>> = tasks_in_order
+            .iter()
+            .map(|task| (task.id(), Rc::clone(task)))
+            .into_group_map();
+        for task in tasks_in_order {
+            let ready = task
+                .dependencies()
+                .iter()
+                .filter_map(|id| match tasks_by_id.get(id) {
+                    Some(parts) => parts
+                        .iter()
+                        .filter_map(|part| {
+                            self.when_scheduled(&Item::Task(Rc::clone(part)))
+                                .map(|&when| when + part.duration())
+                        })
+                        .max(),
+                    None => finished.get(id).copied(),
+                })
+                .max();
+            let when = *self
+                .when_scheduled(&Item::Task(Rc::clone(&task)))
+                .ok_or(Error::Internal("I couldn't find a scheduled task"))?;
+            match ready {
+                Some(ready) if when < ready => {
+                    let scheduled_entry = self
+                        .unschedule(&Item::Task(Rc::clone(&task)))
+                        .ok_or(Error::Internal("I couldn't unschedule a task"))?;
+                    if !self.schedule_close_after(
+                        ready,
+                        task.duration(),
+                        Some(task.deadline()),
+                        scheduled_entry.data,
+                    ) {
+                        return Err(Error::NotEnoughTime {
+                            task: Box::new((*task).clone()),
+                        });
+                    }
+                    log_shift(self, &task, when);
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Logs where a task was placed, along with what the strategies sort it on.
+/// Looking the task up is skipped unless debug logging is enabled.
+fn log_placement<TaskT: Task>(tree: &ScheduleTree<DateTime<Utc>, Item<TaskT>>, task: &Rc<TaskT>) {
+    if !log_enabled!(Level::Debug) {
+        return;
+    }
+    if let Some(when) = tree.when_scheduled(&Item::Task(Rc::clone(task))) {
+        debug!(
+            "Placed {} (importance {}, deadline {}) at {}",
+            task,
+            task.importance(),
+            task.deadline(),
+            when
+        );
+    }
+}
+
+/// Logs that a task moved from `before`, if it did.
+fn log_shift<TaskT: Task>(
+    tree: &ScheduleTree<DateTime<Utc>, Item<TaskT>>,
+    task: &Rc<TaskT>,
+    before: DateTime<Utc>,
+) {
+    if !log_enabled!(Level::Debug) {
+        return;
+    }
+    if let Some(&when) = tree.when_scheduled(&Item::Task(Rc::clone(task))) {
+        if when != before {
+            debug!("Shifted {} from {} to {}", task, before, when);
+        }
+    }
+}
+
+impl fmt::Display for crate::Task {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
     use assert_matches::assert_matches;
     use chrono::Duration;
 
@@ -369,6 +2189,11 @@ mod tests {
     }
 
     impl super::Task for Task {
+        fn id(&self) -> u32 {
+            // These tasks never depend on each other, so they don't need to be told apart
+            0
+        }
+
         fn deadline(&self) -> DateTime<Utc> {
             self.deadline
         }
@@ -417,7 +2242,7 @@ mod tests {
                     /// Schedules the given tasks in a time segment without
                     /// gaps.
                     fn schedule(tasks: Vec<Task>, start: DateTime<Utc>) -> Result<Schedule<Task>> {
-                        Schedule::schedule_within_segment(start, tasks, anytime(), $strategy)
+                        Schedule::schedule(start, [(anytime(), tasks)], $strategy, EstimateBuffers::default(), Duration::zero(), &[], None)
                     }
 
                     #[test]
@@ -562,11 +2387,11 @@ mod tests {
                             start: now,
                             period: Duration::days(1),
                         };
-                        let schedule = Schedule::schedule_within_segment(now, tasks, segment, $strategy);
+                        let schedule = Schedule::schedule(now, [(segment, tasks)], $strategy, EstimateBuffers::default(), Duration::zero(), &[], None);
                         assert_matches!(schedule, Ok(Schedule(scheduled_tasks)) => {
                             for scheduled_task in scheduled_tasks {
                                 let start = scheduled_task.when;
-                                let end = scheduled_task.when + scheduled_task.task.duration;
+                                let end = scheduled_task.end();
                                 assert!(
                                     (start >= now + Duration::hours(10)
                                      && end <= now + Duration::hours(12))
@@ -589,16 +2414,17 @@ mod tests {
                             period: Duration::days(1),
                         };
 
-                        // Trying to schedule tasks longer than two hours fails
+                        // Trying to schedule tasks longer than the two hours
+                        // a day before their deadline fails, even when split
                         let tasks = vec![
                             Task {
                                 content: "too-long".to_string(),
-                                deadline: now + Duration::days(4),
-                                duration: Duration::hours(2) + Duration::seconds(1),
+                                deadline: now + Duration::days(2),
+                                duration: Duration::hours(4) + Duration::seconds(1),
                                 importance: 10,
                             },
                         ];
-                        let schedule = Schedule::schedule_within_segment(now, tasks, segment.clone(), $strategy);
+                        let schedule = Schedule::schedule(now, [(segment.clone(), tasks)], $strategy, EstimateBuffers::default(), Duration::zero(), &[], None);
                         assert_matches!(schedule, Err(Error::NotEnoughTime { .. }));
 
                         // Trying to schedule more tasks than possible to fit in
@@ -623,17 +2449,47 @@ mod tests {
                                 importance: 5,
                             },
                         ];
-                        let schedule = Schedule::schedule_within_segment(now, tasks, segment, $strategy);
+                        let schedule = Schedule::schedule(now, [(segment, tasks)], $strategy, EstimateBuffers::default(), Duration::zero(), &[], None);
                         assert_matches!(schedule, Err(Error::NotEnoughTime { .. }));
                     }
 
+                    #[test]
+                    fn splits_long_tasks_over_consecutive_windows() {
+                        let now = Utc.with_ymd_and_hms(2019, 8, 1, 0, 0, 0).unwrap();
+                        // Segment: eight hours daily, from nine to five
+                        let segment = UnnamedTimeSegment {
+                            ranges: vec![now + Duration::hours(9)..now + Duration::hours(17)],
+                            start: now,
+                            period: Duration::days(1),
+                        };
+                        let tasks = vec![
+                            Task {
+                                content: "twenty-hours".to_string(),
+                                deadline: now + Duration::days(5),
+                                duration: Duration::hours(20),
+                                importance: 5,
+                            },
+                        ];
+                        let schedule = Schedule::schedule(now, [(segment, tasks.clone())], $strategy, EstimateBuffers::default(), Duration::zero(), &[], None).unwrap();
+                        assert_eq!(schedule.0.len(), 3);
+                        for (day, scheduled) in schedule.0.iter().enumerate() {
+                            let morning = now + Duration::days(day as i64) + Duration::hours(9);
+                            assert_eq!(scheduled.task, tasks[0]);
+                            assert_eq!(scheduled.part, Some(Part { number: day as u32 + 1, of: 3 }));
+                            assert_eq!(scheduled.when, morning);
+                            assert!(scheduled.end() <= morning + Duration::hours(8));
+                        }
+                        let total = schedule.0.iter().fold(Duration::zero(), |total, scheduled| total + scheduled.duration);
+                        assert_eq!(total, Duration::hours(20));
+                    }
+
                     #[test]
                     fn can_handle_never_time_segment() {
                         let tasks = taskset_of_myrjam();
-                        let schedule = Schedule::schedule_within_segment(Utc::now(), tasks, never(), $strategy);
+                        let schedule = Schedule::schedule(Utc::now(), [(never(), tasks)], $strategy, EstimateBuffers::default(), Duration::zero(), &[], None);
                         assert_matches!(schedule, Err(Error::NotEnoughTime { .. }));
                         let tasks: Vec<Task> = vec![];
-                        let schedule = Schedule::schedule_within_segment(Utc::now(), tasks, never(), $strategy);
+                        let schedule = Schedule::schedule(Utc::now(), [(never(), tasks)], $strategy, EstimateBuffers::default(), Duration::zero(), &[], None);
                         assert_matches!(schedule, Ok(Schedule(tasks)) if tasks.is_empty());
                     }
                 }
@@ -644,6 +2500,9 @@ mod tests {
     test_generic_properties! {
         importance: SchedulingStrategy::Importance,
         urgency: SchedulingStrategy::Urgency,
+        balanced: SchedulingStrategy::Balanced,
+        deadline: SchedulingStrategy::Deadline,
+        shuffled: SchedulingStrategy::Shuffled { seed: 42 },
     }
 
     // Note that some of these task sets are not representative at all, since tasks should be small
@@ -711,11 +2570,14 @@ mod tests {
     fn schedule_for_myrjam() {
         let tasks = taskset_of_myrjam();
         let start = Utc::now();
-        let schedule = Schedule::schedule_within_segment(
+        let schedule = Schedule::schedule(
             start,
-            tasks.clone(),
-            anytime(),
+            [(anytime(), tasks.clone())],
             SchedulingStrategy::Urgency,
+            EstimateBuffers::default(),
+            Duration::zero(),
+            &[],
+            None,
         )
         .unwrap();
         let mut expected_when = start;
@@ -748,11 +2610,14 @@ mod tests {
     fn schedule_myrjams_schedule_by_importance() {
         let tasks = taskset_of_myrjam();
         let start = Utc::now();
-        let schedule = Schedule::schedule_within_segment(
+        let schedule = Schedule::schedule(
             start,
-            tasks.clone(),
-            anytime(),
+            [(anytime(), tasks.clone())],
             SchedulingStrategy::Importance,
+            EstimateBuffers::default(),
+            Duration::zero(),
+            &[],
+            None,
         )
         .unwrap();
         let mut expected_when = start;
@@ -781,103 +2646,649 @@ mod tests {
         assert_eq!(schedule.0[5].when, expected_when);
     }
 
-    fn taskset_of_gandalf() -> Vec<Task> {
+    #[test]
+    fn schedule_balanced_between_importance_and_urgency() {
         let now = Utc::now();
-        vec![
-            Task {
-                content: "Think of plan to get rid of The Ring".to_string(),
-                deadline: now + Duration::days(12) + Duration::hours(15),
-                duration: Duration::days(2),
-                importance: 9,
-            },
-            Task {
-                content: "Ask advice from Saruman".to_string(),
-                deadline: now + Duration::days(8) + Duration::hours(15),
-                duration: Duration::days(3),
-                importance: 4,
-            },
-            Task {
-                content: "Visit Bilbo in Rivendel".to_string(),
-                deadline: now + Duration::days(13) + Duration::hours(15),
-                duration: Duration::days(2),
-                importance: 2,
-            },
-            Task {
-                content: "Make some firework for the hobbits".to_string(),
-                deadline: now + Duration::hours(33),
-                duration: Duration::hours(3),
-                importance: 3,
-            },
-            Task {
-                content: "Get riders of Rohan to help Gondor".to_string(),
-                deadline: now + Duration::days(21) + Duration::hours(15),
-                duration: Duration::days(7),
-                importance: 7,
-            },
-            Task {
-                content: "Find some good pipe-weed".to_string(),
-                deadline: now + Duration::days(2) + Duration::hours(15),
-                duration: Duration::hours(1),
-                importance: 8,
-            },
-            Task {
-                content: "Go shop for white clothing".to_string(),
-                deadline: now + Duration::days(33) + Duration::hours(15),
-                duration: Duration::hours(2),
-                importance: 3,
-            },
-            Task {
-                content: "Prepare epic-sounding one-liners".to_string(),
-                deadline: now + Duration::hours(34),
-                duration: Duration::hours(2),
-                importance: 10,
-            },
-            Task {
-                content: "Recharge staff batteries".to_string(),
-                deadline: now + Duration::days(1) + Duration::hours(15),
-                duration: Duration::minutes(30),
-                importance: 5,
-            },
-        ]
+        let task = |content: &str, days_left, hours, importance| Task {
+            content: content.to_string(),
+            deadline: now + Duration::days(days_left),
+            duration: Duration::hours(hours),
+            importance,
+        };
+        let tasks = vec![
+            task("write speech", 5, 2, 10),
+            task("water the plants", 2, 1, 2),
+            task("tidy the attic", 30, 3, 6),
+            task("pay rent", 1, 1, 5),
+        ];
+        let schedule = Schedule::schedule(
+            now,
+            [(anytime(), tasks.clone())],
+            SchedulingStrategy::Balanced,
+            EstimateBuffers::default(),
+            Duration::zero(),
+            &[],
+            None,
+        )
+        .unwrap();
+        // The very important task goes first, but a fairly important task isn't
+        // worth putting off urgent ones for
+        let order = schedule
+            .0
+            .iter()
+            .map(|scheduled| &scheduled.task)
+            .collect_vec();
+        assert_eq!(order, [&tasks[0], &tasks[3], &tasks[1], &tasks[2]]);
+        assert_eq!(schedule.0[0].when, now);
+        assert_eq!(schedule.0[1].when, now + Duration::hours(2));
     }
 
     #[test]
-    fn schedule_gandalfs_schedule_by_importance() {
-        let tasks = taskset_of_gandalf();
-        let start = Utc::now();
-        let schedule = Schedule::schedule_within_segment(
-            start,
-            tasks.clone(),
-            anytime(),
+    fn schedule_the_earliest_deadline_first() {
+        let now = Utc::now();
+        let task = |content: &str, days_left, hours, importance| Task {
+            content: content.to_string(),
+            deadline: now + Duration::days(days_left),
+            duration: Duration::hours(hours),
+            importance,
+        };
+        let tasks = vec![
+            task("write speech", 5, 2, 10),
+            task("water the plants", 2, 1, 2),
+            task("tidy the attic", 30, 3, 6),
+            task("pay rent", 2, 1, 5),
+        ];
+        let schedule = Schedule::schedule(
+            now,
+            [(anytime(), tasks.clone())],
+            SchedulingStrategy::Deadline,
+            EstimateBuffers::default(),
+            Duration::zero(),
+            &[],
+            None,
+        )
+        .unwrap();
+        // However important a task is, the more urgent ones go before it. Only
+        // for the same deadline, the more important task goes first.
+        let order = schedule
+            .0
+            .iter()
+            .map(|scheduled| &scheduled.task)
+            .collect_vec();
+        assert_eq!(order, [&tasks[3], &tasks[1], &tasks[0], &tasks[2]]);
+        // The tasks are packed one after the other from the start
+        let when = schedule
+            .0
+            .iter()
+            .map(|scheduled| scheduled.when)
+            .collect_vec();
+        let hours = |hours| now + Duration::hours(hours);
+        assert_eq!(when, [hours(0), hours(1), hours(2), hours(4)]);
+    }
+
+    #[test]
+    fn leave_out_what_does_not_fit() {
+        let now = Utc::now();
+        let task = |content: &str, days_left, hours| Task {
+            content: content.to_string(),
+            deadline: now + Duration::days(days_left),
+            duration: Duration::hours(hours),
+            importance: 5,
+        };
+        let tasks = vec![
+            task("renew passport", -1, 1),
+            task("water the plants", 1, 1),
+            task("pay rent", 2, 1),
+        ];
+        let (schedule, left_out) = Schedule::schedule_best_effort(
+            now,
+            vec![(anytime(), tasks.clone())],
             SchedulingStrategy::Importance,
+            EstimateBuffers::default(),
+            Duration::zero(),
+            &[],
+            None,
         )
         .unwrap();
-        let mut expected_when = start;
-        // 7. Prepare epic-sounding one-liners
-        assert_eq!(schedule.0[0].task, tasks[7]);
-        assert_eq!(schedule.0[0].when, expected_when);
-        expected_when = expected_when + Duration::hours(2);
-        // 5. Find some good pipe-weed
-        assert_eq!(schedule.0[1].task, tasks[5]);
-        assert_eq!(schedule.0[1].when, expected_when);
-        expected_when = expected_when + Duration::hours(1);
-        // 8. Recharge staff batteries
-        assert_eq!(schedule.0[2].task, tasks[8]);
-        assert_eq!(schedule.0[2].when, expected_when);
-        expected_when = expected_when + Duration::minutes(30);
-        // 3. Make some firework for the hobbits
-        assert_eq!(schedule.0[3].task, tasks[3]);
-        assert_eq!(schedule.0[3].when, expected_when);
-        expected_when = expected_when + Duration::hours(3);
-        // 0. Think of plan to get rid of The Ring
-        assert_eq!(schedule.0[4].task, tasks[0]);
-        assert_eq!(schedule.0[4].when, expected_when);
-        expected_when = expected_when + Duration::days(2);
-        // 1. Ask advice from Saruman
-        assert_eq!(schedule.0[5].task, tasks[1]);
-        assert_eq!(schedule.0[5].when, expected_when);
-        expected_when = expected_when + Duration::days(3);
-        // 6. Go shop for white clothing
+        assert_eq!(left_out, [tasks[0].clone()]);
+        let scheduled = schedule
+            .0
+            .iter()
+            .map(|scheduled| &scheduled.task)
+            .sorted_by_key(|task| task.deadline)
+            .collect_vec();
+        assert_eq!(scheduled, [&tasks[1], &tasks[2]]);
+
+        // Without any time, nothing fits
+        let (schedule, left_out) = Schedule::schedule_best_effort(
+            now,
+            vec![(never(), tasks[1..].to_vec())],
+            SchedulingStrategy::Importance,
+            EstimateBuffers::default(),
+            Duration::zero(),
+            &[],
+            None,
+        )
+        .unwrap();
+        assert!(schedule.0.is_empty());
+        assert_eq!(left_out.len(), 2);
+        assert!(tasks[1..].iter().all(|task| left_out.contains(task)));
+    }
+
+    #[test]
+    fn report_how_late_tasks_would_be() {
+        let now = Utc::now();
+        let task = |content: &str, hours_left, hours| Task {
+            content: content.to_string(),
+            deadline: now + Duration::hours(hours_left),
+            duration: Duration::hours(hours),
+            importance: 5,
+        };
+        // Six hours of work, of which only two fit before the last deadline
+        let tasks = vec![
+            task("write speech", 2, 3),
+            task("pay rent", 4, 2),
+            task("water the plants", 10, 1),
+        ];
+        assert_matches!(
+            Schedule::schedule(
+                now,
+                [(anytime(), tasks.clone())],
+                SchedulingStrategy::Deadline,
+                EstimateBuffers::default(),
+                Duration::zero(),
+                &[],
+                None,
+            ),
+            Err(Error::NotEnoughTime { .. }) | Err(Error::DeadlineMissed { .. })
+        );
+        let (schedule, lateness) = Schedule::schedule_allowing_lateness(
+            now,
+            vec![(anytime(), tasks.clone())],
+            SchedulingStrategy::Deadline,
+            EstimateBuffers::default(),
+            Duration::zero(),
+            &[],
+            None,
+        )
+        .unwrap();
+        // Every task is still scheduled, packed one after the other
+        let when = schedule
+            .iter()
+            .map(|scheduled| scheduled.when)
+            .collect_vec();
+        let hours = |hours| now + Duration::hours(hours);
+        assert_eq!(when, [hours(0), hours(3), hours(5)]);
+        let lateness = lateness
+            .into_iter()
+            .map(|lateness| (lateness.task.content, lateness.lateness.num_hours()))
+            .collect_vec();
+        assert_eq!(
+            lateness,
+            [
+                ("write speech".to_string(), 1),
+                ("pay rent".to_string(), 1),
+                ("water the plants".to_string(), 0),
+            ]
+        );
+
+        // Without any time, not even running late helps
+        assert_matches!(
+            Schedule::schedule_allowing_lateness(
+                now,
+                vec![(never(), tasks)],
+                SchedulingStrategy::Deadline,
+                EstimateBuffers::default(),
+                Duration::zero(),
+                &[],
+                None,
+            ),
+            Err(Error::NotEnoughTime { .. })
+        );
+    }
+
+    thread_local! {
+        /// The messages logged on this thread, while capturing them
+        static LOGGED: std::cell::RefCell<Option<Vec<String>>> = const { std::cell::RefCell::new(None) };
+    }
+
+    struct CapturingLogger;
+
+    impl log::Log for CapturingLogger {
+        fn enabled(&self, _metadata: &log::Metadata) -> bool {
+            LOGGED.with(|logged| logged.borrow().is_some())
+        }
+
+        fn log(&self, record: &log::Record) {
+            LOGGED.with(|logged| {
+                if let Some(logged) = logged.borrow_mut().as_mut() {
+                    logged.push(record.args().to_string());
+                }
+            });
+        }
+
+        fn flush(&self) {}
+    }
+
+    /// Runs `f` and returns what it logged on this thread, so tests running
+    /// alongside it don't mix in their messages.
+    fn capture_logs<T>(f: impl FnOnce() -> T) -> (T, Vec<String>) {
+        static LOGGER: std::sync::Once = std::sync::Once::new();
+        LOGGER.call_once(|| {
+            log::set_logger(&CapturingLogger).unwrap();
+            log::set_max_level(log::LevelFilter::Debug);
+        });
+        LOGGED.with(|logged| *logged.borrow_mut() = Some(vec![]));
+        let result = f();
+        let logged = LOGGED.with(|logged| logged.borrow_mut().take().unwrap_or_default());
+        (result, logged)
+    }
+
+    #[test]
+    fn log_where_tasks_are_placed() {
+        let now = Utc::now();
+        let task = |content: &str, importance| Task {
+            content: content.to_string(),
+            deadline: now + Duration::days(1),
+            duration: Duration::hours(1),
+            importance,
+        };
+        let tasks = vec![task("call mom", 8), task("tidy the attic", 2)];
+        let schedule = || {
+            Schedule::schedule(
+                now,
+                [(anytime(), tasks.clone())],
+                SchedulingStrategy::Importance,
+                EstimateBuffers::default(),
+                Duration::zero(),
+                &[],
+                None,
+            )
+            .unwrap()
+        };
+        let (logged_schedule, logged) = capture_logs(schedule);
+        // Logging doesn't change the outcome
+        assert_eq!(logged_schedule.0, schedule().0);
+
+        // The least important task is placed close to its deadline first, and
+        // then both are shifted towards the start
+        let placed = logged
+            .iter()
+            .filter(|message| message.starts_with("Placed"))
+            .collect_vec();
+        assert_eq!(placed.len(), 2);
+        assert!(placed[0].starts_with("Placed tidy the attic (importance 2, deadline "));
+        assert!(placed[1].starts_with("Placed call mom (importance 8, deadline "));
+        assert!(logged
+            .iter()
+            .any(|message| message.starts_with("Pass 1: shifted call mom from ")));
+    }
+
+    #[test]
+    fn keep_busy_times_free() {
+        let now = Utc::now();
+        let hours = |hours| now + Duration::hours(hours);
+        let task = |content: &str, hours, importance| Task {
+            content: content.to_string(),
+            deadline: now + Duration::days(1),
+            duration: Duration::hours(hours),
+            importance,
+        };
+        let tasks = vec![task("call mom", 1, 10), task("clean the kitchen", 2, 5)];
+        let busy = [hours(1)..hours(2)];
+        let schedule = Schedule::schedule(
+            now,
+            [(anytime(), tasks.clone())],
+            SchedulingStrategy::Importance,
+            EstimateBuffers::default(),
+            Duration::zero(),
+            &busy,
+            None,
+        )
+        .unwrap();
+        // The second task would start right after the first one, if it weren't
+        // for the appointment
+        let when = schedule
+            .0
+            .iter()
+            .map(|scheduled| (&scheduled.task, scheduled.when))
+            .collect_vec();
+        assert_eq!(when, [(&tasks[0], hours(0)), (&tasks[1], hours(2))]);
+
+        for strategy in [
+            SchedulingStrategy::Urgency,
+            SchedulingStrategy::Balanced,
+            SchedulingStrategy::Deadline,
+        ] {
+            let schedule = Schedule::schedule(
+                now,
+                [(anytime(), tasks.clone())],
+                strategy,
+                EstimateBuffers::default(),
+                Duration::zero(),
+                &busy,
+                None,
+            )
+            .unwrap();
+            for scheduled in &schedule.0 {
+                assert!(
+                    scheduled.end() <= busy[0].start || scheduled.when >= busy[0].end,
+                    "{strategy} planned {} during the appointment",
+                    scheduled.task
+                );
+            }
+        }
+    }
+
+    /// Returns how long the tasks which follow each other without a gap take
+    /// together, for every such stretch of work in the schedule.
+    fn stretches_of_work(schedule: &Schedule<Task>) -> Vec<Duration> {
+        schedule
+            .0
+            .iter()
+            .map(|scheduled| (scheduled.when, scheduled.end()))
+            .coalesce(|previous, next| {
+                if previous.1 == next.0 {
+                    Ok((previous.0, next.1))
+                } else {
+                    Err((previous, next))
+                }
+            })
+            .map(|(start, end)| end - start)
+            .collect()
+    }
+
+    #[test]
+    fn take_breaks_between_tasks() {
+        let now = Utc.with_ymd_and_hms(2019, 8, 1, 9, 0, 0).unwrap();
+        let hours = |hours: f64| now + Duration::minutes((hours * 60.0) as i64);
+        let task = |content: &str| Task {
+            content: content.to_string(),
+            deadline: now + Duration::days(1),
+            duration: Duration::hours(1),
+            importance: 5,
+        };
+        let tasks = ["call mom", "tidy the attic", "write report", "water plants"]
+            .iter()
+            .map(|content| task(content))
+            .collect_vec();
+        let breaks = Breaks {
+            max_continuous: Duration::hours(2),
+            length: Duration::minutes(30),
+        };
+        for strategy in [
+            SchedulingStrategy::Importance,
+            SchedulingStrategy::Urgency,
+            SchedulingStrategy::Balanced,
+            SchedulingStrategy::Deadline,
+        ] {
+            let schedule = Schedule::schedule(
+                now,
+                [(anytime(), tasks.clone())],
+                strategy,
+                EstimateBuffers::default(),
+                Duration::minutes(5),
+                &[],
+                Some(breaks),
+            )
+            .unwrap();
+            assert_eq!(schedule.0.len(), tasks.len());
+            for stretch in stretches_of_work(&schedule) {
+                assert!(
+                    stretch <= breaks.max_continuous,
+                    "{strategy} planned {} of work without a break",
+                    stretch
+                );
+            }
+            if strategy == SchedulingStrategy::Importance {
+                assert_eq!(
+                    schedule.breaks(breaks, Duration::minutes(5)),
+                    [hours(2.0)..hours(2.5)]
+                );
+            }
+        }
+
+        // Without breaks, the tasks follow each other right away
+        let schedule = Schedule::schedule(
+            now,
+            [(anytime(), tasks.clone())],
+            SchedulingStrategy::Importance,
+            EstimateBuffers::default(),
+            Duration::minutes(5),
+            &[],
+            None,
+        )
+        .unwrap();
+        assert_eq!(stretches_of_work(&schedule), [Duration::hours(4)]);
+        assert!(schedule.breaks(breaks, Duration::minutes(5)).is_empty());
+    }
+
+    #[test]
+    fn split_tasks_longer_than_the_maximum_continuous_work() {
+        let now = Utc.with_ymd_and_hms(2019, 8, 1, 9, 0, 0).unwrap();
+        let task = Task {
+            content: "write report".to_string(),
+            deadline: now + Duration::days(1),
+            duration: Duration::hours(5),
+            importance: 5,
+        };
+        let breaks = Breaks {
+            // Rounded down to whole quarters of an hour
+            max_continuous: Duration::minutes(100),
+            length: Duration::minutes(20),
+        };
+        let schedule = Schedule::schedule(
+            now,
+            [(anytime(), vec![task])],
+            SchedulingStrategy::Importance,
+            EstimateBuffers::default(),
+            Duration::minutes(15),
+            &[],
+            Some(breaks),
+        )
+        .unwrap();
+
+        let parts = schedule
+            .0
+            .iter()
+            .map(|scheduled| scheduled.duration)
+            .sorted()
+            .collect_vec();
+        assert_eq!(
+            parts,
+            [
+                Duration::minutes(30),
+                Duration::minutes(90),
+                Duration::minutes(90),
+                Duration::minutes(90),
+            ]
+        );
+        assert!(schedule
+            .0
+            .iter()
+            .all(|scheduled| scheduled.part.unwrap().of == 4));
+        assert!(stretches_of_work(&schedule)
+            .iter()
+            .all(|stretch| *stretch <= Duration::minutes(90)));
+        // The parts are at least a break apart
+        for (previous, next) in schedule.0.iter().tuple_windows() {
+            assert!(next.when - previous.end() >= Duration::minutes(30));
+        }
+    }
+
+    #[test]
+    fn leave_a_task_whole_rather_than_split_it_in_countless_parts() {
+        let now = Utc.with_ymd_and_hms(2019, 8, 1, 9, 0, 0).unwrap();
+        let task = Task {
+            content: "write thesis".to_string(),
+            deadline: now + Duration::days(6),
+            duration: Duration::hours(10),
+            importance: 5,
+        };
+        let breaks = Breaks {
+            max_continuous: Duration::minutes(5),
+            length: Duration::minutes(5),
+        };
+        let (schedule, logged) = capture_logs(|| {
+            Schedule::schedule(
+                now,
+                [(anytime(), vec![task])],
+                SchedulingStrategy::Importance,
+                EstimateBuffers::default(),
+                Duration::minutes(5),
+                &[],
+                Some(breaks),
+            )
+        });
+        assert!(schedule.is_err());
+        assert!(logged.contains(
+            &"Task 0 would have to be split into more than 100 parts, so I left it whole."
+                .to_string()
+        ));
+    }
+
+    #[test]
+    fn schedule_equally_important_tasks_in_a_seeded_order() {
+        let now = Utc::now();
+        let tasks = (0..8)
+            .map(|i| Task {
+                content: format!("chore {}", i),
+                deadline: now + Duration::days(7 + i),
+                duration: Duration::hours(1),
+                importance: 5,
+            })
+            .chain(std::iter::once(Task {
+                content: "write speech".to_string(),
+                deadline: now + Duration::days(30),
+                duration: Duration::hours(1),
+                importance: 10,
+            }))
+            .collect_vec();
+        let order = |seed| {
+            let schedule = Schedule::schedule(
+                now,
+                [(anytime(), tasks.clone())],
+                SchedulingStrategy::Shuffled { seed },
+                EstimateBuffers::default(),
+                Duration::zero(),
+                &[],
+                None,
+            )
+            .unwrap();
+            schedule
+                .0
+                .into_iter()
+                .map(|scheduled| scheduled.task.content)
+                .collect_vec()
+        };
+        assert_eq!(order(1), order(1));
+        assert_ne!(order(1), order(2));
+        // Importance still goes first
+        for seed in 0..10 {
+            assert_eq!(order(seed)[0], "write speech");
+        }
+    }
+
+    fn taskset_of_gandalf() -> Vec<Task> {
+        let now = Utc::now();
+        vec![
+            Task {
+                content: "Think of plan to get rid of The Ring".to_string(),
+                deadline: now + Duration::days(12) + Duration::hours(15),
+                duration: Duration::days(2),
+                importance: 9,
+            },
+            Task {
+                content: "Ask advice from Saruman".to_string(),
+                deadline: now + Duration::days(8) + Duration::hours(15),
+                duration: Duration::days(3),
+                importance: 4,
+            },
+            Task {
+                content: "Visit Bilbo in Rivendel".to_string(),
+                deadline: now + Duration::days(13) + Duration::hours(15),
+                duration: Duration::days(2),
+                importance: 2,
+            },
+            Task {
+                content: "Make some firework for the hobbits".to_string(),
+                deadline: now + Duration::hours(33),
+                duration: Duration::hours(3),
+                importance: 3,
+            },
+            Task {
+                content: "Get riders of Rohan to help Gondor".to_string(),
+                deadline: now + Duration::days(21) + Duration::hours(15),
+                duration: Duration::days(7),
+                importance: 7,
+            },
+            Task {
+                content: "Find some good pipe-weed".to_string(),
+                deadline: now + Duration::days(2) + Duration::hours(15),
+                duration: Duration::hours(1),
+                importance: 8,
+            },
+            Task {
+                content: "Go shop for white clothing".to_string(),
+                deadline: now + Duration::days(33) + Duration::hours(15),
+                duration: Duration::hours(2),
+                importance: 3,
+            },
+            Task {
+                content: "Prepare epic-sounding one-liners".to_string(),
+                deadline: now + Duration::hours(34),
+                duration: Duration::hours(2),
+                importance: 10,
+            },
+            Task {
+                content: "Recharge staff batteries".to_string(),
+                deadline: now + Duration::days(1) + Duration::hours(15),
+                duration: Duration::minutes(30),
+                importance: 5,
+            },
+        ]
+    }
+
+    #[test]
+    fn schedule_gandalfs_schedule_by_importance() {
+        let tasks = taskset_of_gandalf();
+        let start = Utc::now();
+        let schedule = Schedule::schedule(
+            start,
+            [(anytime(), tasks.clone())],
+            SchedulingStrategy::Importance,
+            EstimateBuffers::default(),
+            Duration::zero(),
+            &[],
+            None,
+        )
+        .unwrap();
+        let mut expected_when = start;
+        // 7. Prepare epic-sounding one-liners
+        assert_eq!(schedule.0[0].task, tasks[7]);
+        assert_eq!(schedule.0[0].when, expected_when);
+        expected_when = expected_when + Duration::hours(2);
+        // 5. Find some good pipe-weed
+        assert_eq!(schedule.0[1].task, tasks[5]);
+        assert_eq!(schedule.0[1].when, expected_when);
+        expected_when = expected_when + Duration::hours(1);
+        // 8. Recharge staff batteries
+        assert_eq!(schedule.0[2].task, tasks[8]);
+        assert_eq!(schedule.0[2].when, expected_when);
+        expected_when = expected_when + Duration::minutes(30);
+        // 3. Make some firework for the hobbits
+        assert_eq!(schedule.0[3].task, tasks[3]);
+        assert_eq!(schedule.0[3].when, expected_when);
+        expected_when = expected_when + Duration::hours(3);
+        // 0. Think of plan to get rid of The Ring
+        assert_eq!(schedule.0[4].task, tasks[0]);
+        assert_eq!(schedule.0[4].when, expected_when);
+        expected_when = expected_when + Duration::days(2);
+        // 1. Ask advice from Saruman
+        assert_eq!(schedule.0[5].task, tasks[1]);
+        assert_eq!(schedule.0[5].when, expected_when);
+        expected_when = expected_when + Duration::days(3);
+        // 6. Go shop for white clothing
         assert_eq!(schedule.0[6].task, tasks[6]);
         assert_eq!(schedule.0[6].when, expected_when);
         expected_when = expected_when + Duration::hours(2);
@@ -890,6 +3301,1059 @@ mod tests {
         assert_eq!(schedule.0[8].when, expected_when);
     }
 
+    #[test]
+    fn less_confident_estimates_are_buffered() {
+        let start = Utc::now();
+        let task = |id, days, confidence| crate::Task {
+            id,
+            content: format!("task {}", id),
+            deadline: start + Duration::days(days),
+            duration: Duration::hours(1),
+            importance: 5,
+            confidence,
+            time_segment_id: 0,
+            depends_on: vec![],
+            color: None,
+            scheduled_for: None,
+            done_at: None,
+            pinned: false,
+        };
+        let tasks = vec![
+            task(1, 1, Confidence::Low),
+            task(2, 2, Confidence::High),
+            task(3, 3, Confidence::High),
+        ];
+        let estimate_buffers = EstimateBuffers {
+            low_confidence: 25,
+            medium_confidence: 10,
+        };
+        let schedule = Schedule::schedule(
+            start,
+            [(anytime(), tasks.clone())],
+            SchedulingStrategy::Urgency,
+            estimate_buffers,
+            Duration::zero(),
+            &[],
+            None,
+        )
+        .unwrap();
+        assert_eq!(schedule.0.len(), 3);
+        // The low-confidence task takes up 1h15m...
+        assert_eq!(schedule.0[0].task, tasks[0]);
+        assert_eq!(schedule.0[0].when, start);
+        // ... while the high-confidence ones keep their estimate of 1h
+        assert_eq!(schedule.0[1].task, tasks[1]);
+        assert_eq!(schedule.0[1].when, start + Duration::minutes(75));
+        assert_eq!(schedule.0[2].task, tasks[2]);
+        assert_eq!(schedule.0[2].when, start + Duration::minutes(135));
+        // The scheduled tasks still have their original estimate, but are
+        // planned for longer
+        assert_eq!(schedule.0[0].task.duration, Duration::hours(1));
+        assert_eq!(schedule.0[0].duration, Duration::minutes(75));
+        assert_eq!(schedule.0[0].end(), schedule.0[1].when);
+        assert_eq!(schedule.0[1].duration, Duration::hours(1));
+    }
+
+    #[test]
+    fn diff_detects_added_removed_and_moved_tasks() {
+        let start = Utc::now();
+        let task = |id| crate::Task {
+            id,
+            content: format!("task {}", id),
+            deadline: start + Duration::days(1),
+            duration: Duration::hours(1),
+            importance: 5,
+            confidence: Confidence::High,
+            time_segment_id: 0,
+            depends_on: vec![],
+            color: None,
+            scheduled_for: None,
+            done_at: None,
+            pinned: false,
+        };
+        let scheduled = |id, hours| Scheduled {
+            task: task(id),
+            when: start + Duration::hours(hours),
+            duration: Duration::hours(1),
+            part: None,
+        };
+        let old = Schedule(vec![scheduled(1, 0), scheduled(2, 1), scheduled(3, 2)]);
+        let new = Schedule(vec![scheduled(1, 0), scheduled(3, 1), scheduled(4, 2)]);
+
+        let diff = old.diff(&new);
+        assert_eq!(diff.added, [scheduled(4, 2)]);
+        assert_eq!(diff.removed, [scheduled(2, 1)]);
+        assert_eq!(
+            diff.moved,
+            [Moved {
+                task: task(3),
+                from: start + Duration::hours(2),
+                to: start + Duration::hours(1),
+            }]
+        );
+        assert!(old.diff(&old).is_empty());
+
+        // Moving a few seconds isn't worth mentioning
+        let mut new = Schedule(vec![scheduled(1, 0), scheduled(2, 1), scheduled(3, 2)]);
+        for scheduled in &mut new.0 {
+            scheduled.when += Duration::seconds(30);
+        }
+        assert!(old.diff(&new).is_empty());
+    }
+
+    #[test]
+    fn schedule_deadlines_centuries_away() {
+        let start = Utc::now();
+        let tasks = vec![
+            Task {
+                content: "plant a forest".to_string(),
+                deadline: start + Duration::days(500 * 365),
+                duration: Duration::hours(2),
+                importance: 5,
+            },
+            Task {
+                content: "water the plants".to_string(),
+                deadline: start + Duration::days(1),
+                duration: Duration::hours(1),
+                importance: 5,
+            },
+        ];
+        let schedule = Schedule::schedule(
+            start,
+            [(anytime(), tasks.clone())],
+            SchedulingStrategy::Importance,
+            EstimateBuffers::default(),
+            Duration::zero(),
+            &[],
+            None,
+        )
+        .unwrap();
+        // With equal importance, the more urgent task goes first
+        assert_eq!(schedule.0[0].task, tasks[1]);
+        assert_eq!(schedule.0[0].when, start);
+        assert_eq!(schedule.0[1].task, tasks[0]);
+        assert_eq!(schedule.0[1].when, start + Duration::hours(1));
+    }
+
+    #[test]
+    fn settle_many_tasks_of_different_lengths_in_bounded_passes() {
+        use rand::Rng;
+
+        let start = Utc::now();
+        let mut rng = ChaCha8Rng::seed_from_u64(2025);
+        let tasks = (0..200)
+            .map(|i| Task {
+                content: format!("task {}", i),
+                deadline: start + Duration::hours(rng.gen_range(24..2000)),
+                duration: Duration::minutes(rng.gen_range(5..240)),
+                importance: rng.gen_range(1..10),
+            })
+            .map(Rc::new)
+            .collect_vec();
+        let mut least_important_first = tasks.clone();
+        least_important_first.sort_by_key(|task| (task.importance, Reverse(task.deadline)));
+
+        let mut tree: ScheduleTree<DateTime<Utc>, Item<Task>> = ScheduleTree::new();
+        for task in &least_important_first {
+            assert!(tree.schedule_close_before(
+                task.deadline,
+                task.duration,
+                Some(start),
+                Item::Task(Rc::clone(task)),
+            ));
+        }
+        let most_important_first = least_important_first.into_iter().rev().collect_vec();
+        let passes = tree
+            .shift_towards_start(start, &most_important_first)
+            .unwrap();
+        assert!(
+            passes <= MAX_PASSES_PER_TASK * tasks.len(),
+            "took {} passes",
+            passes
+        );
+
+        let mut tree: ScheduleTree<DateTime<Utc>, Item<Task>> = ScheduleTree::new();
+        tree.schedule_according_to_importance(start, tasks).unwrap();
+    }
+
+    fn task_with_dependencies(id: u32, importance: u32, depends_on: Vec<u32>) -> crate::Task {
+        crate::Task {
+            id,
+            content: format!("task {}", id),
+            deadline: Utc::now() + Duration::days(3),
+            duration: Duration::hours(2),
+            importance,
+            confidence: Confidence::High,
+            time_segment_id: 0,
+            depends_on,
+            color: None,
+            scheduled_for: None,
+            done_at: None,
+            pinned: false,
+        }
+    }
+
+    #[test]
+    fn schedule_tasks_after_their_dependencies() {
+        let start = Utc::now();
+        // The most important task depends on the least important ones
+        let tasks = vec![
+            task_with_dependencies(1, 10, vec![3]),
+            task_with_dependencies(2, 1, vec![]),
+            task_with_dependencies(3, 1, vec![2]),
+        ];
+        for strategy in [SchedulingStrategy::Importance, SchedulingStrategy::Urgency] {
+            let schedule = Schedule::schedule(
+                start,
+                [(anytime(), tasks.clone())],
+                strategy,
+                EstimateBuffers::default(),
+                Duration::zero(),
+                &[],
+                None,
+            )
+            .unwrap();
+            let ids = schedule
+                .0
+                .iter()
+                .map(|scheduled| scheduled.task.id)
+                .collect_vec();
+            assert_eq!(ids, [2, 3, 1]);
+        }
+    }
+
+    #[test]
+    fn schedule_pinned_tasks_first() {
+        let start = Utc::now();
+        let pinned = |id, importance, days_left| crate::Task {
+            deadline: start + Duration::days(days_left),
+            pinned: true,
+            ..task_with_dependencies(id, importance, vec![])
+        };
+        // However unimportant the pinned tasks are, they go first, and the
+        // more urgent one of them goes before the other
+        let tasks = vec![
+            task_with_dependencies(1, 10, vec![]),
+            task_with_dependencies(2, 8, vec![]),
+            pinned(3, 1, 3),
+            pinned(4, 2, 2),
+        ];
+        for strategy in [
+            SchedulingStrategy::Importance,
+            SchedulingStrategy::Urgency,
+            SchedulingStrategy::Balanced,
+            SchedulingStrategy::Deadline,
+        ] {
+            let schedule = Schedule::schedule(
+                start,
+                [(anytime(), tasks.clone())],
+                strategy,
+                EstimateBuffers::default(),
+                Duration::zero(),
+                &[],
+                None,
+            )
+            .unwrap();
+            let ids = schedule
+                .0
+                .iter()
+                .map(|scheduled| scheduled.task.id)
+                .collect_vec();
+            assert_eq!(ids, [4, 3, 1, 2], "with strategy {:?}", strategy);
+            assert_eq!(schedule.0[0].when, start);
+        }
+
+        // A pinned task which can't make its deadline doesn't get scheduled
+        // anyway
+        let (schedule, left_out) = Schedule::schedule_best_effort(
+            start,
+            vec![(anytime(), vec![tasks[0].clone(), pinned(3, 1, -1)])],
+            SchedulingStrategy::Importance,
+            EstimateBuffers::default(),
+            Duration::zero(),
+            &[],
+            None,
+        )
+        .unwrap();
+        assert_eq!(schedule.0.len(), 1);
+        assert_eq!(left_out, [pinned(3, 1, -1)]);
+    }
+
+    #[test]
+    fn explain_which_tasks_go_first() {
+        let start = Utc::now();
+        // Two tasks are more important, and the last one has to wait for its
+        // dependency too
+        let tasks = vec![
+            task_with_dependencies(1, 9, vec![]),
+            task_with_dependencies(2, 5, vec![]),
+            task_with_dependencies(3, 1, vec![]),
+            task_with_dependencies(4, 1, vec![3]),
+        ];
+        let schedule = Schedule::schedule(
+            start,
+            [(anytime(), tasks.clone())],
+            SchedulingStrategy::Importance,
+            EstimateBuffers::default(),
+            Duration::zero(),
+            &[],
+            None,
+        )
+        .unwrap();
+
+        let explanation = schedule
+            .explain(4, start, SchedulingStrategy::Importance)
+            .unwrap();
+        assert_eq!(explanation.scheduled, schedule.0[3]);
+        assert_eq!(
+            explanation.sort_key,
+            SortKey::Importance {
+                importance: 1,
+                time_left: tasks[3].deadline - start,
+            }
+        );
+        assert_eq!(
+            explanation.slack,
+            tasks[3].deadline - explanation.scheduled.end()
+        );
+        let precedences = explanation
+            .preceded_by
+            .iter()
+            .map(|precedence| (precedence.task.id, precedence.reason))
+            .collect_vec();
+        assert_eq!(
+            precedences,
+            [
+                (1, Reason::MoreImportant),
+                (2, Reason::MoreImportant),
+                (3, Reason::DependedOn),
+            ]
+        );
+
+        let explanation = schedule
+            .explain(1, start, SchedulingStrategy::Importance)
+            .unwrap();
+        assert!(explanation.preceded_by.is_empty());
+        assert_eq!(
+            schedule.explain(42, start, SchedulingStrategy::Importance),
+            None
+        );
+    }
+
+    #[test]
+    fn schedule_tasks_after_dependencies_in_other_time_segments() {
+        let start = Utc::now();
+        let later = start + Duration::days(1);
+        let anytime = UnnamedTimeSegment {
+            ranges: vec![start..start + Duration::weeks(1)],
+            start,
+            period: Duration::weeks(1),
+        };
+        let later_segment = UnnamedTimeSegment {
+            ranges: vec![later..later + Duration::hours(8)],
+            start: later,
+            period: Duration::weeks(1),
+        };
+        let prerequisite = task_with_dependencies(1, 1, vec![]);
+        let dependent = task_with_dependencies(2, 10, vec![1]);
+        let schedule = Schedule::schedule(
+            start,
+            vec![
+                (anytime, vec![dependent]),
+                (later_segment, vec![prerequisite]),
+            ],
+            SchedulingStrategy::Importance,
+            EstimateBuffers::default(),
+            Duration::zero(),
+            &[],
+            None,
+        )
+        .unwrap();
+        assert_eq!(schedule.0[0].task.id, 1);
+        assert_eq!(schedule.0[0].when, later);
+        assert_eq!(schedule.0[1].task.id, 2);
+        assert_eq!(schedule.0[1].when, later + Duration::hours(2));
+    }
+
+    #[test]
+    fn schedule_tasks_in_different_time_segments_at_the_same_moment() {
+        let start = Utc::now();
+        let segment = || UnnamedTimeSegment {
+            ranges: vec![start..start + Duration::hours(2)],
+            start,
+            period: Duration::days(1),
+        };
+        let first = (segment(), vec![task_with_dependencies(1, 5, vec![])]);
+        let second = (segment(), vec![task_with_dependencies(2, 5, vec![])]);
+        // Whichever segment comes first, the order is the same
+        for tasks_per_segment in [
+            vec![first.clone(), second.clone()],
+            vec![second.clone(), first.clone()],
+        ] {
+            let schedule = Schedule::schedule(
+                start,
+                tasks_per_segment,
+                SchedulingStrategy::Importance,
+                EstimateBuffers::default(),
+                Duration::zero(),
+                &[],
+                None,
+            )
+            .unwrap();
+            let scheduled = schedule
+                .0
+                .iter()
+                .map(|scheduled| (scheduled.task.id, scheduled.when))
+                .collect_vec();
+            assert_eq!(scheduled, [(1, start), (2, start)]);
+            assert!(schedule.0[0] < schedule.0[1]);
+        }
+    }
+
+    #[test]
+    fn schedule_time_segments_according_to_their_own_strategy() {
+        let start = Utc.with_ymd_and_hms(2019, 8, 1, 6, 0, 0).unwrap();
+        let segment = |id, from, to, strategy| crate::time_segment::NamedTimeSegment {
+            id,
+            name: format!("segment {}", id),
+            ranges: vec![start + Duration::hours(from)..start + Duration::hours(to)],
+            start,
+            period: Duration::days(1),
+            hue: 0,
+            strategy,
+        };
+        // In each segment, the important task isn't urgent and vice versa
+        let tasks = |important_id, urgent_id| {
+            let mut important = task_with_dependencies(important_id, 10, vec![]);
+            important.deadline = start + Duration::days(3);
+            let mut urgent = task_with_dependencies(urgent_id, 1, vec![]);
+            urgent.deadline = start + Duration::days(1);
+            vec![important, urgent]
+        };
+        let deep_work = segment(0, 2, 6, Some(SchedulingStrategy::Importance));
+        let errands = segment(1, 7, 11, Some(SchedulingStrategy::Urgency));
+        let undecided = segment(2, 12, 16, None);
+
+        let schedule = Schedule::schedule(
+            start,
+            [
+                (deep_work, tasks(1, 2)),
+                (errands, tasks(3, 4)),
+                (undecided, tasks(5, 6)),
+            ],
+            SchedulingStrategy::Urgency,
+            EstimateBuffers::default(),
+            Duration::zero(),
+            &[],
+            None,
+        )
+        .unwrap();
+        let order = schedule
+            .0
+            .iter()
+            .map(|scheduled| scheduled.task.id)
+            .collect_vec();
+        // The segment without a strategy of its own uses the one for all
+        assert_eq!(order, [1, 2, 4, 3, 6, 5]);
+    }
+
+    #[test]
+    fn round_to_the_granularity() {
+        let five_minutes = Duration::minutes(5);
+        assert_eq!(
+            round_up(Duration::minutes(7), five_minutes),
+            Duration::minutes(10)
+        );
+        assert_eq!(
+            round_up(Duration::minutes(10), five_minutes),
+            Duration::minutes(10)
+        );
+        assert_eq!(
+            round_up(
+                Duration::minutes(10) + Duration::milliseconds(1),
+                five_minutes
+            ),
+            Duration::minutes(15)
+        );
+        assert_eq!(
+            round_up(Duration::minutes(7), Duration::zero()),
+            Duration::minutes(7)
+        );
+
+        let moment = Utc.with_ymd_and_hms(2019, 8, 5, 9, 7, 13).unwrap();
+        let nine = Utc.with_ymd_and_hms(2019, 8, 5, 9, 0, 0).unwrap();
+        assert_eq!(floor(moment, five_minutes), nine + Duration::minutes(5));
+        assert_eq!(ceil(moment, five_minutes), nine + Duration::minutes(10));
+        assert_eq!(floor(nine, five_minutes), nine);
+        assert_eq!(ceil(nine, five_minutes), nine);
+        assert_eq!(floor(moment, Duration::zero()), moment);
+        assert_eq!(ceil(moment, Duration::zero()), moment);
+    }
+
+    #[test]
+    fn fragment_the_schedule_less_with_a_granularity() {
+        let midnight = Utc.with_ymd_and_hms(2019, 8, 5, 0, 0, 0).unwrap();
+        let start = midnight + Duration::hours(8) + Duration::seconds(17);
+        let at = |hours, minutes| midnight + Duration::hours(hours) + Duration::minutes(minutes);
+        // The short range in the middle is too short to do anything in
+        let segment = UnnamedTimeSegment {
+            ranges: vec![
+                at(9, 2)..at(9, 58),
+                at(10, 1)..at(10, 3),
+                at(10, 6)..at(11, 59),
+            ],
+            start: midnight,
+            period: Duration::days(1),
+        };
+        let tasks = [17, 23, 31, 13, 19, 26]
+            .iter()
+            .enumerate()
+            .map(|(i, &minutes)| {
+                let mut task = task_with_dependencies(i as u32, 5, vec![]);
+                task.duration = Duration::minutes(minutes);
+                task.deadline = start + Duration::days(3);
+                task
+            })
+            .collect_vec();
+        let fragmentation = |granularity: Duration| {
+            let tasks = tasks
+                .iter()
+                .map(|task| Buffered::new(task.clone(), EstimateBuffers::default(), granularity))
+                .collect_vec();
+            let start = ceil(start, granularity);
+            let tree = Schedule::tree_within_segment(
+                start,
+                tasks,
+                segment.clone(),
+                SchedulingStrategy::Importance,
+                granularity,
+                &[],
+                &HashMap::new(),
+            )
+            .unwrap();
+            let tiny_gaps = tree
+                .free_slots(start, start + Duration::days(3))
+                .into_iter()
+                .filter(|slot| slot.end - slot.start < Duration::minutes(5))
+                .count();
+            (tree.iter().count(), tiny_gaps)
+        };
+
+        let (nodes_to_the_second, tiny_gaps_to_the_second) = fragmentation(Duration::zero());
+        let (nodes_per_five_minutes, tiny_gaps_per_five_minutes) =
+            fragmentation(Duration::minutes(5));
+        assert!(nodes_per_five_minutes < nodes_to_the_second);
+        assert!(tiny_gaps_to_the_second > 0);
+        assert_eq!(tiny_gaps_per_five_minutes, 0);
+
+        // Every task starts on the granularity and still meets its deadline
+        let schedule = Schedule::schedule(
+            start,
+            [(segment, tasks)],
+            SchedulingStrategy::Importance,
+            EstimateBuffers::default(),
+            Duration::minutes(5),
+            &[],
+            None,
+        )
+        .unwrap();
+        assert_eq!(schedule.0.len(), 6);
+        for scheduled in &schedule.0 {
+            assert_eq!(scheduled.when.timestamp() % 300, 0);
+            assert!(scheduled.end() <= scheduled.task.deadline);
+        }
+    }
+
+    #[test]
+    fn refuse_tasks_which_only_fit_without_rounding() {
+        let start = Utc.with_ymd_and_hms(2019, 8, 5, 10, 0, 0).unwrap();
+        let mut task = task_with_dependencies(1, 5, vec![]);
+        task.duration = Duration::minutes(7);
+        task.deadline = start + Duration::minutes(8);
+        let error = |granularity| {
+            Schedule::schedule(
+                start,
+                [(anytime(), vec![task.clone()])],
+                SchedulingStrategy::Importance,
+                EstimateBuffers::default(),
+                granularity,
+                &[],
+                None,
+            )
+            .err()
+        };
+        assert_matches!(error(Duration::zero()), None);
+        assert_matches!(
+            error(Duration::minutes(5)),
+            Some(Error::NotEnoughTime { .. })
+        );
+    }
+
+    #[test]
+    fn refuse_to_schedule_dependency_cycles() {
+        let tasks = vec![
+            task_with_dependencies(1, 5, vec![2]),
+            task_with_dependencies(2, 5, vec![3]),
+            task_with_dependencies(3, 5, vec![1]),
+        ];
+        let schedule = Schedule::schedule(
+            Utc::now(),
+            [(anytime(), tasks)],
+            SchedulingStrategy::Importance,
+            EstimateBuffers::default(),
+            Duration::zero(),
+            &[],
+            None,
+        );
+        assert_matches!(schedule, Err(Error::DependencyCycle { .. }));
+    }
+
+    #[test]
+    fn schedule_after_start_of_day() {
+        let local = FixedOffset::east_opt(2 * 3600).unwrap();
+        let start = local.with_ymd_and_hms(2019, 8, 1, 3, 12, 0).unwrap();
+        let start_of_day = NaiveTime::from_hms_opt(8, 30, 0).unwrap();
+        let mut tasks = vec![];
+        for id in 1..=10 {
+            let mut task = task_with_dependencies(id, id, vec![]);
+            task.deadline = start.with_timezone(&Utc) + Duration::days(3);
+            tasks.push(task);
+        }
+        let schedule = Schedule::schedule(
+            start.with_timezone(&Utc),
+            [(
+                UnnamedTimeSegment::daily_from(&start, start_of_day),
+                tasks.clone(),
+            )],
+            SchedulingStrategy::Importance,
+            EstimateBuffers::default(),
+            Duration::zero(),
+            &[],
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(schedule.0.len(), tasks.len());
+        for scheduled in &schedule.0 {
+            assert!(scheduled.when.with_timezone(&local).time() >= start_of_day);
+        }
+    }
+
+    #[test]
+    fn schedule_tasks_after_all_parts_of_a_split_task() {
+        let start = Utc.with_ymd_and_hms(2019, 8, 1, 0, 0, 0).unwrap();
+        let segment = UnnamedTimeSegment {
+            ranges: vec![start + Duration::hours(9)..start + Duration::hours(17)],
+            start,
+            period: Duration::days(1),
+        };
+        let mut long = task_with_dependencies(1, 1, vec![]);
+        long.duration = Duration::hours(20);
+        long.deadline = start + Duration::days(5);
+        let mut dependent = task_with_dependencies(2, 10, vec![1]);
+        dependent.deadline = start + Duration::days(5);
+        let mut schedule = Schedule::schedule(
+            start,
+            [(segment, vec![dependent, long])],
+            SchedulingStrategy::Importance,
+            EstimateBuffers::default(),
+            Duration::zero(),
+            &[],
+            None,
+        )
+        .unwrap();
+
+        let parts = schedule.0.iter().filter(|scheduled| scheduled.task.id == 1);
+        assert_eq!(parts.clone().count(), 3);
+        let finished = parts.map(Scheduled::end).max().unwrap();
+        let dependent = schedule.0.iter().find(|scheduled| scheduled.task.id == 2);
+        assert!(dependent.unwrap().when >= finished);
+        assert_eq!(dependent.unwrap().part, None);
+
+        // Its parts can't be told apart when changing the duration in place
+        assert_matches!(
+            schedule.reschedule(1, Duration::hours(16)),
+            Err(Error::Split { id: 1 })
+        );
+    }
+
+    #[test]
+    fn report_the_time_segments_over_capacity() {
+        let start = Utc.with_ymd_and_hms(2019, 8, 1, 0, 0, 0).unwrap();
+        let segment = |id, hours| crate::time_segment::NamedTimeSegment {
+            id,
+            name: format!("segment {}", id),
+            ranges: vec![start..start + Duration::hours(hours)],
+            start,
+            period: Duration::days(1),
+            hue: 0,
+            strategy: None,
+        };
+        let task = |id, hours, confidence, time_segment_id| crate::Task {
+            duration: Duration::hours(hours),
+            deadline: start + Duration::days(3),
+            confidence,
+            time_segment_id,
+            ..task_with_dependencies(id, 5, vec![])
+        };
+        // Two hours a day for three days is too little, eight hours is plenty
+        let tasks_per_segment = vec![
+            (
+                segment(0, 2),
+                vec![
+                    task(1, 4, Confidence::High, 0),
+                    task(2, 3, Confidence::High, 0),
+                ],
+            ),
+            (segment(1, 8), vec![task(3, 2, Confidence::Medium, 1)]),
+        ];
+
+        let report =
+            Schedule::capacity_report(start, tasks_per_segment.clone(), EstimateBuffers::default());
+        assert_eq!(report.len(), 2);
+        assert_eq!(report[0].tasks, tasks_per_segment[0].1);
+        assert_eq!(report[0].available, Duration::hours(6));
+        assert_eq!(report[0].required, Duration::hours(7));
+        assert_eq!(report[0].deficit(), Some(Duration::hours(1)));
+        assert_eq!(report[1].available, Duration::hours(24));
+        assert_eq!(report[1].required, Duration::minutes(132));
+        assert_eq!(report[1].deficit(), None);
+        // Even though scheduling fails
+        assert_matches!(
+            Schedule::schedule(
+                start,
+                tasks_per_segment,
+                SchedulingStrategy::Importance,
+                EstimateBuffers::default(),
+                Duration::zero(),
+                &[],
+                None,
+            ),
+            Err(Error::NotEnoughTime { .. })
+        );
+    }
+
+    #[test]
+    fn reschedule_task_within_freed_gap() {
+        let start = Utc::now();
+        let first = task_with_dependencies(1, 5, vec![]);
+        let second = task_with_dependencies(2, 5, vec![]);
+        let mut schedule = Schedule(vec![
+            Scheduled {
+                task: first,
+                when: start,
+                duration: Duration::hours(2),
+                part: None,
+            },
+            Scheduled {
+                task: second.clone(),
+                when: start + Duration::hours(3),
+                duration: Duration::hours(2),
+                part: None,
+            },
+        ]);
+
+        let rescheduled = schedule.reschedule(1, Duration::hours(3)).unwrap();
+        assert_eq!(rescheduled.when, start);
+        assert_eq!(rescheduled.task.duration, Duration::hours(3));
+        assert_eq!(schedule.0[0], rescheduled);
+        // The other tasks stay where they are
+        assert_eq!(schedule.0[1].task, second);
+        assert_eq!(schedule.0[1].when, start + Duration::hours(3));
+
+        // Shrinking always fits
+        let rescheduled = schedule.reschedule(2, Duration::minutes(30)).unwrap();
+        assert_eq!(rescheduled.when, start + Duration::hours(3));
+    }
+
+    #[test]
+    fn refuse_to_reschedule_task_beyond_freed_gap() {
+        let start = Utc::now();
+        let mut first = task_with_dependencies(1, 5, vec![]);
+        first.deadline = start + Duration::hours(4);
+        let second = task_with_dependencies(2, 5, vec![]);
+        let mut schedule = Schedule(vec![
+            Scheduled {
+                task: first,
+                when: start,
+                duration: Duration::hours(2),
+                part: None,
+            },
+            Scheduled {
+                task: second,
+                when: start + Duration::hours(5),
+                duration: Duration::hours(2),
+                part: None,
+            },
+        ]);
+        let original = schedule.0.clone();
+
+        // It would overlap with the next task
+        let result = schedule.reschedule(1, Duration::hours(6));
+        assert_matches!(result, Err(Error::NotEnoughTime { task }) => {
+            assert_eq!(task.id, 1);
+        });
+        // It would miss its deadline
+        let result = schedule.reschedule(1, Duration::hours(5));
+        assert_matches!(result, Err(Error::NotEnoughTime { .. }));
+        let result = schedule.reschedule(3, Duration::hours(1));
+        assert_matches!(result, Err(Error::NotScheduled { id: 3 }));
+        assert_eq!(schedule.0, original);
+    }
+
+    #[test]
+    fn check_feasibility_without_a_database() {
+        // Like the out_of_time case, but with the tasks of the crate
+        let start = Utc::now();
+        let learn_rust = crate::Task {
+            deadline: start + Duration::days(1),
+            duration: Duration::days(1),
+            ..task_with_dependencies(1, 5, vec![])
+        };
+        let program_eva = crate::Task {
+            deadline: start + Duration::days(2),
+            duration: Duration::days(1) + Duration::minutes(1),
+            ..task_with_dependencies(2, 5, vec![])
+        };
+        assert_matches!(
+            feasibility(
+                start,
+                vec![(anytime(), vec![learn_rust.clone()])],
+                SchedulingStrategy::Importance
+            ),
+            Ok(())
+        );
+        assert_matches!(
+            feasibility(
+                start,
+                vec![(anytime(), vec![learn_rust.clone(), program_eva])],
+                SchedulingStrategy::Importance
+            ),
+            Err(Error::NotEnoughTime { .. })
+        );
+        // Uncertain estimates are taken as they are
+        let unsure = crate::Task {
+            confidence: Confidence::Low,
+            ..learn_rust
+        };
+        assert_matches!(
+            feasibility(
+                start,
+                vec![(anytime(), vec![unsure])],
+                SchedulingStrategy::Importance
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn defer_tasks_beyond_the_horizon() {
+        let start = Utc::now();
+        let scheduled = |id, when, hours, part| Scheduled {
+            task: task_with_dependencies(id, 5, vec![]),
+            when,
+            duration: Duration::hours(hours),
+            part,
+        };
+        let split = task_with_dependencies(2, 5, vec![]);
+        let far_future = crate::Task {
+            deadline: start + Duration::days(3650),
+            ..task_with_dependencies(4, 5, vec![])
+        };
+        let horizon = start + Duration::days(7);
+        let schedule = Schedule(vec![
+            scheduled(1, start, 2, None),
+            scheduled(
+                2,
+                start + Duration::hours(3),
+                2,
+                Some(Part { number: 1, of: 2 }),
+            ),
+            scheduled(3, horizon - Duration::hours(1), 2, None),
+            scheduled(
+                2,
+                horizon + Duration::hours(1),
+                2,
+                Some(Part { number: 2, of: 2 }),
+            ),
+            Scheduled {
+                task: far_future.clone(),
+                when: start + Duration::days(3000),
+                duration: Duration::hours(2),
+                part: None,
+            },
+        ]);
+
+        let (clipped, deferred) = Schedule(schedule.0.clone()).clip(horizon);
+        assert_eq!(
+            clipped
+                .0
+                .iter()
+                .map(|scheduled| (scheduled.task.id, scheduled.when, scheduled.duration))
+                .collect::<Vec<_>>(),
+            [
+                (1, start, Duration::hours(2)),
+                (split.id, start + Duration::hours(3), Duration::hours(2)),
+                // Cut short at the horizon
+                (3, horizon - Duration::hours(1), Duration::hours(1)),
+            ]
+        );
+        // The split task already started, so only the far future one is left out
+        assert_eq!(deferred, [far_future]);
+
+        let (clipped, deferred) = Schedule(schedule.0.clone()).clip(start);
+        assert!(clipped.0.is_empty());
+        assert_eq!(
+            deferred.iter().map(|task| task.id).collect::<Vec<_>>(),
+            [1, 2, 3, 4]
+        );
+        let (clipped, deferred) = schedule.clip(start + Duration::days(3650));
+        assert_eq!(clipped.0.len(), 5);
+        assert!(deferred.is_empty());
+    }
+
+    #[test]
+    fn sum_busy_and_idle_time_between_scheduled_tasks() {
+        let start = Utc::now();
+        let schedule = Schedule(vec![
+            Scheduled {
+                task: task_with_dependencies(1, 5, vec![]),
+                when: start,
+                duration: Duration::hours(2),
+                part: None,
+            },
+            Scheduled {
+                task: task_with_dependencies(2, 5, vec![]),
+                when: start + Duration::minutes(150),
+                duration: Duration::hours(2),
+                part: None,
+            },
+            Scheduled {
+                task: task_with_dependencies(3, 5, vec![]),
+                when: start + Duration::hours(6),
+                duration: Duration::hours(2),
+                part: None,
+            },
+        ]);
+
+        let (busy, idle) = schedule.utilisation(start + Duration::days(1));
+        assert_eq!(busy, Duration::hours(6));
+        assert_eq!(idle, Duration::hours(2));
+        // Tasks after the given moment don't count, nor does the time after them
+        let (busy, idle) = schedule.utilisation(start + Duration::hours(3));
+        assert_eq!(busy, Duration::minutes(150));
+        assert_eq!(idle, Duration::minutes(30));
+        let (busy, idle) = schedule.utilisation(start);
+        assert_eq!(busy, Duration::zero());
+        assert_eq!(idle, Duration::zero());
+
+        let (busy, idle) = Schedule::<crate::Task>::default().utilisation(start);
+        assert_eq!(busy, Duration::zero());
+        assert_eq!(idle, Duration::zero());
+    }
+
+    #[test]
+    fn compute_the_slack_of_tasks() {
+        use super::Task as _;
+
+        let now = Utc::now();
+        let task = |hours_left| Task {
+            content: "Go to Mordor".to_string(),
+            deadline: now + Duration::hours(hours_left),
+            duration: Duration::hours(2),
+            importance: 5,
+        };
+        assert_eq!(task(5).slack(now), Duration::hours(3));
+        assert_eq!(task(2).slack(now), Duration::zero());
+        assert_eq!(task(1).slack(now), Duration::hours(-1));
+        assert_eq!(task(-1).slack(now), Duration::hours(-3));
+    }
+
+    #[test]
+    fn rank_tasks_with_less_time_left_as_more_urgent() {
+        use super::Task as _;
+
+        let now = Utc::now();
+        let task = |hours_left| Task {
+            content: "Go to Mordor".to_string(),
+            deadline: now + Duration::hours(hours_left),
+            duration: Duration::hours(2),
+            importance: 5,
+        };
+        assert!(task(1).urgency_rank(now) > task(5).urgency_rank(now));
+        assert!(task(-1).urgency_rank(now) > task(1).urgency_rank(now));
+        assert_eq!(task(3).urgency_rank(now), task(3).urgency_rank(now));
+    }
+
+    #[test]
+    fn find_tasks_ending_close_to_their_deadline() {
+        let start = Utc::now();
+        let scheduled = |id, when, deadline| {
+            let mut task = task_with_dependencies(id, 5, vec![]);
+            task.deadline = deadline;
+            Scheduled {
+                task,
+                when,
+                duration: Duration::hours(2),
+                part: None,
+            }
+        };
+        let schedule = Schedule(vec![
+            // Ends exactly at its deadline
+            scheduled(1, start, start + Duration::hours(2)),
+            // Ends with plenty of time to spare
+            scheduled(2, start + Duration::hours(2), start + Duration::days(3)),
+            // Ends just within the threshold
+            scheduled(
+                3,
+                start + Duration::hours(4),
+                start + Duration::minutes(400),
+            ),
+        ]);
+
+        let tight = schedule.tight_tasks(Duration::hours(1));
+        assert_eq!(tight, [&schedule.0[0], &schedule.0[2]]);
+        assert_eq!(schedule.tight_tasks(Duration::zero()), [&schedule.0[0]]);
+        assert!(Schedule::<crate::Task>::default()
+            .tight_tasks(Duration::hours(1))
+            .is_empty());
+    }
+
+    #[test]
+    fn find_the_task_in_progress_or_the_next_one() {
+        let start = Utc::now();
+        let schedule = Schedule(vec![
+            Scheduled {
+                task: task_with_dependencies(1, 5, vec![]),
+                when: start,
+                duration: Duration::hours(2),
+                part: None,
+            },
+            Scheduled {
+                task: task_with_dependencies(2, 5, vec![]),
+                when: start + Duration::hours(3),
+                duration: Duration::hours(2),
+                part: None,
+            },
+        ]);
+
+        assert_eq!(
+            schedule.next_after(start - Duration::hours(1)),
+            Some(&schedule.0[0])
+        );
+        // The first task is still in progress
+        assert_eq!(
+            schedule.next_after(start + Duration::hours(1)),
+            Some(&schedule.0[0])
+        );
+        assert_eq!(
+            schedule.next_after(start + Duration::hours(2)),
+            Some(&schedule.0[1])
+        );
+        assert_eq!(schedule.next_after(start + Duration::hours(5)), None);
+        assert_eq!(Schedule::<crate::Task>::default().next_after(start), None);
+    }
+
     fn taskset_with_missed_deadline() -> Vec<Task> {
         let task1 = Task {
             content: "conquer the world".to_string(),
@@ -0,0 +1,263 @@
+//! Property tests which check the invariants of every schedule on randomly
+//! generated tasks and time segments. They run a modest number of cases by
+//! default; set `PROPTEST_CASES` to run more.
+
+use chrono::prelude::*;
+use chrono::Duration;
+use proptest::prelude::*;
+
+use super::{Error, Schedule};
+use crate::configuration::{EstimateBuffers, SchedulingStrategy};
+use crate::time_segment::{NamedTimeSegment, TimeSegment};
+use crate::{Confidence, Task};
+
+const CASES: u32 = 64;
+/// Feasible task sets fit in this many days
+const DAYS: i64 = 8;
+
+fn start() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2019, 8, 1, 0, 0, 0).unwrap()
+}
+
+fn strategy() -> impl Strategy<Value = SchedulingStrategy> {
+    prop_oneof![
+        Just(SchedulingStrategy::Importance),
+        Just(SchedulingStrategy::Urgency),
+        Just(SchedulingStrategy::Balanced),
+        Just(SchedulingStrategy::Deadline),
+        any::<u64>().prop_map(|seed| SchedulingStrategy::Shuffled { seed }),
+    ]
+}
+
+fn confidence() -> impl Strategy<Value = Confidence> {
+    prop_oneof![
+        Just(Confidence::Low),
+        Just(Confidence::Medium),
+        Just(Confidence::High),
+    ]
+}
+
+/// A time segment which covers `hours` hours every day, from `from_hour` on.
+fn daily_segment(id: u32, from_hour: i64, hours: i64) -> NamedTimeSegment {
+    let range_start = start() + Duration::hours(from_hour);
+    NamedTimeSegment {
+        id,
+        name: format!("segment {}", id),
+        ranges: vec![range_start..range_start + Duration::hours(hours)],
+        start: start(),
+        period: Duration::days(1),
+        hue: 0,
+        strategy: None,
+    }
+}
+
+/// Tasks which are certainly feasible in a daily time segment of `hours`
+/// hours: there is at most one task per day before the earliest deadline and
+/// every task fits in a single day, even when its estimate is buffered.
+fn feasible_tasks(segment_id: u32, hours: i64) -> impl Strategy<Value = Vec<Task>> {
+    // Leave room for a buffer of 25% at most
+    let task = (
+        15..=hours * 48,
+        0..=DAYS,
+        1..=10u32,
+        confidence(),
+        prop::option::of(0..360u16),
+    );
+    prop::collection::vec(task, 0..=DAYS as usize).prop_map(move |tasks| {
+        tasks
+            .into_iter()
+            .map(
+                |(minutes, extra_days, importance, confidence, color)| Task {
+                    id: 0,
+                    content: String::new(),
+                    deadline: start() + Duration::days(DAYS + extra_days),
+                    duration: Duration::minutes(minutes),
+                    importance,
+                    confidence,
+                    time_segment_id: segment_id,
+                    depends_on: vec![],
+                    color,
+                    scheduled_for: None,
+                    done_at: None,
+                    pinned: false,
+                },
+            )
+            .collect()
+    })
+}
+
+/// One or two daily time segments which don't overlap, each with a feasible set
+/// of tasks, with unique task ids.
+fn feasible_input() -> impl Strategy<Value = Vec<(NamedTimeSegment, Vec<Task>)>> {
+    // Each segment lies within its own half of the day
+    let segment = (0..=4i64, 1..=8i64);
+    prop::collection::vec(segment, 1..=2)
+        .prop_flat_map(|segments| {
+            segments
+                .into_iter()
+                .enumerate()
+                .map(|(id, (offset, hours))| {
+                    let from_hour = 12 * id as i64 + offset;
+                    let segment = daily_segment(id as u32, from_hour, hours);
+                    (Just(segment), feasible_tasks(id as u32, hours))
+                })
+                .collect::<Vec<_>>()
+        })
+        .prop_map(number_tasks)
+}
+
+fn number_tasks(
+    mut tasks_per_segment: Vec<(NamedTimeSegment, Vec<Task>)>,
+) -> Vec<(NamedTimeSegment, Vec<Task>)> {
+    let tasks = tasks_per_segment
+        .iter_mut()
+        .flat_map(|(_, tasks)| tasks.iter_mut());
+    for (id, task) in tasks.enumerate() {
+        task.id = id as u32 + 1;
+        task.content = format!("task {}", task.id);
+    }
+    tasks_per_segment
+}
+
+fn schedule(
+    tasks_per_segment: &[(NamedTimeSegment, Vec<Task>)],
+    strategy: SchedulingStrategy,
+) -> Result<Schedule<Task>, Error<Task>> {
+    Schedule::schedule(
+        start(),
+        tasks_per_segment.to_vec(),
+        strategy,
+        EstimateBuffers::default(),
+        Duration::zero(),
+        &[],
+        None,
+    )
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(CASES))]
+
+    #[test]
+    fn feasible_tasks_are_scheduled_in_time_without_overlaps(
+        tasks_per_segment in feasible_input(),
+        strategy in strategy(),
+    ) {
+        let schedule = schedule(&tasks_per_segment, strategy)
+            .map_err(|error| TestCaseError::fail(error.to_string()))?;
+
+        // Every task is scheduled exactly once
+        let tasks = tasks_per_segment
+            .iter()
+            .flat_map(|(_, tasks)| tasks)
+            .collect::<Vec<_>>();
+        prop_assert_eq!(schedule.0.len(), tasks.len());
+        for task in tasks {
+            let n_scheduled = schedule
+                .0
+                .iter()
+                .filter(|scheduled| scheduled.task == *task)
+                .count();
+            prop_assert_eq!(n_scheduled, 1, "{} is scheduled {} times", task, n_scheduled);
+        }
+
+        let horizon = start() + Duration::days(3 * DAYS);
+        for scheduled in &schedule.0 {
+            let end = scheduled.end();
+            // Planned for at least as long as it takes
+            prop_assert!(scheduled.duration >= scheduled.task.duration);
+            // In time
+            prop_assert!(scheduled.when >= start());
+            prop_assert!(end <= scheduled.task.deadline, "{} is late", scheduled.task);
+            // Within its time segment
+            let (segment, _) = &tasks_per_segment[scheduled.task.time_segment_id as usize];
+            prop_assert!(
+                segment
+                    .generate_ranges(start(), horizon)
+                    .iter()
+                    .any(|range| range.start <= scheduled.when && end <= range.end),
+                "{} is outside of its time segment",
+                scheduled.task
+            );
+        }
+
+        // Without overlaps, in chronological order
+        for (scheduled, next) in schedule.0.iter().zip(schedule.0.iter().skip(1)) {
+            prop_assert!(
+                scheduled.end() <= next.when,
+                "{} overlaps with {}",
+                scheduled.task,
+                next.task
+            );
+        }
+    }
+
+    #[test]
+    fn overloaded_tasks_are_refused(
+        tasks_per_segment in feasible_input(),
+        strategy in strategy(),
+        extra_hours in 0..48i64,
+    ) {
+        // A task longer than all the time that is left, in any segment
+        let mut tasks_per_segment = tasks_per_segment;
+        let (segment, tasks) = &mut tasks_per_segment[0];
+        let deadline = start() + Duration::days(DAYS);
+        let available = segment
+            .generate_ranges(start(), deadline)
+            .iter()
+            .fold(Duration::zero(), |total, range| total + (range.end - range.start));
+        tasks.push(Task {
+            id: 0,
+            content: "too much".to_string(),
+            deadline,
+            duration: available + Duration::hours(extra_hours) + Duration::minutes(1),
+            importance: 5,
+            confidence: Confidence::High,
+            time_segment_id: segment.id,
+            depends_on: vec![],
+            color: None,
+            scheduled_for: None,
+            done_at: None,
+            pinned: false,
+        });
+        let tasks_per_segment = number_tasks(tasks_per_segment);
+
+        let result = schedule(&tasks_per_segment, strategy);
+        prop_assert!(
+            matches!(result, Err(Error::NotEnoughTime { .. })),
+            "expected not enough time, got {:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn tasks_past_their_deadline_are_refused(
+        tasks_per_segment in feasible_input(),
+        strategy in strategy(),
+        hours_late in 1..100i64,
+    ) {
+        let mut tasks_per_segment = tasks_per_segment;
+        let (segment, tasks) = &mut tasks_per_segment[0];
+        tasks.push(Task {
+            id: 0,
+            content: "too late".to_string(),
+            deadline: start() - Duration::hours(hours_late),
+            duration: Duration::minutes(30),
+            importance: 5,
+            confidence: Confidence::High,
+            time_segment_id: segment.id,
+            depends_on: vec![],
+            color: None,
+            scheduled_for: None,
+            done_at: None,
+            pinned: false,
+        });
+        let tasks_per_segment = number_tasks(tasks_per_segment);
+
+        let result = schedule(&tasks_per_segment, strategy);
+        prop_assert!(
+            matches!(result, Err(Error::DeadlineMissed { .. })),
+            "expected a missed deadline, got {:?}",
+            result
+        );
+    }
+}
@@ -2,6 +2,7 @@ use std::cmp::{max, min};
 use std::collections::HashMap;
 use std::fmt::Debug;
 use std::hash::Hash;
+use std::iter::Sum;
 use std::ops::{Add, Range, Sub};
 use std::rc::Rc;
 
@@ -61,6 +62,39 @@ where
         self.root.is_none()
     }
 
+    /// Returns how many entries are scheduled in the tree.
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    /// Returns how many nodes the tree consists of, i.e. the entries along
+    /// with the intermediate nodes above them.
+    pub fn node_count(&self) -> usize {
+        self.root.as_ref().map_or(0, Node::count)
+    }
+
+    /// Sums how long all entries in the tree last.
+    pub fn total_scheduled_duration<W>(&self) -> W
+    where
+        T: Sub<Output = W>,
+        W: Sum,
+    {
+        self.total_duration_where(|_| true)
+    }
+
+    /// Sums how long the entries of which `include` accepts the data last,
+    /// e.g. to leave out the entries which only block some time.
+    pub fn total_duration_where<W>(&self, include: impl Fn(&D) -> bool) -> W
+    where
+        T: Sub<Output = W>,
+        W: Sum,
+    {
+        self.iter()
+            .filter(|entry| include(entry.data))
+            .map(|entry| entry.end - entry.start)
+            .sum()
+    }
+
     /// Tries to schedule `data` at the exact `start` with the given `duration`.
     ///
     /// Returns whether the scheduling succeeded.
@@ -310,6 +344,24 @@ where
         }
     }
 
+    /// Returns the ranges between `from` and `to` in which nothing is scheduled, in chronological
+    /// order. Empty ranges are left out.
+    pub fn free_slots(&self, from: T, to: T) -> Vec<Range<T>> {
+        let mut free = vec![];
+        match (&self.root, &self.scope) {
+            (Some(root), Some(scope)) => {
+                free.push(from..scope.start);
+                root.collect_free(&mut free);
+                free.push(scope.end..to);
+            }
+            _ => free.push(from..to),
+        }
+        free.into_iter()
+            .map(|range| max(range.start, from)..min(range.end, to))
+            .filter(|range| range.start < range.end)
+            .collect()
+    }
+
     pub fn when_scheduled<'a>(&self, data: &'a D) -> Option<&T> {
         self.data_map.get(data)
     }
@@ -519,6 +571,29 @@ where
         }
     }
 
+    /// Appends the free ranges between the descendants of this node to `free`, in chronological
+    /// order.
+    fn collect_free(&self, free: &mut Vec<Range<T>>) {
+        if let Node::Intermediate {
+            free: range,
+            left,
+            right,
+        } = self
+        {
+            left.collect_free(free);
+            free.push(range.clone());
+            right.collect_free(free);
+        }
+    }
+
+    /// Counts this node and all of its descendants.
+    fn count(&self) -> usize {
+        match self {
+            Node::Leaf { .. } => 1,
+            Node::Intermediate { left, right, .. } => 1 + left.count() + right.count(),
+        }
+    }
+
     /// Calculates the scope of all descendants of this node.
     fn find_scope(&self) -> Range<T> {
         match self {
@@ -778,6 +853,91 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_free_slots() {
+        let data = generate_data(10);
+
+        let mut tree = ScheduleTree::new();
+        assert_eq!(tree.free_slots(0, 20), vec![0..20]);
+
+        // 5..9
+        tree.schedule_exact(5, 4, &data[0]);
+        assert_eq!(tree.free_slots(0, 20), vec![0..5, 9..20]);
+
+        //   free:9..13
+        //    /        \
+        // 5..9       13..18
+        tree.schedule_exact(13, 5, &data[1]);
+        assert_eq!(tree.free_slots(0, 20), vec![0..5, 9..13, 18..20]);
+
+        //   free:9..10
+        //    /        \
+        // 5..9      free:12..13
+        //             /     \
+        //          10..12  13..18
+        tree.schedule_exact(10, 2, &data[2]);
+        assert_eq!(tree.free_slots(0, 20), vec![0..5, 9..10, 12..13, 18..20]);
+
+        //     free:9..9
+        //    /         \
+        // 5..9      free:10..10
+        //            /       \
+        //         9..10   free:12..13
+        //                   /     \
+        //               10..12   13..18
+        tree.schedule_exact(9, 1, &data[6]);
+        assert_eq!(tree.free_slots(0, 20), vec![0..5, 12..13, 18..20]);
+
+        // Free ranges are clipped
+        assert_eq!(tree.free_slots(3, 15), vec![3..5, 12..13]);
+        assert_eq!(tree.free_slots(5, 18), vec![12..13]);
+        assert_eq!(tree.free_slots(6, 9), vec![]);
+    }
+
+    #[test]
+    fn test_sizes_and_durations() {
+        let data = generate_data(10);
+
+        let mut tree = ScheduleTree::new();
+        assert_eq!(tree.len(), 0);
+        assert_eq!(tree.node_count(), 0);
+        assert_eq!(tree.total_scheduled_duration(), 0);
+
+        // 5..9
+        tree.schedule_exact(5, 4, &data[0]);
+        assert_eq!(tree.len(), 1);
+        assert_eq!(tree.node_count(), 1);
+        assert_eq!(tree.total_scheduled_duration(), 4);
+
+        //   free:9..13
+        //    /        \
+        // 5..9       13..18
+        tree.schedule_exact(13, 5, &data[1]);
+        assert_eq!(tree.len(), 2);
+        assert_eq!(tree.node_count(), 3);
+        assert_eq!(tree.total_scheduled_duration(), 9);
+
+        //     free:9..9
+        //    /         \
+        // 5..9      free:10..10
+        //            /       \
+        //         9..10   free:12..13
+        //                   /     \
+        //               10..12   13..18
+        tree.schedule_exact(10, 2, &data[2]);
+        tree.schedule_exact(9, 1, &data[6]);
+        assert_eq!(tree.len(), 4);
+        assert_eq!(tree.node_count(), 7);
+        assert_eq!(tree.total_scheduled_duration(), 12);
+        assert_eq!(tree.total_duration_where(|&&data| data != 6), 11);
+        assert_eq!(tree.total_duration_where(|_| false), 0);
+
+        tree.unschedule(&&data[1]);
+        assert_eq!(tree.len(), 3);
+        assert_eq!(tree.node_count(), 5);
+        assert_eq!(tree.total_scheduled_duration(), 7);
+    }
+
     #[test]
     fn test_schedule_close_before() {
         let data = generate_data(10);
@@ -0,0 +1,213 @@
+use chrono::Duration;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::configuration::SchedulingStrategy;
+
+/// Serialises a duration as a whole number of seconds.
+pub(crate) fn serialize_duration_as_seconds<S>(
+    duration: &Duration,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_i64(duration.num_seconds())
+}
+
+/// Deserialises a duration from a whole number of seconds.
+pub(crate) fn deserialize_duration_from_seconds<'de, D>(
+    deserializer: D,
+) -> Result<Duration, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(Duration::seconds(i64::deserialize(deserializer)?))
+}
+
+/// Serialises a strategy in the same form as it's written in the
+/// configuration, e.g. "shuffled:42".
+impl Serialize for SchedulingStrategy {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for SchedulingStrategy {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::prelude::*;
+    use chrono::Duration;
+
+    use crate::configuration::SchedulingStrategy;
+    use crate::time_segment::{NamedTimeSegment, NewNamedTimeSegment};
+    use crate::{Confidence, NewTask, Schedule, Scheduled, Task};
+
+    /// Serialises the value, checks it reads back the same and returns the
+    /// JSON in between.
+    fn round_trip<T>(value: &T) -> String
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned + std::fmt::Debug,
+    {
+        let json = serde_json::to_string(value).unwrap();
+        let deserialized: T = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&deserialized).unwrap(), json);
+        json
+    }
+
+    #[test]
+    fn serialize_and_deserialize_schedule() {
+        let when = Utc.with_ymd_and_hms(2019, 8, 1, 14, 23, 0).unwrap();
+        let schedule = Schedule(vec![Scheduled {
+            task: Task {
+                id: 13,
+                content: "Prepare epic-sounding one-liners".to_string(),
+                deadline: Utc.with_ymd_and_hms(2019, 8, 2, 19, 0, 0).unwrap(),
+                duration: Duration::hours(2),
+                importance: 10,
+                confidence: Confidence::Medium,
+                time_segment_id: 0,
+                depends_on: vec![],
+                color: None,
+                scheduled_for: None,
+                done_at: None,
+                pinned: false,
+            },
+            when,
+            duration: Duration::minutes(150),
+            part: None,
+        }]);
+        assert_eq!(
+            serde_json::to_string(&schedule).unwrap(),
+            "[{\"task\":{\"id\":13,\"content\":\"Prepare epic-sounding one-liners\",\
+             \"deadline\":\"2019-08-02T19:00:00Z\",\"duration\":7200,\"importance\":10,\
+             \"confidence\":\"medium\",\"time_segment_id\":0,\"depends_on\":[],\"color\":null,\
+             \"scheduled_for\":null},\
+             \"when\":\"2019-08-01T14:23:00Z\",\"duration\":9000}]"
+        );
+        let deserialized: Schedule<Task> =
+            serde_json::from_str(&serde_json::to_string(&schedule).unwrap()).unwrap();
+        assert_eq!(deserialized.0, schedule.0);
+    }
+
+    #[test]
+    fn serialize_and_deserialize_tasks() {
+        let task = Task {
+            id: 7,
+            content: "Water the plants".to_string(),
+            deadline: Utc.with_ymd_and_hms(2019, 8, 2, 19, 0, 0).unwrap(),
+            duration: Duration::minutes(15),
+            importance: 4,
+            confidence: Confidence::Low,
+            time_segment_id: 1,
+            depends_on: vec![3],
+            color: Some(120),
+            scheduled_for: Some(Utc.with_ymd_and_hms(2019, 8, 1, 9, 0, 0).unwrap()),
+            done_at: Some(Utc.with_ymd_and_hms(2019, 8, 1, 9, 15, 0).unwrap()),
+            pinned: false,
+        };
+        assert_eq!(
+            round_trip(&task),
+            "{\"id\":7,\"content\":\"Water the plants\",\"deadline\":\"2019-08-02T19:00:00Z\",\
+             \"duration\":900,\"importance\":4,\"confidence\":\"low\",\"time_segment_id\":1,\
+             \"depends_on\":[3],\"color\":120,\"scheduled_for\":\"2019-08-01T09:00:00Z\",\
+             \"done_at\":\"2019-08-01T09:15:00Z\"}"
+        );
+        let deserialized: Task = serde_json::from_str(&round_trip(&task)).unwrap();
+        assert_eq!(deserialized, task);
+
+        let new_task = NewTask {
+            content: task.content.clone(),
+            deadline: task.deadline,
+            duration: task.duration,
+            importance: task.importance,
+            confidence: task.confidence,
+            time_segment_id: task.time_segment_id,
+            depends_on: task.depends_on.clone(),
+        };
+        assert_eq!(
+            round_trip(&new_task),
+            "{\"content\":\"Water the plants\",\"deadline\":\"2019-08-02T19:00:00Z\",\
+             \"duration\":900,\"importance\":4,\"confidence\":\"low\",\"time_segment_id\":1,\
+             \"depends_on\":[3]}"
+        );
+        let deserialized: NewTask = serde_json::from_str(&round_trip(&new_task)).unwrap();
+        assert_eq!(
+            Task {
+                color: None,
+                ..task
+            },
+            deserialized
+        );
+    }
+
+    #[test]
+    fn serialize_and_deserialize_time_segments() {
+        let start = Utc.with_ymd_and_hms(2019, 8, 1, 9, 0, 0).unwrap();
+        let new_time_segment = NewNamedTimeSegment {
+            name: "Work".to_string(),
+            ranges: vec![start..start + Duration::hours(8)],
+            start,
+            period: Duration::weeks(1),
+            hue: 210,
+            strategy: Some(SchedulingStrategy::Shuffled { seed: 42 }),
+        };
+        assert_eq!(
+            round_trip(&new_time_segment),
+            "{\"name\":\"Work\",\"ranges\":[{\"start\":\"2019-08-01T09:00:00Z\",\
+             \"end\":\"2019-08-01T17:00:00Z\"}],\"start\":\"2019-08-01T09:00:00Z\",\
+             \"period\":604800,\"hue\":210,\"strategy\":\"shuffled:42\"}"
+        );
+
+        let time_segment = NamedTimeSegment {
+            id: 2,
+            name: new_time_segment.name.clone(),
+            ranges: new_time_segment.ranges.clone(),
+            start,
+            period: new_time_segment.period,
+            hue: new_time_segment.hue,
+            strategy: new_time_segment.strategy,
+        };
+        assert_eq!(
+            round_trip(&time_segment),
+            "{\"id\":2,\"name\":\"Work\",\"ranges\":[{\"start\":\"2019-08-01T09:00:00Z\",\
+             \"end\":\"2019-08-01T17:00:00Z\"}],\"start\":\"2019-08-01T09:00:00Z\",\
+             \"period\":604800,\"hue\":210,\"strategy\":\"shuffled:42\"}"
+        );
+        let deserialized: NamedTimeSegment =
+            serde_json::from_str(&round_trip(&time_segment)).unwrap();
+        assert_eq!(deserialized, time_segment);
+        assert_eq!(deserialized, new_time_segment);
+    }
+
+    #[test]
+    fn serialize_and_deserialize_strategies() {
+        for (strategy, json) in [
+            (SchedulingStrategy::Importance, "\"importance\""),
+            (SchedulingStrategy::Urgency, "\"urgency\""),
+            (SchedulingStrategy::Balanced, "\"balanced\""),
+            (SchedulingStrategy::Deadline, "\"deadline\""),
+            (SchedulingStrategy::Shuffled { seed: 42 }, "\"shuffled:42\""),
+        ] {
+            assert_eq!(round_trip(&strategy), json);
+            assert_eq!(
+                serde_json::from_str::<SchedulingStrategy>(json).unwrap(),
+                strategy
+            );
+        }
+        assert!(serde_json::from_str::<SchedulingStrategy>("\"random\"").is_err());
+    }
+}
@@ -0,0 +1,168 @@
+use std::ops::RangeInclusive;
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::time_segment::NamedTimeSegment;
+use crate::Task;
+
+/// The importances which are counted together: low, medium and high.
+pub const IMPORTANCE_BUCKETS: [RangeInclusive<u32>; 3] = [1..=3, 4..=7, 8..=10];
+
+/// An overview of the tasks which are still to do.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statistics {
+    pub tasks: TaskCount,
+    /// The tasks per bucket of `IMPORTANCE_BUCKETS`, in the same order
+    pub per_importance: Vec<(RangeInclusive<u32>, TaskCount)>,
+    pub per_time_segment: Vec<(NamedTimeSegment, TaskCount)>,
+    /// The first deadline of all tasks, if there are any
+    pub nearest_deadline: Option<DateTime<Utc>>,
+    /// The last deadline of all tasks, if there are any
+    pub furthest_deadline: Option<DateTime<Utc>>,
+}
+
+/// How many tasks there are in a group and how long they take together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TaskCount {
+    pub count: usize,
+    pub duration: Duration,
+}
+
+impl TaskCount {
+    fn of<'a>(tasks: impl IntoIterator<Item = &'a Task>) -> TaskCount {
+        tasks.into_iter().fold(
+            TaskCount {
+                count: 0,
+                duration: Duration::zero(),
+            },
+            |total, task| TaskCount {
+                count: total.count + 1,
+                duration: total.duration + task.duration,
+            },
+        )
+    }
+}
+
+/// Sums up the tasks as a whole, per importance and per time segment. Tasks
+/// in a time segment which isn't given only count towards the totals.
+pub fn compute(tasks: &[Task], time_segments: &[NamedTimeSegment]) -> Statistics {
+    Statistics {
+        tasks: TaskCount::of(tasks),
+        per_importance: IMPORTANCE_BUCKETS
+            .iter()
+            .map(|bucket| {
+                let in_bucket = tasks
+                    .iter()
+                    .filter(|task| bucket.contains(&task.importance));
+                (bucket.clone(), TaskCount::of(in_bucket))
+            })
+            .collect(),
+        per_time_segment: time_segments
+            .iter()
+            .map(|time_segment| {
+                let in_segment = tasks
+                    .iter()
+                    .filter(|task| task.time_segment_id == time_segment.id);
+                (time_segment.clone(), TaskCount::of(in_segment))
+            })
+            .collect(),
+        nearest_deadline: tasks.iter().map(|task| task.deadline).min(),
+        furthest_deadline: tasks.iter().map(|task| task.deadline).max(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::Confidence;
+
+    fn time_segment(id: u32, name: &str) -> NamedTimeSegment {
+        let start = Utc.with_ymd_and_hms(2019, 8, 1, 9, 0, 0).unwrap();
+        NamedTimeSegment {
+            id,
+            name: name.to_string(),
+            ranges: vec![start..start + Duration::hours(8)],
+            start,
+            period: Duration::days(1),
+            hue: 0,
+            strategy: None,
+        }
+    }
+
+    fn task(id: u32, importance: u32, hours: i64, days_left: i64, time_segment_id: u32) -> Task {
+        Task {
+            id,
+            content: format!("task {}", id),
+            deadline: Utc.with_ymd_and_hms(2019, 8, 1, 12, 0, 0).unwrap()
+                + Duration::days(days_left),
+            duration: Duration::hours(hours),
+            importance,
+            confidence: Confidence::High,
+            time_segment_id,
+            depends_on: vec![],
+            color: None,
+            scheduled_for: None,
+            done_at: None,
+            pinned: false,
+        }
+    }
+
+    #[test]
+    fn sums_up_tasks_per_importance_and_time_segment() {
+        let time_segments = [time_segment(0, "Default"), time_segment(1, "Work")];
+        let tasks = [
+            task(1, 2, 1, 3, 0),
+            task(2, 5, 2, 1, 1),
+            task(3, 7, 3, 10, 1),
+            task(4, 10, 4, 5, 1),
+        ];
+        let count = |count, hours| TaskCount {
+            count,
+            duration: Duration::hours(hours),
+        };
+
+        let statistics = compute(&tasks, &time_segments);
+        assert_eq!(statistics.tasks, count(4, 10));
+        assert_eq!(
+            statistics.per_importance,
+            [
+                (1..=3, count(1, 1)),
+                (4..=7, count(2, 5)),
+                (8..=10, count(1, 4))
+            ]
+        );
+        assert_eq!(
+            statistics.per_time_segment,
+            [
+                (time_segments[0].clone(), count(1, 1)),
+                (time_segments[1].clone(), count(3, 9))
+            ]
+        );
+        assert_eq!(statistics.nearest_deadline, Some(tasks[1].deadline));
+        assert_eq!(statistics.furthest_deadline, Some(tasks[2].deadline));
+    }
+
+    #[test]
+    fn sums_up_no_tasks() {
+        let time_segments = [time_segment(0, "Default")];
+        let none = TaskCount {
+            count: 0,
+            duration: Duration::zero(),
+        };
+
+        let statistics = compute(&[], &time_segments);
+        assert_eq!(statistics.tasks, none);
+        assert!(statistics
+            .per_importance
+            .iter()
+            .all(|(_, count)| *count == none));
+        assert_eq!(
+            statistics.per_time_segment,
+            [(time_segments[0].clone(), none)]
+        );
+        assert_eq!(statistics.nearest_deadline, None);
+        assert_eq!(statistics.furthest_deadline, None);
+    }
+}
@@ -1,13 +1,81 @@
+use std::hash::{Hash, Hasher};
 use std::ops::Range;
 
-use chrono::{DateTime, Duration, Utc};
+use chrono::{DateTime, Duration, NaiveTime, Offset, TimeZone, Utc};
 use itertools::Itertools;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::configuration::SchedulingStrategy;
+
+/// The ways in which the ranges of a time segment can be malformed.
+#[derive(Debug, Error, PartialEq)]
+pub enum TimeSegmentError {
+    #[error("The time segment should have a period longer than zero")]
+    EmptyPeriod,
+    #[error("The time range starting at {start} is empty")]
+    EmptyRange { start: DateTime<Utc> },
+    #[error(
+        "The time range starting at {start} should come before the one starting at {previous}"
+    )]
+    OutOfOrder {
+        previous: DateTime<Utc>,
+        start: DateTime<Utc>,
+    },
+    #[error("The time ranges starting at {previous} and {start} overlap")]
+    Overlap {
+        previous: DateTime<Utc>,
+        start: DateTime<Utc>,
+    },
+    #[error("The time ranges span more than a single period of the time segment")]
+    LongerThanPeriod,
+}
 
 pub trait TimeSegment: Clone {
     fn ranges(&self) -> &Vec<Range<DateTime<Utc>>>;
     fn start(&self) -> DateTime<Utc>;
     fn period(&self) -> Duration;
 
+    /// The strategy to schedule the tasks in this segment with, if it has its
+    /// own rather than the one for all segments.
+    fn strategy(&self) -> Option<SchedulingStrategy> {
+        None
+    }
+
+    /// Checks that the ranges are sorted, non-empty, don't overlap and fit
+    /// within a single period. The other methods assume they do.
+    fn validate(&self) -> Result<(), TimeSegmentError> {
+        if self.period() <= Duration::zero() {
+            return Err(TimeSegmentError::EmptyPeriod);
+        }
+        for range in self.ranges() {
+            if range.start >= range.end {
+                return Err(TimeSegmentError::EmptyRange { start: range.start });
+            }
+        }
+        for (previous, range) in self.ranges().iter().tuple_windows() {
+            if range.start < previous.start {
+                return Err(TimeSegmentError::OutOfOrder {
+                    previous: previous.start,
+                    start: range.start,
+                });
+            }
+            if range.start < previous.end {
+                return Err(TimeSegmentError::Overlap {
+                    previous: previous.start,
+                    start: range.start,
+                });
+            }
+        }
+        if let (Some(first), Some(last)) = (self.ranges().first(), self.ranges().last()) {
+            if last.end - first.start > self.period() {
+                return Err(TimeSegmentError::LongerThanPeriod);
+            }
+        }
+        Ok(())
+    }
+
     /// Construct the inverse of the time segment, i.e. the time segment made up
     /// of all time that the given time segment _doesn't_ cover.
     fn inverse(&self) -> UnnamedTimeSegment {
@@ -80,6 +148,17 @@ pub trait TimeSegment: Clone {
         all_ranges
     }
 
+    /// Returns whether the given moment falls within one of the ranges the time
+    /// segment covers, in whichever period that moment lies.
+    fn contains(&self, at: DateTime<Utc>) -> bool {
+        // Shifted towards `at`, the only ranges which can contain it are the
+        // ones starting right at it
+        self.with_start(at)
+            .ranges()
+            .iter()
+            .any(|range| range.contains(&at))
+    }
+
     /// Returns a new time segment with its start and ranges shifted towards the
     /// given start time.
     fn with_start(&self, start: DateTime<Utc>) -> UnnamedTimeSegment {
@@ -127,35 +206,87 @@ pub trait TimeSegment: Clone {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// Time segments are equal when they have the same ranges, in whichever order.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct NamedTimeSegment {
     pub id: u32,
     pub name: String,
-    // ranges is assumed to be in order
+    // ranges is assumed to be valid, see TimeSegment::validate
     pub ranges: Vec<Range<DateTime<Utc>>>,
     pub start: DateTime<Utc>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            serialize_with = "crate::serialization::serialize_duration_as_seconds",
+            deserialize_with = "crate::serialization::deserialize_duration_from_seconds"
+        )
+    )]
     pub period: Duration,
     pub hue: u16,
+    /// How to schedule the tasks in this segment, instead of the strategy in
+    /// the configuration
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub strategy: Option<SchedulingStrategy>,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct NewNamedTimeSegment {
     pub name: String,
-    // ranges is assumed to be in order
+    // ranges is assumed to be valid, see TimeSegment::validate
     pub ranges: Vec<Range<DateTime<Utc>>>,
     pub start: DateTime<Utc>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            serialize_with = "crate::serialization::serialize_duration_as_seconds",
+            deserialize_with = "crate::serialization::deserialize_duration_from_seconds"
+        )
+    )]
     pub period: Duration,
     pub hue: u16,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub strategy: Option<SchedulingStrategy>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct UnnamedTimeSegment {
-    // ranges is assumed to be in order
+    // ranges is assumed to be valid, see TimeSegment::validate
     pub ranges: Vec<Range<DateTime<Utc>>>,
     pub start: DateTime<Utc>,
     pub period: Duration,
 }
 
+impl UnnamedTimeSegment {
+    /// Returns a time segment which covers all time.
+    pub fn anytime(start: DateTime<Utc>) -> UnnamedTimeSegment {
+        UnnamedTimeSegment {
+            ranges: vec![start..start + Duration::days(1)],
+            start,
+            period: Duration::days(1),
+        }
+    }
+
+    /// Returns a time segment which covers every day from `start_of_day` until
+    /// midnight, in the time zone of `today`. Every day is assumed to last 24
+    /// hours with the current UTC offset, so after a change to or from
+    /// daylight saving time, the days shift by an hour.
+    pub fn daily_from<Tz: TimeZone>(
+        today: &DateTime<Tz>,
+        start_of_day: NaiveTime,
+    ) -> UnnamedTimeSegment {
+        let midnight = today.date_naive().and_time(NaiveTime::MIN);
+        let midnight = Utc.from_utc_datetime(&midnight)
+            - Duration::seconds(today.offset().fix().local_minus_utc().into());
+        UnnamedTimeSegment {
+            ranges: vec![midnight + (start_of_day - NaiveTime::MIN)..midnight + Duration::days(1)],
+            start: midnight,
+            period: Duration::days(1),
+        }
+    }
+}
+
 impl TimeSegment for NamedTimeSegment {
     fn ranges(&self) -> &Vec<Range<DateTime<Utc>>> {
         &self.ranges
@@ -168,6 +299,28 @@ impl TimeSegment for NamedTimeSegment {
     fn period(&self) -> Duration {
         self.period
     }
+
+    fn strategy(&self) -> Option<SchedulingStrategy> {
+        self.strategy
+    }
+}
+
+impl TimeSegment for NewNamedTimeSegment {
+    fn ranges(&self) -> &Vec<Range<DateTime<Utc>>> {
+        &self.ranges
+    }
+
+    fn start(&self) -> DateTime<Utc> {
+        self.start
+    }
+
+    fn period(&self) -> Duration {
+        self.period
+    }
+
+    fn strategy(&self) -> Option<SchedulingStrategy> {
+        self.strategy
+    }
 }
 
 impl TimeSegment for UnnamedTimeSegment {
@@ -184,19 +337,131 @@ impl TimeSegment for UnnamedTimeSegment {
     }
 }
 
+impl PartialEq for NamedTimeSegment {
+    fn eq(&self, other: &NamedTimeSegment) -> bool {
+        let NamedTimeSegment {
+            id,
+            name,
+            ranges,
+            start,
+            period,
+            hue,
+            strategy,
+        } = self;
+        *id == other.id
+            && *name == other.name
+            && chronological(ranges) == chronological(&other.ranges)
+            && *start == other.start
+            && *period == other.period
+            && *hue == other.hue
+            && *strategy == other.strategy
+    }
+}
+
+impl Hash for NamedTimeSegment {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+        self.name.hash(state);
+        chronological(&self.ranges).hash(state);
+        self.start.hash(state);
+        self.period.hash(state);
+        self.hue.hash(state);
+        self.strategy.hash(state);
+    }
+}
+
 impl PartialEq<NewNamedTimeSegment> for NamedTimeSegment {
     fn eq(&self, other: &NewNamedTimeSegment) -> bool {
         self.name == other.name
-            && self.ranges == other.ranges
+            && chronological(&self.ranges) == chronological(&other.ranges)
             && self.start == other.start
             && self.period == other.period
     }
 }
 
+/// Returns the ranges sorted by when they start, since the order they're stored
+/// in doesn't change which time a segment covers.
+fn chronological(ranges: &[Range<DateTime<Utc>>]) -> Vec<&Range<DateTime<Utc>>> {
+    ranges
+        .iter()
+        .sorted_by_key(|range| (range.start, range.end))
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
+    use chrono::FixedOffset;
+
     use super::*;
 
+    #[test]
+    fn daily_from_start_of_day_in_local_time() {
+        let brussels = FixedOffset::east_opt(2 * 3600).unwrap();
+        let today = brussels.with_ymd_and_hms(2019, 8, 1, 3, 12, 0).unwrap();
+        let segment =
+            UnnamedTimeSegment::daily_from(&today, NaiveTime::from_hms_opt(8, 30, 0).unwrap());
+        assert_eq!(
+            segment.ranges,
+            [Utc.with_ymd_and_hms(2019, 8, 1, 6, 30, 0).unwrap()
+                ..Utc.with_ymd_and_hms(2019, 8, 1, 22, 0, 0).unwrap()]
+        );
+        assert_eq!(segment.period, Duration::days(1));
+        assert!(!segment.contains(today.with_timezone(&Utc)));
+        assert!(segment.contains(Utc.with_ymd_and_hms(2019, 8, 5, 7, 0, 0).unwrap()));
+    }
+
+    #[test]
+    fn validate_ranges() {
+        let start = Utc.with_ymd_and_hms(2019, 8, 1, 0, 0, 0).unwrap();
+        let hours = |hours| start + Duration::hours(hours);
+        let daily = |ranges| UnnamedTimeSegment {
+            ranges,
+            start,
+            period: Duration::days(1),
+        };
+
+        assert_eq!(daily(vec![]).validate(), Ok(()));
+        assert_eq!(
+            daily(vec![hours(9)..hours(12), hours(12)..hours(17)]).validate(),
+            Ok(())
+        );
+        // The ranges can lie outside of the first period, as long as they span
+        // at most one
+        assert_eq!(daily(vec![hours(20)..hours(30)]).validate(), Ok(()));
+        assert_eq!(
+            daily(vec![hours(9)..hours(13), hours(12)..hours(17)]).validate(),
+            Err(TimeSegmentError::Overlap {
+                previous: hours(9),
+                start: hours(12)
+            })
+        );
+        assert_eq!(
+            daily(vec![hours(13)..hours(17), hours(9)..hours(12)]).validate(),
+            Err(TimeSegmentError::OutOfOrder {
+                previous: hours(13),
+                start: hours(9)
+            })
+        );
+        assert_eq!(
+            daily(vec![hours(9)..hours(9)]).validate(),
+            Err(TimeSegmentError::EmptyRange { start: hours(9) })
+        );
+        assert_eq!(
+            daily(vec![hours(9)..hours(34)]).validate(),
+            Err(TimeSegmentError::LongerThanPeriod)
+        );
+        assert_eq!(
+            daily(vec![hours(9)..hours(12), hours(30)..hours(34)]).validate(),
+            Err(TimeSegmentError::LongerThanPeriod)
+        );
+        let never = UnnamedTimeSegment {
+            ranges: vec![],
+            start,
+            period: Duration::zero(),
+        };
+        assert_eq!(never.validate(), Err(TimeSegmentError::EmptyPeriod));
+    }
+
     #[test]
     fn inverse_base_cases() {
         let start = Utc::now();
@@ -368,6 +633,44 @@ mod tests {
         );
     }
 
+    #[test]
+    fn contains() {
+        let start = Utc::now();
+        let period = Duration::days(1);
+        // From 10 to 12 and from 22 to 2 the next day
+        let segment = UnnamedTimeSegment {
+            ranges: vec![
+                start + Duration::hours(10)..start + Duration::hours(12),
+                start + Duration::hours(22)..start + Duration::hours(26),
+            ],
+            start,
+            period,
+        };
+
+        // In a window
+        assert!(segment.contains(start + Duration::hours(10)));
+        assert!(segment.contains(start + Duration::hours(11)));
+        assert!(segment.contains(start + Duration::days(3) + Duration::hours(11)));
+        assert!(segment.contains(start - Duration::days(3) + Duration::hours(11)));
+        // In a gap
+        assert!(!segment.contains(start + Duration::hours(12)));
+        assert!(!segment.contains(start + Duration::hours(15)));
+        assert!(!segment.contains(start + Duration::days(3) + Duration::hours(9)));
+        assert!(!segment.contains(start - Duration::days(3) + Duration::hours(21)));
+        // Across the boundary between two periods
+        assert!(segment.contains(start + Duration::hours(1)));
+        assert!(segment.contains(start + Duration::days(2) + Duration::hours(23)));
+        assert!(segment.contains(start + Duration::days(3)));
+        assert!(!segment.contains(start + Duration::days(3) + Duration::hours(2)));
+
+        let never = UnnamedTimeSegment {
+            ranges: vec![],
+            start,
+            period,
+        };
+        assert!(!never.contains(start));
+    }
+
     #[test]
     fn with_start() {
         let start = Utc::now();
@@ -486,4 +789,43 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn equality_ignores_the_order_of_ranges() {
+        let start = Utc.with_ymd_and_hms(2019, 8, 1, 0, 0, 0).unwrap();
+        let morning = start + Duration::hours(9)..start + Duration::hours(12);
+        let afternoon = start + Duration::hours(13)..start + Duration::hours(17);
+        let segment = |ranges| NamedTimeSegment {
+            id: 1,
+            name: "Work".to_string(),
+            ranges,
+            start,
+            period: Duration::days(1),
+            hue: 0,
+            strategy: None,
+        };
+        let chronological = segment(vec![morning.clone(), afternoon.clone()]);
+        let shuffled = segment(vec![afternoon.clone(), morning.clone()]);
+        assert_eq!(chronological, shuffled);
+        let hash = |segment: &NamedTimeSegment| {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            segment.hash(&mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(hash(&chronological), hash(&shuffled));
+        assert_eq!(
+            chronological,
+            NewNamedTimeSegment {
+                name: "Work".to_string(),
+                ranges: vec![afternoon.clone(), morning.clone()],
+                start,
+                period: Duration::days(1),
+                hue: 0,
+                strategy: None,
+            }
+        );
+        // Other ranges still make a difference
+        assert_ne!(chronological, segment(vec![morning]));
+        assert_ne!(shuffled, segment(vec![afternoon]));
+    }
 }